@@ -0,0 +1,73 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use metrology::*;
+
+
+fn fake_dat(lines: usize) -> Vec<u8> {
+    let mut s = String::new();
+    for i in 0..lines {
+        let t = i as f64 * 0.04;
+        s.push_str(
+            format!(
+                "{:.3} {:.5} {:.4} {:.4} {:.4} {:.4}\n",
+                t, 0.08 + 0.001*(t*0.7).sin(), 0.1 + 0.05*(t*0.05).sin(), 1.0 + 0.01*(t*1.3).cos(), 10.0 + 0.004*t, 5.0
+            ).as_str()
+        );
+    }
+    s.into_bytes()
+}
+
+fn bench_parsing(c: &mut Criterion) {
+    let data = fake_dat(400_000);
+    let mut group = c.benchmark_group("parse");
+    group.sample_size(10);
+    group.bench_function("sequential", |b| b.iter(|| get_data_lines(data.as_slice()).map(|x| x.1.len())));
+    for threads in [2usize, 4, 8].iter() {
+        group.bench_function(format!("parallel-{}", threads), |b| b.iter(|| parse_data_lines_parallel(data.as_slice(), *threads).map(|x| x.len())));
+    }
+    group.finish();
+}
+
+// A directory of 100k files, nine in ten of them tracks
+fn big_directory() -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("metrology-bench-listing-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    for i in 0..100_000 {
+        std::fs::write(dir.join(format!("plate.{}.{}", i, if i % 10 == 0 { "txt" } else { "dat" })), b"").unwrap();
+    }
+    dir
+}
+
+// How track files were once listed: entries read as one stream, then their
+// file types looked up on several threads
+fn threaded_track_files(formats: &TrackFormats, dir: &std::path::Path, threads: usize) -> Vec<std::path::PathBuf> {
+    let entries: Vec<std::fs::DirEntry> = std::fs::read_dir(dir).unwrap().map(|e| e.unwrap()).filter(|e| formats.is_track_file(&e.path())).collect();
+    let chunk = ((entries.len() + threads - 1) / threads).max(1);
+    let mut files: Vec<std::path::PathBuf> = std::thread::scope(|scope| {
+        let handles: Vec<_> = entries.chunks(chunk).map(|c| scope.spawn(move || {
+            c.iter().filter(|e| !e.file_type().unwrap().is_dir()).map(|e| e.path()).collect::<Vec<_>>()
+        })).collect();
+        handles.into_iter().flat_map(|h| h.join().unwrap()).collect()
+    });
+    files.sort();
+    files
+}
+
+fn bench_listing(c: &mut Criterion) {
+    let dir = big_directory();
+    let formats = TrackFormats::new(&InputFormat::default(), Precision::Rounded, 1);
+    let mut group = c.benchmark_group("listing");
+    group.sample_size(10);
+    group.bench_function("serial", |b| b.iter(|| formats.track_files(&dir).unwrap().len()));
+    group.bench_function("threaded-8", |b| b.iter(|| threaded_track_files(&formats, &dir, 8).len()));
+    group.finish();
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+criterion_group!(benches, bench_parsing, bench_listing);
+criterion_main!(benches);
@@ -0,0 +1,46 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+
+use metrology::*;
+
+
+fn fake_lines(n: usize) -> Vec<DataLine> {
+    (0..n).map(|i| {
+        let t = i as f64 * 0.04;
+        DataLine{
+            time: t, area: 0.08 + 0.001*(t*0.7).sin(), speed: 0.1 + 0.05*(t*0.05).sin(), midline: 1.0 + 0.01*(t*1.3).cos(),
+            x: 10.0 + 0.004*t, y: 5.0, frame: i as u64, source_line: 0
+        }
+    }).collect()
+}
+
+fn bench_statistics(c: &mut Criterion) {
+    let lines = fake_lines(2_000_000);
+    let columns = Columns::from_lines(&lines);
+    let mut group = c.benchmark_group("statistics");
+    group.sample_size(10);
+    group.bench_function("per-line", |b| b.iter(|| the_coord(|d| d.x, Precision::default(), &lines)));
+    group.bench_function("columnar", |b| b.iter(|| column_coord(columns.get(Column::X), Precision::default())));
+    group.bench_function("columnar-from-lines", |b| b.iter(|| column_coord(&lines.iter().map(|d| d.x).collect::<Vec<_>>(), Precision::default())));
+    group.finish();
+
+    // The statistics of one long worm, as scoring takes them: the first time
+    // copies each column out of the lines, and later times reuse the copies
+    let summaries = |d: &Dataset| (
+        d.summary(Column::Area, false, Precision::default()), d.summary(Column::Midline, false, Precision::default()),
+        d.coord(Column::X, Precision::default()), d.coord(Column::Y, Precision::default())
+    );
+    let mut group = c.benchmark_group("dataset");
+    group.sample_size(10);
+    group.bench_function("summaries-first", |b| b.iter_batched_ref(|| Dataset::new(lines.clone()), |d| summaries(d), BatchSize::LargeInput));
+    let dataset = Dataset::new(lines);
+    summaries(&dataset);
+    group.bench_function("summaries-again", |b| b.iter(|| summaries(&dataset)));
+    group.finish();
+}
+
+criterion_group!(benches, bench_statistics);
+criterion_main!(benches);
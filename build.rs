@@ -0,0 +1,39 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+
+fn git(dir: &Path, args: &[&str]) -> Option<String> {
+    let out = Command::new("git").arg("-C").arg(dir).args(args).output().ok()?;
+    if !out.status.success() { return None; }
+    let text = String::from_utf8(out.stdout).ok()?.trim().to_string();
+    if text.is_empty() { None } else { Some(text) }
+}
+
+// Sets METROLOGY_GIT_COMMIT to the commit being built, for the manifest's
+// environment.  A build outside this crate's own checkout (from a crates.io
+// package, or without git) leaves it as the builder set it, if at all.
+fn main() {
+    if std::env::var_os("METROLOGY_GIT_COMMIT").is_some() {
+        println!("cargo:rerun-if-env-changed=METROLOGY_GIT_COMMIT");
+        return;
+    }
+    let here = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+    let top = match git(&here, &["rev-parse", "--show-toplevel"]) {
+        Some(t) => PathBuf::from(t),
+        None    => return,
+    };
+    if top.canonicalize().ok() != here.canonicalize().ok() { return; }
+
+    // Rebuilt when HEAD moves, whether to another branch or another commit
+    println!("cargo:rerun-if-env-changed=METROLOGY_GIT_COMMIT");
+    if let Some(dir) = git(&here, &["rev-parse", "--absolute-git-dir"]).map(PathBuf::from) {
+        let mut watched = vec![dir.join("HEAD"), dir.join("packed-refs")];
+        if let Some(branch) = git(&here, &["symbolic-ref", "-q", "HEAD"]) { watched.push(dir.join(branch)); }
+        for w in watched.iter().filter(|w| w.exists()) { println!("cargo:rerun-if-changed={}", w.display()); }
+    }
+    if let Some(sha) = git(&here, &["rev-parse", "HEAD"]) { println!("cargo:rustc-env=METROLOGY_GIT_COMMIT={}", sha); }
+}
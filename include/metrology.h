@@ -0,0 +1,68 @@
+#ifndef METROLOGY_H
+#define METROLOGY_H
+
+/* Generated by cbindgen from src/ffi.rs; do not edit. */
+
+#include <stddef.h>
+#include <stdint.h>
+
+#define METROLOGY_OK 0
+
+#define METROLOGY_BAD_ARGUMENT 1
+
+#define METROLOGY_BAD_CONFIG 2
+
+#define METROLOGY_BAD_DATA 3
+
+#define METROLOGY_PANIC 4
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/*
+ Scores the contents of one .dat file, `len` bytes at `data`, with the
+ default configuration.  On success returns 0 and sets `*json_out` to the
+ scores array as it appears in the scores file; otherwise returns an error
+ code and sets `*json_out` to a message.  Either way the string must be
+ freed with `metrology_free_string`.
+
+ # Safety
+
+ `data` must point to `len` readable bytes, and `json_out` to a writable pointer.
+ */
+int metrology_score_buffer(const char *data, size_t len, char **json_out);
+
+/*
+ As `metrology_score_buffer`, for worm `id` and a configuration in TOML
+ (NUL-terminated; NULL for the default).
+
+ # Safety
+
+ As `metrology_score_buffer`, and `config_toml` must be NULL or a NUL-terminated string.
+ */
+int metrology_score_buffer_with(const char *data,
+                                size_t len,
+                                uint32_t id,
+                                const char *config_toml,
+                                char **json_out);
+
+/*
+ Frees a string from `metrology_score_buffer`; NULL is ignored.
+
+ # Safety
+
+ `s` must have come from this library and not been freed already.
+ */
+void metrology_free_string(char *s);
+
+/*
+ The library's version, as a static NUL-terminated string.
+ */
+const char *metrology_version(void);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* METROLOGY_H */
@@ -0,0 +1,109 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use serde::{Serialize, Deserialize};
+
+use crate::*;
+
+
+// Tracker start times can jitter by a few seconds between worms on a plate.
+// With `enabled`, each worm's speed, averaged in bins of `bin` seconds, is
+// cross-correlated with the average of the other worms on the plate, and the
+// shift of up to `max_shift` seconds that fits best is subtracted from the
+// worm's times before windows are applied.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AlignmentSettings {
+    pub enabled: bool,
+    pub max_shift: f64,
+    pub bin: f64,
+
+    // Bins both traces must have for a shift to be considered
+    pub min_overlap: usize,
+}
+
+impl Default for AlignmentSettings {
+    fn default() -> Self { AlignmentSettings{ enabled: false, max_shift: 5.0, bin: 0.5, min_overlap: 20 } }
+}
+
+impl AlignmentSettings {
+    pub fn errors(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if !(self.max_shift >= 0.0 && self.max_shift.is_finite()) { errors.push(format!("alignment max_shift must be a non-negative number, not {}", self.max_shift)); }
+        if !(self.bin > 0.0 && self.bin.is_finite()) { errors.push(format!("alignment bin must be positive, not {}", self.bin)); }
+        if self.min_overlap < 2 { errors.push(format!("alignment min_overlap must be at least 2, not {}", self.min_overlap)); }
+        errors
+    }
+}
+
+// Mean speed in bins of `bin` seconds from `t0`; NaN where there is none
+fn binned_speed(bin: f64, t0: f64, n: usize, input: &[DataLine]) -> Vec<f64> {
+    let mut sum = vec![0.0; n];
+    let mut count = vec![0u32; n];
+    for d in input.iter().filter(|d| d.time.is_finite() && d.speed.is_finite()) {
+        let k = ((d.time - t0) / bin).floor();
+        if k >= 0.0 && (k as usize) < n {
+            sum[k as usize] += d.speed;
+            count[k as usize] += 1;
+        }
+    }
+    sum.iter().zip(count.iter()).map(|(s, &c)| if c > 0 { s / c as f64 } else { std::f64::NAN }).collect()
+}
+
+// Pearson correlation of a[i + shift] with b[i] over indices where both are finite
+fn shifted_correlation(a: &[f64], b: &[f64], shift: isize, min_overlap: usize) -> Option<f64> {
+    let pairs: Vec<(f64, f64)> = (0..b.len() as isize)
+        .filter_map(|i| {
+            let j = i + shift;
+            if j < 0 || j >= a.len() as isize { return None; }
+            let (x, y) = (a[j as usize], b[i as usize]);
+            if x.is_finite() && y.is_finite() { Some((x, y)) } else { None }
+        })
+        .collect();
+    if pairs.len() < min_overlap { return None; }
+    let n = pairs.len() as f64;
+    let mx = pairs.iter().map(|p| p.0).sum::<f64>() / n;
+    let my = pairs.iter().map(|p| p.1).sum::<f64>() / n;
+    let sxy: f64 = pairs.iter().map(|p| (p.0 - mx) * (p.1 - my)).sum();
+    let sxx: f64 = pairs.iter().map(|p| (p.0 - mx).powi(2)).sum();
+    let syy: f64 = pairs.iter().map(|p| (p.1 - my).powi(2)).sum();
+    if sxx > 0.0 && syy > 0.0 { Some(sxy / (sxx * syy).sqrt()) } else { None }
+}
+
+// Offset in seconds to subtract from each worm's times, or None if it can't
+// be estimated (too little data, or no other worm to compare with).  Among
+// equally good shifts the smallest wins.
+pub fn the_alignment_offsets(settings: &AlignmentSettings, precision: Precision, worms: &[&[DataLine]]) -> Vec<Option<f64>> {
+    let times = worms.iter().flat_map(|w| w.iter().map(|d| d.time)).filter(|t| t.is_finite());
+    let (t0, t1) = times.fold((std::f64::INFINITY, std::f64::NEG_INFINITY), |(a, b), t| (a.min(t), b.max(t)));
+    if !(t1 > t0) || worms.len() < 2 { return vec![None; worms.len()]; }
+    let n = ((t1 - t0) / settings.bin).floor() as usize + 1;
+    let traces: Vec<Vec<f64>> = worms.iter().map(|w| binned_speed(settings.bin, t0, n, w)).collect();
+    let reach = (settings.max_shift / settings.bin).floor() as isize;
+
+    // The plate average without worm `i` is the total less its own trace
+    let mut total = vec![0.0; n];
+    let mut count = vec![0u32; n];
+    for t in traces.iter() {
+        for (k, v) in t.iter().enumerate().filter(|(_, v)| v.is_finite()) {
+            total[k] += v;
+            count[k] += 1;
+        }
+    }
+    traces.iter().map(|trace| {
+        let others: Vec<f64> = (0..n).map(|k| {
+            let (s, c) = if trace[k].is_finite() { (total[k] - trace[k], count[k] - 1) } else { (total[k], count[k]) };
+            if c > 0 { s / c as f64 } else { std::f64::NAN }
+        }).collect();
+        let mut shifts: Vec<isize> = (-reach..=reach).collect();
+        shifts.sort_by_key(|s| s.abs());
+        let mut best: Option<(isize, f64)> = None;
+        for s in shifts {
+            if let Some(r) = shifted_correlation(trace, &others, s, settings.min_overlap) {
+                if best.map(|(_, b)| r > b).unwrap_or(true) { best = Some((s, r)); }
+            }
+        }
+        best.map(|(s, _)| precision.r6(s as f64 * settings.bin))
+    }).collect()
+}
@@ -0,0 +1,58 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use std::fmt;
+use std::fmt::Display;
+
+use serde::{Serialize, Deserialize};
+
+use crate::*;
+
+
+// Aroused minus calm mean speed, compared as in Welch's t-test: the SEMs are
+// combined in quadrature and the degrees of freedom come from the
+// Welch-Satterthwaite equation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Arousal {
+    #[serde(deserialize_with = "nan_if_null")]
+    pub difference: f64,
+
+    #[serde(deserialize_with = "nan_if_null")]
+    pub sem: f64,
+
+    #[serde(deserialize_with = "nan_if_null")]
+    pub t: f64,
+
+    #[serde(deserialize_with = "nan_if_null")]
+    pub df: f64,
+}
+
+impl Arousal {
+    pub fn zero() -> Arousal { Arousal{ difference: std::f64::NAN, sem: std::f64::NAN, t: std::f64::NAN, df: std::f64::NAN } }
+}
+
+impl Display for Arousal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} {} {}", self.difference, self.sem, self.t, self.df)
+    }
+}
+
+impl StaticEntitled for Arousal {
+    fn titles(specifier: &str) -> Vec<String> { prefixed(specifier, &["difference", "sem", "t", "df"]) }
+}
+
+// Needs both windows scored; with fewer than two speeds in a window the SEM is
+// undefined, and so are t and df.
+pub fn the_arousal(calm: Option<&Speed>, aroused: Option<&Speed>, precision: Precision) -> Option<Arousal> {
+    let (c, a) = match (calm, aroused) {
+        (Some(c), Some(a)) => (&c.stats, &a.stats),
+        _                  => return None,
+    };
+    let (vc, va) = (c.sem * c.sem, a.sem * a.sem);
+    let variance = vc + va;
+    let difference = a.mean - c.mean;
+    let sem = variance.sqrt();
+    let df = variance * variance / (vc * vc / (c.n as f64 - 1.0) + va * va / (a.n as f64 - 1.0));
+    Some(Arousal{ difference: precision.r6(difference), sem: precision.r6(sem), t: precision.r6(difference / sem), df: precision.r6(df) })
+}
@@ -0,0 +1,105 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use std::fmt;
+use std::fmt::Display;
+
+use serde::{Serialize, Deserialize};
+
+use average::Estimate;
+
+use crate::*;
+
+
+// A burst starts when speed reaches `start` and ends when it drops below
+// `end`, which is lower so that noise near one threshold doesn't split a
+// burst.  Bursts shorter than `min_duration` seconds are ignored.  Speeds in
+// mm/s.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BurstSettings {
+    pub report: bool,
+    pub start: f64,
+    pub end: f64,
+    pub min_duration: f64,
+}
+
+impl Default for BurstSettings {
+    fn default() -> Self { BurstSettings{ report: false, start: 0.4, end: 0.25, min_duration: 0.0 } }
+}
+
+impl BurstSettings {
+    pub fn errors(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if !(self.end <= self.start) { errors.push(format!("burst end speed {} must not exceed start speed {}", self.end, self.start)); }
+        if !(self.min_duration >= 0.0) { errors.push(format!("burst min_duration must not be negative, not {}", self.min_duration)); }
+        errors
+    }
+}
+
+// Rate is bursts per minute of recording; amplitude is each burst's peak
+// speed, and intervals are from the start of one burst to the next.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Bursts {
+    pub count: u64,
+
+    #[serde(deserialize_with = "nan_if_null")]
+    pub rate: f64,
+
+    pub amplitude: Sampled,
+    pub interval: Sampled,
+}
+
+impl Bursts {
+    pub fn zero() -> Bursts { Bursts{ count: 0, rate: std::f64::NAN, amplitude: Sampled::zero(), interval: Sampled::zero() } }
+
+    pub fn titles(specifier: &str, intervals: bool) -> Vec<String> {
+        let mut titles = prefixed(specifier, &["count", "rate"]);
+        titles.extend(Sampled::titles(format!("{}amplitude-", specifier).as_str(), intervals));
+        titles.extend(Sampled::titles(format!("{}interval-", specifier).as_str(), intervals));
+        titles
+    }
+}
+
+impl Display for Bursts {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} {} {}", self.count, self.rate, self.amplitude, self.interval)
+    }
+}
+
+impl Entitled for Bursts {
+    fn push_subtitle(&self, specifier: &str, to: &mut String) { to.push_str(Bursts::titles(specifier, self.amplitude.has_intervals()).join(" ").as_str()); }
+}
+
+// Start time, end time, and peak speed of each burst.  A burst still going
+// when the recording stops ends at the last frame.
+pub fn find_bursts(settings: &BurstSettings, input: &[DataLine]) -> Vec<(f64, f64, f64)> {
+    let mut found = Vec::new();
+    let mut current: Option<(f64, f64, f64)> = None;
+    for d in input.iter().filter(|d| d.time.is_finite() && d.speed.is_finite()) {
+        current = match current {
+            None if d.speed >= settings.start => Some((d.time, d.time, d.speed)),
+            None                              => None,
+            Some(b) if d.speed < settings.end => { found.push(b); None },
+            Some((t0, _, peak))               => Some((t0, d.time, peak.max(d.speed))),
+        };
+    }
+    found.extend(current);
+    found.retain(|(t0, t1, _)| t1 - t0 >= settings.min_duration);
+    found
+}
+
+pub fn the_bursts(settings: &BurstSettings, precision: Precision, input: &[DataLine]) -> Bursts {
+    let found = find_bursts(settings, input);
+    let times: Vec<f64> = input.iter().map(|d| d.time).filter(|t| t.is_finite()).collect();
+    let minutes = match (times.first(), times.last()) {
+        (Some(t0), Some(t1)) if t1 > t0 => (t1 - t0) / 60.0,
+        _ => std::f64::NAN,
+    };
+    let amplitude: average::Variance = found.iter().map(|b| b.2).collect();
+    let mut interval = average::Variance::new();
+    for w in found.windows(2) { interval.add(w[1].0 - w[0].0); }
+    Bursts{ count: found.len() as u64, rate: precision.r6(found.len() as f64 / minutes),
+        amplitude: Sampled::of_variance(&amplitude, precision), interval: Sampled::of_variance(&interval, precision) }
+}
@@ -0,0 +1,71 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use std::path::{Path, PathBuf};
+
+use serde::{Serialize, Deserialize};
+
+use crate::*;
+
+
+// Bumped whenever parsing changes the lines read from the same text, so that
+// caches written before the change are parsed again.  Caches from before the
+// format was recorded (which predate byte-order marks, non-breaking spaces,
+// and hexadecimal floats being read) don't decode at all.
+pub const CACHE_FORMAT: u32 = 1;
+
+// Parsed lines of one .dat file, saved so that later runs can skip parsing
+// text.  The size and modification time (in nanoseconds) of the source, the
+// input format, the cache format, and the version tell whether the cache
+// still matches.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParsedCache {
+    pub format: u32,
+    pub version: String,
+    pub source_bytes: u64,
+    pub source_modified: Option<u64>,
+    pub input: InputFormat,
+    pub lines: Vec<DataLine>,
+}
+
+fn source_stamp(source: &Path) -> std::io::Result<(u64, Option<u64>)> {
+    let meta = std::fs::metadata(source)?;
+    let modified = meta.modified().ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_nanos() as u64);
+    Ok((meta.len(), modified))
+}
+
+impl ParsedCache {
+    pub fn of(source: &Path, input: &InputFormat, lines: Vec<DataLine>) -> Result<ParsedCache, MetrologyError> {
+        let (source_bytes, source_modified) = source_stamp(source)?;
+        Ok(ParsedCache{ format: CACHE_FORMAT, version: env!("CARGO_PKG_VERSION").to_string(), source_bytes, source_modified, input: input.clone(), lines })
+    }
+
+    pub fn is_current(&self, source: &Path, input: &InputFormat) -> bool {
+        self.format == CACHE_FORMAT && self.version == env!("CARGO_PKG_VERSION") && self.input == *input &&
+            source_stamp(source).map(|(n, m)| n == self.source_bytes && m == self.source_modified).unwrap_or(false)
+    }
+
+    pub fn encode(&self) -> Result<Vec<u8>, MetrologyError> {
+        rmp_serde::to_vec(self).map_err(|e| MetrologyError::Decode(e.to_string()))
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<ParsedCache, MetrologyError> {
+        rmp_serde::from_slice(bytes).map_err(|e| MetrologyError::Decode(e.to_string()))
+    }
+
+    // The cached lines, if the cache exists and still matches the source
+    pub fn load(path: &Path, source: &Path, input: &InputFormat) -> Option<Vec<DataLine>> {
+        let cache = ParsedCache::decode(std::fs::read(path).ok()?.as_slice()).ok()?;
+        if cache.is_current(source, input) { Some(cache.lines) } else { None }
+    }
+}
+
+// Where the cache for a .dat file goes, relative to a target directory
+pub fn cache_name(dat: &Path) -> PathBuf {
+    let mut name = dat.file_name().map(|f| f.to_os_string()).unwrap_or_default();
+    name.push(".lines");
+    Path::new("cache").join(name)
+}
@@ -0,0 +1,55 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use serde::{Serialize, Deserialize};
+
+use crate::*;
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CeilingAction {
+    // Speeds above the limit are set to the limit
+    Clip,
+
+    // Speeds above the limit are treated as missing
+    Drop,
+}
+
+impl Default for CeilingAction {
+    fn default() -> Self { CeilingAction::Clip }
+}
+
+// Tracking errors give speeds no worm could reach (over 2 mm/s for an adult).
+// With a `limit` (mm/s), faster speeds are clipped or dropped before any
+// statistics, after the speed estimator, and counted per worm.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpeedCeiling {
+    pub limit: Option<f64>,
+    pub action: CeilingAction,
+}
+
+impl SpeedCeiling {
+    pub fn errors(&self) -> Vec<String> {
+        match self.limit {
+            Some(v) if !(v > 0.0 && v.is_finite()) => vec![format!("speed ceiling limit must be positive, not {}", v)],
+            _ => Vec::new(),
+        }
+    }
+
+    // Speeds over the limit; None if there is no limit
+    pub fn apply(&self, lines: &mut [DataLine]) -> Option<u64> {
+        let limit = self.limit?;
+        let mut n = 0;
+        for d in lines.iter_mut().filter(|d| d.speed > limit) {
+            d.speed = match self.action {
+                CeilingAction::Clip => limit,
+                CeilingAction::Drop => std::f64::NAN,
+            };
+            n += 1;
+        }
+        Some(n)
+    }
+}
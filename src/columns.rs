@@ -0,0 +1,189 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use crate::*;
+
+
+// Below this many lines the per-line estimators are as fast.  Above it the
+// columnar ones are used; they add in a different order, so may differ from
+// the per-line ones in the last bits before rounding.
+pub const COLUMNAR_MIN_LINES: usize = 100_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Column {
+    Time,
+    Area,
+    Speed,
+    Midline,
+    X,
+    Y,
+}
+
+impl Column {
+    pub fn of(self, d: &DataLine) -> f64 {
+        match self {
+            Column::Time    => d.time,
+            Column::Area    => d.area,
+            Column::Speed   => d.speed,
+            Column::Midline => d.midline,
+            Column::X       => d.x,
+            Column::Y       => d.y,
+        }
+    }
+}
+
+// The data one column at a time, so statistics run over contiguous values
+#[derive(Debug, Clone, Default)]
+pub struct Columns {
+    pub time: Vec<f64>,
+    pub area: Vec<f64>,
+    pub speed: Vec<f64>,
+    pub midline: Vec<f64>,
+    pub x: Vec<f64>,
+    pub y: Vec<f64>,
+}
+
+impl Columns {
+    pub fn from_lines(input: &[DataLine]) -> Columns {
+        let mut c = Columns::default();
+        for v in [&mut c.time, &mut c.area, &mut c.speed, &mut c.midline, &mut c.x, &mut c.y].iter_mut() { v.reserve_exact(input.len()); }
+        for d in input {
+            c.time.push(d.time);
+            c.area.push(d.area);
+            c.speed.push(d.speed);
+            c.midline.push(d.midline);
+            c.x.push(d.x);
+            c.y.push(d.y);
+        }
+        c
+    }
+
+    pub fn len(&self) -> usize { self.time.len() }
+
+    pub fn is_empty(&self) -> bool { self.time.is_empty() }
+
+    pub fn get(&self, column: Column) -> &[f64] {
+        match column {
+            Column::Time    => &self.time,
+            Column::Area    => &self.area,
+            Column::Speed   => &self.speed,
+            Column::Midline => &self.midline,
+            Column::X       => &self.x,
+            Column::Y       => &self.y,
+        }
+    }
+}
+
+// Columns held elsewhere, such as in HDF5 or Arrow arrays, to be scored
+// without first building the caller's own `DataLine`s
+#[derive(Debug, Clone, Copy)]
+pub struct ColumnSlices<'a> {
+    pub time: &'a [f64],
+    pub area: &'a [f64],
+    pub speed: &'a [f64],
+    pub midline: &'a [f64],
+    pub x: &'a [f64],
+    pub y: &'a [f64],
+}
+
+impl<'a> ColumnSlices<'a> {
+    pub fn of(columns: &'a Columns) -> ColumnSlices<'a> {
+        ColumnSlices{ time: &columns.time, area: &columns.area, speed: &columns.speed, midline: &columns.midline, x: &columns.x, y: &columns.y }
+    }
+
+    // Frames are numbered from 0; there are no source lines
+    pub fn to_lines(&self) -> Result<Vec<DataLine>, MetrologyError> {
+        let n = self.time.len();
+        let others = [("area", self.area), ("speed", self.speed), ("midline", self.midline), ("x", self.x), ("y", self.y)];
+        if let Some((column, c)) = others.iter().find(|(_, c)| c.len() != n) {
+            return Err(MetrologyError::ColumnLength{ column, expected: n, found: c.len() });
+        }
+        Ok((0..n).map(|i| DataLine{
+            time: self.time[i], area: self.area[i], speed: self.speed[i], midline: self.midline[i], x: self.x[i], y: self.y[i],
+            frame: i as u64, source_line: 0
+        }).collect())
+    }
+}
+
+impl Scores {
+    // As `the_everything`, building the lines once, as scoring would copy them anyway
+    pub fn from_columns(id: WormId, columns: ColumnSlices, config: &Config) -> Result<Scores, MetrologyError> {
+        Ok(everything_of(id, columns.to_lines()?, config))
+    }
+}
+
+// Count, mean, sample variance, and bounds of the finite values
+#[derive(Debug, Clone, Copy)]
+pub struct ColumnStats {
+    pub n: u64,
+    pub mean: f64,
+    pub variance: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl ColumnStats {
+    // Rounded like `Sampled::of_variance`
+    pub fn sampled(&self, precision: Precision) -> Sampled {
+        if self.n == 0 { return Sampled::of_variance(&average::Variance::new(), precision); }
+        let sem = if self.n < 2 { 0.0 } else { (self.variance / self.n as f64).sqrt() };
+        Sampled::of(self.mean, sem, self.n, precision)
+    }
+}
+
+// Independent accumulators, one per lane, with no branches in the loop body,
+// so the compiler can keep them in vector registers
+const LANES: usize = 8;
+
+#[inline(always)]
+fn by_lane<F: FnMut(usize, f64)>(values: &[f64], mut f: F) {
+    let chunks = values.chunks_exact(LANES);
+    let rest = chunks.remainder();
+    for c in chunks {
+        for k in 0..LANES { f(k, c[k]); }
+    }
+    for (k, &v) in rest.iter().enumerate() { f(k, v); }
+}
+
+pub fn column_stats(values: &[f64]) -> ColumnStats {
+    let mut n = [0f64; LANES];
+    let mut sum = [0f64; LANES];
+    let mut min = [std::f64::INFINITY; LANES];
+    let mut max = [std::f64::NEG_INFINITY; LANES];
+    by_lane(values, |k, v| {
+        let ok = v.is_finite();
+        n[k] += if ok { 1.0 } else { 0.0 };
+        sum[k] += if ok { v } else { 0.0 };
+        min[k] = if ok && v < min[k] { v } else { min[k] };
+        max[k] = if ok && v > max[k] { v } else { max[k] };
+    });
+    let count: f64 = n.iter().sum();
+    if count == 0.0 { return ColumnStats{ n: 0, mean: std::f64::NAN, variance: std::f64::NAN, min: std::f64::NAN, max: std::f64::NAN }; }
+    let mean = sum.iter().sum::<f64>() / count;
+
+    let mut squares = [0f64; LANES];
+    by_lane(values, |k, v| {
+        let d = if v.is_finite() { v - mean } else { 0.0 };
+        squares[k] += d * d;
+    });
+    let variance = if count < 2.0 { 0.0 } else { squares.iter().sum::<f64>() / (count - 1.0) };
+    ColumnStats{
+        n: count as u64, mean, variance,
+        min: min.iter().cloned().fold(std::f64::INFINITY, f64::min),
+        max: max.iter().cloned().fold(std::f64::NEG_INFINITY, f64::max),
+    }
+}
+
+// As `the_coord` and `the_summary`, from a column
+pub fn column_coord(values: &[f64], precision: Precision) -> Coord {
+    let s = column_stats(values);
+    if s.n == 0 { return Coord::zero(); }
+    let first = values.iter().cloned().find(|v| v.is_finite()).unwrap_or(std::f64::NAN);
+    let last = values.iter().rev().cloned().find(|v| v.is_finite()).unwrap_or(std::f64::NAN);
+    Coord{ first, last, bound0: s.min, bound1: s.max, stats: s.sampled(precision) }
+}
+
+pub fn column_summary(values: &[f64], bounded: bool, precision: Precision) -> Summary {
+    if bounded { column_coord(values, precision).into() } else { column_stats(values).sampled(precision).into() }
+}
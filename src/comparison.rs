@@ -0,0 +1,102 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use serde::{Serialize, Deserialize};
+
+use crate::*;
+
+
+// One group against the control, without assuming either is normal: the
+// Mann-Whitney U test, with the rank-biserial correlation as its effect size
+// (positive when the group tends higher), and a permutation test of the
+// difference in means.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Comparison {
+    pub label: String,
+    pub n: u64,
+
+    #[serde(deserialize_with = "nan_if_null")]
+    pub difference: f64,
+    #[serde(deserialize_with = "nan_if_null")]
+    pub u: f64,
+    #[serde(deserialize_with = "nan_if_null")]
+    pub rank_biserial: f64,
+    #[serde(deserialize_with = "nan_if_null")]
+    pub mann_whitney_p: f64,
+    #[serde(deserialize_with = "nan_if_null")]
+    pub permutation_p: f64,
+}
+
+// Every group against one control; the seed makes the permutation p-values repeatable
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Comparisons {
+    pub control: String,
+    pub n_control: u64,
+    pub permutations: usize,
+    pub seed: u64,
+    pub groups: Vec<Comparison>,
+}
+
+// Complementary error function, to about 1e-7 relative error (Numerical Recipes' erfcc)
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let poly = -1.26551223 + t*(1.00002368 + t*(0.37409196 + t*(0.09678418 + t*(-0.18628806 + t*(0.27886807 + t*(-1.13520398 + t*(1.48851587 + t*(-0.82215223 + t*0.17087277))))))));
+    let r = t * (-z * z + poly).exp();
+    if x >= 0.0 { r } else { 2.0 - r }
+}
+
+// U of `a` against `b`, with ties given their average rank, and its two-sided
+// p-value from the normal approximation with tie and continuity corrections
+pub fn mann_whitney(a: &[f64], b: &[f64]) -> (f64, f64) {
+    if a.is_empty() || b.is_empty() { return (std::f64::NAN, std::f64::NAN); }
+    let mut all: Vec<(f64, bool)> = a.iter().map(|x| (*x, true)).chain(b.iter().map(|x| (*x, false))).collect();
+    all.sort_by(|x, y| x.0.partial_cmp(&y.0).unwrap_or(std::cmp::Ordering::Equal));
+    let (mut rank_sum, mut ties, mut i) = (0.0, 0.0, 0);
+    while i < all.len() {
+        let j = (i..all.len()).find(|&j| all[j].0 != all[i].0).unwrap_or(all.len());
+        let rank = (i + j + 1) as f64 / 2.0;
+        rank_sum += rank * all[i..j].iter().filter(|x| x.1).count() as f64;
+        let t = (j - i) as f64;
+        ties += t * t * t - t;
+        i = j;
+    }
+    let (n1, n2) = (a.len() as f64, b.len() as f64);
+    let n = n1 + n2;
+    let u = rank_sum - n1 * (n1 + 1.0) / 2.0;
+    let variance = n1 * n2 / 12.0 * ((n + 1.0) - ties / (n * (n - 1.0)));
+    if !(variance > 0.0) { return (u, 1.0); }
+    let z = ((u - n1 * n2 / 2.0).abs() - 0.5).max(0.0) / variance.sqrt();
+    (u, erfc(z / std::f64::consts::SQRT_2).min(1.0))
+}
+
+fn mean_of(xs: &[f64]) -> f64 { xs.iter().sum::<f64>() / xs.len() as f64 }
+
+// How often relabeling the values at random gives a difference in means at
+// least as large as the one seen, counting the one seen so p is never zero
+pub fn permutation_p(a: &[f64], b: &[f64], permutations: usize, rng: &mut Rng) -> f64 {
+    if a.is_empty() || b.is_empty() || permutations == 0 { return std::f64::NAN; }
+    let seen = (mean_of(a) - mean_of(b)).abs();
+    // Sums of the same values in another order can differ in the last bits
+    let tolerance = 1e-12 * (seen + a.iter().chain(b.iter()).fold(0.0f64, |m, x| m.max(x.abs())));
+    let mut pool: Vec<f64> = a.iter().chain(b.iter()).cloned().collect();
+    let mut extreme = 0;
+    for _ in 0..permutations {
+        rng.shuffle(&mut pool);
+        let (x, y) = pool.split_at(a.len());
+        if (mean_of(x) - mean_of(y)).abs() >= seen - tolerance { extreme += 1; }
+    }
+    (extreme + 1) as f64 / (permutations + 1) as f64
+}
+
+pub fn the_comparison(label: &str, values: &[f64], control: &[f64], permutations: usize, rng: &mut Rng, precision: Precision) -> Comparison {
+    let r6 = |v| precision.r6(v);
+    let (u, p) = mann_whitney(values, control);
+    let pairs = (values.len() * control.len()) as f64;
+    let difference = if values.is_empty() || control.is_empty() { std::f64::NAN } else { mean_of(values) - mean_of(control) };
+    Comparison{
+        label: label.to_string(), n: values.len() as u64, difference: r6(difference), u,
+        rank_biserial: r6(2.0 * u / pairs - 1.0), mann_whitney_p: r6(p), permutation_p: r6(permutation_p(values, control, permutations, rng)),
+    }
+}
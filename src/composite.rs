@@ -0,0 +1,59 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use serde::{Serialize, Deserialize};
+
+use crate::*;
+
+
+// Each component is divided by its reference value before weighting, so a
+// healthy young adult scores near 1 on every component.  Area in mm^2, speed
+// in mm/s; activity is already a fraction.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CompositeWeights {
+    pub area: f64,
+    pub speed: f64,
+    pub activity: f64,
+    pub area_reference: f64,
+    pub speed_reference: f64,
+    pub activity_threshold: f64,
+}
+
+impl Default for CompositeWeights {
+    fn default() -> Self {
+        CompositeWeights{ 
+            area: 1.0, speed: 1.0, activity: 1.0,
+            area_reference: 0.1, speed_reference: 0.2, activity_threshold: 0.02
+        }
+    }
+}
+
+pub fn the_activity(threshold: f64, input: &[DataLine]) -> Option<f64> {
+    let mut n = 0u64;
+    let mut active = 0u64;
+    for line in input.iter() {
+        if line.speed.is_finite() {
+            n += 1;
+            if line.speed > threshold { active += 1; }
+        }
+    }
+    if n > 0 { Some(active as f64 / n as f64) } else { None }
+}
+
+pub fn the_composite(weights: &CompositeWeights, precision: Precision, input: &[DataLine]) -> Option<f64> {
+    let area = the_area(input);
+    let speed: average::Variance = input.iter().map(|line| line.speed).filter(|x| x.is_finite()).collect();
+    let activity = the_activity(weights.activity_threshold, input)?;
+    if area.len() == 0 || speed.len() == 0 { return None; }
+
+    let total = weights.area + weights.speed + weights.activity;
+    if !(total > 0.0) { return None; }
+
+    let value = 
+        weights.area     * area.mean()  / weights.area_reference +
+        weights.speed    * speed.mean() / weights.speed_reference +
+        weights.activity * activity;
+    if value.is_finite() { Some(precision.r6(value / total)) } else { None }
+}
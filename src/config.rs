@@ -0,0 +1,166 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use serde::{Serialize, Deserialize};
+
+use crate::{MaxEstimator, Precision};
+use crate::smoothing::SpeedEstimator;
+use crate::ceiling::SpeedCeiling;
+use crate::error::MetrologyError;
+use crate::alignment::AlignmentSettings;
+use crate::bursts::BurstSettings;
+use crate::composite::CompositeWeights;
+use crate::derived::derived_errors;
+use crate::duplicates::DuplicateSettings;
+use crate::exploration::ExplorationSettings;
+use crate::fragments::FragmentSettings;
+use crate::histogram::HistogramSettings;
+use crate::omega::OmegaSettings;
+use crate::intervals::IntervalSettings;
+use crate::metadata::MetadataSettings;
+use crate::parsing::InputFormat;
+use crate::qc::QcLimits;
+use crate::selection::MetricSelection;
+use crate::swaps::SwapPolicy;
+use crate::thigmotaxis::EdgeSettings;
+use crate::transforms::ColumnTransforms;
+use crate::validity::ValidityRanges;
+use crate::windows::{SpeedWindows, NamedWindow, named_window_errors};
+
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MetricOptions {
+    pub bounds: bool,
+
+    // Mean absolute rates of change of area and midline
+    pub rates: bool,
+
+    // Midline squared over area, frame by frame
+    pub shape_index: bool,
+
+    // Bounding box area and radius of gyration of the positions
+    pub spread: bool,
+
+    // 95% confidence intervals from Student's t alongside each standard error
+    pub ci95: bool,
+
+    // Values as computed, rather than rounded to about six decimals
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub full_precision: bool,
+
+    // Metrics to compute, as named in `METRIC_NAMES` or `speed:<window>`; all if empty
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub only: Vec<String>,
+}
+
+impl MetricOptions {
+    pub fn precision(&self) -> Precision { if self.full_precision { Precision::Full } else { Precision::Rounded } }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpeedSettings {
+    pub estimator: SpeedEstimator,
+    pub max: MaxEstimator,
+    pub ceiling: SpeedCeiling,
+
+    // Speed statistics of each window also over only the frames faster than this
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub moving_threshold: Option<f64>,
+}
+
+impl SpeedSettings {
+    pub fn errors(&self) -> Vec<String> {
+        let mut errors = self.estimator.errors();
+        errors.extend(self.max.errors());
+        errors.extend(self.ceiling.errors());
+        if let Some(v) = self.moving_threshold.filter(|v| !(*v >= 0.0 && v.is_finite())) {
+            errors.push(format!("moving speed threshold must be zero or more, not {}", v));
+        }
+        errors
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub input: InputFormat,
+    pub fragments: FragmentSettings,
+    pub duplicates: DuplicateSettings,
+    pub metrics: MetricOptions,
+    pub speed: SpeedSettings,
+    pub validity: ValidityRanges,
+    pub transforms: ColumnTransforms,
+    pub windows: SpeedWindows,
+    pub alignment: AlignmentSettings,
+    pub exploration: ExplorationSettings,
+    pub edge: EdgeSettings,
+    pub composite: CompositeWeights,
+    pub swaps: SwapPolicy,
+    pub histogram: HistogramSettings,
+    pub intervals: IntervalSettings,
+    pub bursts: BurstSettings,
+    pub omega: OmegaSettings,
+    pub windowed: Vec<NamedWindow>,
+    pub qc: QcLimits,
+    pub metadata: MetadataSettings,
+
+    // Named metrics computed from expressions; see `derived`
+    pub derived: BTreeMap<String, String>,
+}
+
+impl Config {
+    pub fn from_toml(text: &str) -> Result<Config, MetrologyError> {
+        let config: Config = toml::from_str(text)?;
+        let mut errors = config.input.errors();
+        errors.extend(config.duplicates.errors());
+        errors.extend(MetricSelection::of(&config.metrics.only, &config.windows.names()).err());
+        errors.extend(config.windows.errors());
+        errors.extend(config.alignment.errors());
+        errors.extend(config.speed.errors());
+        errors.extend(config.validity.errors());
+        errors.extend(config.exploration.errors());
+        errors.extend(config.edge.errors());
+        errors.extend(config.histogram.errors());
+        errors.extend(config.intervals.errors());
+        errors.extend(config.bursts.errors());
+        errors.extend(config.omega.errors());
+        errors.extend(config.qc.errors());
+        errors.extend(named_window_errors(&config.windowed));
+        errors.extend(derived_errors(&config.derived));
+        errors.extend(config.metadata.errors());
+        let builtin = crate::Scores::titles_for(&Config{ derived: BTreeMap::new(), metadata: MetadataSettings::default(), ..config.clone() }, "");
+        for name in config.derived.keys().filter(|k| builtin.contains(k)) { errors.push(format!("derived metric {} has the name of a built-in column", name)); }
+        for name in config.metadata.fields().iter().filter(|k| builtin.contains(k) || config.derived.contains_key(*k)) {
+            errors.push(format!("metadata field {} has the name of another column", name));
+        }
+        if errors.len() > 0 { return Err(MetrologyError::BadConfig(errors.join("; "))); }
+        Ok(config)
+    }
+
+    // The metrics to compute, of those there are with these speed windows
+    pub fn selection(&self) -> MetricSelection {
+        let windows = self.windows.names();
+        MetricSelection::of(&self.metrics.only, &windows).unwrap_or_else(|_| MetricSelection::all(&windows))
+    }
+
+    // FNV-1a over the canonical JSON form, so it is stable across runs and platforms
+    pub fn hash(&self) -> String {
+        let text = serde_json::to_string(self).unwrap_or_default();
+        let mut h: u64 = 0xcbf29ce484222325;
+        for b in text.bytes() {
+            h ^= b as u64;
+            h = h.wrapping_mul(0x100000001b3);
+        }
+        format!("{:016x}", h)
+    }
+
+    pub fn load(path: &Path) -> Result<Config, MetrologyError> {
+        Config::from_toml(std::fs::read_to_string(path)?.as_str())
+    }
+}
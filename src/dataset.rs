@@ -0,0 +1,272 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use std::borrow::Cow;
+use std::sync::OnceLock;
+
+use crate::*;
+
+
+// One worm's data.  Recordings are nearly always in time order, which is
+// checked once on creation; time slices are then found by binary search
+// instead of a scan.  Out-of-order or non-finite times fall back to scanning.
+// Statistics of long recordings are taken from a copy of the one column,
+// made the first time it is needed and kept.
+#[derive(Debug, Clone)]
+pub struct Dataset {
+    lines: Vec<DataLine>,
+    ordered: bool,
+    columns: [OnceLock<Vec<f64>>; 6],
+}
+
+impl Dataset {
+    pub fn new(lines: Vec<DataLine>) -> Dataset {
+        let ordered = lines.iter().all(|d| d.time.is_finite()) && lines.windows(2).all(|w| w[0].time <= w[1].time);
+        Dataset{ lines, ordered, columns: Default::default() }
+    }
+
+    pub fn lines(&self) -> &[DataLine] { &self.lines }
+
+    pub fn into_lines(self) -> Vec<DataLine> { self.lines }
+
+    pub fn len(&self) -> usize { self.lines.len() }
+
+    pub fn is_empty(&self) -> bool { self.lines.is_empty() }
+
+    pub fn is_ordered(&self) -> bool { self.ordered }
+
+    // Index of the first line at or after t (ordered data only)
+    fn index_from(&self, t: f64) -> usize { self.lines.partition_point(|d| d.time < t) }
+
+    // Index of the first line after t (ordered data only)
+    fn index_after(&self, t: f64) -> usize { self.lines.partition_point(|d| d.time <= t) }
+
+    // Lines with t0 <= time <= t1
+    pub fn slice_time(&self, t0: f64, t1: f64) -> Cow<'_, [DataLine]> {
+        if self.ordered {
+            let i0 = self.index_from(t0);
+            let i1 = std::cmp::max(i0, self.index_after(t1));
+            Cow::Borrowed(&self.lines[i0..i1])
+        }
+        else {
+            Cow::Owned(self.lines.iter().filter(|d| d.time >= t0 && d.time <= t1).cloned().collect())
+        }
+    }
+
+    pub fn column<F>(&self, f: F) -> Vec<f64>
+    where F: Fn(&DataLine) -> f64 {
+        self.lines.iter().map(f).collect()
+    }
+
+    pub fn first_time(&self) -> Option<f64> { self.lines.iter().map(|d| d.time).find(|t| t.is_finite()) }
+
+    pub fn last_time(&self) -> Option<f64> { self.lines.iter().rev().map(|d| d.time).find(|t| t.is_finite()) }
+
+    // Consecutive windows [start, start + width) from the first time onward
+    pub fn windows(&self, width: f64) -> impl Iterator<Item = (Window, Cow<'_, [DataLine]>)> + '_ {
+        let first = self.first_time().unwrap_or(std::f64::NAN);
+        let last = self.last_time().unwrap_or(std::f64::NAN);
+        let n = if width > 0.0 && first.is_finite() { ((last - first) / width).floor() as usize + 1 } else { 0 };
+        (0..n).map(move |k| {
+            let w = Window::new(first + (k as f64) * width, first + ((k + 1) as f64) * width);
+            let inside: Cow<[DataLine]> =
+                if self.ordered { Cow::Borrowed(&self.lines[self.index_from(w.start) .. self.index_from(w.end)]) }
+                else { Cow::Owned(self.lines.iter().filter(|d| d.time >= w.start && d.time < w.end).cloned().collect()) };
+            (w, inside)
+        })
+    }
+
+    pub fn area(&self) -> average::Variance { the_area(&self.lines) }
+
+    pub fn midline(&self) -> average::Variance { the_midline(&self.lines) }
+
+    // The column's values, if the recording is long enough to score by column
+    fn values(&self, column: Column) -> Option<&[f64]> {
+        if self.lines.len() < COLUMNAR_MIN_LINES { return None; }
+        Some(self.columns[column as usize].get_or_init(|| self.column(|d| column.of(d))))
+    }
+
+    pub fn coord(&self, column: Column, precision: Precision) -> Coord {
+        match self.values(column) {
+            Some(v) => column_coord(v, precision),
+            None    => the_coord(|d| column.of(d), precision, &self.lines),
+        }
+    }
+
+    pub fn summary(&self, column: Column, bounded: bool, precision: Precision) -> Summary {
+        match self.values(column) {
+            Some(v) => column_summary(v, bounded, precision),
+            None    => the_summary(|d| column.of(d), bounded, precision, &self.lines),
+        }
+    }
+
+    // Indices of the first line in the window and the first after it, as
+    // `bounds` places them (ordered data only)
+    fn window_indices(&self, w: &Window, bounds: &WindowBounds) -> (usize, usize) {
+        let start = match bounds.start { Bound::Closed => self.index_from(w.start), Bound::Open => self.index_after(w.start) };
+        let end = match bounds.end { Bound::Closed => self.index_after(w.end), Bound::Open => self.index_from(w.end) };
+        (std::cmp::min(start, end), end)
+    }
+
+    // Lines `bounds` puts in the window
+    pub fn slice_within(&self, w: &Window, bounds: &WindowBounds) -> Cow<'_, [DataLine]> {
+        if self.ordered {
+            let (i0, i1) = self.window_indices(w, bounds);
+            Cow::Borrowed(&self.lines[i0..i1])
+        }
+        else {
+            Cow::Owned(self.lines.iter().filter(|d| bounds.contains(w, d.time)).cloned().collect())
+        }
+    }
+
+    // Same rules as `the_speed_within`
+    pub fn speed_in(&self, w: &Window, bounds: &WindowBounds, max: &MaxEstimator, precision: Precision) -> Option<Speed> {
+        if !self.ordered { return the_speed_within(w, bounds, max, precision, &self.lines); }
+        let (start, end) = self.window_indices(w, bounds);
+        if (end >= self.lines.len() && bounds.require_after) || (start == 0 && bounds.require_before) { return None; }
+        the_speed_by(max, precision, self.lines[start..end].iter())
+    }
+
+    // Fraction of [t0, t1] between the first and last recorded times
+    pub fn coverage(&self, t0: f64, t1: f64) -> f64 {
+        match (self.first_time(), self.last_time()) {
+            (Some(first), Some(last)) if t1 > t0 => ((last.min(t1) - first.max(t0)) / (t1 - t0)).max(0.0),
+            _ => 0.0,
+        }
+    }
+
+    // As `speed_in`, but with a minimum coverage a window the recording only
+    // partly spans is scored from the data it does have
+    pub fn speed_covering(&self, w: &Window, bounds: &WindowBounds, max: &MaxEstimator, min_coverage: Option<f64>, precision: Precision) -> Option<Speed> {
+        let c = match min_coverage {
+            None    => return self.speed_in(w, bounds, max, precision),
+            Some(c) => c,
+        };
+        let (mut speed, coverage) = match self.speed_in(w, bounds, max, precision) {
+            Some(s) => (s, 1.0),
+            None    => {
+                let coverage = self.coverage(w.start, w.end);
+                if !(coverage >= c) { return None; }
+                (the_speed_by(max, precision, self.slice_within(w, bounds).iter())?, coverage)
+            }
+        };
+        speed.coverage = Some(precision.r6(coverage));
+        Some(speed)
+    }
+
+    pub fn window_scores(&self, window: &NamedWindow, activity_threshold: f64, max: &MaxEstimator, precision: Precision) -> WindowScores {
+        window_scores_of(window, activity_threshold, max, precision, &self.slice_time(window.start, window.end))
+    }
+
+    pub fn windowed(&self, windows: &[NamedWindow], activity_threshold: f64, max: &MaxEstimator, precision: Precision) -> Vec<WindowScores> {
+        windows.iter().map(|w| self.window_scores(w, activity_threshold, max, precision)).collect()
+    }
+
+    pub fn exploration(&self, settings: &ExplorationSettings, precision: Precision) -> Option<Exploration> { the_exploration(settings, precision, &self.lines) }
+
+    pub fn composite(&self, weights: &CompositeWeights, precision: Precision) -> Option<f64> { the_composite(weights, precision, &self.lines) }
+
+    pub fn activity(&self, threshold: f64) -> Option<f64> { the_activity(threshold, &self.lines) }
+
+    pub fn qc(&self, limits: &QcLimits, precision: Precision) -> Qc { the_qc(limits, precision, &self.lines) }
+
+    // Fractional windows are resolved against this recording's times
+    pub fn speed_windows(&self, windows: &SpeedWindows) -> SpeedWindows {
+        match (self.first_time(), self.last_time()) {
+            (Some(first), Some(last)) => windows.resolved(first, last),
+            _                         => windows.clone(),
+        }
+    }
+
+    // Speeds in each configured window, marked with the estimator used
+    pub fn window_speeds(&self, config: &Config) -> WindowSpeeds {
+        let (max, cover, bounds, ws) = (&config.speed.max, config.windows.min_coverage, &config.windows.bounds, &self.speed_windows(&config.windows));
+        let only = config.selection();
+        let precision = config.metrics.precision();
+        let mut speeds = WindowSpeeds::of(&config.windows);
+        for (name, w) in ws.named().iter() {
+            if !only.has(format!("speed:{}", name).as_str()) { continue; }
+            let mut speed = estimated(config, self.speed_covering(w, bounds, max, cover, precision));
+            if let Some(s) = speed.as_mut().filter(|_| !bounds.is_default()) { s.bounds = Some(*bounds); }
+            if let (Some(s), Some(threshold)) = (speed.as_mut(), config.speed.moving_threshold) {
+                let v: average::Variance = self.slice_within(w, bounds).iter().map(|d| d.speed).filter(|v| *v > threshold).collect();
+                s.moving = Some(Sampled::of_variance(&v, precision));
+            }
+            speeds.set(name, speed);
+        }
+        speeds
+    }
+
+    pub fn configured_windows(&self, config: &Config) -> Vec<WindowScores> {
+        let mut windowed = self.windowed(&config.windowed, config.composite.activity_threshold, &config.speed.max, config.metrics.precision());
+        for w in windowed.iter_mut() { w.speed = estimated(config, w.speed.take()); }
+        windowed
+    }
+
+    // QC including the frame count of each scored speed window
+    pub fn checked_qc(&self, config: &Config, speeds: &WindowSpeeds) -> Qc {
+        let mut qc = self.qc(&config.qc, config.metrics.precision());
+        for (name, w) in self.speed_windows(&config.windows).named().iter() {
+            if let Some(s) = speeds.get(name) {
+                let fraction = config.qc.min_window_fraction * s.coverage.unwrap_or(1.0);
+                qc.check_window_count(name, w, s.stats.n, fraction);
+            }
+        }
+        qc
+    }
+
+    // Partly covered windows that were still scored are reported by their coverage instead
+    pub fn window_warnings(&self, config: &Config, speeds: &WindowSpeeds) -> Vec<WindowWarning> {
+        let windows = self.speed_windows(&config.windows);
+        let only = config.selection();
+        let mut warnings = the_window_warnings(&windows, &self.lines);
+        warnings.retain(|w| only.has(format!("speed:{}", w.window).as_str()));
+        warnings.retain(|w| speeds.get(w.window.as_str()).is_none());
+        warnings
+    }
+
+    pub fn everything(&self, id: WormId, config: &Config) -> Scores {
+        let (t0, t1) = match (self.first_time(), self.last_time()) {
+            (Some(t0), Some(t1)) => (t0, t1),
+            _                    => return the_nothing(config),
+        };
+
+        // Metrics not selected are left as they are with no data, so the columns stay the same
+        let only = config.selection();
+        let precision = config.metrics.precision();
+        let mut s = the_nothing(config);
+        let bounds = config.metrics.bounds;
+        if only.has("area") { s.area = self.summary(Column::Area, bounds, precision); }
+        if only.has("midline") { s.midline = self.summary(Column::Midline, bounds, precision); }
+        if only.has("rates") && config.metrics.rates { s.shape_rates = Some(the_shape_rates(precision, &self.lines)); }
+        if only.has("shape") && config.metrics.shape_index { s.shape_index = Some(the_shape_index(precision, &self.lines)); }
+        let speeds = self.window_speeds(config);
+        if only.has("arousal") { s.arousal = the_arousal(speeds.get("calm"), speeds.get("aroused"), precision); }
+        if only.has("x") { s.x = self.coord(Column::X, precision); }
+        if only.has("y") { s.y = self.coord(Column::Y, precision); }
+        if only.has("spread") && config.metrics.spread { s.spread = Some(the_spread(&s.x, &s.y, precision)); }
+        if only.has("windowed") { s.windowed = self.configured_windows(config); }
+        if only.has("derived") { s.extras = the_extras(&config.derived, precision, &self.lines); }
+        if only.has("bursts") && config.bursts.report { s.bursts = Some(the_bursts(&config.bursts, precision, &self.lines)); }
+        if only.has("omega") && config.omega.report { s.omega = Some(the_omega_turns(&config.omega, precision, &self.lines)); }
+        if only.has("exploration") { s.exploration = self.exploration(&config.exploration, precision); }
+        if only.has("composite") { s.composite = self.composite(&config.composite, precision); }
+        if only.has("edge") { s.edge_fraction = the_edge_fraction(&config.edge, config.exploration.arena, precision, &self.lines); }
+        if only.has("qc") { s.qc = self.checked_qc(config, &speeds); }
+        s.warnings = self.window_warnings(config, &speeds);
+        if only.has("intervals") && config.intervals.report {
+            s.intervals = the_tracked_intervals(&config.intervals, s.qc.frame_rate.as_ref().map(|fr| fr.median_dt), &self.lines);
+        }
+
+        Scores{ id, t0, t1, speeds, ..s }
+    }
+}
+
+fn estimated(config: &Config, speed: Option<Speed>) -> Option<Speed> {
+    speed.map(|s| Speed{ estimator: Some(config.speed.estimator.clone()), ..s })
+}
+
+impl From<Vec<DataLine>> for Dataset {
+    fn from(lines: Vec<DataLine>) -> Dataset { Dataset::new(lines) }
+}
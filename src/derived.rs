@@ -0,0 +1,218 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use std::collections::BTreeMap;
+
+use crate::*;
+
+
+// Derived metrics are arithmetic (+ - * / and parentheses) on numbers and
+// summaries of a column over a time range, such as
+//     mean(speed, 440..450) / mean(speed, 270..290)
+// where the range includes its start but not its end and may be left out to
+// use the whole recording.  Columns are time, area, speed, midline, x, and
+// y; summaries are mean, median, sd, sem, min, max, sum, count, first, and
+// last; abs, sqrt, and ln apply to any expression.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Number(f64),
+    Negate(Box<Expr>),
+    Binary(char, Box<Expr>, Box<Expr>),
+    Function(String, Box<Expr>),
+    Summary{ function: String, column: String, range: Option<(f64, f64)> },
+}
+
+const COLUMNS: [&str; 6] = ["time", "area", "speed", "midline", "x", "y"];
+const SUMMARIES: [&str; 10] = ["mean", "median", "sd", "sem", "min", "max", "sum", "count", "first", "last"];
+const FUNCTIONS: [&str; 3] = ["abs", "sqrt", "ln"];
+
+struct Parser<'a> {
+    text: &'a str,
+    at: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str { &self.text[self.at..] }
+
+    fn skip_space(&mut self) { self.at = self.text.len() - self.rest().trim_start().len(); }
+
+    fn peek(&mut self) -> Option<char> { self.skip_space(); self.rest().chars().next() }
+
+    fn error<T>(&self, what: &str) -> Result<T, String> { Err(format!("expected {} at position {} of {:?}", what, self.at + 1, self.text)) }
+
+    fn expect(&mut self, token: &str) -> Result<(), String> {
+        self.skip_space();
+        if self.rest().starts_with(token) { self.at += token.len(); Ok(()) } else { self.error(format!("{:?}", token).as_str()) }
+    }
+
+    fn name(&mut self) -> Option<&'a str> {
+        self.skip_space();
+        let n = self.rest().find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(self.rest().len());
+        if n == 0 || !self.rest().starts_with(|c: char| c.is_ascii_alphabetic()) { return None; }
+        let name = &self.rest()[..n];
+        self.at += n;
+        Some(name)
+    }
+
+    // Digits are taken greedily, but a '.' only if a digit follows, so that 440..450 is two numbers
+    fn number(&mut self) -> Result<f64, String> {
+        self.skip_space();
+        let bytes = self.rest().as_bytes();
+        let mut n = if bytes.first() == Some(&b'-') { 1 } else { 0 };
+        while n < bytes.len() && (bytes[n].is_ascii_digit() || (bytes[n] == b'.' && bytes.get(n+1).map(|c| c.is_ascii_digit()).unwrap_or(false))) { n += 1; }
+        if n < bytes.len() && (bytes[n] == b'e' || bytes[n] == b'E') {
+            let mut m = n + 1;
+            if m < bytes.len() && (bytes[m] == b'-' || bytes[m] == b'+') { m += 1; }
+            if m < bytes.len() && bytes[m].is_ascii_digit() {
+                n = m;
+                while n < bytes.len() && bytes[n].is_ascii_digit() { n += 1; }
+            }
+        }
+        match self.rest()[..n].parse::<f64>() {
+            Ok(x) => { self.at += n; Ok(x) },
+            Err(_) => self.error("a number"),
+        }
+    }
+
+    fn expression(&mut self) -> Result<Expr, String> {
+        let mut e = self.term()?;
+        while let Some(op) = self.peek().filter(|c| *c == '+' || *c == '-') {
+            self.at += 1;
+            e = Expr::Binary(op, Box::new(e), Box::new(self.term()?));
+        }
+        Ok(e)
+    }
+
+    fn term(&mut self) -> Result<Expr, String> {
+        let mut e = self.factor()?;
+        while let Some(op) = self.peek().filter(|c| *c == '*' || *c == '/') {
+            self.at += 1;
+            e = Expr::Binary(op, Box::new(e), Box::new(self.factor()?));
+        }
+        Ok(e)
+    }
+
+    fn factor(&mut self) -> Result<Expr, String> {
+        match self.peek() {
+            Some('-') => { self.at += 1; Ok(Expr::Negate(Box::new(self.factor()?))) },
+            Some('(') => { self.at += 1; let e = self.expression()?; self.expect(")")?; Ok(e) },
+            Some(c) if c.is_ascii_digit() || c == '.' => self.number().map(Expr::Number),
+            Some(c) if c.is_ascii_alphabetic() => {
+                let name = self.name().unwrap_or_default().to_string();
+                self.expect("(")?;
+                if FUNCTIONS.contains(&name.as_str()) {
+                    let e = self.expression()?;
+                    self.expect(")")?;
+                    return Ok(Expr::Function(name, Box::new(e)));
+                }
+                if !SUMMARIES.contains(&name.as_str()) { return Err(format!("unknown function {:?} in {:?}", name, self.text)); }
+                let column = match self.name() {
+                    Some(c) if COLUMNS.contains(&c) => c.to_string(),
+                    _ => return self.error(format!("a column ({})", COLUMNS.join(", ")).as_str()),
+                };
+                let range = if self.peek() == Some(',') {
+                    self.at += 1;
+                    let start = self.number()?;
+                    self.expect("..")?;
+                    let end = self.number()?;
+                    if !(start < end) { return Err(format!("range {}..{} in {:?} is empty", start, end, self.text)); }
+                    Some((start, end))
+                }
+                else { None };
+                self.expect(")")?;
+                Ok(Expr::Summary{ function: name, column, range })
+            },
+            _ => self.error("a number, function, or '('"),
+        }
+    }
+}
+
+pub fn parse_expression(text: &str) -> Result<Expr, String> {
+    let mut p = Parser{ text, at: 0 };
+    let e = p.expression()?;
+    if p.peek().is_some() { return p.error("an operator or the end"); }
+    Ok(e)
+}
+
+fn column_of(name: &str, d: &DataLine) -> f64 {
+    match name {
+        "time"    => d.time,
+        "area"    => d.area,
+        "speed"   => d.speed,
+        "midline" => d.midline,
+        "x"       => d.x,
+        _         => d.y,
+    }
+}
+
+fn summarize(function: &str, mut v: Vec<f64>) -> f64 {
+    let n = v.len() as f64;
+    let mean = v.iter().sum::<f64>() / n;
+    let sd = if v.len() < 2 { std::f64::NAN } else { (v.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0)).sqrt() };
+    match function {
+        "mean"   => mean,
+        "sd"     => sd,
+        "sem"    => sd / n.sqrt(),
+        "sum"    => v.iter().sum(),
+        "count"  => n,
+        "min"    => v.iter().cloned().fold(std::f64::NAN, f64::min),
+        "max"    => v.iter().cloned().fold(std::f64::NAN, f64::max),
+        "first"  => v.first().cloned().unwrap_or(std::f64::NAN),
+        "last"   => v.last().cloned().unwrap_or(std::f64::NAN),
+        _        => {
+            v.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            let k = v.len();
+            if k == 0 { std::f64::NAN } else if k % 2 == 1 { v[k/2] } else { 0.5 * (v[k/2 - 1] + v[k/2]) }
+        },
+    }
+}
+
+impl Expr {
+    // Summaries skip frames where the column isn't finite; an empty one is NaN, except count
+    pub fn eval(&self, input: &[DataLine]) -> f64 {
+        match self {
+            Expr::Number(x) => *x,
+            Expr::Negate(e) => -e.eval(input),
+            Expr::Binary(op, a, b) => {
+                let (x, y) = (a.eval(input), b.eval(input));
+                match op { '+' => x + y, '-' => x - y, '*' => x * y, _ => x / y }
+            },
+            Expr::Function(name, e) => {
+                let x = e.eval(input);
+                match name.as_str() { "abs" => x.abs(), "sqrt" => x.sqrt(), _ => x.ln() }
+            },
+            Expr::Summary{ function, column, range } => {
+                let values: Vec<f64> = input.iter()
+                    .filter(|d| range.map(|(t0, t1)| d.time >= t0 && d.time < t1).unwrap_or(true))
+                    .map(|d| column_of(column.as_str(), d))
+                    .filter(|v| v.is_finite())
+                    .collect();
+                summarize(function.as_str(), values)
+            },
+        }
+    }
+}
+
+// Names become CSV columns, so must be a letter then letters, digits, - or _
+pub fn derived_errors(derived: &BTreeMap<String, String>) -> Vec<String> {
+    let mut errors = Vec::new();
+    for (name, text) in derived.iter() {
+        let ok_name = name.starts_with(|c: char| c.is_ascii_alphabetic()) && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !ok_name { errors.push(format!("derived metric name {:?} must be a letter followed by letters, digits, - or _", name)); }
+        if let Err(e) = parse_expression(text.as_str()) { errors.push(format!("derived metric {}: {}", name, e)); }
+    }
+    errors
+}
+
+pub fn the_extras(derived: &BTreeMap<String, String>, precision: Precision, input: &[DataLine]) -> BTreeMap<String, f64> {
+    derived.iter().map(|(name, text)| {
+        let value = parse_expression(text.as_str()).map(|e| precision.r6(e.eval(input))).unwrap_or(std::f64::NAN);
+        (name.clone(), value)
+    }).collect()
+}
+
+pub(crate) fn extras_nan_if_null<'de, D: serde::Deserializer<'de>>(d: D) -> Result<BTreeMap<String, f64>, D::Error> {
+    let m: BTreeMap<String, Option<f64>> = serde::Deserialize::deserialize(d)?;
+    Ok(m.into_iter().map(|(k, v)| (k, v.unwrap_or(std::f64::NAN))).collect())
+}
@@ -0,0 +1,234 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use serde::{Serialize, Deserialize};
+
+use crate::*;
+use crate::smoothing::solve;
+
+
+// Scores from one condition, labeled with its dose somewhere in the label
+// (e.g. `paraquat-2.5mM`)
+#[derive(Debug, Clone)]
+pub struct DoseCondition {
+    pub label: String,
+    pub scores: Vec<Scores>,
+}
+
+// The first number in a label, so `drugA_10uM` is dose 10
+pub fn dose_in_label(label: &str) -> Option<f64> {
+    let bytes = label.as_bytes();
+    let start = bytes.iter().position(|c| c.is_ascii_digit())?;
+    let start = if start > 0 && bytes[start - 1] == b'.' { start - 1 } else { start };
+    let mut end = start;
+    let mut seen_point = false;
+    while end < bytes.len() && (bytes[end].is_ascii_digit() || (bytes[end] == b'.' && !seen_point)) {
+        if bytes[end] == b'.' { seen_point = true; }
+        end += 1;
+    }
+    label[start..end].trim_end_matches('.').parse::<f64>().ok()
+}
+
+// A metric by its name in the scores table (e.g. `calm-mean`)
+pub fn metric_of(scores: &Scores, metric: &str) -> Option<f64> {
+    let title = scores.title();
+    let row = scores.to_string();
+    title.split(' ').zip(row.split(' ')).find(|(name, _)| *name == metric).and_then(|(_, value)| value.parse::<f64>().ok())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DoseGroup {
+    pub label: String,
+    pub dose: f64,
+    #[serde(flatten)]
+    pub stats: Sampled,
+}
+
+// A fitted parameter with its approximate 95% confidence interval
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Fitted {
+    #[serde(deserialize_with = "nan_if_null")]
+    pub value: f64,
+    #[serde(deserialize_with = "nan_if_null")]
+    pub lower: f64,
+    #[serde(deserialize_with = "nan_if_null")]
+    pub upper: f64,
+}
+
+impl Fitted {
+    fn of(value: f64, se: f64, precision: Precision) -> Fitted {
+        Fitted{ value: precision.r6(value), lower: precision.r6(value - 1.96 * se), upper: precision.r6(value + 1.96 * se) }
+    }
+}
+
+// y = bottom + (top - bottom) / (1 + (dose / ec50)^hill)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogisticFit {
+    pub bottom: Fitted,
+    pub top: Fitted,
+    pub ec50: Fitted,
+    pub hill: Fitted,
+
+    #[serde(deserialize_with = "nan_if_null")]
+    pub residual_sd: f64,
+
+    pub n: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DoseResponse {
+    pub metric: String,
+    pub groups: Vec<DoseGroup>,
+
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub fit: Option<LogisticFit>,
+
+    // Why there is no fit
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub failure: Option<String>,
+
+    // Each dose against the lowest, when there are at least two
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub comparisons: Option<Comparisons>,
+}
+
+// Model value and its gradient in (bottom, top, ln ec50, hill).  Dose zero is
+// the limit of the curve, where the gradient in ln ec50 and hill vanishes.
+fn logistic(p: &[f64; 4], dose: f64) -> (f64, [f64; 4]) {
+    let [b, t, u, h] = *p;
+    if dose <= 0.0 {
+        let s = if h > 0.0 { 1.0 } else { 0.0 };
+        return (b + (t - b) * s, [1.0 - s, s, 0.0, 0.0]);
+    }
+    let lx = dose.ln() - u;
+    let s = 1.0 / (1.0 + (h * lx).exp());
+    let w = s * (1.0 - s);
+    (b + (t - b) * s, [1.0 - s, s, (t - b) * h * w, -(t - b) * lx * w])
+}
+
+fn sum_of_squares(p: &[f64; 4], points: &[(f64, f64)]) -> f64 {
+    points.iter().map(|(x, y)| (y - logistic(p, *x).0).powi(2)).sum()
+}
+
+fn normal_matrix(p: &[f64; 4], points: &[(f64, f64)]) -> (Vec<Vec<f64>>, Vec<f64>) {
+    let mut a = vec![vec![0.0; 4]; 4];
+    let mut g = vec![0.0; 4];
+    for (x, y) in points.iter() {
+        let (f, d) = logistic(p, *x);
+        for i in 0..4 {
+            for j in 0..4 { a[i][j] += d[i] * d[j]; }
+            g[i] += d[i] * (y - f);
+        }
+    }
+    (a, g)
+}
+
+// Levenberg-Marquardt from a start at the extreme group means, with ec50 at
+// the geometric mean of the positive doses and a hill slope of 1
+fn fit_logistic(groups: &[DoseGroup], points: &[(f64, f64)], precision: Precision) -> Result<LogisticFit, String> {
+    let positive: Vec<f64> = groups.iter().map(|g| g.dose).filter(|d| *d > 0.0).collect();
+    if groups.len() < 4 || positive.len() < 3 { return Err(format!("{} doses, but at least 4 are needed, 3 of them above zero", groups.len())); }
+    if points.len() < 5 { return Err(format!("{} worms, but at least 5 are needed", points.len())); }
+
+    let u0 = positive.iter().map(|d| d.ln()).sum::<f64>() / positive.len() as f64;
+    let mut p = [groups[groups.len() - 1].stats.mean, groups[0].stats.mean, u0, 1.0];
+    let mut ssr = sum_of_squares(&p, points);
+    let mut lambda = 1e-3;
+    for _ in 0..500 {
+        let (a, g) = normal_matrix(&p, points);
+        let mut damped = a.clone();
+        for i in 0..4 { damped[i][i] += lambda * a[i][i].max(1e-12); }
+        let step = match solve(damped, g) { Some(s) => s, None => { lambda *= 10.0; continue; } };
+        let trial = [p[0] + step[0], p[1] + step[1], p[2] + step[2], p[3] + step[3]];
+        let next = sum_of_squares(&trial, points);
+        if next.is_finite() && next <= ssr {
+            let done = ssr - next <= 1e-12 * ssr.max(1e-300);
+            p = trial;
+            ssr = next;
+            lambda = (lambda / 10.0).max(1e-12);
+            if done { break; }
+        }
+        else {
+            lambda *= 10.0;
+            if lambda > 1e12 { break; }
+        }
+    }
+
+    let (a, _) = normal_matrix(&p, points);
+    let dof = (points.len() - 4) as f64;
+    let variance = ssr / dof;
+    let mut se = [std::f64::NAN; 4];
+    for i in 0..4 {
+        let mut e = vec![0.0; 4];
+        e[i] = 1.0;
+        if let Some(column) = solve(a.clone(), e) { se[i] = (variance * column[i]).sqrt(); }
+    }
+    if !p.iter().all(|x| x.is_finite()) { return Err("fit did not converge".to_string()); }
+    let r6 = |v| precision.r6(v);
+    let ec50 = Fitted{ value: r6(p[2].exp()), lower: r6((p[2] - 1.96 * se[2]).exp()), upper: r6((p[2] + 1.96 * se[2]).exp()) };
+    Ok(LogisticFit{
+        bottom: Fitted::of(p[0], se[0], precision), top: Fitted::of(p[1], se[1], precision), ec50, hill: Fitted::of(p[3], se[3], precision),
+        residual_sd: r6(variance.sqrt()), n: points.len() as u64
+    })
+}
+
+// Groups worms by dose and fits a four-parameter logistic to every worm's
+// value of the metric.  Conditions with the same dose are pooled.  Each dose
+// is also tested against the lowest, with `permutations` relabelings drawn
+// from `seed`.
+pub fn the_dose_response(conditions: &[DoseCondition], metric: &str, permutations: usize, seed: u64, precision: Precision) -> Result<DoseResponse, MetrologyError> {
+    let mut groups: Vec<(DoseGroup, Vec<f64>)> = Vec::new();
+    let mut points = Vec::new();
+    let mut found = false;
+    for c in conditions.iter() {
+        let dose = dose_in_label(c.label.as_str()).ok_or_else(|| MetrologyError::BadConfig(format!("no dose in condition {:?}", c.label)))?;
+        let all: Vec<f64> = c.scores.iter().filter_map(|s| metric_of(s, metric)).collect();
+        found = found || all.len() > 0;
+        let values: Vec<f64> = all.into_iter().filter(|v| v.is_finite()).collect();
+        points.extend(values.iter().map(|v| (dose, *v)));
+        match groups.iter_mut().find(|g| g.0.dose == dose) {
+            Some(g) => { g.0.label = format!("{}+{}", g.0.label, c.label); g.1.extend(values); },
+            None    => groups.push((DoseGroup{ label: c.label.clone(), dose, stats: Sampled::zero() }, values)),
+        }
+    }
+    if !found { return Err(MetrologyError::BadConfig(format!("the scores have no column {:?}", metric))); }
+    groups.sort_by(|a, b| a.0.dose.partial_cmp(&b.0.dose).unwrap_or(std::cmp::Ordering::Equal));
+    let comparisons = if groups.len() < 2 { None } else {
+        let mut rng = Rng::new(seed);
+        let control = &groups[0];
+        Some(Comparisons{
+            control: control.0.label.clone(), n_control: control.1.len() as u64, permutations, seed,
+            groups: groups[1..].iter().map(|(g, v)| the_comparison(g.label.as_str(), v, &control.1, permutations, &mut rng, precision)).collect(),
+        })
+    };
+    let groups: Vec<DoseGroup> = groups.into_iter().map(|(mut g, v)| { g.stats = Sampled::of_variance(&v.into_iter().collect(), precision); g }).collect();
+    let (fit, failure) = match fit_logistic(&groups, &points, precision) {
+        Ok(f)  => (Some(f), None),
+        Err(e) => (None, Some(e)),
+    };
+    Ok(DoseResponse{ metric: metric.to_string(), groups, fit, failure, comparisons })
+}
+
+// Groups, then fitted parameters, then tests against the control, in long
+// format.  For the tests the value is the effect size: the rank-biserial
+// correlation, or the difference in means.
+pub fn dose_response_table(dr: &DoseResponse) -> String {
+    let mut table = String::from("metric kind name dose n value sem lower upper p\n");
+    for g in dr.groups.iter() {
+        table.push_str(format!("{} group {} {} {} {} {} NaN NaN NaN\n", dr.metric, g.label, g.dose, g.stats.n, g.stats.mean, g.stats.sem).as_str());
+    }
+    if let Some(f) = &dr.fit {
+        for (name, p) in [("bottom", &f.bottom), ("top", &f.top), ("ec50", &f.ec50), ("hill", &f.hill)].iter() {
+            table.push_str(format!("{} fit {} NaN {} {} NaN {} {} NaN\n", dr.metric, name, f.n, p.value, p.lower, p.upper).as_str());
+        }
+    }
+    if let Some(cs) = &dr.comparisons {
+        for c in cs.groups.iter() {
+            let dose = dr.groups.iter().find(|g| g.label == c.label).map(|g| g.dose).unwrap_or(std::f64::NAN);
+            table.push_str(format!("{} mann-whitney {} {} {} {} NaN NaN NaN {}\n", dr.metric, c.label, dose, c.n, c.rank_biserial, c.mann_whitney_p).as_str());
+            table.push_str(format!("{} permutation {} {} {} {} NaN NaN NaN {}\n", dr.metric, c.label, dose, c.n, c.difference, c.permutation_p).as_str());
+        }
+    }
+    table
+}
@@ -0,0 +1,38 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use serde::{Serialize, Deserialize};
+
+use crate::*;
+
+
+// Trackers sometimes write a frame twice.  A frame whose time is within
+// `epsilon` seconds of the frame kept before it is dropped, so that it isn't
+// counted twice.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DuplicateSettings {
+    pub remove: bool,
+    pub epsilon: f64,
+}
+
+impl Default for DuplicateSettings {
+    fn default() -> Self { DuplicateSettings{ remove: true, epsilon: 1e-6 } }
+}
+
+impl DuplicateSettings {
+    pub fn errors(&self) -> Vec<String> {
+        if self.epsilon >= 0.0 && self.epsilon.is_finite() { Vec::new() }
+        else { vec![format!("duplicates epsilon must be a non-negative number of seconds, not {}", self.epsilon)] }
+    }
+
+    // The number of frames removed; None if removal is off
+    pub fn apply(&self, lines: &mut Vec<DataLine>) -> Option<u64> {
+        if !self.remove { return None; }
+        let before = lines.len();
+        let epsilon = self.epsilon;
+        lines.dedup_by(|d, kept| (d.time - kept.time).abs() <= epsilon);
+        Some((before - lines.len()) as u64)
+    }
+}
@@ -0,0 +1,35 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use serde::{Serialize, Deserialize};
+
+use crate::publish::host_name;
+
+
+// The build and machine that scored a run, so results can be traced to both
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Environment {
+    pub host: String,
+    pub os: String,
+    pub arch: String,
+    pub cpus: usize,
+    pub version: String,
+
+    // Commit the binary was built from, when built from a git checkout (see build.rs)
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub git_commit: Option<String>,
+}
+
+impl Environment {
+    pub fn capture() -> Environment {
+        Environment{
+            host: host_name(),
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            cpus: std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            git_commit: option_env!("METROLOGY_GIT_COMMIT").filter(|s| s.len() > 0).map(|s| s.to_string()),
+        }
+    }
+}
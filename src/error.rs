@@ -0,0 +1,78 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use std::fmt;
+use std::fmt::Display;
+use std::io;
+use std::path::PathBuf;
+
+
+#[derive(Debug)]
+pub enum MetrologyError {
+    Io(io::Error),
+    Parse{ line: usize },
+    Misformat{ line: usize, hint: String },
+    EmptyData,
+    UnknownFormat(String),
+    BadConfig(String),
+    Schema{ row: usize, expected: usize, found: usize },
+    ColumnLength{ column: &'static str, expected: usize, found: usize },
+    FragmentOverlap{ end: f64, start: f64 },
+    Integrity{ problem: String, others: usize },
+    Decode(String),
+    Export(String),
+    Locked{ path: PathBuf, host: String, pid: u32, started: u64 },
+}
+
+impl Display for MetrologyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MetrologyError::Io(e)         => write!(f, "I/O error: {}", e),
+            MetrologyError::Parse{line}   => write!(f, "could not parse data on line {}", line),
+            MetrologyError::Misformat{line, hint} => write!(f, "could not parse data on line {}: {}", line, hint),
+            MetrologyError::EmptyData     => write!(f, "no data"),
+            MetrologyError::UnknownFormat(s) => write!(f, "no known track format for {}", s),
+            MetrologyError::BadConfig(s)  => write!(f, "bad configuration: {}", s),
+            MetrologyError::Decode(s)     => write!(f, "could not convert scores: {}", s),
+            MetrologyError::Export(s)     => write!(f, "could not export: {}", s),
+            MetrologyError::Locked{path, host, pid, started} =>
+                write!(f, "another run holds {:?} (process {} on {}, started {} s after 1970)", path, pid, host, started),
+            MetrologyError::Schema{row, expected, found} =>
+                write!(f, "row {} has {} values but the title has {} columns", row, found, expected),
+            MetrologyError::ColumnLength{column, expected, found} =>
+                write!(f, "column {} has {} values but time has {}", column, found, expected),
+            MetrologyError::FragmentOverlap{end, start} =>
+                write!(f, "track fragments overlap: one ends at {} s but the next starts at {} s", end, start),
+            MetrologyError::Integrity{problem, others} =>
+                if *others == 0 { write!(f, "data failed an integrity check on {}", problem) }
+                else { write!(f, "data failed an integrity check on {} (and {} more)", problem, others) },
+        }
+    }
+}
+
+impl std::error::Error for MetrologyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MetrologyError::Io(e) => Some(e),
+            _                     => None,
+        }
+    }
+}
+
+impl From<io::Error> for MetrologyError {
+    fn from(e: io::Error) -> MetrologyError { MetrologyError::Io(e) }
+}
+
+impl From<toml::de::Error> for MetrologyError {
+    fn from(e: toml::de::Error) -> MetrologyError { MetrologyError::BadConfig(e.to_string()) }
+}
+
+impl From<serde_json::Error> for MetrologyError {
+    fn from(e: serde_json::Error) -> MetrologyError { MetrologyError::Decode(e.to_string()) }
+}
+
+#[cfg(feature = "hdf5")]
+impl From<hdf5::Error> for MetrologyError {
+    fn from(e: hdf5::Error) -> MetrologyError { MetrologyError::Export(e.to_string()) }
+}
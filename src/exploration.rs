@@ -0,0 +1,138 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use std::collections::HashSet;
+use std::fmt;
+use std::fmt::Display;
+
+use serde::{Serialize, Deserialize};
+
+use crate::*;
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Arena {
+    pub x0: f64,
+    pub x1: f64,
+    pub y0: f64,
+    pub y1: f64,
+}
+
+impl Arena {
+    pub fn contains(&self, x: f64, y: f64) -> bool { x >= self.x0 && x <= self.x1 && y >= self.y0 && y <= self.y1 }
+}
+
+// Without an arena, each worm's own bounding box is gridded, unless the
+// arena is to be estimated from every worm on the plate
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExplorationSettings {
+    pub cell: f64,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub arena: Option<Arena>,
+
+    pub estimate_arena: bool,
+
+    // Fraction of worms whose bounds may lie outside the estimated arena on each side
+    pub arena_trim: f64,
+}
+
+impl Default for ExplorationSettings {
+    fn default() -> Self { ExplorationSettings{ cell: 0.5, arena: None, estimate_arena: false, arena_trim: 0.05 } }
+}
+
+impl ExplorationSettings {
+    pub fn errors(&self) -> Vec<String> {
+        if self.arena_trim >= 0.0 && self.arena_trim < 0.5 { Vec::new() }
+        else { vec![format!("exploration arena_trim must be at least 0 and below 0.5, not {}", self.arena_trim)] }
+    }
+
+    // Whether spatial metrics wait for an arena estimated from the whole plate
+    pub fn is_adaptive(&self) -> bool { self.estimate_arena && self.arena.is_none() }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Exploration {
+    pub visited: u64,
+    pub cells: u64,
+    #[serde(deserialize_with = "nan_if_null")]
+    pub fraction: f64,
+    #[serde(deserialize_with = "nan_if_null")]
+    pub dispersal: f64,
+}
+
+impl Exploration {
+    pub fn zero() -> Exploration { Exploration{ visited: 0, cells: 0, fraction: std::f64::NAN, dispersal: std::f64::NAN } }
+}
+
+impl Display for Exploration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} {} {}", self.visited, self.cells, self.fraction, self.dispersal)
+    }
+}
+
+impl StaticEntitled for Exploration {
+    fn titles(specifier: &str) -> Vec<String> { prefixed(specifier, &["visited", "cells", "fraction", "dispersal"]) }
+}
+
+fn trimmed(mut values: Vec<f64>, q: f64) -> f64 {
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let x = q * (values.len() - 1) as f64;
+    let (i, f) = (x.floor() as usize, x - x.floor());
+    if i + 1 < values.len() { values[i] + f * (values[i+1] - values[i]) } else { values[i] }
+}
+
+// The plate's extent from every worm's bounding box.  Each side is a
+// percentile of the worms' bounds on that side rather than the extreme, so a
+// few tracking errors far outside the plate don't stretch it.
+pub fn estimate_arena(rows: &[Scores], trim: f64, precision: Precision) -> Option<Arena> {
+    let side = |f: &dyn Fn(&Scores) -> f64| -> Vec<f64> { rows.iter().map(|r| f(r)).filter(|v| v.is_finite()).collect() };
+    let (x0, x1, y0, y1) = (side(&|r| r.x.bound0), side(&|r| r.x.bound1), side(&|r| r.y.bound0), side(&|r| r.y.bound1));
+    if x0.len() == 0 || x1.len() == 0 || y0.len() == 0 || y1.len() == 0 { return None; }
+    let r6 = |v| precision.r6(v);
+    let arena = Arena{ x0: r6(trimmed(x0, trim)), x1: r6(trimmed(x1, 1.0 - trim)), y0: r6(trimmed(y0, trim)), y1: r6(trimmed(y1, 1.0 - trim)) };
+    if arena.x1 > arena.x0 && arena.y1 > arena.y0 { Some(arena) } else { None }
+}
+
+// Dispersal is the farthest distance reached from the starting point,
+// divided by the time taken to first get there.
+pub fn the_exploration(settings: &ExplorationSettings, precision: Precision, input: &[DataLine]) -> Option<Exploration> {
+    if !(settings.cell > 0.0) { return None; }
+    let arena = match settings.arena {
+        Some(a) => a,
+        None    => {
+            let x = the_coord(|d| d.x, precision, input);
+            let y = the_coord(|d| d.y, precision, input);
+            if x.stats.n == 0 || y.stats.n == 0 { return None; }
+            Arena{ x0: x.bound0, x1: x.bound1, y0: y.bound0, y1: y.bound1 }
+        }
+    };
+    let nx = std::cmp::max(1, ((arena.x1 - arena.x0) / settings.cell).ceil() as i64);
+    let ny = std::cmp::max(1, ((arena.y1 - arena.y0) / settings.cell).ceil() as i64);
+
+    let mut seen: HashSet<(i64, i64)> = HashSet::new();
+    let mut start: Option<(f64, f64, f64)> = None;
+    let mut farthest = 0f64;
+    let mut when = std::f64::NAN;
+    for d in input.iter() {
+        if !(d.x.is_finite() && d.y.is_finite() && d.time.is_finite()) || !arena.contains(d.x, d.y) { continue; }
+        let i = std::cmp::min(nx - 1, ((d.x - arena.x0) / settings.cell).floor() as i64);
+        let j = std::cmp::min(ny - 1, ((d.y - arena.y0) / settings.cell).floor() as i64);
+        seen.insert((i, j));
+        match start {
+            None => start = Some((d.time, d.x, d.y)),
+            Some((t, x, y)) => {
+                let r = ((d.x - x).powi(2) + (d.y - y).powi(2)).sqrt();
+                if r > farthest { farthest = r; when = d.time - t; }
+            }
+        }
+    }
+    if seen.len() == 0 { return None; }
+
+    let cells = (nx * ny) as u64;
+    let visited = seen.len() as u64;
+    let dispersal = if when > 0.0 { precision.r6(farthest / when) } else { 0.0 };
+    Some(Exploration{ visited, cells, fraction: precision.r6(visited as f64 / cells as f64), dispersal })
+}
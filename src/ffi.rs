@@ -0,0 +1,88 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int};
+
+use crate::*;
+
+
+// Return codes of the C functions; anything but OK leaves a message in `json_out`
+pub const METROLOGY_OK: c_int = 0;
+pub const METROLOGY_BAD_ARGUMENT: c_int = 1;
+pub const METROLOGY_BAD_CONFIG: c_int = 2;
+pub const METROLOGY_BAD_DATA: c_int = 3;
+pub const METROLOGY_PANIC: c_int = 4;
+
+// Hands `text` to the caller, who frees it with `metrology_free_string`.  A
+// message can't hold a NUL, but JSON escapes them anyway.
+unsafe fn hand_out(text: String, out: *mut *mut c_char) {
+    let c = CString::new(text.replace('\0', " ")).unwrap_or_default();
+    *out = c.into_raw();
+}
+
+unsafe fn scored(data: *const c_char, len: usize, id: u32, config: Option<&CStr>, json_out: *mut *mut c_char) -> c_int {
+    if json_out.is_null() { return METROLOGY_BAD_ARGUMENT; }
+    *json_out = std::ptr::null_mut();
+    if data.is_null() && len > 0 {
+        hand_out("data is null".to_string(), json_out);
+        return METROLOGY_BAD_ARGUMENT;
+    }
+    let bytes: &[u8] = if len == 0 { &[] } else { std::slice::from_raw_parts(data as *const u8, len) };
+    let config = match config.map(|c| c.to_str().map_err(|e| e.to_string()).and_then(|t| Config::from_toml(t).map_err(|e| e.to_string()))) {
+        None          => Config::default(),
+        Some(Ok(c))   => c,
+        Some(Err(e))  => { hand_out(e, json_out); return METROLOGY_BAD_CONFIG; },
+    };
+    // Unwinding into C is undefined, so panics stop here
+    match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| scores_json(id.into(), bytes, &config))) {
+        Ok(Ok(json)) => { hand_out(json, json_out); METROLOGY_OK },
+        Ok(Err(e))   => { hand_out(e.to_string(), json_out); METROLOGY_BAD_DATA },
+        Err(_)       => { hand_out("scoring failed unexpectedly".to_string(), json_out); METROLOGY_PANIC },
+    }
+}
+
+/// Scores the contents of one .dat file, `len` bytes at `data`, with the
+/// default configuration.  On success returns 0 and sets `*json_out` to the
+/// scores array as it appears in the scores file; otherwise returns an error
+/// code and sets `*json_out` to a message.  Either way the string must be
+/// freed with `metrology_free_string`.
+///
+/// # Safety
+///
+/// `data` must point to `len` readable bytes, and `json_out` to a writable pointer.
+#[no_mangle]
+pub unsafe extern "C" fn metrology_score_buffer(data: *const c_char, len: usize, json_out: *mut *mut c_char) -> c_int {
+    scored(data, len, 0, None, json_out)
+}
+
+/// As `metrology_score_buffer`, for worm `id` and a configuration in TOML
+/// (NUL-terminated; NULL for the default).
+///
+/// # Safety
+///
+/// As `metrology_score_buffer`, and `config_toml` must be NULL or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn metrology_score_buffer_with(
+    data: *const c_char, len: usize, id: u32, config_toml: *const c_char, json_out: *mut *mut c_char
+) -> c_int {
+    let config = if config_toml.is_null() { None } else { Some(CStr::from_ptr(config_toml)) };
+    scored(data, len, id, config, json_out)
+}
+
+/// Frees a string from `metrology_score_buffer`; NULL is ignored.
+///
+/// # Safety
+///
+/// `s` must have come from this library and not been freed already.
+#[no_mangle]
+pub unsafe extern "C" fn metrology_free_string(s: *mut c_char) {
+    if !s.is_null() { drop(CString::from_raw(s)); }
+}
+
+/// The library's version, as a static NUL-terminated string.
+#[no_mangle]
+pub extern "C" fn metrology_version() -> *const c_char {
+    concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr() as *const c_char
+}
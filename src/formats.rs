@@ -0,0 +1,156 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use std::path::{Path, PathBuf};
+
+use crate::*;
+
+
+// A tracker's output format.  Files are found by extension, named
+// `<prefix>.<worm>.<extension>`, and `sniff` confirms from the first bytes
+// that a file really is in the format.
+pub trait TrackFormat: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn extension(&self) -> &'static str;
+
+    fn sniff(&self, bytes: &[u8]) -> bool;
+
+    fn parse(&self, bytes: &[u8]) -> Result<Vec<DataLine>, MetrologyError>;
+}
+
+// The whitespace-separated text written by the tracker: time, area, speed,
+// midline, x, and y on each line
+#[derive(Debug, Clone, Default)]
+pub struct DatFormat {
+    pub input: InputFormat,
+    pub threads: usize,
+}
+
+impl TrackFormat for DatFormat {
+    fn name(&self) -> &'static str { "dat" }
+
+    fn extension(&self) -> &'static str { "dat" }
+
+    fn sniff(&self, bytes: &[u8]) -> bool {
+        let bytes = strip_bom(bytes);
+        let start = bytes.iter().position(|c| !c.is_ascii_whitespace()).unwrap_or(bytes.len());
+        let end = bytes[start..].iter().position(|&c| c == b'\n').map(|k| start + k + 1).unwrap_or(bytes.len());
+        starts_with_data_line(&self.input.normalize(&bytes[start..end]))
+    }
+
+    fn parse(&self, bytes: &[u8]) -> Result<Vec<DataLine>, MetrologyError> {
+        let text = self.input.normalize(bytes);
+        parse_data_lines_parallel(&text, std::cmp::max(1, self.threads)).map_err(|e| match e {
+            MetrologyError::Parse{ line } => match misformat_hint(&text, line) {
+                Some(hint) => MetrologyError::Misformat{ line, hint },
+                None       => e,
+            },
+            _ => e,
+        })
+    }
+}
+
+const DAT_COLUMNS: &str = "6 numeric columns (time, area, speed, midline, x, y)";
+
+fn is_number(token: &str) -> bool {
+    let t = token.trim_start_matches(|c| c == '+' || c == '-');
+    t.parse::<f64>().is_ok() || t == "Infinity" || t.starts_with("0x") || t.starts_with("0X")
+}
+
+// What a file that does not parse as tracker output, failing on `line`,
+// seems to be instead: other files the tracker writes, archives, or tables
+// laid out differently
+pub fn misformat_hint(text: &[u8], line: usize) -> Option<String> {
+    let head = &text[..text.len().min(4096)];
+    if head.starts_with(b"PK\x03\x04") { return Some("this looks like a zip archive; extract it and analyze the files inside".into()); }
+    if head.starts_with(&[0x1F, 0x8B]) { return Some("this looks like a gzip-compressed file; decompress it first".into()); }
+    let control = head.iter().filter(|&&c| c < 0x20 && c != b'\t' && c != b'\n' && c != b'\r').count();
+    if control > 0 { return Some(format!("this looks like a binary file; expected text with {}", DAT_COLUMNS)); }
+
+    let as_text = |l: &[u8]| String::from_utf8_lossy(l).trim().to_string();
+    let lines: Vec<String> = text.split(|&c| c == b'\n').map(as_text).filter(|l| l.len() > 0).take(5).collect();
+    let first = lines.first()?;
+    if first.starts_with('{') || first.starts_with('[') { return Some("this looks like JSON; WCON files are read only with the .wcon extension".into()); }
+    if first.starts_with('%') && first[1..].trim().parse::<u64>().is_ok() {
+        return Some(format!("this looks like an MWT .blobs file; expected choreography output with {}", DAT_COLUMNS));
+    }
+    let summary = |l: &String| {
+        let tokens: Vec<&str> = l.split_whitespace().collect();
+        l.contains("%%") || (tokens.len() > 6 && tokens[0].parse::<u64>().is_ok() && tokens.iter().take(6).all(|t| is_number(t)))
+    };
+    if lines.iter().any(summary) { return Some(format!("this looks like an MWT summary file; expected {}", DAT_COLUMNS)); }
+
+    // A line that ends early fails where the next one starts, so look back past blank lines
+    let mut k = line.checked_sub(1)?;
+    let mut bad = as_text(text.split(|&c| c == b'\n').nth(k)?);
+    while bad.is_empty() && k > 0 {
+        k -= 1;
+        bad = as_text(text.split(|&c| c == b'\n').nth(k)?);
+    }
+    let tokens: Vec<&str> = bad.split_whitespace().collect();
+    if bad.contains(',') || bad.contains(';') {
+        let fields = bad.split(|c| c == ',' || c == ';').filter(|f| f.trim().len() > 0).count();
+        if fields == 6 { return Some(format!("values are separated by commas or semicolons; expected {} separated by spaces or tabs", DAT_COLUMNS)); }
+    }
+    if tokens.iter().any(|t| !is_number(t)) {
+        if bad == *first && tokens.iter().all(|t| !is_number(t)) {
+            return Some(format!("the first line looks like a column header; expected only {}", DAT_COLUMNS));
+        }
+        return None;
+    }
+    if tokens.len() != 6 { return Some(format!("expected {} but line {} has {}", DAT_COLUMNS, k + 1, tokens.len())); }
+    None
+}
+
+// The formats a run can read.  New formats are added here, or with
+// `register` by programs using the library.
+pub struct TrackFormats {
+    formats: Vec<Box<dyn TrackFormat>>,
+}
+
+impl TrackFormats {
+    pub fn new(input: &InputFormat, precision: Precision, threads: usize) -> TrackFormats {
+        TrackFormats{ formats: vec![Box::new(DatFormat{ input: input.clone(), threads }), Box::new(WconFormat{ precision })] }
+    }
+
+    // A format registered later takes precedence for its extension
+    pub fn register(&mut self, format: Box<dyn TrackFormat>) { self.formats.insert(0, format); }
+
+    pub fn for_path(&self, path: &Path) -> Option<&dyn TrackFormat> {
+        let ext = path.extension()?;
+        self.formats.iter().find(|f| ext == f.extension()).map(|f| f.as_ref())
+    }
+
+    pub fn is_track_file(&self, path: &Path) -> bool { self.for_path(path).is_some() }
+
+    // Track files in the directory, sorted by name.  A directory can only be
+    // read as one stream, so this is serial, but names are filtered by
+    // extension first, and the file type comes with the entry on most file
+    // systems (NFS included), so only symbolic links cost another round trip.
+    pub fn track_files(&self, dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            if !self.is_track_file(&path) { continue; }
+            let file_type = entry.file_type()?;
+            let is_dir = if file_type.is_symlink() { path.is_dir() } else { file_type.is_dir() };
+            if !is_dir { files.push(path); }
+        }
+        files.sort();
+        Ok(files)
+    }
+
+    // Parses with the format for the file's extension, unless the contents
+    // are clearly in another format
+    pub fn parse(&self, path: &Path, bytes: &[u8]) -> Result<Vec<DataLine>, MetrologyError> {
+        let named = self.for_path(path).ok_or_else(|| MetrologyError::UnknownFormat(path.to_string_lossy().to_string()))?;
+        if named.sniff(bytes) { return named.parse(bytes); }
+        match self.formats.iter().find(|f| f.sniff(bytes)) {
+            Some(f) => f.parse(bytes),
+            None    => named.parse(bytes),
+        }
+    }
+}
@@ -0,0 +1,45 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use serde::{Serialize, Deserialize};
+
+use crate::*;
+
+
+// Some trackers split one worm's track over several files, lettered after
+// the worm number (`plate.042a.dat`, `plate.042b.dat`).  With `stitch`, the
+// pieces are joined in time order and scored as one worm; otherwise they are
+// skipped, as the worm number alone can't tell them apart.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FragmentSettings {
+    pub stitch: bool,
+}
+
+// The worm number and fragment letters (empty if none) of the number in a
+// file name, as `042a`
+pub fn worm_fragment(number: &str) -> Option<(WormId, &str)> {
+    let digits = number.find(|c: char| !c.is_ascii_digit()).unwrap_or(number.len());
+    let (n, letters) = number.split_at(digits);
+    if !letters.chars().all(|c| c.is_ascii_lowercase()) { return None; }
+    Some((n.parse().ok()?, letters))
+}
+
+fn time_span(piece: &[DataLine]) -> Option<(f64, f64)> {
+    let mut times = piece.iter().map(|d| d.time).filter(|t| t.is_finite());
+    let first = times.next()?;
+    Some(times.fold((first, first), |(a, b), t| (a.min(t), b.max(t))))
+}
+
+// The pieces in order of their first time, one after another.  A piece may
+// start when the one before ends, but not before.
+pub fn stitch_fragments(pieces: Vec<Vec<DataLine>>) -> Result<Vec<DataLine>, MetrologyError> {
+    let mut spans: Vec<((f64, f64), Vec<DataLine>)> = pieces.into_iter().filter_map(|p| time_span(&p).map(|s| (s, p))).collect();
+    spans.sort_by(|a, b| (a.0).0.partial_cmp(&(b.0).0).unwrap());
+    for w in spans.windows(2) {
+        let ((_, end), (start, _)) = ((w[0].0), (w[1].0));
+        if start < end { return Err(MetrologyError::FragmentOverlap{ end, start }); }
+    }
+    Ok(spans.into_iter().flat_map(|(_, p)| p.into_iter()).collect())
+}
@@ -0,0 +1,57 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use std::path::Path;
+
+use crate::*;
+
+
+// One worm's raw data together with the scores computed from it
+pub struct WormRecord<'a> {
+    pub id: WormId,
+    pub data: &'a [DataLine],
+    pub scores: &'a [Scores],
+}
+
+fn write_column<F>(group: &hdf5::Group, name: &str, data: &[DataLine], f: F) -> Result<(), MetrologyError>
+where F: Fn(&DataLine) -> f64 {
+    let values: Vec<f64> = data.iter().map(f).collect();
+    group.new_dataset::<f64>().shape(values.len()).create(name)?.write(values.as_slice())?;
+    Ok(())
+}
+
+// Every column of the scores table becomes a scalar attribute; anything that
+// is not a number is stored as NaN.
+fn write_scores(group: &hdf5::Group, scores: &Scores) -> Result<(), MetrologyError> {
+    let title = scores.title();
+    let row = scores.to_string();
+    for (name, value) in title.split(' ').zip(row.split(' ')) {
+        let x = value.parse::<f64>().unwrap_or(std::f64::NAN);
+        group.new_attr::<f64>().shape(()).create(name)?.write_scalar(&x)?;
+    }
+    Ok(())
+}
+
+// One group per worm, `worm<id>`, holding the raw columns as datasets and the
+// scores as attributes.  Worms split into tracks get a subgroup `track<n>`
+// per track for the scores.
+pub fn write_hdf5(path: &Path, worms: &[WormRecord]) -> Result<(), MetrologyError> {
+    let file = hdf5::File::create(path)?;
+    for w in worms.iter() {
+        let group = file.create_group(format!("worm{}", w.id).as_str())?;
+        write_column(&group, "time",    w.data, |d| d.time)?;
+        write_column(&group, "area",    w.data, |d| d.area)?;
+        write_column(&group, "speed",   w.data, |d| d.speed)?;
+        write_column(&group, "midline", w.data, |d| d.midline)?;
+        write_column(&group, "x",       w.data, |d| d.x)?;
+        write_column(&group, "y",       w.data, |d| d.y)?;
+        for s in w.scores.iter() {
+            match s.track {
+                None    => write_scores(&group, s)?,
+                Some(k) => write_scores(&group.create_group(format!("track{}", k).as_str())?, s)?,
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,64 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use serde::{Serialize, Deserialize};
+
+use crate::*;
+
+
+// No edges means no histograms are written
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HistogramSettings {
+    pub edges: Vec<f64>,
+}
+
+impl HistogramSettings {
+    pub fn errors(&self) -> Vec<String> {
+        if self.edges.len() == 1 { return vec!["histogram needs at least two edges".into()]; }
+        if self.edges.iter().any(|e| !e.is_finite()) { return vec!["histogram edges must be finite".into()]; }
+        if self.edges.windows(2).any(|w| !(w[0] < w[1])) { return vec!["histogram edges must be increasing".into()]; }
+        Vec::new()
+    }
+}
+
+// Bins are closed on the left and open on the right, except for the last,
+// which includes its right edge.  Speeds outside all bins are counted in
+// `below` and `above`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpeedHistogram {
+    pub id: WormId,
+    pub edges: Vec<f64>,
+    pub counts: Vec<u64>,
+    pub below: u64,
+    pub above: u64,
+}
+
+impl SpeedHistogram {
+    pub fn long_title() -> String { "id lower upper count".into() }
+
+    pub fn push_long_rows(&self, to: &mut String) {
+        for (i, c) in self.counts.iter().enumerate() {
+            to.push_str(format!("{} {} {} {}\n", self.id, self.edges[i], self.edges[i+1], c).as_str());
+        }
+    }
+}
+
+pub fn the_speed_histogram(id: WormId, edges: &[f64], input: &[DataLine]) -> SpeedHistogram {
+    let bins = if edges.len() > 1 { edges.len() - 1 } else { 0 };
+    let mut counts = vec![0u64; bins];
+    let mut below = 0u64;
+    let mut above = 0u64;
+    for line in input.iter() {
+        let s = line.speed;
+        if !s.is_finite() { continue; }
+        if bins == 0 || s < edges[0] { below += 1; }
+        else if s > edges[bins] { above += 1; }
+        else {
+            let i = edges.partition_point(|e| *e <= s);
+            counts[std::cmp::min(i, bins) - 1] += 1;
+        }
+    }
+    SpeedHistogram{ id, edges: edges.to_vec(), counts, below, above }
+}
@@ -0,0 +1,291 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::*;
+
+
+// Cells taken as missing whatever `--missing` token was used
+const MISSING_CELLS: [&str; 7] = ["", "NaN", "nan", "NA", "N/A", "null", "-"];
+
+// The text of a table as the crate wrote it, or as a spreadsheet saved it
+// again: UTF-16 of either byte order (with its byte order mark) or UTF-8,
+// with or without a byte order mark.
+fn decoded(bytes: &[u8]) -> Result<String, MetrologyError> {
+    let utf16 = |big: bool| {
+        let units: Vec<u16> = bytes[2..].chunks(2)
+            .map(|c| if c.len() < 2 { c[0] as u16 } else if big { u16::from_be_bytes([c[0], c[1]]) } else { u16::from_le_bytes([c[0], c[1]]) })
+            .collect();
+        String::from_utf16(&units).map_err(|e| MetrologyError::Decode(e.to_string()))
+    };
+    if bytes.starts_with(&[0xFF, 0xFE])      { utf16(false) }
+    else if bytes.starts_with(&[0xFE, 0xFF]) { utf16(true) }
+    else {
+        let bytes = if bytes.starts_with(&[0xEF, 0xBB, 0xBF]) { &bytes[3..] } else { bytes };
+        String::from_utf8(bytes.to_vec()).map_err(|e| MetrologyError::Decode(e.to_string()))
+    }
+}
+
+// The crate separates values with single spaces; spreadsheets use tabs, commas,
+// or (where the comma is the decimal mark) semicolons.
+fn separator_of(title: &str) -> char {
+    if title.contains('\t')     { '\t' }
+    else if title.contains(';') { ';' }
+    else if title.contains(',') { ',' }
+    else                        { ' ' }
+}
+
+fn cells_of(line: &str, separator: char) -> Vec<String> {
+    line.split(separator).map(|c| c.trim().trim_matches('"').trim().to_string()).collect()
+}
+
+struct CsvRow<'a> {
+    names: &'a [String],
+    cells: Vec<String>,
+
+    // Whether a comma in a value is the decimal mark
+    comma: bool,
+}
+
+impl<'a> CsvRow<'a> {
+    fn cell(&self, name: &str) -> Option<&str> {
+        self.names.iter().position(|n| n == name).map(|i| self.cells[i].as_str())
+    }
+
+    // Anything that isn't a number, the missing token included, is NaN
+    fn num(&self, name: &str) -> f64 {
+        let text = self.cell(name).unwrap_or("");
+        let parsed = if self.comma { text.replace(',', ".").parse::<f64>() } else { text.parse::<f64>() };
+        parsed.unwrap_or(std::f64::NAN)
+    }
+
+    fn count(&self, name: &str) -> u64 {
+        let x = self.num(name);
+        if x.is_finite() && x >= 0.0 { x.round() as u64 } else { 0 }
+    }
+
+    fn some(&self, name: &str) -> Option<f64> { Some(self.num(name)).filter(|x| x.is_finite()) }
+
+    fn sampled(&self, prefix: &str) -> Sampled {
+        let ci = |end: &str| if self.cell(format!("{}ci95-{}", prefix, end).as_str()).is_some() { Some(self.num(format!("{}ci95-{}", prefix, end).as_str())) } else { None };
+        Sampled{
+            mean: self.num(format!("{}mean", prefix).as_str()),
+            sem: self.num(format!("{}sem", prefix).as_str()),
+            n: self.count(format!("{}n", prefix).as_str()),
+            ci95_lo: ci("lo"),
+            ci95_hi: ci("hi"),
+        }
+    }
+
+    fn coord(&self, prefix: &str) -> Coord {
+        let at = |name: &str| self.num(format!("{}{}", prefix, name).as_str());
+        Coord{ first: at("first"), last: at("last"), bound0: at("smallest"), bound1: at("largest"), stats: self.sampled(prefix) }
+    }
+
+    fn summary(&self, bounded: bool, prefix: &str) -> Summary {
+        if bounded { self.coord(prefix).into() } else { self.sampled(prefix).into() }
+    }
+
+    // Windows too short to score are written as zero samples
+    fn speed(&self, prefix: &str) -> Option<Speed> {
+        let stats = self.sampled(prefix);
+        if stats.n == 0 { return None; }
+        let moving = format!("{}moving-", prefix);
+        let moving = if self.cell(format!("{}n", moving).as_str()).is_some() { Some(self.sampled(moving.as_str())) } else { None };
+        Some(Speed{ stats, max: self.num(format!("{}max", prefix).as_str()), coverage: None, estimator: None, moving, bounds: None })
+    }
+}
+
+fn transform_before(names: &[String], column: &str) -> Transform {
+    if names.iter().any(|n| *n == format!("log-{}", column))       { Transform::Log }
+    else if names.iter().any(|n| *n == format!("sqrt-{}", column)) { Transform::Sqrt }
+    else                                                           { Transform::None }
+}
+
+// The named-window metric whose first column is `column`, with the window's name
+fn window_metric_at(column: &str) -> Option<(&str, WindowMetric)> {
+    let metrics = [
+        ("-area-n", WindowMetric::Area), ("-midline-n", WindowMetric::Midline), ("-x-first", WindowMetric::Coord),
+        ("-speed-n", WindowMetric::Speed), ("-activity", WindowMetric::Activity),
+    ];
+    metrics.iter()
+        .find(|(suffix, _)| column.len() > suffix.len() && column.ends_with(suffix))
+        .map(|(suffix, m)| (&column[..column.len() - suffix.len()], *m))
+}
+
+fn window_metric_of(row: &CsvRow, w: &mut WindowScores, metric: WindowMetric) {
+    let name = w.name.clone();
+    let sub = |m: &str| format!("{}-{}", name, m);
+    match metric {
+        WindowMetric::Area     => w.area = Some(row.sampled(sub("area-").as_str())),
+        WindowMetric::Midline  => w.midline = Some(row.sampled(sub("midline-").as_str())),
+        WindowMetric::Coord    => { w.x = Some(row.coord(sub("x-").as_str())); w.y = Some(row.coord(sub("y-").as_str())); },
+        WindowMetric::Speed    => w.speed = row.speed(sub("speed-").as_str()),
+        WindowMetric::Activity => w.activity = row.some(sub("activity").as_str()),
+    }
+    w.metrics.push(metric);
+}
+
+impl Scores {
+    // Scores read back from a `.csv` table this crate wrote, for runs where
+    // only the tables were kept.  The columns are recognized from their names.
+    // Only what the table holds comes back: QC flags, warnings, and the times
+    // of named windows are lost.  After the named windows, columns that are
+    // numbers in every row are derived metrics, and the rest metadata.
+    pub fn from_csv(path: &Path) -> Result<Vec<Scores>, MetrologyError> {
+        Scores::from_csv_text(decoded(std::fs::read(path)?.as_slice())?.as_str())
+    }
+
+    pub fn from_csv_text(text: &str) -> Result<Vec<Scores>, MetrologyError> {
+        let mut config_hash = None;
+        let mut lines = Vec::new();
+        for line in text.lines().map(|l| l.trim_end_matches('\r')) {
+            if line.starts_with('#') {
+                let words: Vec<&str> = line[1..].split_whitespace().collect();
+                if words.len() == 2 && words[0] == "config" { config_hash = Some(words[1].to_string()); }
+            }
+            else if line.trim().len() > 0 { lines.push(line); }
+        }
+        let title = match lines.first() {
+            Some(t) => *t,
+            None    => return Ok(Vec::new()),
+        };
+        let separator = separator_of(title);
+        let names = cells_of(title, separator);
+        let rows: Vec<CsvRow> = lines[1..].iter().map(|l| CsvRow{ names: &names, cells: cells_of(l, separator), comma: separator != ',' }).collect();
+        for (i, row) in rows.iter().enumerate() {
+            if row.cells.len() != names.len() { return Err(MetrologyError::Schema{ row: i + 1, expected: names.len(), found: row.cells.len() }); }
+        }
+
+        let has = |name: &str| names.iter().any(|n| n == name);
+        let transforms = ColumnTransforms{
+            area: transform_before(&names, "area-n"),
+            midline: transform_before(&names, "midline-n"),
+            speed: Transform::None,
+        };
+        let (area, midline) = (format!("{}area-", transforms.area.prefix()), format!("{}midline-", transforms.midline.prefix()));
+        let arousal_at = names.iter().position(|n| n == "arousal-difference")
+            .ok_or_else(|| MetrologyError::Decode("no arousal-difference column; is this a scores table?".to_string()))?;
+        let speed_prefixes: Vec<String> = names[..arousal_at].iter().filter(|n| n.ends_with("-max")).map(|n| n[..n.len() - 3].to_string()).collect();
+        let speed = [Transform::Log, Transform::Sqrt].iter().cloned()
+            .find(|t| speed_prefixes.len() > 0 && speed_prefixes.iter().all(|p| p.starts_with(t.prefix())))
+            .unwrap_or(Transform::None);
+        let transforms = ColumnTransforms{ speed, ..transforms };
+        let speed_names: Vec<String> = speed_prefixes.iter().map(|p| p[speed.prefix().len()..p.len() - 1].to_string()).collect();
+        let optional = OptionalColumns{
+            area_bounded: has(format!("{}first", area).as_str()), midline_bounded: has(format!("{}first", midline).as_str()),
+            rates: has("area-rate-n"), shape_index: has("shape-n"),
+            moving: speed_prefixes.first().map(|p| has(format!("{}moving-n", p).as_str())).unwrap_or(false),
+            spread: has("spread-bbox-area"), bursts: has("bursts-count"), omega: has("omega-count"), transforms,
+            ci95: has("x-ci95-lo"),
+        };
+
+        // Everything up to the named windows is fixed by the optional groups
+        let fixed = Scores::titles_with("", optional, speed_names.iter().map(|n| n.as_str()), std::iter::empty(), std::iter::empty(), std::iter::empty());
+        if let Some((expected, found)) = fixed.iter().zip(names.iter()).find(|(e, n)| e != n) {
+            return Err(MetrologyError::Decode(format!("found column {} where {} was expected", found, expected)));
+        }
+        if names.len() < fixed.len() { return Err(MetrologyError::Decode(format!("no column {}", fixed[names.len()]))); }
+
+        // Named windows are runs of their metrics' columns; what follows is
+        // derived metrics, then metadata.
+        let mut windows: Vec<(String, Vec<WindowMetric>)> = Vec::new();
+        let mut i = fixed.len();
+        while i < names.len() {
+            let (name, metric) = match window_metric_at(names[i].as_str()) {
+                Some(x) => x,
+                None    => break,
+            };
+            let expected = WindowScores::titles_of(name, &[metric], "", optional.ci95);
+            if names.len() < i + expected.len() || names[i..i + expected.len()] != expected[..] { break; }
+            match windows.last_mut() {
+                Some((n, ms)) if n == name && !ms.contains(&metric) => ms.push(metric),
+                _                                                 => windows.push((name.to_string(), vec![metric])),
+            }
+            i += expected.len();
+        }
+        let numeric = |column: &str| rows.iter().all(|r| MISSING_CELLS.contains(&r.cell(column).unwrap_or("")) || !r.num(column).is_nan());
+        let extras_end = (i..names.len()).find(|&j| !numeric(names[j].as_str())).unwrap_or(names.len());
+        let (extras, metadata) = (&names[i..extras_end], &names[extras_end..]);
+
+        let mut scores = Vec::with_capacity(rows.len());
+        for row in rows.iter() {
+            let id_cell = row.cell("id").unwrap_or("").replace(',', ".");
+            let (id, track) = match id_cell.find('.') {
+                Some(k) => (id_cell[..k].parse::<WormId>(), id_cell[k+1..].parse::<u32>().ok()),
+                None    => (id_cell.parse::<WormId>(), None),
+            };
+            let id = id.map_err(|_| MetrologyError::Decode(format!("worm id {:?} is not a number", id_cell)))?;
+            let mut s = Scores::zero();
+            s.id = id;
+            s.track = track;
+            s.t0 = row.num("t0");
+            s.t1 = row.num("t1");
+            s.area = row.summary(optional.area_bounded, area.as_str());
+            s.midline = row.summary(optional.midline_bounded, midline.as_str());
+            if optional.rates { s.shape_rates = Some(ShapeRates{ area: row.sampled("area-rate-"), midline: row.sampled("midline-rate-") }); }
+            if optional.shape_index { s.shape_index = Some(row.sampled("shape-")); }
+            s.speeds = WindowSpeeds::named(&speed_names[..]);
+            for (name, prefix) in speed_names.iter().zip(speed_prefixes.iter()) { s.speeds.set(name, row.speed(prefix)); }
+            // The table doesn't say what the threshold was
+            if optional.moving { s.moving_threshold = Some(std::f64::NAN); }
+            s.arousal = row.some("arousal-difference").map(|difference|
+                Arousal{ difference, sem: row.num("arousal-sem"), t: row.num("arousal-t"), df: row.num("arousal-df") }
+            );
+            s.x = row.coord("x-");
+            s.y = row.coord("y-");
+            if optional.spread { s.spread = Some(Spread{ bbox_area: row.num("spread-bbox-area"), gyration: row.num("spread-gyration") }); }
+            s.exploration = row.some("exploration-fraction").map(|fraction| Exploration{
+                visited: row.count("exploration-visited"), cells: row.count("exploration-cells"), fraction, dispersal: row.num("exploration-dispersal")
+            });
+            s.composite = row.some("composite");
+            s.edge_fraction = row.some("edge-fraction");
+            s.qc.area_rate = row.num("qc-max-area-rate");
+            s.qc.midline_rate = row.num("qc-max-midline-rate");
+            s.qc.x_rate = row.num("qc-max-x-rate");
+            s.qc.y_rate = row.num("qc-max-y-rate");
+            s.qc.speed_path_ratio = row.some("qc-speed-path-ratio");
+            s.qc.frame_rate = row.some("qc-median-dt").map(|median_dt|
+                FrameRate{ median_dt, jitter: row.num("qc-jitter"), dropped_frames: row.count("qc-dropped-frames") }
+            );
+            if optional.bursts {
+                s.bursts = Some(Bursts{
+                    count: row.count("bursts-count"), rate: row.num("bursts-rate"),
+                    amplitude: row.sampled("bursts-amplitude-"), interval: row.sampled("bursts-interval-"),
+                });
+            }
+            if optional.omega { s.omega = Some(OmegaTurns{ count: row.count("omega-count"), rate: row.num("omega-rate") }); }
+            for (name, metrics) in windows.iter() {
+                let mut w = WindowScores{
+                    name: name.clone(), start: std::f64::NAN, end: std::f64::NAN, metrics: Vec::new(),
+                    area: None, midline: None, x: None, y: None, speed: None, activity: None,
+                };
+                for m in metrics.iter() { window_metric_of(row, &mut w, *m); }
+                s.windowed.push(w);
+            }
+            s.extras = extras.iter().map(|e| (e.clone(), row.num(e))).collect::<BTreeMap<String, f64>>();
+            s.metadata = metadata.iter().map(|m| {
+                let c = row.cell(m).unwrap_or("");
+                (m.clone(), if MISSING_CELLS.contains(&c) { None } else { Some(c.to_string()) })
+            }).collect();
+            s.transforms = transforms;
+            s.config_hash = config_hash.clone();
+            s.set_intervals(optional.ci95, Precision::Full);
+            scores.push(s);
+        }
+        Ok(scores)
+    }
+}
+
+// Scores from a file of any kind this crate writes, told apart by extension:
+// `.msgpack`, `.csv`, or else JSON
+pub fn read_scores_file(path: &Path) -> Result<Vec<Scores>, MetrologyError> {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("msgpack") => read_scores_msgpack(path),
+        Some("csv")     => Scores::from_csv(path),
+        _               => read_scores(path),
+    }
+}
@@ -0,0 +1,33 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use crate::*;
+
+
+// Where a line came from, for messages: its line in the file if known
+fn place_of(d: &DataLine) -> String {
+    if d.source_line > 0 { format!("line {}", d.source_line) } else { format!("frame {}", d.frame) }
+}
+
+// What no tracker writes, so a file breaking them was damaged on the way:
+// times that don't strictly increase, and area or speed that is infinite,
+// negative, or (for area) zero.  Missing values, NaN, are allowed.  Every line
+// is checked; the error gives the first problem and how many others.
+pub fn check_integrity(lines: &[DataLine]) -> Result<(), MetrologyError> {
+    let mut problems: Vec<String> = Vec::new();
+    let mut previous: Option<&DataLine> = None;
+    for d in lines.iter() {
+        if !d.time.is_finite() { problems.push(format!("{}: time is {}", place_of(d), d.time)); }
+        else if let Some(p) = previous.filter(|p| !(d.time > p.time)) {
+            problems.push(format!("{}: time {} does not follow {} on {}", place_of(d), d.time, p.time, place_of(p)));
+        }
+        if d.time.is_finite() { previous = Some(d); }
+        if d.area.is_infinite() || d.area <= 0.0 { problems.push(format!("{}: area is {}", place_of(d), d.area)); }
+        if d.speed.is_infinite() || d.speed < 0.0 { problems.push(format!("{}: speed is {}", place_of(d), d.speed)); }
+    }
+    match problems.first() {
+        None        => Ok(()),
+        Some(first) => Err(MetrologyError::Integrity{ problem: first.clone(), others: problems.len() - 1 }),
+    }
+}
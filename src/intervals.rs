@@ -0,0 +1,70 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use serde::{Serialize, Deserialize};
+
+use crate::*;
+
+
+// Contiguous stretches of tracking are reported only if asked for.  A gap
+// longer than `max_gap` seconds (by default 1.5 typical frame intervals) or a
+// frame without a position ends a stretch.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IntervalSettings {
+    pub report: bool,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_gap: Option<f64>,
+
+    // Also write the intervals of every worm as a long table
+    pub csv: bool,
+}
+
+impl IntervalSettings {
+    pub fn errors(&self) -> Vec<String> {
+        match self.max_gap {
+            Some(g) if !(g > 0.0) => vec![format!("interval max_gap must be positive, not {}", g)],
+            _                     => Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrackedInterval {
+    pub start: f64,
+    pub end: f64,
+    pub frames: u64,
+}
+
+pub fn the_tracked_intervals(settings: &IntervalSettings, median_dt: Option<f64>, input: &[DataLine]) -> Vec<TrackedInterval> {
+    let max_gap = settings.max_gap.or(median_dt.map(|dt| 1.5 * dt)).filter(|g| g.is_finite()).unwrap_or(std::f64::INFINITY);
+    let mut intervals: Vec<TrackedInterval> = Vec::new();
+    let mut current: Option<TrackedInterval> = None;
+    for d in input.iter().filter(|d| d.time.is_finite()) {
+        if !(d.x.is_finite() && d.y.is_finite()) {
+            intervals.extend(current.take());
+            continue;
+        }
+        match current.as_mut() {
+            Some(c) if d.time - c.end <= max_gap => { c.end = d.time; c.frames += 1; },
+            _ => {
+                intervals.extend(current.take());
+                current = Some(TrackedInterval{ start: d.time, end: d.time, frames: 1 });
+            }
+        }
+    }
+    intervals.extend(current);
+    intervals
+}
+
+pub fn intervals_long_title() -> String { "id track start end frames".into() }
+
+// Rows of the long table; the track is NaN for worms that were not split
+pub fn push_interval_rows(scores: &Scores, to: &mut String) {
+    let track = scores.track.map(|k| k.to_string()).unwrap_or_else(|| "NaN".to_string());
+    for i in scores.intervals.iter() {
+        to.push_str(format!("{} {} {} {} {}\n", scores.id, track, i.start, i.end, i.frames).as_str());
+    }
+}
@@ -0,0 +1,119 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use crate::*;
+
+
+// Environment variables for values often changed between cluster jobs, and
+// where they go in the configuration.  Windows are given as `START,END`.
+pub const CONFIG_VARIABLES: [(&str, &str); 6] = [
+    ("METROLOGY_INITIAL_WINDOW", "windows.speed.initial"),
+    ("METROLOGY_CALM_WINDOW", "windows.speed.calm"),
+    ("METROLOGY_AROUSED_WINDOW", "windows.speed.aroused"),
+    ("METROLOGY_MIN_COVERAGE", "windows.min_coverage"),
+    ("METROLOGY_SPEED_CEILING", "speed.ceiling.limit"),
+    ("METROLOGY_METRICS", "metrics.only"),
+];
+
+// Any other value can be set with `section.key=value` pairs separated by `;`
+pub const CONFIG_SET_VARIABLE: &str = "METROLOGY_SET";
+
+// Overrides, as `section.key=value`, from the environment variables among `vars`
+pub fn environment_overrides<I: Iterator<Item = (String, String)>>(vars: I) -> Vec<String> {
+    let vars: Vec<(String, String)> = vars.filter(|(k, _)| k.starts_with("METROLOGY_")).collect();
+    let value_of = |name: &str| vars.iter().find(|(k, _)| k == name).map(|(_, v)| v.trim().to_string());
+    let mut overrides = Vec::new();
+    for (name, path) in CONFIG_VARIABLES.iter() {
+        if let Some(v) = value_of(name) {
+            let parts: Vec<&str> = v.split(',').map(|p| p.trim()).collect();
+            let value =
+                if path.starts_with("windows.speed.") && parts.len() == 2 { format!("{{ start = {}, end = {} }}", parts[0], parts[1]) }
+                else if *path == "metrics.only" { format!("[{}]", parts.iter().map(|p| format!("{:?}", p)).collect::<Vec<String>>().join(", ")) }
+                else { v };
+            overrides.push(format!("{}={}", path, value));
+        }
+    }
+    if let Some(v) = value_of(CONFIG_SET_VARIABLE) {
+        overrides.extend(v.split(';').map(|s| s.trim().to_string()).filter(|s| s.len() > 0));
+    }
+    overrides
+}
+
+// The value under `key`: the entry of a table, made if missing, or the
+// element of an array of tables with that `name`, added if there is none
+fn entry<'a>(here: &'a mut toml::Value, key: &str, over: &str) -> Result<&'a mut toml::Value, String> {
+    match here {
+        toml::Value::Table(table) => Ok(table.entry(key.to_string()).or_insert_with(|| toml::Value::Table(toml::value::Table::new()))),
+        toml::Value::Array(list)  => {
+            let i = match list.iter().position(|v| v.get("name").and_then(|n| n.as_str()) == Some(key)) {
+                Some(i) => i,
+                None    => {
+                    let mut named = toml::value::Table::new();
+                    named.insert("name".to_string(), toml::Value::String(key.to_string()));
+                    list.push(toml::Value::Table(named));
+                    list.len() - 1
+                }
+            };
+            Ok(&mut list[i])
+        },
+        _ => Err(format!("override {:?} goes inside a value that is not a section", over)),
+    }
+}
+
+// Sets the value at a dotted path, making tables as needed.  A value that
+// isn't valid TOML is taken as a string.  A key naming an element of a list,
+// such as `calm` in `windows.speed.calm`, picks it by its `name`, and a table
+// set there is merged into it.
+fn set_path(root: &mut toml::Value, over: &str) -> Result<(), String> {
+    let (path, text) = match over.find('=') {
+        Some(i) => (over[..i].trim(), over[i+1..].trim()),
+        None    => return Err(format!("override {:?} should be section.key=value", over)),
+    };
+    let value = match toml::from_str::<toml::Value>(format!("v = {}", text).as_str()) {
+        Ok(toml::Value::Table(mut t)) => t.remove("v").unwrap_or_else(|| toml::Value::String(text.to_string())),
+        _                             => toml::Value::String(text.to_string()),
+    };
+    let keys: Vec<&str> = path.split('.').collect();
+    if keys.iter().any(|k| k.is_empty()) { return Err(format!("override {:?} has an empty key", over)); }
+    let mut here = root;
+    for k in keys[..keys.len() - 1].iter() { here = entry(here, k, over)?; }
+    let in_list = here.is_array();
+    let target = entry(here, keys[keys.len() - 1], over)?;
+    match value {
+        toml::Value::Table(fields) if in_list => {
+            let element = target.as_table_mut().ok_or_else(|| format!("override {:?} goes inside a value that is not a section", over))?;
+            for (k, v) in fields { element.insert(k, v); }
+        },
+        _ if in_list => return Err(format!("override {:?} should set a table, such as {{ start = 0, end = 1 }}", over)),
+        _            => *target = value,
+    }
+    Ok(())
+}
+
+// Overrides of single speed windows change them among the default windows
+// when the configuration doesn't list its own
+fn with_speed_windows(root: &mut toml::Value) -> Result<(), String> {
+    let over = "windows.speed";
+    let windows = entry(root, "windows", over)?.as_table_mut().ok_or_else(|| format!("{} goes inside a value that is not a section", over))?;
+    if !windows.contains_key("speed") {
+        windows.insert("speed".to_string(), toml::Value::try_from(&SpeedWindows::default().speed).map_err(|e| e.to_string())?);
+    }
+    Ok(())
+}
+
+impl Config {
+    // The configuration in `text`, with each override applied in turn, so a
+    // later one wins; checked as `from_toml`
+    pub fn layered(text: &str, overrides: &[String]) -> Result<Config, MetrologyError> {
+        let mut root: toml::Value = toml::from_str(text)?;
+        if overrides.iter().any(|o| o.trim_start().starts_with("windows.speed.")) { with_speed_windows(&mut root).map_err(MetrologyError::BadConfig)?; }
+        for o in overrides.iter() { set_path(&mut root, o.as_str()).map_err(MetrologyError::BadConfig)?; }
+        Config::from_toml(root.to_string().as_str())
+    }
+
+    // Every value, defaults included, as a configuration file
+    pub fn to_toml(&self) -> String {
+        toml::Value::try_from(self).map(|v| v.to_string()).unwrap_or_default()
+    }
+}
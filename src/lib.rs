@@ -2,16 +2,136 @@
 // Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
 
 
+use std::collections::BTreeMap;
 use std::fmt;
 use std::fmt::Display;
+use std::path::Path;
 
-use serde::{Serialize, Deserialize};
+use serde::{Serialize, Deserialize, Deserializer};
 
 use average::Estimate;
 
+pub mod error;
 pub mod parsing;
-
+pub mod config;
+pub mod alignment;
+pub mod arousal;
+pub mod bursts;
+pub mod cache;
+pub mod ceiling;
+pub mod columns;
+pub mod comparison;
+pub mod composite;
+pub mod dataset;
+pub mod derived;
+pub mod duplicates;
+pub mod dose;
+pub mod environment;
+pub mod exploration;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod formats;
+pub mod fragments;
+#[cfg(feature = "hdf5")]
+pub mod h5;
+pub mod histogram;
+pub mod import;
+pub mod integrity;
+pub mod intervals;
+pub mod layers;
+pub mod longitudinal;
+pub mod manifest;
+pub mod metadata;
+pub mod missingness;
+pub mod naming;
+pub mod offsets;
+pub mod omega;
+pub mod panel;
+pub mod pivot;
+pub mod publish;
+pub mod qc;
+pub mod random;
+pub mod rates;
+pub mod report;
+pub mod rescore;
+pub mod running;
+pub mod selection;
+pub mod selftest;
+pub mod smoothing;
+pub mod snapshot;
+pub mod spread;
+pub mod swaps;
+pub mod synth;
+pub mod table;
+pub mod thigmotaxis;
+pub mod transforms;
+pub mod validity;
+pub mod verbosity;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod wcon;
+pub mod windows;
+
+pub use error::*;
 pub use parsing::*;
+pub use config::*;
+pub use alignment::*;
+pub use arousal::*;
+pub use bursts::*;
+pub use cache::*;
+pub use ceiling::*;
+pub use columns::*;
+pub use comparison::*;
+pub use composite::*;
+pub use dataset::*;
+pub use derived::*;
+pub use duplicates::*;
+pub use dose::*;
+pub use environment::*;
+pub use exploration::*;
+#[cfg(feature = "ffi")]
+pub use ffi::*;
+pub use formats::*;
+pub use fragments::*;
+#[cfg(feature = "hdf5")]
+pub use h5::*;
+pub use histogram::*;
+pub use import::*;
+pub use integrity::*;
+pub use intervals::*;
+pub use layers::*;
+pub use longitudinal::*;
+pub use manifest::*;
+pub use metadata::*;
+pub use missingness::*;
+pub use naming::*;
+pub use offsets::*;
+pub use omega::*;
+pub use panel::*;
+pub use pivot::*;
+pub use publish::*;
+pub use qc::*;
+pub use random::*;
+pub use rates::*;
+pub use report::*;
+pub use rescore::*;
+pub use running::*;
+pub use selection::*;
+pub use selftest::*;
+pub use smoothing::*;
+pub use snapshot::*;
+pub use spread::*;
+pub use swaps::*;
+pub use synth::*;
+pub use table::*;
+pub use thigmotaxis::*;
+pub use transforms::*;
+pub use validity::*;
+pub use verbosity::*;
+#[cfg(feature = "wasm")]
+pub use wasm::*;
+pub use wcon::*;
+pub use windows::*;
 
 
 pub trait Entitled {
@@ -25,12 +145,30 @@ pub trait Entitled {
     }
 }
 
+// Column names that do not depend on any particular value, so headers can be
+// written before there are data.
+pub trait StaticEntitled {
+    fn titles(specifier: &str) -> Vec<String>;
+}
+
+impl<T: StaticEntitled> Entitled for T {
+    fn push_subtitle(&self, specifier: &str, to: &mut String) { to.push_str(T::titles(specifier).join(" ").as_str()); }
+}
+
+fn prefixed(specifier: &str, names: &[&str]) -> Vec<String> {
+    names.iter().map(|name| format!("{}{}", specifier, name)).collect()
+}
+
+
+pub fn decimate(input: &[DataLine], every: usize) -> Vec<DataLine> {
+    input.iter().step_by(std::cmp::max(every, 1)).cloned().collect()
+}
 
-pub fn the_area(input: &Vec<DataLine>) -> average::Variance {
+pub fn the_area(input: &[DataLine]) -> average::Variance {
     input.iter().map(|line| line.area).filter(|x| x.is_finite()).collect()
 }
 
-pub fn the_midline(input: &Vec<DataLine>) -> average::Variance {
+pub fn the_midline(input: &[DataLine]) -> average::Variance {
     input.iter().map(|line| line.midline).filter(|x| x.is_finite()).collect()
 }
 
@@ -50,43 +188,125 @@ fn median5(input: &[f64; 5]) -> f64 {
     else                  { input[4] }
 }
 
-fn r6(value: f64) -> f64 {
-    let a = value.abs();
-    if a < 1e12 {
-        if      a >= 1e-2 { (value*1e6).round()/1e6 }
-        else if a >= 1e-4 { (value*1e8).round()/1e8 }
-        else              { value }
+// serde_json writes non-finite values as null, so read null back as NaN
+pub(crate) fn nan_if_null<'de, D: Deserializer<'de>>(d: D) -> Result<f64, D::Error> {
+    Ok(Option::<f64>::deserialize(d)?.unwrap_or(std::f64::NAN))
+}
+
+// How reported values are rounded, as `metrics.full_precision` asks.  Since
+// `{}` already prints the shortest text that parses back to the same f64,
+// outputs at full precision are bit-exact.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Precision {
+    Rounded,
+    Full,
+}
+
+impl Default for Precision {
+    fn default() -> Self { Precision::Rounded }
+}
+
+impl Precision {
+    pub fn r6(self, value: f64) -> f64 {
+        if self == Precision::Full { return value; }
+        let a = value.abs();
+        if a < 1e12 {
+            if      a >= 1e-2 { (value*1e6).round()/1e6 }
+            else if a >= 1e-4 { (value*1e8).round()/1e8 }
+            else              { value }
+        }
+        else { value }
     }
-    else { value }
+}
+
+// Two-sided 95% critical value of Student's t with `df` degrees of freedom:
+// tabulated up to 30, and from the Cornish-Fisher expansion (good to 1e-5)
+// beyond.
+pub fn student_t95(df: u64) -> f64 {
+    const TABLE: [f64; 30] = [
+        12.706205, 4.302653, 3.182446, 2.776445, 2.570582, 2.446912, 2.364624, 2.306004, 2.262157, 2.228139,
+        2.200985, 2.178813, 2.160369, 2.144787, 2.131450, 2.119905, 2.109816, 2.100922, 2.093024, 2.085963,
+        2.079614, 2.073873, 2.068658, 2.063899, 2.059539, 2.055529, 2.051831, 2.048407, 2.045230, 2.042272,
+    ];
+    if df == 0 { return std::f64::NAN; }
+    if df <= 30 { return TABLE[df as usize - 1]; }
+    let z: f64 = 1.959964;
+    let v = df as f64;
+    z + (z.powi(3) + z) / (4.0 * v)
+      + (5.0 * z.powi(5) + 16.0 * z.powi(3) + 3.0 * z) / (96.0 * v * v)
+      + (3.0 * z.powi(7) + 19.0 * z.powi(5) + 17.0 * z.powi(3) - 15.0 * z) / (384.0 * v * v * v)
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Sampled {
+    #[serde(deserialize_with = "nan_if_null")]
     pub mean: f64,
+    #[serde(deserialize_with = "nan_if_null")]
     pub sem: f64,
-    pub n: u64
+    pub n: u64,
+
+    // mean ± t * sem, with n - 1 degrees of freedom, when `metrics.ci95` asks;
+    // see `set_intervals`.  A missing interval is written as null, and read
+    // back as NaN, so the columns stay the same.
+    #[serde(skip_serializing_if = "Option::is_none", default, deserialize_with = "some_nan_if_null")]
+    pub ci95_lo: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none", default, deserialize_with = "some_nan_if_null")]
+    pub ci95_hi: Option<f64>,
 }
 
-impl Sampled {
-    pub fn zero() -> Self { Sampled{ mean: std::f64::NAN, sem: std::f64::NAN, n: 0 } }
+fn some_nan_if_null<'de, D: Deserializer<'de>>(d: D) -> Result<Option<f64>, D::Error> {
+    Ok(Some(Option::<f64>::deserialize(d)?.unwrap_or(std::f64::NAN)))
 }
 
-impl From<average::Variance> for Sampled {
-    fn from(v: average::Variance) -> Sampled { Sampled { mean: r6(v.mean()), sem: r6(v.error()), n: v.len() } }
+impl Sampled {
+    pub fn zero() -> Self { Sampled{ mean: std::f64::NAN, sem: std::f64::NAN, n: 0, ci95_lo: None, ci95_hi: None } }
+
+    // As `zero`, with missing intervals if there are to be intervals
+    pub fn missing(intervals: bool) -> Self {
+        let mut s = Sampled::zero();
+        s.set_intervals(intervals, Precision::Full);
+        s
+    }
+
+    pub fn of(mean: f64, sem: f64, n: u64, precision: Precision) -> Sampled {
+        Sampled{ mean: precision.r6(mean), sem: precision.r6(sem), n, ci95_lo: None, ci95_hi: None }
+    }
+
+    pub fn of_variance(v: &average::Variance, precision: Precision) -> Sampled { Sampled::of(v.mean(), v.error(), v.len(), precision) }
+
+    pub fn has_intervals(&self) -> bool { self.ci95_lo.is_some() }
+
+    // Adds the 95% confidence interval, from the mean and standard error as
+    // reported, or takes it away.  One that is there already is kept.
+    pub fn set_intervals(&mut self, on: bool, precision: Precision) {
+        let (lo, hi) =
+            if !on { (None, None) }
+            else if self.ci95_lo.is_some() { (self.ci95_lo, self.ci95_hi) }
+            else if self.n < 2 { (Some(std::f64::NAN), Some(std::f64::NAN)) }
+            else {
+                let half = student_t95(self.n - 1) * self.sem;
+                (Some(precision.r6(self.mean - half)), Some(precision.r6(self.mean + half)))
+            };
+        self.ci95_lo = lo;
+        self.ci95_hi = hi;
+    }
+
+    pub fn titles(specifier: &str, intervals: bool) -> Vec<String> {
+        if intervals { prefixed(specifier, &["n", "mean", "sem", "ci95-lo", "ci95-hi"]) }
+        else { prefixed(specifier, &["n", "mean", "sem"]) }
+    }
 }
 
 impl Display for Sampled {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{} {} {}", self.n, self.mean, self.sem)
+        write!(f, "{} {} {}", self.n, self.mean, self.sem)?;
+        if let (Some(lo), Some(hi)) = (self.ci95_lo, self.ci95_hi) { write!(f, " {} {}", lo, hi)?; }
+        Ok(())
     }
 }
 
 impl Entitled for Sampled {
-    fn push_subtitle(&self, specifier: &str, to: &mut String) {
-        to.push_str(specifier); to.push_str("n ");
-        to.push_str(specifier); to.push_str("mean ");
-        to.push_str(specifier); to.push_str("sem");
-    }
+    fn push_subtitle(&self, specifier: &str, to: &mut String) { to.push_str(Sampled::titles(specifier, self.has_intervals()).join(" ").as_str()); }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -94,11 +314,45 @@ pub struct Speed {
     #[serde(flatten)]
     pub stats: Sampled,
     
-    pub max: f64
+    #[serde(deserialize_with = "nan_if_null")]
+    pub max: f64,
+
+    // Fraction of the window the recording covers, when partial windows are allowed
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub coverage: Option<f64>,
+
+    // Which estimator produced the per-frame speeds
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub estimator: Option<SpeedEstimator>,
+
+    // Over only the frames faster than `speed.moving_threshold`, leaving out pauses
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub moving: Option<Sampled>,
+
+    // Which frames the window took, when not the default [start, end]
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub bounds: Option<WindowBounds>,
 }
 
 impl Speed {
-    pub fn zero() -> Speed { Speed{ stats: Sampled::zero(), max: std::f64::NAN } }
+    pub fn zero() -> Speed { Speed{ stats: Sampled::zero(), max: std::f64::NAN, coverage: None, estimator: None, moving: None, bounds: None } }
+
+    pub fn missing(intervals: bool) -> Speed { Speed{ stats: Sampled::missing(intervals), ..Speed::zero() } }
+
+    pub fn of(stats: &average::Variance, max: f64, precision: Precision) -> Speed {
+        Speed{ stats: Sampled::of_variance(stats, precision), max, coverage: None, estimator: None, moving: None, bounds: None }
+    }
+
+    pub fn set_intervals(&mut self, on: bool, precision: Precision) {
+        self.stats.set_intervals(on, precision);
+        if let Some(m) = self.moving.as_mut() { m.set_intervals(on, precision); }
+    }
+
+    pub fn titles(specifier: &str, intervals: bool) -> Vec<String> {
+        let mut titles = Sampled::titles(specifier, intervals);
+        titles.extend(prefixed(specifier, &["max"]));
+        titles
+    }
 }
 
 impl From<Speed> for Sampled {
@@ -109,12 +363,6 @@ impl From<&Speed> for Sampled {
     fn from(sp: &Speed) -> Sampled { sp.stats.clone() }
 }
 
-impl From<(average::Variance, f64)> for Speed {
-    fn from(tup: (average::Variance, f64)) -> Speed {
-        Speed{ stats: tup.0.into(), max: tup.1 }
-    }
-}
-
 impl Display for Speed {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{} {}", self.stats, self.max)
@@ -122,51 +370,105 @@ impl Display for Speed {
 }
 
 impl Entitled for Speed {
-    fn push_subtitle(&self, specifier: &str, to: &mut String) {
-        self.stats.push_subtitle(specifier, to);
-        to.push_str(" ");
-        to.push_str(specifier); to.push_str("max");
-    }
-}
-
-pub fn the_speed_in(t0: f64, t1: f64, input: &Vec<DataLine>) -> Option<Speed> {
-    let mut stats = average::Variance::new();
-    let mut five = [0f64; 5];
-    let mut max_s = 0f64;
-    let mut j = 0;
-    let mut n = 0;
-    let mut i = input.iter();
-    let mut before = false;
-    while let Some(data) = i.next() {
-        if data.time < t0 { before = true; }
-        else if data.time > t1 { 
-            return { 
-                if before && n >= 5 { Some((stats, max_s).into()) } 
-                else { None } 
-            }; 
-        }
-        else {
-            if data.speed.is_finite() {
-                stats.add(data.speed);
-                five[j] = data.speed;
-                n += 1;
-                j += 1;
-                if j >= 5 { j = 0; };
-                if n >= 5 {
-                    let s = median5(&five);
-                    if s > max_s { max_s = s; };
-                }
+    fn push_subtitle(&self, specifier: &str, to: &mut String) { to.push_str(Speed::titles(specifier, self.stats.has_intervals()).join(" ").as_str()); }
+}
+
+// How the maximum speed in a window is found.  The running median of five
+// suits high frame rates; at low frame rates five frames can span seconds,
+// so a high percentile of lightly smoothed (running mean) speed is better.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum MaxEstimator {
+    Median5,
+    Percentile {
+        #[serde(default = "MaxEstimator::default_percentile")]
+        percentile: f64,
+        #[serde(default = "MaxEstimator::default_smoothing")]
+        smoothing: usize,
+    },
+}
+
+impl MaxEstimator {
+    fn default_percentile() -> f64 { 95.0 }
+
+    fn default_smoothing() -> usize { 3 }
+
+    pub fn errors(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if let MaxEstimator::Percentile{ percentile, smoothing } = self {
+            if !(*percentile > 0.0 && *percentile <= 100.0) {
+                errors.push(format!("Max speed percentile must be in (0, 100], not {}", percentile));
             }
+            if *smoothing < 1 { errors.push("Max speed smoothing must be at least 1 frame".to_string()); }
         }
+        errors
     }
-    None
+}
+
+impl Default for MaxEstimator {
+    fn default() -> Self { MaxEstimator::Median5 }
+}
+
+// Linear interpolation between closest ranks
+fn percentile_of(values: &mut Vec<f64>, percentile: f64) -> f64 {
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    if values.len() == 0 { return std::f64::NAN; }
+    let x = (percentile / 100.0) * ((values.len() - 1) as f64);
+    let i = x.floor() as usize;
+    if i + 1 >= values.len() { values[values.len() - 1] }
+    else { values[i] + (x - i as f64) * (values[i+1] - values[i]) }
+}
+
+// Mean speed, and maximum of the running median of five, over the given
+// lines; needs at least five valid speeds.
+pub fn the_speed_of<'a, I>(precision: Precision, lines: I) -> Option<Speed>
+where I: Iterator<Item = &'a DataLine> {
+    let mut speed = RunningSpeed::new();
+    for data in lines { speed.add(data); }
+    speed.finish(precision)
+}
+
+// As `the_speed_of`, but with the maximum found by the given estimator
+pub fn the_speed_by<'a, I>(max: &MaxEstimator, precision: Precision, lines: I) -> Option<Speed>
+where I: Iterator<Item = &'a DataLine> {
+    match max {
+        MaxEstimator::Median5 => the_speed_of(precision, lines),
+        MaxEstimator::Percentile{ percentile, smoothing } => {
+            let speeds: Vec<f64> = lines.map(|data| data.speed).filter(|s| s.is_finite()).collect();
+            if speeds.len() < 5 { return None; }
+            let stats: average::Variance = speeds.iter().cloned().collect();
+            let w = std::cmp::max(1, std::cmp::min(*smoothing, speeds.len()));
+            let mut smoothed: Vec<f64> = speeds.windows(w).map(|ss| ss.iter().sum::<f64>() / (w as f64)).collect();
+            Some(Speed::of(&stats, precision.r6(percentile_of(&mut smoothed, *percentile)), precision))
+        }
+    }
+}
+
+// Speed within [t0, t1], only if the recording visibly spans the window:
+// some data must come before t0 and after t1.
+pub fn the_speed_in(t0: f64, t1: f64, max: &MaxEstimator, precision: Precision, input: &[DataLine]) -> Option<Speed> {
+    the_speed_within(&Window::new(t0, t1), &WindowBounds::default(), max, precision, input)
+}
+
+// Speed over the frames `bounds` puts in the window, up to the first frame
+// after it
+pub fn the_speed_within(w: &Window, bounds: &WindowBounds, max: &MaxEstimator, precision: Precision, input: &[DataLine]) -> Option<Speed> {
+    let end = input.iter().position(|data| bounds.is_after(w, data.time));
+    if end.is_none() && bounds.require_after { return None; }
+    let prior = &input[..end.unwrap_or(input.len())];
+    if bounds.require_before && !prior.iter().any(|data| bounds.is_before(w, data.time)) { return None; }
+    the_speed_by(max, precision, prior.iter().filter(|data| bounds.contains(w, data.time)))
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Coord {
+    #[serde(deserialize_with = "nan_if_null")]
     pub first: f64,
+    #[serde(deserialize_with = "nan_if_null")]
     pub last: f64,
+    #[serde(deserialize_with = "nan_if_null")]
     pub bound0: f64,
+    #[serde(deserialize_with = "nan_if_null")]
     pub bound1: f64,
 
     #[serde(flatten)]
@@ -177,6 +479,14 @@ impl Coord {
     pub fn zero() -> Coord { 
         Coord { first: std::f64::NAN, last: std::f64::NAN, bound0: std::f64::NAN, bound1: std::f64::NAN, stats: Sampled::zero() }
     }
+
+    pub fn missing(intervals: bool) -> Coord { Coord{ stats: Sampled::missing(intervals), ..Coord::zero() } }
+
+    pub fn titles(specifier: &str, intervals: bool) -> Vec<String> {
+        let mut titles = prefixed(specifier, &["first", "last", "smallest", "largest"]);
+        titles.extend(Sampled::titles(specifier, intervals));
+        titles
+    }
 }
 
 impl Display for Coord {
@@ -186,146 +496,475 @@ impl Display for Coord {
 }
 
 impl Entitled for Coord {
+    fn push_subtitle(&self, specifier: &str, to: &mut String) { to.push_str(Coord::titles(specifier, self.stats.has_intervals()).join(" ").as_str()); }
+}
+
+pub fn the_coord<F>(f: F, precision: Precision, input: &[DataLine]) -> Coord
+where F: Fn(&DataLine) -> f64 {
+    let mut coord = RunningCoord::new(|_| std::f64::NAN);
+    for a in input.iter().map(f) { coord.add_value(a); }
+    coord.finish(precision)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Summary {
+    Bounded(Coord),
+    Plain(Sampled),
+}
+
+impl Summary {
+    pub fn stats(&self) -> &Sampled {
+        match self {
+            Summary::Bounded(c) => &c.stats,
+            Summary::Plain(s)   => s,
+        }
+    }
+
+    pub fn set_intervals(&mut self, on: bool, precision: Precision) {
+        match self {
+            Summary::Bounded(c) => c.stats.set_intervals(on, precision),
+            Summary::Plain(s)   => s.set_intervals(on, precision),
+        }
+    }
+}
+
+impl From<Sampled> for Summary {
+    fn from(s: Sampled) -> Summary { Summary::Plain(s) }
+}
+
+impl From<Coord> for Summary {
+    fn from(c: Coord) -> Summary { Summary::Bounded(c) }
+}
+
+impl Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Summary::Bounded(c) => c.fmt(f),
+            Summary::Plain(s)   => s.fmt(f),
+        }
+    }
+}
+
+impl Summary {
+    pub fn is_bounded(&self) -> bool { match self { Summary::Bounded(_) => true, Summary::Plain(_) => false } }
+
+    pub fn titles(bounded: bool, specifier: &str, intervals: bool) -> Vec<String> {
+        if bounded { Coord::titles(specifier, intervals) } else { Sampled::titles(specifier, intervals) }
+    }
+}
+
+impl Entitled for Summary {
     fn push_subtitle(&self, specifier: &str, to: &mut String) {
-        to.push_str(specifier); to.push_str("first ");
-        to.push_str(specifier); to.push_str("last ");
-        to.push_str(specifier); to.push_str("smallest ");
-        to.push_str(specifier); to.push_str("largest ");
-        self.stats.push_subtitle(specifier, to);
+        to.push_str(Summary::titles(self.is_bounded(), specifier, self.stats().has_intervals()).join(" ").as_str());
     }
 }
 
-pub fn the_coord<F>(f: F, input: &Vec<DataLine>) -> Coord
+pub fn the_summary<F>(f: F, bounded: bool, precision: Precision, input: &[DataLine]) -> Summary
 where F: Fn(&DataLine) -> f64 {
-    if input.len() == 0 { return Coord::zero(); }
-
-    let mut i = input.iter().map(f);
-    let mut anything = false;
-    let mut first = std::f64::NAN;
-    let mut last = std::f64::NAN;
-    let mut bound0 = std::f64::NAN;
-    let mut bound1 = std::f64::NAN;
-    let mut stats = average::Variance::new();
-    while let Some(a) = i.next() {
-        if a.is_finite() {
-            if !anything {
-                anything = true;
-                first = a;
-                bound0 = a;
-                bound1 = a;
-            }
-            else {
-                if a < bound0 { bound0 = a; }
-                if a > bound1 { bound1 = a; }
-            }
-            last = a;
-            stats.add(a);
-        }
+    if bounded { the_coord(f, precision, input).into() }
+    else {
+        let v: average::Variance = input.iter().map(f).filter(|x| x.is_finite()).collect();
+        Sampled::of_variance(&v, precision).into()
     }
-    if anything { Coord{ first, last, bound0, bound1, stats: stats.into() } }
-    else { Coord::zero() }
 }
 
+// Worm numbers from file names.  Rigs that compose them from a plate and a
+// position count past u32, so they are u64; older files' ids read the same.
+pub type WormId = u64;
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Scores {
-    pub id: u32,
+    pub id: WormId,
+
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub track: Option<u32>,
+
+    #[serde(deserialize_with = "nan_if_null")]
     pub t0: f64,
+    #[serde(deserialize_with = "nan_if_null")]
     pub t1: f64,
-    pub area: Sampled,
-    pub midline: Sampled,
+    pub area: Summary,
+    pub midline: Summary,
+
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub shape_rates: Option<ShapeRates>,
+
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub shape_index: Option<Sampled>,
+
+    // By window name, as `initial_speed` and so on
+    #[serde(flatten)]
+    pub speeds: WindowSpeeds,
+
+    // `speed.moving_threshold`, when each speed also has statistics while moving
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub moving_threshold: Option<f64>,
 
     #[serde(skip_serializing_if = "Option::is_none", default)]
-    pub initial_speed: Option<Speed>,
+    pub arousal: Option<Arousal>,
 
     #[serde(skip_serializing_if = "Option::is_none", default)]
-    pub calm_speed: Option<Speed>,
+    pub bursts: Option<Bursts>,
 
     #[serde(skip_serializing_if = "Option::is_none", default)]
-    pub aroused_speed: Option<Speed>,
+    pub omega: Option<OmegaTurns>,
 
     pub x: Coord,
     pub y: Coord,
+
+    // Bounding box and radius of gyration of the positions
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub spread: Option<Spread>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub windowed: Vec<WindowScores>,
+
+    // Metrics defined in the configuration's `derived` section
+    #[serde(skip_serializing_if = "BTreeMap::is_empty", default, deserialize_with = "extras_nan_if_null")]
+    pub extras: BTreeMap<String, f64>,
+
+    // Recording details from the file name; see `metadata`
+    #[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
+    pub metadata: BTreeMap<String, Option<String>>,
+
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub exploration: Option<Exploration>,
+
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub composite: Option<f64>,
+
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub edge_fraction: Option<f64>,
+
+    pub qc: Qc,
+
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub intervals: Vec<TrackedInterval>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub warnings: Vec<WindowWarning>,
+
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub out_of_range: Option<OutOfRange>,
+
+    // Repeated frames removed before scoring
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub duplicates: Option<u64>,
+
+    // Speeds over `speed.ceiling` clipped or dropped
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub ceiling: Option<u64>,
+
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub decimation: Option<u32>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub swaps: Vec<Swap>,
+
+    // `Config::hash` of the configuration that gave these scores
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub config_hash: Option<String>,
+
+    // Where and with what build these scores were written
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub environment: Option<Environment>,
+
+    // Transforms of the data before scoring, which rename the columns
+    #[serde(skip_serializing_if = "ColumnTransforms::is_identity", default)]
+    pub transforms: ColumnTransforms,
+
+    // Whether every mean has a 95% confidence interval, as `metrics.ci95` asks
+    #[serde(skip_serializing_if = "std::ops::Not::not", default)]
+    pub ci95: bool,
 }
 
 impl Scores {
     pub fn zero() -> Self {
         Scores{ 
             id: 0,
+            track: None,
             t0: std::f64::NAN,
             t1: std::f64::NAN,
-            area: Sampled::zero(),
-            midline: Sampled::zero(),
-            initial_speed: None,
-            calm_speed: None,
-            aroused_speed: None,
+            area: Sampled::zero().into(),
+            midline: Sampled::zero().into(),
+            shape_rates: None,
+            shape_index: None,
+            speeds: WindowSpeeds::default(),
+            moving_threshold: None,
+            arousal: None,
+            bursts: None,
+            omega: None,
             x: Coord::zero(),
             y: Coord::zero(),
+            spread: None,
+            windowed: Vec::new(),
+            extras: BTreeMap::new(),
+            metadata: BTreeMap::new(),
+            exploration: None,
+            composite: None,
+            edge_fraction: None,
+            qc: Qc::zero(),
+            intervals: Vec::new(),
+            warnings: Vec::new(),
+            out_of_range: None,
+            duplicates: None,
+            ceiling: None,
+            decimation: None,
+            swaps: Vec::new(),
+            config_hash: None,
+            environment: None,
+            transforms: ColumnTransforms::default(),
+            ci95: false,
         }
     }
+
+    // Gives every mean a confidence interval, missing where there are too few
+    // samples, or takes them all away, so that rows have the columns `on` says
+    pub fn set_intervals(&mut self, on: bool, precision: Precision) {
+        self.ci95 = on;
+        self.area.set_intervals(on, precision);
+        self.midline.set_intervals(on, precision);
+        if let Some(r) = self.shape_rates.as_mut() { r.area.set_intervals(on, precision); r.midline.set_intervals(on, precision); }
+        if let Some(x) = self.shape_index.as_mut() { x.set_intervals(on, precision); }
+        for name in self.speeds.names().map(|n| n.to_string()).collect::<Vec<String>>() {
+            if let Some(mut s) = self.speeds.get(name.as_str()).cloned() {
+                s.set_intervals(on, precision);
+                self.speeds.set(name.as_str(), Some(s));
+            }
+        }
+        self.x.stats.set_intervals(on, precision);
+        self.y.stats.set_intervals(on, precision);
+        if let Some(b) = self.bursts.as_mut() { b.amplitude.set_intervals(on, precision); b.interval.set_intervals(on, precision); }
+        for w in self.windowed.iter_mut() { w.set_intervals(on, precision); }
+    }
+
+    // Single-number summaries, named as in the CSV title, that are comparable across runs
+    pub fn headline(&self) -> Vec<(&'static str, f64)> {
+        let mean = |name: &str| self.speeds.get(name).map(|x| x.stats.mean).unwrap_or(std::f64::NAN);
+        vec![
+            ("area-mean", self.area.stats().mean),
+            ("midline-mean", self.midline.stats().mean),
+            ("initial-mean", mean("initial")),
+            ("calm-mean", mean("calm")),
+            ("aroused-mean", mean("aroused")),
+            ("composite", self.composite.unwrap_or(std::f64::NAN)),
+        ]
+    }
 }
 
 impl Display for Scores {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{} {} {} {} {} {} {} {} {} {}",
-            self.id, self.t0, self.t1,
-            self.area, self.midline,
-            self.initial_speed.clone().unwrap_or(Speed::zero()),
-            self.calm_speed.clone().unwrap_or(Speed::zero()),
-            self.aroused_speed.clone().unwrap_or(Speed::zero()),
-            self.x, self.y
-        )
+        match self.track {
+            Some(k) => write!(f, "{}.{} ", self.id, k)?,
+            None    => write!(f, "{} ", self.id)?,
+        }
+        write!(f, "{} {} {} {} ", self.t0, self.t1, self.area, self.midline)?;
+        if let Some(r) = &self.shape_rates { write!(f, "{} ", r)?; }
+        if let Some(x) = &self.shape_index { write!(f, "{} ", x)?; }
+        for (_, s) in self.speeds.iter() {
+            write!(f, "{} ", s.cloned().unwrap_or(Speed::missing(self.ci95)))?;
+            if self.moving_threshold.is_some() { write!(f, "{} ", s.and_then(|x| x.moving.clone()).unwrap_or(Sampled::missing(self.ci95)))?; }
+        }
+        write!(f, "{} {} {} ", self.arousal.clone().unwrap_or(Arousal::zero()), self.x, self.y)?;
+        if let Some(s) = &self.spread { write!(f, "{} ", s)?; }
+        write!(f, "{} {} {} {}",
+            self.exploration.clone().unwrap_or(Exploration::zero()),
+            self.composite.unwrap_or(std::f64::NAN),
+            self.edge_fraction.unwrap_or(std::f64::NAN),
+            self.qc
+        )?;
+        if let Some(b) = &self.bursts { write!(f, " {}", b)?; }
+        if let Some(o) = &self.omega { write!(f, " {}", o)?; }
+        for w in self.windowed.iter() {
+            write!(f, " ")?;
+            w.write_cells(f, self.ci95)?;
+        }
+        for x in self.extras.values() { write!(f, " {}", x)?; }
+        for m in self.metadata.values() { write!(f, " {}", metadata_cell(m))?; }
+        Ok(())
+    }
+}
+
+// Which optional groups of columns a row has
+#[derive(Debug, Clone, Copy)]
+struct OptionalColumns {
+    area_bounded: bool,
+    midline_bounded: bool,
+    rates: bool,
+    shape_index: bool,
+    moving: bool,
+    spread: bool,
+    bursts: bool,
+    omega: bool,
+    transforms: ColumnTransforms,
+    ci95: bool,
+}
+
+impl Scores {
+    fn titles_with<'a, H, I, J, K>(specifier: &str, optional: OptionalColumns, speeds: H, windows: I, extras: J, metadata: K) -> Vec<String>
+    where H: Iterator<Item = &'a str>, I: Iterator<Item = (&'a str, &'a [WindowMetric])>, J: Iterator<Item = &'a String>, K: Iterator<Item = String> {
+        let sub = |name: &str| format!("{}{}", specifier, name);
+        let ci = optional.ci95;
+        let mut titles = prefixed(specifier, &["id", "t0", "t1"]);
+        let (area, midline, speed) = (optional.transforms.area.prefix(), optional.transforms.midline.prefix(), optional.transforms.speed.prefix());
+        titles.extend(Summary::titles(optional.area_bounded, format!("{}{}area-", specifier, area).as_str(), ci));
+        titles.extend(Summary::titles(optional.midline_bounded, format!("{}{}midline-", specifier, midline).as_str(), ci));
+        if optional.rates { titles.extend(ShapeRates::titles(specifier, ci)); }
+        if optional.shape_index { titles.extend(Sampled::titles(sub("shape-").as_str(), ci)); }
+        for name in speeds {
+            titles.extend(Speed::titles(format!("{}{}{}-", specifier, speed, name).as_str(), ci));
+            if optional.moving { titles.extend(Sampled::titles(format!("{}{}{}-moving-", specifier, speed, name).as_str(), ci)); }
+        }
+        titles.extend(Arousal::titles(sub("arousal-").as_str()));
+        titles.extend(Coord::titles(sub("x-").as_str(), ci));
+        titles.extend(Coord::titles(sub("y-").as_str(), ci));
+        if optional.spread { titles.extend(Spread::titles(sub("spread-").as_str())); }
+        titles.extend(Exploration::titles(sub("exploration-").as_str()));
+        titles.push(sub("composite"));
+        titles.push(sub("edge-fraction"));
+        titles.extend(Qc::titles(sub("qc-").as_str()));
+        if optional.bursts { titles.extend(Bursts::titles(sub("bursts-").as_str(), ci)); }
+        if optional.omega { titles.extend(OmegaTurns::titles(sub("omega-").as_str())); }
+        for (name, metrics) in windows { titles.extend(WindowScores::titles_of(name, metrics, specifier, ci)); }
+        titles.extend(extras.map(|name| sub(name)));
+        titles.extend(metadata.map(|name| sub(name.as_str())));
+        titles
+    }
+
+    // The columns every row scored with this configuration will have
+    pub fn titles_for(config: &Config, specifier: &str) -> Vec<String> {
+        let windows = config.windowed.iter().map(|w| (w.name.as_str(), w.metrics.as_slice()));
+        let optional = OptionalColumns{
+            area_bounded: config.metrics.bounds, midline_bounded: config.metrics.bounds, rates: config.metrics.rates,
+            shape_index: config.metrics.shape_index, moving: config.speed.moving_threshold.is_some(),
+            spread: config.metrics.spread, bursts: config.bursts.report, omega: config.omega.report, transforms: config.transforms,
+            ci95: config.metrics.ci95,
+        };
+        Scores::titles_with(specifier, optional, config.windows.speed.iter().map(|w| w.name.as_str()), windows, config.derived.keys(), config.metadata.fields().into_iter())
     }
 }
 
 impl Entitled for Scores {
     fn push_subtitle(&self, specifier: &str, to: &mut String) {
-        to.push_str(specifier); to.push_str("id ");
-        to.push_str(specifier); to.push_str("t0 ");
-        to.push_str(specifier); to.push_str("t1");
-        let mock = Speed::zero();
-        if specifier.len() == 0 {
-            to.push_str(" "); self.area.push_subtitle("area-", to);
-            to.push_str(" "); self.midline.push_subtitle("midline-", to);
-            to.push_str(" "); mock.push_subtitle("initial-", to);
-            to.push_str(" "); mock.push_subtitle("calm-", to);
-            to.push_str(" "); mock.push_subtitle("aroused-", to);
-            to.push_str(" "); self.x.push_subtitle("x-", to);
-            to.push_str(" "); self.y.push_subtitle("y-", to);
+        let windows = self.windowed.iter().map(|w| (w.name.as_str(), w.metrics.as_slice()));
+        let optional = OptionalColumns{
+            area_bounded: self.area.is_bounded(), midline_bounded: self.midline.is_bounded(), rates: self.shape_rates.is_some(),
+            shape_index: self.shape_index.is_some(), moving: self.moving_threshold.is_some(),
+            spread: self.spread.is_some(), bursts: self.bursts.is_some(), omega: self.omega.is_some(), transforms: self.transforms,
+            ci95: self.ci95,
+        };
+        let titles = Scores::titles_with(specifier, optional, self.speeds.names(), windows, self.extras.keys(), self.metadata.keys().cloned());
+        to.push_str(titles.join(" ").as_str());
+    }
+}
+
+pub fn read_scores(path: &Path) -> Result<Vec<Scores>, MetrologyError> {
+    Ok(serde_json::from_str(std::fs::read_to_string(path)?.as_str())?)
+}
+
+pub fn read_scores_msgpack(path: &Path) -> Result<Vec<Scores>, MetrologyError> {
+    rmp_serde::from_slice(std::fs::read(path)?.as_slice()).map_err(|e| MetrologyError::Decode(e.to_string()))
+}
+
+// Rows in the order they are written: by worm id, then track, with rows that
+// tie kept as they were
+pub fn sort_scores(rows: &mut [Scores]) {
+    rows.sort_by_key(|s| (s.id, s.track.unwrap_or(0)));
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScoresFormat {
+    Json,
+    Msgpack,
+}
+
+impl ScoresFormat {
+    pub fn ext(&self) -> &'static str {
+        match self {
+            ScoresFormat::Json    => "scores",
+            ScoresFormat::Msgpack => "msgpack",
+        }
+    }
+
+    // MessagePack is written with field names, as flattened and optional fields need them
+    pub fn encode(&self, rows: &[Scores]) -> Result<Vec<u8>, MetrologyError> {
+        match self {
+            ScoresFormat::Json    => Ok(serde_json::to_vec(rows)?),
+            ScoresFormat::Msgpack => rmp_serde::to_vec_named(rows).map_err(|e| MetrologyError::Decode(e.to_string())),
         }
-        else {
-            let mut sub = String::new();
-            sub.push_str(specifier);
-            let n = sub.len();
-
-            to.push_str(" "); sub.truncate(n); sub.push_str("area-");    self.area.push_subtitle(sub.as_str(), to);
-            to.push_str(" "); sub.truncate(n); sub.push_str("midline-"); self.midline.push_subtitle(sub.as_str(), to);
-            to.push_str(" "); sub.truncate(n); sub.push_str("initial-"); mock.push_subtitle(sub.as_str(), to);
-            to.push_str(" "); sub.truncate(n); sub.push_str("calm-");    mock.push_subtitle(sub.as_str(), to);
-            to.push_str(" "); sub.truncate(n); sub.push_str("aroused-"); mock.push_subtitle(sub.as_str(), to);
-            to.push_str(" "); sub.truncate(n); sub.push_str("x-");       self.x.push_subtitle(sub.as_str(), to);
-            to.push_str(" "); sub.truncate(n); sub.push_str("y-");       self.y.push_subtitle(sub.as_str(), to);
+    }
+}
+
+impl std::str::FromStr for ScoresFormat {
+    type Err = String;
+    fn from_str(s: &str) -> Result<ScoresFormat, String> {
+        match s {
+            "json"    => Ok(ScoresFormat::Json),
+            "msgpack" => Ok(ScoresFormat::Msgpack),
+            _         => Err(format!("unknown format {:?}; use json or msgpack", s)),
         }
     }
 }
 
-pub fn the_everything(id: u32, input: &Vec<DataLine>) -> Scores {
-    if input.len() == 0 { return Scores::zero(); }
+pub(crate) fn the_nothing(config: &Config) -> Scores {
+    let mut scores = Scores::zero();
+    if config.metrics.bounds {
+        scores.area = Coord::zero().into();
+        scores.midline = Coord::zero().into();
+    }
+    if config.metrics.rates { scores.shape_rates = Some(ShapeRates::zero()); }
+    if config.metrics.shape_index { scores.shape_index = Some(Sampled::zero()); }
+    if config.metrics.spread { scores.spread = Some(Spread::zero()); }
+    scores.moving_threshold = config.speed.moving_threshold;
+    if config.bursts.report { scores.bursts = Some(Bursts::zero()); }
+    if config.omega.report { scores.omega = Some(OmegaTurns::zero()); }
+    scores.speeds = WindowSpeeds::of(&config.windows);
+    scores.transforms = config.transforms;
+    scores.windowed = config.windowed.iter().map(|w| window_scores_of(w, config.composite.activity_threshold, &config.speed.max, config.metrics.precision(), &[])).collect();
+    scores.extras = config.derived.keys().map(|k| (k.clone(), std::f64::NAN)).collect();
+    scores
+}
+
+// The data as scored, with what was removed to get there
+pub struct Prepared {
+    pub lines: Vec<DataLine>,
+    pub out_of_range: Option<OutOfRange>,
+    pub duplicates: Option<u64>,
+    pub ceiling: Option<u64>,
+}
+
+// Repeated frames and out-of-range values removed, speeds re-estimated and capped, and columns transformed, as configured
+pub fn the_prepared(input: &[DataLine], config: &Config) -> Prepared { prepared_of(input.to_vec(), config) }
 
-    let mut i0 = 0;
-    let mut i1 = input.len() - 1;
-    while i0 <  i1 && !input[i0].time.is_finite() { i0 += 1; }
-    while i1 >= i0 && !input[i1].time.is_finite() { i1 -= 1; }
-    if i1 < i0 { return Scores::zero(); }
-    let t0 = input[i0].time;
-    let t1 = input[i1].time;
+fn prepared_of(mut lines: Vec<DataLine>, config: &Config) -> Prepared {
+    let duplicates = config.duplicates.apply(&mut lines);
+    let out_of_range = config.validity.apply(&mut lines);
+    config.speed.estimator.apply(config.metrics.precision(), &mut lines);
+    let ceiling = config.speed.ceiling.apply(&mut lines);
+    config.transforms.apply(&mut lines);
+    Prepared{ lines, out_of_range, duplicates, ceiling }
+}
 
-    let area: Sampled = the_area(input).into();
-    let midline: Sampled = the_midline(input).into();
-    let initial_speed = the_speed_in(10.0, 20.0, input);
-    let calm_speed = the_speed_in(270.0, 290.0, input);
-    let aroused_speed = the_speed_in(440.0, 450.0, input);
-    let x = the_coord(|d| d.x, input);
-    let y = the_coord(|d| d.y, input);
+// The scores of the contents of one .dat file, as the JSON array (one entry
+// per track) that appears in the scores file
+pub fn scores_json(id: WormId, bytes: &[u8], config: &Config) -> Result<String, MetrologyError> {
+    let data = parse_data_lines(&config.input.normalize(bytes))?;
+    let mut rows = the_tracks(id, &data, config);
+    flag_lost_at_edge(&config.qc, config.exploration.arena, &mut rows);
+    Ok(serde_json::to_string(&rows)?)
+}
 
-    Scores{ id, t0, t1, area, midline, initial_speed, calm_speed, aroused_speed, x, y }
+pub fn the_everything(id: WormId, input: &[DataLine], config: &Config) -> Scores { everything_of(id, input.to_vec(), config) }
+
+// As `the_everything`, taking the lines rather than copying them
+pub(crate) fn everything_of(id: WormId, lines: Vec<DataLine>, config: &Config) -> Scores {
+    let prepared = prepared_of(lines, config);
+    let mut scores = Dataset::new(prepared.lines).everything(id, config);
+    scores.out_of_range = prepared.out_of_range;
+    scores.duplicates = prepared.duplicates.filter(|n| *n > 0);
+    scores.ceiling = prepared.ceiling;
+    scores.config_hash = Some(config.hash());
+    scores.set_intervals(config.metrics.ci95, config.metrics.precision());
+    scores
 }
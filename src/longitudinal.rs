@@ -0,0 +1,112 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use std::collections::BTreeMap;
+
+use serde::{Serialize, Deserialize};
+
+use crate::*;
+
+
+#[derive(Debug, Clone)]
+pub struct Timepoint {
+    pub day: f64,
+    pub scores: Vec<Scores>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Trajectory {
+    pub metric: String,
+    pub days: Vec<f64>,
+
+    #[serde(deserialize_with = "nans_if_null")]
+    pub values: Vec<f64>,
+
+    #[serde(deserialize_with = "nan_if_null")]
+    pub slope: f64,
+
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub decline_day: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WormHistory {
+    pub id: WormId,
+
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub track: Option<u32>,
+
+    pub trajectories: Vec<Trajectory>,
+}
+
+fn nans_if_null<'de, D: serde::Deserializer<'de>>(d: D) -> Result<Vec<f64>, D::Error> {
+    let v: Vec<Option<f64>> = Deserialize::deserialize(d)?;
+    Ok(v.into_iter().map(|x| x.unwrap_or(std::f64::NAN)).collect())
+}
+
+// Least-squares slope of value against day, ignoring non-finite values
+pub fn the_slope(days: &[f64], values: &[f64], precision: Precision) -> f64 {
+    let pts: Vec<(f64, f64)> = days.iter().zip(values.iter()).filter(|(d, v)| d.is_finite() && v.is_finite()).map(|(d, v)| (*d, *v)).collect();
+    if pts.len() < 2 { return std::f64::NAN; }
+    let n = pts.len() as f64;
+    let md = pts.iter().map(|p| p.0).sum::<f64>() / n;
+    let mv = pts.iter().map(|p| p.1).sum::<f64>() / n;
+    let sdd: f64 = pts.iter().map(|p| (p.0 - md).powi(2)).sum();
+    let sdv: f64 = pts.iter().map(|p| (p.0 - md) * (p.1 - mv)).sum();
+    if sdd > 0.0 { precision.r6(sdv / sdd) } else { std::f64::NAN }
+}
+
+// First day on which the value has fallen below `fraction` of the first valid
+// value and never recovers above it on any later day.
+pub fn the_decline_day(days: &[f64], values: &[f64], fraction: f64) -> Option<f64> {
+    let first = values.iter().cloned().find(|v| v.is_finite())?;
+    let threshold = first * fraction;
+    let mut decline = None;
+    for (d, v) in days.iter().zip(values.iter()) {
+        if !v.is_finite() { continue; }
+        if *v < threshold { if decline.is_none() { decline = Some(*d); } }
+        else { decline = None; }
+    }
+    decline
+}
+
+pub fn the_longitudinal(timepoints: &[Timepoint], decline_fraction: f64, precision: Precision) -> Vec<WormHistory> {
+    let mut order: Vec<&Timepoint> = timepoints.iter().collect();
+    order.sort_by(|a, b| a.day.partial_cmp(&b.day).unwrap_or(std::cmp::Ordering::Equal));
+
+    let mut worms: BTreeMap<(WormId, Option<u32>), Vec<(f64, Vec<(&'static str, f64)>)>> = BTreeMap::new();
+    for tp in order.iter() {
+        for s in tp.scores.iter() {
+            worms.entry((s.id, s.track)).or_insert_with(Vec::new).push((tp.day, s.headline()));
+        }
+    }
+
+    worms.into_iter().map(|((id, track), visits)| {
+        let names: Vec<&'static str> = visits[0].1.iter().map(|x| x.0).collect();
+        let days: Vec<f64> = visits.iter().map(|v| v.0).collect();
+        let trajectories = names.iter().enumerate().map(|(i, name)| {
+            let values: Vec<f64> = visits.iter().map(|v| v.1[i].1).collect();
+            let slope = the_slope(&days, &values, precision);
+            let decline_day = the_decline_day(&days, &values, decline_fraction);
+            Trajectory{ metric: name.to_string(), days: days.clone(), values, slope, decline_day }
+        }).collect();
+        WormHistory{ id, track, trajectories }
+    }).collect()
+}
+
+// One row per worm, metric, and day, in long format
+pub fn longitudinal_table(histories: &[WormHistory]) -> String {
+    let mut table = String::from("id metric day value slope decline-day\n");
+    for h in histories.iter() {
+        let id = match h.track { Some(k) => format!("{}.{}", h.id, k), None => h.id.to_string() };
+        for t in h.trajectories.iter() {
+            for (d, v) in t.days.iter().zip(t.values.iter()) {
+                table.push_str(
+                    format!("{} {} {} {} {} {}\n", id, t.metric, d, v, t.slope, t.decline_day.unwrap_or(std::f64::NAN)).as_str()
+                );
+            }
+        }
+    }
+    table
+}
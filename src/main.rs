@@ -8,6 +8,7 @@ use std::io;
 use std::io::Read;
 use std::path::{Path, PathBuf};
 
+use serde::Serialize;
 use structopt::StructOpt;
 
 use metrology::*;
@@ -16,8 +17,104 @@ use metrology::*;
 #[derive(Debug, StructOpt, Clone)]
 #[structopt(name = "metrology", about = "Metrology computes health metrics for individual worms.")]
 struct Opt {
-    #[structopt(short="v", long="verbose")]
-    verbose: bool,
+    #[structopt(short="v", long="verbose", parse(from_occurrences), help="Print more detail: -v per file, -vv per worm, -vvv every score")]
+    verbose: u64,
+
+    #[structopt(long="verbose-only", use_delimiter=true, help="Print detail only about these, comma-separated: parsing, scoring, io")]
+    verbose_only: Vec<LogArea>,
+
+    #[structopt(long="config", parse(from_os_str))]
+    config: Option<PathBuf>,
+
+    #[structopt(long="set", number_of_values=1, help="Set a configuration value, as section.key=value; may be repeated")]
+    set: Vec<String>,
+
+    #[structopt(long="print-config", help="Print the configuration with files, environment, and flags applied, then stop")]
+    print_config: bool,
+
+    #[structopt(long="paranoid", help="Check that parsed times increase, areas are positive, and speeds are not negative, and stop if not")]
+    paranoid: bool,
+
+    #[structopt(long="quick", help="Quick look: score only every Nth line")]
+    quick: Option<u32>,
+
+    #[structopt(long="sample", help="Score only K randomly chosen worms")]
+    sample: Option<usize>,
+
+    #[structopt(long="seed", help="Random seed for --sample")]
+    seed: Option<u64>,
+
+    #[structopt(long="full-precision", help="Report values without rounding")]
+    full_precision: bool,
+
+    #[structopt(long="format", default_value="json", help="Scores format: json or msgpack")]
+    format: ScoresFormat,
+
+    #[structopt(long="output-template", default_value="{prefix}.{ext}",
+        help="Output file names; may use {prefix}, {date}, {version}, {config}, and must use {ext}")]
+    output_template: String,
+
+    #[structopt(long="columns", use_delimiter=true, help="Only these CSV columns, comma-separated (e.g. id,area-mean,initial-mean)")]
+    columns: Vec<String>,
+
+    #[structopt(long="missing", default_value="NaN", parse(try_from_str = "parse_missing"),
+        help="How missing values are written in .csv tables: NaN, NA, an empty string, or another word")]
+    missing: String,
+
+    #[structopt(long="missingness", help="Also write a table of which metrics could be computed for each worm, and why not")]
+    missingness: bool,
+
+    #[structopt(long="report", help="Also write a self-contained HTML report of the run")]
+    report: bool,
+
+    #[structopt(long="profile", help="Write per-file timings to profile.csv and report throughput")]
+    profile: bool,
+
+    #[structopt(long="summary-json", parse(from_os_str), help="Write a machine-readable summary of the run here")]
+    summary_json: Option<PathBuf>,
+
+    #[structopt(long="hdf5", help="Also write raw data and scores to one HDF5 file (needs the hdf5 feature)")]
+    hdf5: bool,
+
+    #[structopt(long="wcon", help="Also write each worm's track and window metrics as WCON, in the wcon directory")]
+    wcon: bool,
+
+    #[structopt(long="export-speed-series", help="Also write each worm's times and speeds, as scored, in the speed directory")]
+    export_speed_series: bool,
+
+    #[structopt(long="panel", help="Also write a table of headline metrics whose columns stay the same between versions")]
+    panel: bool,
+
+    #[structopt(long="inspect", help="Print the time and speed of every frame each speed window of this worm was scored from")]
+    inspect: Option<WormId>,
+
+    #[structopt(long="inspect-to", parse(from_os_str), help="Write the frames from --inspect to this file instead")]
+    inspect_to: Option<PathBuf>,
+
+    #[structopt(long="metrics", use_delimiter=true, help="Compute only these metrics (e.g. area,speed:initial), and what they need")]
+    metrics: Vec<String>,
+
+    #[structopt(long="max-memory", parse(try_from_str = "parse_memory"),
+        help="Roughly bound memory for files being scored at once, in bytes or with K, M, or G (e.g. 8G)")]
+    max_memory: Option<usize>,
+
+    #[structopt(long="cache", help="Save parsed data in the target's cache directory for later runs")]
+    cache: bool,
+
+    #[structopt(long="from-cache", parse(from_os_str), help="Use parsed data cached by --cache in this earlier target, where still current")]
+    from_cache: Option<PathBuf>,
+
+    #[structopt(long="time-offsets", parse(from_os_str), help="Tab-separated file or prefix names and the time (s) to subtract from each")]
+    time_offsets: Option<PathBuf>,
+
+    #[structopt(long="overwrite", conflicts_with="backup", help="Replace the target directory if it exists")]
+    overwrite: bool,
+
+    #[structopt(long="backup", help="Rename an existing target directory with a timestamp suffix rather than refusing to run")]
+    backup: bool,
+
+    #[structopt(long="append", conflicts_with="overwrite", help="Add worms from files the existing target has not scored to its scores and table")]
+    append: bool,
 
     #[structopt(name="source", parse(from_os_str))]
     source: PathBuf,
@@ -26,11 +123,219 @@ struct Opt {
     target: PathBuf,
 }
 
+impl Opt {
+    fn verbosity(&self) -> Verbosity { Verbosity::new(self.verbose, &self.verbose_only) }
+
+    // Configuration overrides from flags, which come after those from the environment
+    fn config_flags(&self) -> Vec<String> {
+        let mut flags = Vec::new();
+        if self.metrics.len() > 0 { flags.push(format!("metrics.only={:?}", self.metrics)); }
+        if self.full_precision { flags.push("metrics.full_precision=true".to_string()); }
+        flags.extend(self.set.iter().cloned());
+        flags
+    }
+}
+
+#[derive(Debug, StructOpt, Clone)]
+struct LongitudinalOpt {
+    #[structopt(long="decline", default_value="0.8", help="Fraction of the first value below which a worm has declined")]
+    decline: f64,
+
+    #[structopt(long="missing", default_value="NaN", parse(try_from_str = "parse_missing"),
+        help="How missing values are written in the .csv table: NaN, NA, an empty string, or another word")]
+    missing: String,
+
+    #[structopt(name="output", parse(from_os_str), help="JSON output; a long-format table is written beside it as .csv")]
+    output: PathBuf,
+
+    #[structopt(name="timepoints", required=true, help="Scores files tagged with their day, as DAY=FILE")]
+    timepoints: Vec<String>,
+}
+
+#[derive(Debug, StructOpt, Clone)]
+struct DoseOpt {
+    #[structopt(long="metric", default_value="calm-mean", help="Column of the scores table to fit")]
+    metric: String,
+
+    #[structopt(long="permutations", default_value="10000", help="Relabelings for the permutation test of each dose against the lowest")]
+    permutations: usize,
+
+    #[structopt(long="seed", help="Random seed for the permutation tests")]
+    seed: Option<u64>,
+
+    #[structopt(long="missing", default_value="NaN", parse(try_from_str = "parse_missing"),
+        help="How missing values are written in the .csv table: NaN, NA, an empty string, or another word")]
+    missing: String,
+
+    #[structopt(name="output", parse(from_os_str), help="JSON output; a table of groups and parameters is written beside it as .csv")]
+    output: PathBuf,
+
+    #[structopt(name="conditions", required=true, help="Scores files tagged with a condition label containing the dose, as LABEL=FILE")]
+    conditions: Vec<String>,
+}
+
+#[derive(Debug, StructOpt, Clone)]
+struct PivotOpt {
+    #[structopt(long="index", default_value="id", use_delimiter=true, help="Columns identifying each worm, comma-separated")]
+    index: Vec<String>,
+
+    #[structopt(long="values", use_delimiter=true, help="Columns to keep, comma-separated (default: all others)")]
+    values: Vec<String>,
+
+    #[structopt(long="shape", default_value="wide", help="wide, one row per worm; or long, one row per worm and value")]
+    shape: PivotShape,
+
+    #[structopt(long="missing", default_value="NaN", parse(try_from_str = "parse_missing"),
+        help="How missing values are written: NaN, NA, an empty string, or another word")]
+    missing: String,
+
+    #[structopt(long="output", parse(from_os_str), help="Write the table here instead of printing it")]
+    output: Option<PathBuf>,
+
+    #[structopt(name="scores", parse(from_os_str), help="A .scores, .msgpack, or .csv file from an earlier run")]
+    scores: PathBuf,
+}
+
+#[derive(Debug, StructOpt, Clone)]
+struct SnapshotOpt {
+    #[structopt(long="lines", default_value="100", help="Lines kept from each end of the file")]
+    lines: usize,
+
+    #[structopt(long="name", help="Fixture name (default: the file's name without its extension)")]
+    name: Option<String>,
+
+    #[structopt(long="into", default_value="testdata", parse(from_os_str), help="Directory of fixtures; the expected outputs go in its golden directory")]
+    into: PathBuf,
+
+    #[structopt(name="file", parse(from_os_str), help="A track file in any known format")]
+    file: PathBuf,
+}
+
+#[derive(Debug, StructOpt, Clone)]
+struct SelftestOpt {
+    #[structopt(long="keep", help="Keep the temporary directory of tracks and outputs")]
+    keep: bool,
+
+    #[structopt(long="threads", help="Threads to score with (default: all available)")]
+    threads: Option<usize>,
+}
+
+#[derive(Debug, StructOpt, Clone)]
+struct BatchOpt {
+    #[structopt(short="v", long="verbose", parse(from_occurrences), help="Print more detail: -v per file, -vv per worm, -vvv every score")]
+    verbose: u64,
+
+    #[structopt(long="verbose-only", use_delimiter=true, help="Print detail only about these, comma-separated: parsing, scoring, io")]
+    verbose_only: Vec<LogArea>,
+
+    #[structopt(long="threads", help="Threads shared by all the runs to score with (default: all cores)")]
+    threads: Option<usize>,
+
+    #[structopt(long="summary-json", parse(from_os_str), help="Write a machine-readable summary of every run here")]
+    summary_json: Option<PathBuf>,
+
+    #[structopt(name="spec", parse(from_os_str), help="Tab-separated lines of source, target, and optionally config")]
+    spec: PathBuf,
+}
+
+#[derive(Debug, StructOpt, Clone)]
+struct RescoreOpt {
+    #[structopt(short="v", long="verbose", parse(from_occurrences), help="Print more detail: -v per file, -vv per worm, -vvv every score")]
+    verbose: u64,
+
+    #[structopt(long="verbose-only", use_delimiter=true, help="Print detail only about these, comma-separated: parsing, scoring, io")]
+    verbose_only: Vec<LogArea>,
+
+    #[structopt(long="config", parse(from_os_str))]
+    config: PathBuf,
+
+    #[structopt(long="threads", help="Threads to use (default: all cores)")]
+    threads: Option<usize>,
+
+    #[structopt(name="old_target", parse(from_os_str), help="Target directory of the earlier run")]
+    old_target: PathBuf,
+
+    #[structopt(name="new_target", parse(from_os_str))]
+    new_target: PathBuf,
+}
+
+#[derive(Debug, StructOpt, Clone)]
+struct ServeOpt {
+    #[structopt(long="port", default_value="8080", help="Port to listen on; 0 picks a free one")]
+    port: u16,
+
+    #[structopt(long="bind", default_value="127.0.0.1", help="Address to listen on; 0.0.0.0 accepts requests from other machines")]
+    bind: std::net::IpAddr,
+
+    #[structopt(long="max-body", default_value="256M", parse(try_from_str = "parse_memory"),
+        help="Largest request body accepted, in bytes or with K, M, or G")]
+    max_body: usize,
+
+    #[structopt(long="config", parse(from_os_str))]
+    config: Option<PathBuf>,
+
+    #[structopt(long="threads", help="Requests handled at once (default: all cores)")]
+    threads: Option<usize>,
+}
+
+// Subcommands.  Analyze is the default, so `metrology SOURCE TARGET` needs none.
+#[derive(Debug, StructOpt)]
+#[structopt(name = "metrology", about = "Metrology computes health metrics for individual worms.",
+    raw(usage = r#""metrology [analyze] [FLAGS] [OPTIONS] <source> <target>\n    metrology <SUBCOMMAND>""#))]
+enum Command {
+    #[structopt(name = "analyze", about = "Scores every worm in a source directory into a target directory; the default, so may be left out.")]
+    Analyze(Opt),
+
+    #[structopt(name = "batch", about = "Runs metrology on many source/target pairs.")]
+    Batch(BatchOpt),
+
+    #[structopt(name = "rescore", about = "Brings an earlier run's scores up to date with a new configuration.")]
+    Rescore(RescoreOpt),
+
+    #[structopt(name = "serve", about = "Scores data posted over HTTP.")]
+    Serve(ServeOpt),
+
+    #[structopt(name = "longitudinal", about = "Combines scores from several timepoints into per-worm trajectories.")]
+    Longitudinal(LongitudinalOpt),
+
+    #[structopt(name = "dose-response", about = "Fits a dose-response curve to one metric across conditions.")]
+    DoseResponse(DoseOpt),
+
+    #[structopt(name = "pivot", about = "Reshapes a scores file into a small table for plotting.")]
+    Pivot(PivotOpt),
+
+    #[structopt(name = "snapshot", about = "Cuts a track file down to a test fixture, and writes the scores it should get.")]
+    Snapshot(SnapshotOpt),
+
+    #[structopt(name = "selftest", about = "Scores synthetic worms through the whole pipeline and checks the results, to test an installation.")]
+    Selftest(SelftestOpt),
+}
+
+impl Command {
+    // Whether the parser takes `word` as a subcommand, which only a subcommand's help confirms
+    fn names(word: &str) -> bool {
+        match Command::from_iter_safe(&["metrology", word, "--help"]) {
+            Err(e) => e.kind == structopt::clap::ErrorKind::HelpDisplayed,
+            Ok(_)  => true,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 struct Dat {
     prefix: String,
-    id: u32,
+    id: WormId,
     path: PathBuf,
+
+    // Letters after the worm number of a fragment of a track, as `042a`
+    fragment: String,
+
+    // Later fragments stitched onto this one
+    joined: Vec<PathBuf>,
+}
+
+impl Dat {
+    fn files(&self) -> impl Iterator<Item = &PathBuf> { std::iter::once(&self.path).chain(self.joined.iter()) }
 }
 
 impl TryFrom<PathBuf> for Dat {
@@ -41,51 +346,1067 @@ impl TryFrom<PathBuf> for Dat {
         let stem = value.file_stem().ok_or_else(|| e("filename empty"))?;
         let prefix = Path::new(stem).file_stem().and_then(|x| x.to_str()).ok_or_else(|| e("filename has no prefix"))?;
         let number = Path::new(stem).extension().and_then(|x| x.to_str()).ok_or_else(|| e("filename has no worm number"))?;
-        let _suffix = value.extension().filter(|p| *p == "dat").ok_or_else(|| e("filename has no .dat extension"))?;
+        let _suffix = value.extension().ok_or_else(|| e("filename has no extension"))?;
 
-        let n: u32 = number.parse().map_err(|_| e("worm number isn't a number"))?;
+        let (n, fragment) = worm_fragment(number).ok_or_else(|| e("worm number isn't a number"))?;
 
-        Ok(Dat{ prefix: prefix.into(), id: n, path: value.clone() })
+        Ok(Dat{ prefix: prefix.into(), id: n, path: value.clone(), fragment: fragment.into(), joined: Vec::new() })
     }
 }
 
-fn get_dats(path: PathBuf) -> std::io::Result<Vec<Dat>> {
-    let mut files = Vec::new();
-    for file in std::fs::read_dir(path)? {
-        let path = file?.path();
-        if !path.is_dir() {
-            if let Some(p) = path.extension() {
-                if p == "dat" { files.push(path.try_into()?); }
+// Track files in any known format, sorted by prefix and worm
+fn get_dats(path: PathBuf, formats: &TrackFormats) -> std::io::Result<Vec<Dat>> {
+    let mut dats = formats.track_files(&path)?.into_iter().map(|p| p.try_into()).collect::<std::io::Result<Vec<Dat>>>()?;
+    dats.sort();
+    Ok(dats)
+}
+
+const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+// Runs take hours at most, so a lock this old is left over from a run that died
+const STALE_LOCK: std::time::Duration = std::time::Duration::from_secs(48 * 3600);
+
+// Exit codes, so workflow engines can tell kinds of failure apart
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Failure {
+    Other = 1,
+    BadArgs = 2,
+    MissingSource = 3,
+    BadInput = 4,
+    Write = 5,
+    Partial = 6,
+    Locked = 7,
+}
+
+impl Failure {
+    fn code(self) -> i32 { self as i32 }
+
+    fn exit(self, message: &str) -> ! {
+        println!("{}", message);
+        std::process::exit(self.code())
+    }
+}
+
+#[derive(Debug)]
+struct RunError {
+    failure: Failure,
+    message: String,
+}
+
+trait OrFail<T> {
+    fn or_fail(self, failure: Failure) -> Result<T, RunError>;
+}
+
+impl<T> OrFail<T> for Result<T, String> {
+    fn or_fail(self, failure: Failure) -> Result<T, RunError> { self.map_err(|message| RunError{ failure, message }) }
+}
+
+// Where a run's messages go: straight to the terminal, or kept to be printed
+// together once the run is done, since the runs of a batch go at once
+enum Output {
+    Direct,
+    Kept(String),
+}
+
+impl Output {
+    fn say(&mut self, line: String) {
+        match self {
+            Output::Direct     => println!("{}", line),
+            Output::Kept(text) => { text.push_str(line.as_str()); text.push('\n'); },
+        }
+    }
+
+    fn text(self) -> String { match self { Output::Direct => String::new(), Output::Kept(text) => text } }
+}
+
+macro_rules! say {
+    ($out:expr, $($arg:tt)*) => { $out.say(format!($($arg)*)) }
+}
+
+fn parse_args<T: StructOpt>(args: &[String]) -> T {
+    use structopt::clap::ErrorKind;
+    match T::from_iter_safe(args.iter()) {
+        Ok(t)  => t,
+        Err(e) => match e.kind {
+            ErrorKind::HelpDisplayed | ErrorKind::VersionDisplayed => { println!("{}", e.message); std::process::exit(0) },
+            _ => { eprintln!("{}", e.message); std::process::exit(Failure::BadArgs.code()) },
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct RunSummary {
+    version: &'static str,
+    source: PathBuf,
+    target: PathBuf,
+    status: &'static str,
+    exit_code: i32,
+    files: usize,
+    tracks: usize,
+    warned: usize,
+    flagged: usize,
+    outputs: Vec<PathBuf>,
+    errors: Vec<String>,
+}
+
+impl RunSummary {
+    fn of(opt: &Opt, result: &Result<Report, RunError>) -> RunSummary {
+        let mut summary = RunSummary{
+            version: VERSION, source: opt.source.clone(), target: opt.target.clone(), status: "ok", exit_code: 0,
+            files: 0, tracks: 0, warned: 0, flagged: 0, outputs: Vec::new(), errors: Vec::new()
+        };
+        match result {
+            Ok(r)  => {
+                summary.files = r.files;
+                summary.tracks = r.rows;
+                summary.warned = r.warned;
+                summary.flagged = r.flagged;
+                summary.outputs = r.outputs.clone();
+            },
+            Err(e) => {
+                summary.status = "failed";
+                summary.exit_code = e.failure.code();
+                summary.errors.push(e.message.clone());
             }
         }
+        summary
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct BatchSummary {
+    version: &'static str,
+    exit_code: i32,
+    runs: Vec<RunSummary>,
+}
+
+fn write_summary<S: Serialize>(path: &Path, summary: &S) -> Result<(), RunError> {
+    std::fs::write(path, serde_json::to_string_pretty(summary).unwrap().as_str())
+        .map_err(|e| format!("Error writing summary {:?}\n  {:?}", path, e))
+        .or_fail(Failure::Write)?;
+    println!("  Wrote {:?}", path);
+    Ok(())
+}
+
+fn longitudinal(opt: LongitudinalOpt) {
+    println!("Metrology version {}", VERSION);
+
+    let csv = opt.output.with_extension("csv");
+    if opt.output.exists() { Failure::BadArgs.exit(format!("Output {:?} exists already", opt.output).as_str()); }
+    if csv.exists()        { Failure::BadArgs.exit(format!("Output {:?} exists already", csv).as_str()); }
+
+    let mut timepoints = Vec::new();
+    for tp in opt.timepoints.iter() {
+        let (day, file) = match tp.find('=') {
+            Some(i) => (&tp[..i], Path::new(&tp[i+1..])),
+            None    => Failure::BadArgs.exit(format!("Timepoint {:?} should be DAY=FILE", tp).as_str()),
+        };
+        let day: f64 = match day.parse() {
+            Ok(d)  => d,
+            Err(_) => Failure::BadArgs.exit(format!("Day {:?} in {:?} is not a number", day, tp).as_str()),
+        };
+        match read_scores_file(file) {
+            Ok(scores) => { println!("  Read {} worms for day {} from {:?}", scores.len(), day, file); timepoints.push(Timepoint{ day, scores }); },
+            Err(e)     => Failure::BadInput.exit(format!("Error reading {:?}\n  {}", file, e).as_str()),
+        }
+    }
+
+    let histories = the_longitudinal(&timepoints, opt.decline, Precision::default());
+    for (file, text) in [
+        (&opt.output, serde_json::to_string(&histories).unwrap()),
+        (&csv, with_missing(longitudinal_table(&histories).as_str(), opt.missing.as_str()))
+    ].iter() {
+        match std::fs::write(file, text.as_str()) {
+            Err(e) => Failure::Write.exit(format!("Error writing {:?}\n  {:?}", file, e).as_str()),
+            _      => { println!("  Wrote {:?}", file); }
+        }
     }
-    Ok(files)
 }
 
+fn dose_response(opt: DoseOpt) {
+    println!("Metrology version {}", VERSION);
+
+    let csv = opt.output.with_extension("csv");
+    if opt.output.exists() { Failure::BadArgs.exit(format!("Output {:?} exists already", opt.output).as_str()); }
+    if csv.exists()        { Failure::BadArgs.exit(format!("Output {:?} exists already", csv).as_str()); }
+
+    let mut conditions = Vec::new();
+    for c in opt.conditions.iter() {
+        let (label, file) = match c.find('=') {
+            Some(i) => (&c[..i], Path::new(&c[i+1..])),
+            None    => Failure::BadArgs.exit(format!("Condition {:?} should be LABEL=FILE", c).as_str()),
+        };
+        if label.contains(char::is_whitespace) { Failure::BadArgs.exit(format!("Label {:?} can't contain spaces", label).as_str()); }
+        if dose_in_label(label).is_none() { Failure::BadArgs.exit(format!("Label {:?} has no dose in it", label).as_str()); }
+        match read_scores_file(file) {
+            Ok(scores) => { println!("  Read {} worms for {} from {:?}", scores.len(), label, file); conditions.push(DoseCondition{ label: label.to_string(), scores }); },
+            Err(e)     => Failure::BadInput.exit(format!("Error reading {:?}\n  {}", file, e).as_str()),
+        }
+    }
+
+    let seed = opt.seed.unwrap_or_else(|| Rng::from_time().next_u64());
+    let dr = match the_dose_response(&conditions, opt.metric.as_str(), opt.permutations, seed, Precision::default()) {
+        Ok(dr) => dr,
+        Err(e) => Failure::BadArgs.exit(e.to_string().as_str()),
+    };
+    match (&dr.fit, &dr.failure) {
+        (Some(f), _) => println!("  EC50 {} ({} to {}), hill {} from {} worms", f.ec50.value, f.ec50.lower, f.ec50.upper, f.hill.value, f.n),
+        (_, Some(e)) => println!("Warning: no dose-response fit: {}", e),
+        _            => {},
+    }
+    if let Some(cs) = &dr.comparisons {
+        for c in cs.groups.iter() {
+            println!("  {} vs {}: rank-biserial {}, Mann-Whitney p = {}, permutation p = {}", c.label, cs.control, c.rank_biserial, c.mann_whitney_p, c.permutation_p);
+        }
+        println!("  Permutation tests used seed {}", seed);
+    }
+    for (file, text) in [
+        (&opt.output, serde_json::to_string(&dr).unwrap()),
+        (&csv, with_missing(dose_response_table(&dr).as_str(), opt.missing.as_str()))
+    ].iter() {
+        match std::fs::write(file, text.as_str()) {
+            Err(e) => Failure::Write.exit(format!("Error writing {:?}\n  {:?}", file, e).as_str()),
+            _      => { println!("  Wrote {:?}", file); }
+        }
+    }
+}
+
+fn pivot(opt: PivotOpt) {
+    if let Some(o) = opt.output.as_ref().filter(|o| o.exists()) { Failure::BadArgs.exit(format!("Output {:?} exists already", o).as_str()); }
+    let scores = match read_scores_file(&opt.scores) {
+        Ok(s)  => s,
+        Err(e) => Failure::BadInput.exit(format!("Error reading {:?}\n  {}", opt.scores, e).as_str()),
+    };
+    let table = match pivot_table(&scores, &opt.index, &opt.values, opt.shape) {
+        Ok(t)  => with_missing(t.as_str(), opt.missing.as_str()),
+        Err(e) => Failure::BadArgs.exit(e.to_string().as_str()),
+    };
+    match &opt.output {
+        None    => print!("{}", table),
+        Some(o) => if let Err(e) = std::fs::write(o, table.as_str()) { Failure::Write.exit(format!("Error writing {:?}\n  {:?}", o, e).as_str()); },
+    }
+}
+
+// The fixture is parsed again from what was kept, so its expected scores are
+// exactly what the regression tests will find
+fn snapshot(opt: SnapshotOpt) {
+    let formats = TrackFormats::new(&InputFormat::default(), Precision::default(), 1);
+    let ext = match formats.for_path(&opt.file) {
+        Some(f) => f.extension(),
+        None    => Failure::BadArgs.exit(format!("No known track format for {:?}", opt.file).as_str()),
+    };
+    let name = opt.name.clone().unwrap_or_else(|| opt.file.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default());
+    if name.is_empty() || name.contains(std::path::is_separator) { Failure::BadArgs.exit(format!("Fixture name {:?} can't be used", name).as_str()); }
+    let bytes = match std::fs::read(&opt.file) {
+        Ok(b)  => snapshot_bytes(&b, opt.lines),
+        Err(e) => Failure::BadInput.exit(format!("Error reading {:?}\n  {:?}", opt.file, e).as_str()),
+    };
+    let fixture = opt.into.join(format!("{}.{}", name, ext));
+    let lines = match formats.parse(&fixture, &bytes) {
+        Ok(l)  => l,
+        Err(e) => Failure::BadInput.exit(format!("The first and last {} lines of {:?} don't parse; try more --lines\n  {}", opt.lines, opt.file, e).as_str()),
+    };
+    let outputs = match golden_outputs(&lines) {
+        Ok(o)  => o,
+        Err(e) => Failure::Other.exit(format!("Error scoring {:?}\n  {}", opt.file, e).as_str()),
+    };
+    let mut files = vec![(fixture, bytes)];
+    files.extend(outputs.into_iter().map(|(e, text)| (opt.into.join("golden").join(format!("{}.{}", name, e)), text.into_bytes())));
+    if let Some((f, _)) = files.iter().find(|(f, _)| f.exists()) { Failure::BadArgs.exit(format!("{:?} exists already", f).as_str()); }
+    if let Err(e) = std::fs::create_dir_all(opt.into.join("golden")) { Failure::Write.exit(format!("Error creating {:?}\n  {:?}", opt.into, e).as_str()); }
+    for (f, contents) in files.iter() {
+        match std::fs::write(f, contents) {
+            Err(e) => Failure::Write.exit(format!("Error writing {:?}\n  {:?}", f, e).as_str()),
+            _      => println!("  Wrote {:?}", f),
+        }
+    }
+    println!("Scored {} lines; tests/regression.rs now checks them", lines.len());
+}
+
+// Writes synthetic worms' tracks to a temporary directory, runs them as any
+// other source, and compares the scores file with the golden values
+fn selftest(opt: SelftestOpt) {
+    println!("Metrology version {}", VERSION);
+    let dir = std::env::temp_dir().join(format!("metrology-selftest-{}", std::process::id()));
+    let (source, target) = (dir.join("tracks"), dir.join("scores"));
+    let result = std::fs::create_dir_all(&source).map_err(|e| format!("Error creating {:?}\n  {:?}", source, e)).or_fail(Failure::Write).and_then(|_| {
+        for (id, settings) in selftest_worms().iter() {
+            let file = source.join(format!("selftest.{:03}.dat", id));
+            std::fs::write(&file, dat_text(&synth_track(settings))).map_err(|e| format!("Error writing {:?}\n  {:?}", file, e)).or_fail(Failure::Write)?;
+        }
+        let args = ["metrology".to_string(), source.to_string_lossy().to_string(), target.to_string_lossy().to_string()];
+        let run_opt = Opt::from_iter_safe(args.iter()).map_err(|e| e.message).or_fail(Failure::BadArgs)?;
+        let report = run(&run_opt, &Config::default(), &ThreadBudget::new(opt.threads.unwrap_or_else(default_threads)), None, &mut Output::Direct)?;
+        let file = report.outputs.iter().find(|o| o.extension().map(|e| e == "scores").unwrap_or(false))
+            .ok_or_else(|| "The run wrote no scores file".to_string()).or_fail(Failure::Other)?;
+        read_scores(file).map_err(|e| format!("Error reading {:?}\n  {}", file, e)).or_fail(Failure::BadInput)
+    });
+    if opt.keep { println!("Tracks and outputs are in {:?}", dir); }
+    else if let Err(e) = std::fs::remove_dir_all(&dir) { println!("Warning: could not remove {:?}\n  {:?}", dir, e); }
+    let scores = match result {
+        Ok(s)  => s,
+        Err(e) => e.failure.exit(format!("Self test could not run\n{}", e.message).as_str()),
+    };
+    let failures = selftest_failures(&scores);
+    for f in failures.iter() { println!("  FAILED: {}", f); }
+    if failures.len() > 0 { Failure::Other.exit(format!("Self test failed {} of {} checks", failures.len(), SELFTEST_GOLDEN.len()).as_str()); }
+    println!("Self test passed: {} worms, {} checks", scores.len(), SELFTEST_GOLDEN.len());
+}
 
 fn main() {
-    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+    let mut args: Vec<String> = std::env::args().collect();
+    // A source directory that shares a subcommand's name is still a source
+    let named = match args.get(1) {
+        None                          => true,
+        Some(a) if a.starts_with('-') => ["-h", "--help", "-V", "--version"].contains(&a.as_str()),
+        Some(a)                       => !Path::new(a).is_dir() && (a == "help" || Command::names(a)),
+    };
+    if !named { args.insert(1, "analyze".to_string()); }
+    match parse_args(&args) {
+        Command::Analyze(opt)      => analyze(opt),
+        Command::Batch(opt)        => batch(opt),
+        Command::Rescore(opt)      => rescore(opt),
+        Command::Serve(opt)        => serve(opt),
+        Command::Longitudinal(opt) => longitudinal(opt),
+        Command::DoseResponse(opt) => dose_response(opt),
+        Command::Pivot(opt)        => pivot(opt),
+        Command::Snapshot(opt)     => snapshot(opt),
+        Command::Selftest(opt)     => selftest(opt),
+    }
+}
+
+// Built-in defaults, then the file, then METROLOGY_ environment variables, then `flags`
+fn load_config(path: &Option<PathBuf>, flags: &[String]) -> Result<Config, String> {
+    let text = match path {
+        None    => String::new(),
+        Some(p) => std::fs::read_to_string(p).map_err(|e| format!("Error loading config {:?}\n  {}", p, e))?,
+    };
+    let mut overrides = environment_overrides(std::env::vars());
+    overrides.extend(flags.iter().cloned());
+    Config::layered(text.as_str(), &overrides).map_err(|e| match path {
+        None    => format!("Error in configuration\n  {}", e),
+        Some(p) => format!("Error loading config {:?}\n  {}", p, e),
+    })
+}
+
+fn default_threads() -> usize { std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1) }
+
+fn analyze(opt: Opt) {
+    if opt.print_config {
+        match load_config(&opt.config, &opt.config_flags()) {
+            Ok(c)  => print!("{}", c.to_toml()),
+            Err(e) => Failure::BadArgs.exit(e.as_str()),
+        }
+        return;
+    }
+    println!("Metrology version {}", VERSION);
+    let result = load_config(&opt.config, &opt.config_flags()).or_fail(Failure::BadArgs).and_then(|config| run(&opt, &config, &ThreadBudget::new(default_threads()), None, &mut Output::Direct));
+    if let Err(e) = &result { println!("{}", e.message); }
+    if let Some(path) = &opt.summary_json {
+        if let Err(e) = write_summary(path, &RunSummary::of(&opt, &result)) { 
+            e.failure.exit(e.message.as_str());
+        }
+    }
+    if let Err(e) = result { std::process::exit(e.failure.code()); }
+}
 
-    let opt = Opt::from_args();
+fn batch(opt: BatchOpt) {
     println!("Metrology version {}", VERSION);
 
+    let spec = match std::fs::read_to_string(&opt.spec) {
+        Ok(s)  => s,
+        Err(e) => Failure::BadArgs.exit(format!("Error reading {:?}\n  {:?}", opt.spec, e).as_str()),
+    };
+    let mut runs: Vec<Opt> = Vec::new();
+    let mut lines: Vec<usize> = Vec::new();
+    for (i, line) in spec.lines().enumerate() {
+        if line.trim().len() == 0 || line.starts_with('#') { continue; }
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() < 2 || fields.len() > 3 {
+            Failure::BadArgs.exit(format!("Line {} of {:?} should be source<TAB>target[<TAB>config]", i+1, opt.spec).as_str());
+        }
+        let mut args: Vec<String> = vec!["metrology".to_string(), fields[0].to_string(), fields[1].to_string()];
+        args.extend(Verbosity::new(opt.verbose, &opt.verbose_only).args());
+        if fields.len() == 3 { args.push("--config".to_string()); args.push(fields[2].to_string()); }
+        let o = match Opt::from_iter_safe(args.iter()) {
+            Ok(o)  => o,
+            Err(e) => Failure::BadArgs.exit(format!("Line {} of {:?}: {}", i+1, opt.spec, e.message).as_str()),
+        };
+        // Runs go at once, so two writing one target would collide
+        if let Some(k) = runs.iter().position(|r| r.target == o.target) {
+            Failure::BadArgs.exit(format!("Line {} of {:?} has the same target as line {}", i+1, opt.spec, lines[k]).as_str());
+        }
+        runs.push(o);
+        lines.push(i+1);
+    }
+
+    // Runs are handed out as threads come free, and all draw on one budget of
+    // threads to score with.  Each run's messages are printed together, in the
+    // order of the spec, once it and the runs before it are done.
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    let threads = ThreadBudget::new(opt.threads.unwrap_or_else(default_threads));
+    let next = AtomicUsize::new(0);
+    let printed = std::sync::Mutex::new(0usize);
+    let texts: Vec<std::sync::Mutex<Option<String>>> = runs.iter().map(|_| std::sync::Mutex::new(None)).collect();
+    let slots: Vec<std::sync::Mutex<Option<Result<Report, RunError>>>> = runs.iter().map(|_| std::sync::Mutex::new(None)).collect();
+    std::thread::scope(|scope| {
+        for _ in 0..std::cmp::min(threads.size, runs.len()) {
+            scope.spawn(|| {
+                loop {
+                    let i = next.fetch_add(1, Ordering::SeqCst);
+                    if i >= runs.len() { break; }
+                    let run_opt = &runs[i];
+                    let mut out = Output::Kept(String::new());
+                    say!(out, "");
+                    say!(out, "Run {}: {:?} -> {:?}", i+1, run_opt.source, run_opt.target);
+                    let result = load_config(&run_opt.config, &run_opt.config_flags()).or_fail(Failure::BadArgs).and_then(|config| run(run_opt, &config, &threads, None, &mut out));
+                    if let Err(e) = &result { say!(out, "{}", e.message); }
+                    *slots[i].lock().unwrap() = Some(result);
+                    *texts[i].lock().unwrap() = Some(out.text());
+                    let mut p = printed.lock().unwrap();
+                    while *p < runs.len() {
+                        match texts[*p].lock().unwrap().take() {
+                            Some(text) => { print!("{}", text); *p += 1; },
+                            None       => break,
+                        }
+                    }
+                }
+            });
+        }
+    });
+    let outcomes: Vec<(Opt, Result<Report, RunError>)> = runs.into_iter().zip(slots.into_iter())
+        .map(|(o, slot)| (o, slot.into_inner().unwrap().expect("Every run finishes")))
+        .collect();
+
+    println!();
+    println!("Batch report for {:?}", opt.spec);
+    let mut failures = 0;
+    for (o, result) in outcomes.iter() {
+        match result {
+            Ok(r)  => println!("  ok      {:?} -> {:?}: {} files, {} tracks", o.source, o.target, r.files, r.rows),
+            Err(e) => { 
+                failures += 1;
+                println!("  FAILED  {:?} -> {:?}: {}", o.source, o.target, e.message.lines().next().unwrap_or(""));
+            }
+        }
+    }
+    println!("{} of {} runs succeeded", outcomes.len() - failures, outcomes.len());
+
+    // Some failures is a partial success; if everything failed, the first failure says why
+    let code =
+        if failures == 0 { 0 }
+        else if failures < outcomes.len() { Failure::Partial.code() }
+        else { outcomes.iter().find_map(|(_, r)| r.as_ref().err()).map(|e| e.failure.code()).unwrap_or(Failure::Other.code()) };
+    if let Some(path) = &opt.summary_json {
+        let runs = outcomes.iter().map(|(o, r)| RunSummary::of(o, r)).collect();
+        if let Err(e) = write_summary(path, &BatchSummary{ version: VERSION, exit_code: code, runs }) { 
+            e.failure.exit(e.message.as_str());
+        }
+    }
+    if code != 0 { std::process::exit(code); }
+}
+
+// An earlier run being brought up to date, with the configuration sections
+// that have changed since
+struct Previous {
+    target: PathBuf,
+    manifest: Manifest,
+    changed: Vec<String>,
+    scores: BTreeMap<WormId, Vec<Scores>>,
+    offsets: TimeOffsets,
+}
+
+fn previous_run(target: &Path, config: &Config) -> Result<Previous, RunError> {
+    let manifest_file = target.join("manifest.json");
+    let text = std::fs::read_to_string(&manifest_file).map_err(|e| format!("Error reading {:?}\n  {:?}", manifest_file, e)).or_fail(Failure::MissingSource)?;
+    let manifest: Manifest = serde_json::from_str(text.as_str()).map_err(|e| format!("Error reading {:?}\n  {}", manifest_file, e)).or_fail(Failure::BadInput)?;
+    let found = manifest.outputs.iter().find_map(|o| match o.extension().and_then(|e| e.to_str()) {
+        Some("scores")  => Some((target.join(o), read_scores(&target.join(o)))),
+        Some("msgpack") => Some((target.join(o), read_scores_msgpack(&target.join(o)))),
+        _               => None,
+    });
+    let rows = match found {
+        Some((_, Ok(rows)))  => rows,
+        Some((file, Err(e))) => return Err(format!("Error reading {:?}\n  {}", file, e)).or_fail(Failure::BadInput),
+        None                 => return Err(format!("No scores file listed in {:?}", manifest_file)).or_fail(Failure::BadInput),
+    };
+    let mut scores: BTreeMap<WormId, Vec<Scores>> = BTreeMap::new();
+    for r in rows.into_iter() { scores.entry(r.id).or_insert_with(Vec::new).push(r); }
+    let mut offsets = TimeOffsets::default();
+    for (file, x) in manifest.time_offsets.iter() {
+        if let Some(name) = file.file_name() { offsets.insert(name.to_string_lossy().as_ref(), *x); }
+    }
+    let changed = changed_sections(&manifest.config, config);
+    Ok(Previous{ target: target.to_path_buf(), manifest, changed, scores, offsets })
+}
+
+fn rescore(opt: RescoreOpt) {
+    println!("Metrology version {}", VERSION);
+    let result = load_config(&Some(opt.config.clone()), &[]).or_fail(Failure::BadArgs).and_then(|config| {
+        let previous = previous_run(&opt.old_target, &config)?;
+        if previous.changed.len() == 0 { println!("Configuration is unchanged; scores are copied"); }
+        else { println!("Configuration changed in: {}", previous.changed.join(", ")); }
+
+        let source = previous.manifest.source.to_string_lossy().to_string();
+        let target = opt.new_target.to_string_lossy().to_string();
+        let config_path = opt.config.to_string_lossy().to_string();
+        let mut args = vec!["metrology".to_string(), source, target, "--config".to_string(), config_path];
+        args.extend(Verbosity::new(opt.verbose, &opt.verbose_only).args());
+        if previous.manifest.full_precision { args.push("--full-precision".to_string()); }
+        if let Some(q) = previous.manifest.quick { args.push("--quick".to_string()); args.push(q.to_string()); }
+        if previous.manifest.outputs.iter().any(|o| o.extension().map(|e| e == "msgpack").unwrap_or(false)) {
+            args.push("--format".to_string());
+            args.push("msgpack".to_string());
+        }
+        let run_opt = Opt::from_iter_safe(args.iter()).map_err(|e| e.message).or_fail(Failure::BadArgs)?;
+        run(&run_opt, &config, &ThreadBudget::new(opt.threads.unwrap_or_else(default_threads)), Some(&previous), &mut Output::Direct)
+    });
+    if let Err(e) = result { e.failure.exit(e.message.as_str()); }
+}
+
+fn score_posted(id: WormId, body: &[u8], config: &Config) -> Result<Vec<Scores>, String> {
+    let data = parse_data_lines(&config.input.normalize(body)).map_err(|e| format!("worm {}: {}", id, e))?;
+    Ok(the_tracks(id, &data, config))
+}
+
+fn respond(request: tiny_http::Request, status: u16, body: String) {
+    let kind = if status == 200 { &b"application/json"[..] } else { &b"text/plain"[..] };
+    let response = tiny_http::Response::from_string(body)
+        .with_status_code(status)
+        .with_header(tiny_http::Header::from_bytes(&b"Content-Type"[..], kind).unwrap());
+    if let Err(e) = request.respond(response) { println!("Error responding\n  {:?}", e); }
+}
+
+// POST /score?id=N with a .dat body gives that worm's scores; POST /batch with
+// a JSON object of id to .dat text gives scores for all of them; GET /version
+// gives the version and configuration hash.
+fn handle(mut request: tiny_http::Request, config: &Config, max_body: usize) {
+    let url = request.url().to_string();
+    let (path, query) = match url.find('?') {
+        Some(i) => (url[..i].to_string(), url[i+1..].to_string()),
+        None    => (url.clone(), String::new()),
+    };
+    let post = *request.method() == tiny_http::Method::Post;
+    let mut body: Vec<u8> = Vec::new();
+    // Bodies without a length (chunked ones) are cut off just past the limit, so they can't fill memory either
+    let too_large = || format!("Request bodies can be at most {} bytes", max_body);
+    if post {
+        if request.body_length().map(|n| n > max_body).unwrap_or(false) { return respond(request, 413, too_large()); }
+        if let Err(e) = request.as_reader().take(max_body as u64 + 1).read_to_end(&mut body) { return respond(request, 400, format!("Could not read request: {}", e)); }
+        if body.len() > max_body { return respond(request, 413, too_large()); }
+    }
+
+    let result: Result<String, (u16, String)> = match (path.as_str(), post) {
+        ("/version", false) => Ok(format!("{{\"version\":\"{}\",\"config\":\"{}\"}}", VERSION, config.hash())),
+        ("/score", true) => {
+            let id = query.split('&').find_map(|kv| kv.strip_prefix("id=")).unwrap_or("0");
+            id.parse::<WormId>()
+                .map_err(|_| (400, format!("Worm id must be a number, not {:?}", id)))
+                .and_then(|id| score_posted(id, &body, config).map_err(|e| (400, e)))
+                .map(|scores| serde_json::to_string(&scores).unwrap())
+        },
+        ("/batch", true) => {
+            serde_json::from_slice::<BTreeMap<WormId, String>>(&body)
+                .map_err(|e| (400, format!("Batch must be a JSON object of worm id to data: {}", e)))
+                .and_then(|dats| {
+                    let mut rows = Vec::new();
+                    for (id, text) in dats.iter() { rows.extend(score_posted(*id, text.as_bytes(), config).map_err(|e| (400, e))?); }
+                    flag_lost_at_edge(&config.qc, config.exploration.arena, &mut rows);
+                    Ok(serde_json::to_string(&rows).unwrap())
+                })
+        },
+        ("/version", true) | ("/score", false) | ("/batch", false) => Err((405, format!("Wrong method for {}", path))),
+        _ => Err((404, format!("Nothing at {}; use /score, /batch, or /version", path))),
+    };
+    match result {
+        Ok(json)          => respond(request, 200, json),
+        Err((status, e))  => respond(request, status, e),
+    }
+}
+
+fn serve(opt: ServeOpt) {
+    println!("Metrology version {}", VERSION);
+    let config = match load_config(&opt.config, &[]) {
+        Ok(c)  => c,
+        Err(e) => Failure::BadArgs.exit(e.as_str()),
+    };
+    let server = match tiny_http::Server::http((opt.bind, opt.port)) {
+        Ok(s)  => s,
+        Err(e) => Failure::Other.exit(format!("Could not listen on {}:{}\n  {}", opt.bind, opt.port, e).as_str()),
+    };
+    let threads = opt.threads.unwrap_or_else(default_threads);
+    println!("Serving on {} with {} threads (config {})", server.server_addr(), threads, config.hash());
+    std::thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| {
+                loop {
+                    match server.recv() {
+                        Ok(request) => handle(request, &config, opt.max_body),
+                        Err(e)      => { println!("Error receiving request\n  {:?}", e); break; },
+                    }
+                }
+            });
+        }
+    });
+}
+
+struct Report {
+    files: usize,
+    rows: usize,
+    warned: usize,
+    flagged: usize,
+    outputs: Vec<PathBuf>,
+}
+
+struct FileResult {
+    scores: Vec<Scores>,
+    histogram: Option<SpeedHistogram>,
+    data: Vec<DataLine>,
+    offset: Option<f64>,
+    timing: FileTiming,
+    log: Vec<String>,
+}
+
+struct FileTiming {
+    bytes: usize,
+    lines: usize,
+    read: std::time::Duration,
+    parse: std::time::Duration,
+    score: std::time::Duration,
+}
+
+fn profile_table(dats: &[Dat], timings: &[FileTiming]) -> String {
+    let mut table = String::from("file bytes lines read-s parse-s score-s mb-per-s\n");
+    for (d, t) in dats.iter().zip(timings.iter()) {
+        let total = (t.read + t.parse + t.score).as_secs_f64();
+        table.push_str(format!("{} {} {} {} {} {} {}\n",
+            d.path.file_name().map(|f| f.to_string_lossy().to_string()).unwrap_or_default(),
+            t.bytes, t.lines, t.read.as_secs_f64(), t.parse.as_secs_f64(), t.score.as_secs_f64(),
+            if total > 0.0 { (t.bytes as f64) / 1e6 / total } else { std::f64::NAN }
+        ).as_str());
+    }
+    table
+}
+
+// An offset given on the command line wins over one beside the file
+fn time_offset(d: &Dat, offsets: &TimeOffsets) -> Result<Option<f64>, RunError> {
+    let name = d.path.file_name().map(|f| f.to_string_lossy().to_string()).unwrap_or_default();
+    match offsets.lookup(name.as_str(), d.prefix.as_str()) {
+        Some(x) => Ok(Some(x)),
+        None    => read_sidecar_offset(&d.path).map_err(|e| format!("Error reading time offset for {:?}\n  {}", d.path, e)).or_fail(Failure::BadInput),
+    }
+}
+
+// Fragments of a worm's track become one `Dat` when stitching; otherwise
+// they are skipped.  `dats` must be sorted.
+fn stitched(dats: Vec<Dat>, stitch: bool, skipped: &mut Vec<SkippedFile>) -> Vec<Dat> {
+    let mut kept: Vec<Dat> = Vec::with_capacity(dats.len());
+    for d in dats.into_iter() {
+        if d.fragment.is_empty() && !stitch { kept.push(d); }
+        else if !stitch {
+            skipped.push(SkippedFile{ path: d.path.clone(), reason: format!("fragment of worm {}; set fragments.stitch to join fragments", d.id) });
+        }
+        else {
+            match kept.last_mut().filter(|k| k.prefix == d.prefix && k.id == d.id && !d.fragment.is_empty()) {
+                Some(k) => k.joined.push(d.path),
+                None    => kept.push(d),
+            }
+        }
+    }
+    kept
+}
+
+// Tables are space-separated, so a token can't contain a space
+fn parse_missing(text: &str) -> Result<String, String> {
+    if text.contains(char::is_whitespace) { Err(format!("{:?} can't be used for missing values as it contains a space", text)) }
+    else { Ok(text.to_string()) }
+}
+
+fn parse_memory(text: &str) -> Result<usize, String> {
+    let t = text.trim();
+    let (digits, scale) = match t.chars().last().map(|c| c.to_ascii_uppercase()) {
+        Some('K') => (&t[..t.len()-1], 1usize << 10),
+        Some('M') => (&t[..t.len()-1], 1 << 20),
+        Some('G') => (&t[..t.len()-1], 1 << 30),
+        _         => (t, 1),
+    };
+    match digits.parse::<f64>() {
+        Ok(x) if x > 0.0 && x.is_finite() => Ok((x * scale as f64) as usize),
+        _ => Err(format!("{:?} is not an amount of memory like 512M or 8G", text)),
+    }
+}
+
+// The file's bytes, a normalized copy, two copies of the parsed lines (each
+// line is a few times smaller as text than as a DataLine), and for long files
+// the columns come to about this many times the file size while it is scored.
+const MEMORY_PER_BYTE: usize = 6;
+
+// Memory promised to files being scored.  A file waits until its estimate
+// fits, except that a file is always let in when nothing else is running, so
+// one bigger than the whole budget is still scored.
+struct MemoryBudget {
+    limit: Option<usize>,
+    used: std::sync::Mutex<usize>,
+    freed: std::sync::Condvar,
+}
+
+struct Reservation<'a> {
+    budget: &'a MemoryBudget,
+    bytes: usize,
+}
+
+impl MemoryBudget {
+    fn new(limit: Option<usize>) -> MemoryBudget {
+        MemoryBudget{ limit, used: std::sync::Mutex::new(0), freed: std::sync::Condvar::new() }
+    }
+
+    fn reserve(&self, bytes: usize) -> Reservation<'_> {
+        let mut used = self.used.lock().unwrap();
+        if let Some(limit) = self.limit {
+            while *used > 0 && *used + bytes > limit { used = self.freed.wait(used).unwrap(); }
+        }
+        *used += bytes;
+        Reservation{ budget: self, bytes }
+    }
+}
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        *self.budget.used.lock().unwrap() -= self.bytes;
+        self.budget.freed.notify_all();
+    }
+}
+
+// Threads that files may be scored on at once, shared by all the runs of a
+// batch.  A file takes as many as it parses with and waits until they are
+// free; one asking for more than there are takes them all.
+struct ThreadBudget {
+    size: usize,
+    free: std::sync::Mutex<usize>,
+    freed: std::sync::Condvar,
+}
+
+struct Lease<'a> {
+    budget: &'a ThreadBudget,
+    threads: usize,
+}
+
+impl ThreadBudget {
+    fn new(size: usize) -> ThreadBudget {
+        let size = std::cmp::max(1, size);
+        ThreadBudget{ size, free: std::sync::Mutex::new(size), freed: std::sync::Condvar::new() }
+    }
+
+    fn take(&self, threads: usize) -> Lease<'_> {
+        let threads = std::cmp::max(1, std::cmp::min(threads, self.size));
+        let mut free = self.free.lock().unwrap();
+        while *free < threads { free = self.freed.wait(free).unwrap(); }
+        *free -= threads;
+        Lease{ budget: self, threads }
+    }
+}
+
+impl Drop for Lease<'_> {
+    fn drop(&mut self) {
+        *self.budget.free.lock().unwrap() += self.threads;
+        self.budget.freed.notify_all();
+    }
+}
+
+// Data are kept after scoring for outputs that include them, and to rescore
+// spatial metrics once the arena has been estimated
+fn keeps_data(opt: &Opt, config: &Config) -> bool { opt.hdf5 || opt.wcon || opt.export_speed_series || opt.panel || config.exploration.is_adaptive() || config.windows.quietest.is_some() || config.alignment.enabled }
+
+// Everything a run reads besides its options and configuration
+struct RunInputs<'a> {
+    offsets: TimeOffsets,
+    previous: Option<&'a Previous>,
+    cache_from: Option<PathBuf>,
+    cache_to: Option<PathBuf>,
+}
+
+// The parsed lines, the file's size, and the time taken to read it
+fn read_and_parse(path: &Path, config: &Config, threads: usize) -> Result<(Vec<DataLine>, usize, std::time::Duration), RunError> {
+    let started = std::time::Instant::now();
+    let mut f = std::fs::File::open(path).map_err(|e| format!("Error opening {:?}\n  {:?}", path, e)).or_fail(Failure::BadInput)?;
+    let mut v: Vec<u8> = Vec::new();
+    f.read_to_end(&mut v).map_err(|e| format!("Error reading {:?}\n  {:?}", path, e)).or_fail(Failure::BadInput)?;
+    let read = started.elapsed();
+    let data = TrackFormats::new(&config.input, config.metrics.precision(), threads).parse(path, &v).map_err(|e| format!("Error parsing {:?}\n  {}", path, e)).or_fail(Failure::BadInput)?;
+    Ok((data, v.len(), read))
+}
+
+// When rescoring, a worm's old scores are kept without reading its data if
+// nothing that needs the data has changed.  Parsed data are taken from a
+// cache if there is a current one, in which case all the time counts as reading.
+fn score_file(d: &Dat, opt: &Opt, config: &Config, inputs: &RunInputs, threads: usize) -> Result<FileResult, RunError> {
+    let mut log = Vec::new();
+    let verbosity = opt.verbosity();
+    let offset = time_offset(d, &inputs.offsets)?;
+    let old = inputs.previous.and_then(|p| p.scores.get(&d.id).map(|s| (p.changed.as_slice(), s.as_slice())));
+    if let Some((changed, s)) = old {
+        if !rescore_needs_data(changed) && config.histogram.edges.len() == 0 && !keeps_data(opt, config) && !opt.cache && !opt.paranoid && opt.inspect != Some(d.id) {
+            if verbosity.shows(LogArea::Io, 1) { log.push(format!("Kept scores for {:?}", d.path)); }
+            let no_time = std::time::Duration::from_secs(0);
+            let timing = FileTiming{ bytes: 0, lines: 0, read: no_time, parse: no_time, score: no_time };
+            return Ok(FileResult{ scores: the_rescored(d.id, s, changed, &[], config), histogram: None, data: Vec::new(), offset, timing, log });
+        }
+    }
+    let started = std::time::Instant::now();
+    let mut pieces: Vec<(&PathBuf, Vec<DataLine>)> = Vec::new();
+    let (mut bytes, mut read) = (0, std::time::Duration::from_secs(0));
+    for path in d.files() {
+        let t = std::time::Instant::now();
+        let cached = inputs.cache_from.as_ref().and_then(|dir| ParsedCache::load(&dir.join(cache_name(path)), path, &config.input));
+        let from_cache = cached.is_some();
+        let (data, b, r) = match cached {
+            Some(data) => (data, std::fs::metadata(path).map(|m| m.len() as usize).unwrap_or(0), t.elapsed()),
+            None       => read_and_parse(path, config, threads)?,
+        };
+        if verbosity.shows(LogArea::Parsing, 1) {
+            log.push(format!("Parsed {} lines from {:?}{}", data.len(), path, if from_cache { " (cached)" } else { "" }));
+        }
+        if opt.paranoid {
+            check_integrity(&data).map_err(|e| format!("Error checking {:?}\n  {}", path, e)).or_fail(Failure::BadInput)?;
+        }
+        bytes += b;
+        read += r;
+        pieces.push((path, data));
+    }
+    let parse = started.elapsed() - read;
+    if let Some(dir) = &inputs.cache_to {
+        for (path, data) in pieces.iter() {
+            let file = dir.join(cache_name(path));
+            ParsedCache::of(path, &config.input, data.clone()).and_then(|c| c.encode())
+                .map_err(|e| e.to_string())
+                .and_then(|bytes| std::fs::write(&file, bytes).map_err(|e| format!("{:?}", e)))
+                .map_err(|e| format!("Error writing cache {:?}\n  {}", file, e)).or_fail(Failure::Write)?;
+            if verbosity.shows(LogArea::Io, 1) { log.push(format!("Cached parsed data as {:?}", file)); }
+        }
+    }
+    let mut data = 
+        if pieces.len() == 1 { pieces.pop().map(|p| p.1).unwrap_or_default() }
+        else {
+            if verbosity.shows(LogArea::Parsing, 1) { log.push(format!("Stitched {} fragments of worm {}", pieces.len(), d.id)); }
+            stitch_fragments(pieces.into_iter().map(|p| p.1).collect())
+                .map_err(|e| format!("Error stitching fragments of worm {} from {:?}\n  {}", d.id, d.path, e)).or_fail(Failure::BadInput)?
+        };
+    let lines = data.len();
+    if let Some(x) = offset { shift_times(x, &mut data); }
+    if let Some(q) = opt.quick { data = decimate(&data, q as usize); }
+
+    if verbosity.shows(LogArea::Scoring, 1) {
+        log.push(format!("Scored {:?}", d.path));
+        if let Some(x) = offset { log.push(format!("  times shifted by -{} s", x)); }
+    }
+    if verbosity.shows(LogArea::Scoring, 2) {
+        let area = the_area(&data);
+        let midline = the_midline(&data);
+        let precision = config.metrics.precision();
+        let speed1 = the_speed_in(0.0, 4.0, &config.speed.max, precision, &data);
+        let speed2 = the_speed_in(1.5, 3.5, &config.speed.max, precision, &data);
+        let xs = the_coord(|d| d.x, precision, &data);
+        let ys = the_coord(|d| d.y, precision, &data);
+        log.push(format!("  a  {}+-{} (n={})", area.mean(), area.error(), area.len()));
+        log.push(format!("  m  {}+-{} (n={})", midline.mean(), midline.error(), midline.len()));
+        log.push(format!("  s  {:?}", speed1));
+        log.push(format!("  s' {:?}", speed2));
+        log.push(format!("  x  {} -> {};  [{}, {}];  {:?}", xs.first, xs.last, xs.bound0, xs.bound1, xs.stats));
+        log.push(format!("  y  {} -> {};  [{}, {}];  {:?}", ys.first, ys.last, ys.bound0, ys.bound1, ys.stats));
+    }
+
+    if opt.inspect == Some(d.id) {
+        let windows = the_window_frames(&data, config);
+        match &opt.inspect_to {
+            Some(file) => {
+                std::fs::write(file, window_frames_table(&windows)).map_err(|e| format!("Error writing {:?}\n  {:?}", file, e)).or_fail(Failure::Write)?;
+                log.push(format!("Wrote frames of worm {}'s speed windows to {:?}", d.id, file));
+            },
+            None => for w in windows.iter() {
+                log.push(format!("Worm {}, {}", d.id, w));
+                for (t, s) in w.frames.iter() { log.push(format!("  {} {}", t, s)); }
+            },
+        }
+    }
+
+    let histogram = 
+        if config.histogram.edges.len() > 0 { Some(the_speed_histogram(d.id, &config.histogram.edges, &data)) }
+        else { None };
+    let mut scores = match old {
+        Some((changed, s)) => the_rescored(d.id, s, changed, &data, config),
+        None               => the_tracks(d.id, &data, config),
+    };
+    for s in scores.iter_mut() {
+        s.decimation = opt.quick;
+        if verbosity.shows(LogArea::Scoring, 1) {
+            for w in s.warnings.iter() { log.push(format!("  Worm {}: {}", s.id, w)); }
+            for q in s.qc.flags.iter() { log.push(format!("  Worm {}: QC {}", s.id, q)); }
+            for w in s.swaps.iter() { log.push(format!("  Worm {}: suspected identity swap at {} s (jump of {})", s.id, w.time, w.jump)); }
+        }
+        if verbosity.shows(LogArea::Parsing, 1) {
+            if let Some(o) = s.out_of_range.as_ref().filter(|o| o.total() > 0) { log.push(format!("  Worm {}: out of range values: {}", s.id, o)); }
+            if let Some(n) = s.duplicates { log.push(format!("  Worm {}: removed {} repeated frames", s.id, n)); }
+            if let Some(n) = s.ceiling.filter(|n| *n > 0) { log.push(format!("  Worm {}: {} speeds over the ceiling", s.id, n)); }
+        }
+        if verbosity.shows(LogArea::Scoring, 3) {
+            log.push(format!("  Worm {}: {}", s.id, s.title()));
+            log.push(format!("  Worm {}: {}", s.id, s));
+        }
+    }
+    if verbosity.shows(LogArea::Scoring, 2) { log.push(String::new()); }
+    let score = started.elapsed() - read - parse;
+    if !keeps_data(opt, config) { data = Vec::new(); }
+    Ok(FileResult{ scores, histogram, data, offset, timing: FileTiming{ bytes, lines, read, parse, score }, log })
+}
+
+// Files are handed out to worker threads as each finishes its last one, as
+// long as the memory and thread budgets allow; results come back in the
+// original order.  Any threads left over go to parsing within each file.
+fn score_files(dats: &[Dat], opt: &Opt, config: &Config, inputs: &RunInputs, threads: &ThreadBudget) -> Result<Vec<FileResult>, RunError> {
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    let workers = std::cmp::max(1, std::cmp::min(threads.size, dats.len()));
+    let parse_threads = std::cmp::max(1, threads.size / workers);
+    let next = AtomicUsize::new(0);
+    let failed = AtomicBool::new(false);
+    let budget = MemoryBudget::new(opt.max_memory);
+    let slots: Vec<std::sync::Mutex<Option<Result<FileResult, RunError>>>> = dats.iter().map(|_| std::sync::Mutex::new(None)).collect();
+
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| {
+                loop {
+                    let i = next.fetch_add(1, Ordering::SeqCst);
+                    if i >= dats.len() || failed.load(Ordering::SeqCst) { break; }
+                    let size = dats[i].files().map(|p| std::fs::metadata(p).map(|m| m.len() as usize).unwrap_or(0)).sum::<usize>();
+                    let reserved = budget.reserve(size.saturating_mul(MEMORY_PER_BYTE));
+                    let leased = threads.take(parse_threads);
+                    let result = score_file(&dats[i], opt, config, inputs, parse_threads);
+                    drop(leased);
+                    drop(reserved);
+                    if result.is_err() { failed.store(true, Ordering::SeqCst); }
+                    *slots[i].lock().unwrap() = Some(result);
+                }
+            });
+        }
+    });
+
+    let mut results = Vec::with_capacity(dats.len());
+    for slot in slots.into_iter() {
+        match slot.into_inner().unwrap() {
+            Some(Ok(r))  => results.push(r),
+            Some(Err(e)) => return Err(e),
+            None         => (),
+        }
+    }
+    Ok(results)
+}
+
+fn write_output<C: AsRef<[u8]>>(dir: &Path, name: &str, contents: C, manifest: &mut Manifest, out: &mut Output) -> Result<(), RunError> {
+    let file = dir.join(Path::new(name));
+    std::fs::write(file.clone(), contents).map_err(|e| format!("Error writing {:?}\n  {:?}", name, e)).or_fail(Failure::Write)?;
+    say!(out, "  Wrote {:?}", file);
+    manifest.outputs.push(Path::new(name).to_path_buf());
+    Ok(())
+}
+
+// Outputs and settings that cover all of a run's worms at once, so can't be
+// extended by another run's worms
+fn append_conflicts(opt: &Opt, config: &Config) -> Vec<&'static str> {
+    let uses = [
+        (opt.sample.is_some(), "--sample"), (opt.quick.is_some(), "--quick"), (opt.hdf5, "--hdf5"), (opt.panel, "--panel"),
+        (opt.missingness, "--missingness"), (opt.report, "--report"), (opt.profile, "--profile"),
+        (config.histogram.edges.len() > 0, "histograms"), (config.intervals.report && config.intervals.csv, "an intervals table"),
+        (config.alignment.enabled, "alignment"), (config.windows.quietest.is_some(), "a quietest calm window"),
+        (config.exploration.is_adaptive(), "an estimated arena"),
+    ];
+    uses.iter().filter(|(used, _)| *used).map(|(_, name)| *name).collect()
+}
+
+// The run an `--append` adds to, which must have used the same configuration
+fn earlier_run(target: &Path, config_hash: &str) -> Result<Manifest, RunError> {
+    let file = target.join("manifest.json");
+    let text = std::fs::read_to_string(&file).map_err(|e| format!("--append needs an earlier run in {:?}\n  {:?}", target, e)).or_fail(Failure::MissingSource)?;
+    let manifest: Manifest = serde_json::from_str(text.as_str()).map_err(|e| format!("Error reading {:?}\n  {}", file, e)).or_fail(Failure::BadInput)?;
+    if manifest.config_hash != config_hash {
+        return Err(format!("{:?} was scored with configuration {}, not {}; rescore it to change the configuration", target, manifest.config_hash, config_hash)).or_fail(Failure::BadArgs);
+    }
+    if manifest.quick.is_some() || manifest.sample.is_some() {
+        return Err(format!("{:?} scored only some of its data, so can't be added to", target)).or_fail(Failure::BadArgs);
+    }
+    Ok(manifest)
+}
+
+// With a previous run, its files are scored again, reusing what it can
+fn run(opt: &Opt, config: &Config, threads: &ThreadBudget, previous: Option<&Previous>, out: &mut Output) -> Result<Report, RunError> {
     let mut atomic_name = match opt.target.file_name() {
         Some(f) => f.to_string_lossy().to_string(),
-        None    => { println!("Empty or invalid target directory {:?}", opt.target); std::process::exit(1) }
+        None    => return Err(format!("Empty or invalid target directory {:?}", opt.target)).or_fail(Failure::BadArgs),
     };
     atomic_name.push_str(".atomic");
     let atomic_target = opt.target.with_file_name(&atomic_name);
 
-    if   !opt.source.exists() { println!("Source directory {:?} does not exist", opt.source ); std::process::exit(1); }
-    if    opt.target.exists() { println!("Target directory {:?} exists already", opt.target ); std::process::exit(1); }
-    if atomic_target.exists() { println!("Temp directory {:?} exists already", atomic_target); std::process::exit(1); }
+    for w in config.windows.warnings().iter() { say!(out, "Warning: {}", w); }
+
+    let run_date = utc_date(std::time::SystemTime::now());
+    let config_hash = config.hash();
+    let output_name = |stem: &str, ext: &str| fill_template(
+        opt.output_template.as_str(),
+        &[("prefix", stem), ("date", run_date.as_str()), ("version", VERSION), ("config", config_hash.as_str()), ("ext", ext)]
+    ).map_err(|e| e.to_string()).or_fail(Failure::BadArgs);
+    output_name("", "")?;
+    let bad_args = |message: &str| Err(message.to_string()).or_fail(Failure::BadArgs);
+    if !opt.output_template.contains("{ext}") { return bad_args("Output template must contain {ext}"); }
+
+    if opt.hdf5 && !cfg!(feature = "hdf5") { return bad_args("--hdf5 needs metrology built with the hdf5 feature"); }
+    if keeps_data(opt, config) && opt.max_memory.is_some() { say!(out, "Warning: raw data kept after scoring is not counted against --max-memory"); }
+    let titles = Scores::titles_for(config, "").join(" ");
+    if opt.columns.len() > 0 { column_indices(titles.as_str(), &opt.columns).map_err(|e| e.to_string()).or_fail(Failure::BadArgs)?; }
+    if opt.quick == Some(0) { return bad_args("--quick needs a decimation factor of at least 1"); }
+    if opt.inspect_to.is_some() && opt.inspect.is_none() { return bad_args("--inspect-to needs --inspect to choose a worm"); }
+    if opt.append {
+        let conflicts = append_conflicts(opt, config);
+        if conflicts.len() > 0 { return bad_args(format!("--append can't add to {}, which cover all of a run's worms", conflicts.join(", ")).as_str()); }
+        if previous.is_some() { return bad_args("--append can't be used when rescoring"); }
+    }
+    let offsets = match (previous, &opt.time_offsets) {
+        (Some(p), _)    => p.offsets.clone(),
+        (None, Some(f)) => TimeOffsets::load(f).map_err(|e| format!("Error reading time offsets {:?}\n  {}", f, e)).or_fail(Failure::BadArgs)?,
+        (None, None)    => TimeOffsets::default(),
+    };
+    if let Some(q) = opt.quick { say!(out, "QUICK LOOK: using only every {}th line; results are approximate", q); }
+
+    if   !opt.source.exists() { return Err(format!("Source directory {:?} does not exist", opt.source)).or_fail(Failure::MissingSource); }
+    let lock = match RunLock::acquire(&opt.target, STALE_LOCK) {
+        Ok(lock)                           => lock,
+        Err(e @ MetrologyError::Locked{..}) => return Err(e.to_string()).or_fail(Failure::Locked),
+        Err(e)                             => return Err(format!("Could not lock {:?}\n  {}", opt.target, e)).or_fail(Failure::Write),
+    };
+    if lock.recovered {
+        say!(out, "Warning: took over the lock on {:?} from a run that stopped", opt.target);
+        if atomic_target.exists() {
+            std::fs::remove_dir_all(&atomic_target).map_err(|e| format!("Error removing {:?}\n{:?}", atomic_target, e)).or_fail(Failure::Write)?;
+            say!(out, "  Removed its temp directory {:?}", atomic_target);
+        }
+    }
+    if opt.target.exists() && !opt.overwrite && !opt.backup && !opt.append {
+        return bad_args(format!("Target directory {:?} exists already; use --overwrite or --backup to replace it, or --append to add to it", opt.target).as_str());
+    }
+    if atomic_target.exists() { return bad_args(format!("Temp directory {:?} exists already", atomic_target).as_str()); }
+    let earlier = if opt.append { Some(earlier_run(&opt.target, config_hash.as_str())?) } else { None };
 
-    match std::fs::create_dir_all(atomic_target.clone()) {
-        Err(e) => { println!("Error creating {:?}\n{:?}", atomic_target, e); std::process::exit(1); },
-        _ => ()
+    std::fs::create_dir_all(atomic_target.clone()).map_err(|e| format!("Error creating {:?}\n{:?}", atomic_target, e)).or_fail(Failure::Write)?;
+    // The earlier run's outputs are kept, and those that cover every worm are rewritten
+    if earlier.is_some() {
+        copy_tree(&opt.target, &atomic_target).map_err(|e| format!("Error copying {:?}\n{:?}", opt.target, e)).or_fail(Failure::Write)?;
     }
 
-    let mut dats = get_dats(opt.source.clone()).expect("Can't read directory");
+    let mut dats = match previous {
+        Some(p) => p.manifest.files.iter().map(|f| f.clone().try_into()).collect::<std::io::Result<Vec<Dat>>>(),
+        None    => get_dats(opt.source.clone(), &TrackFormats::new(&config.input, config.metrics.precision(), threads.size)),
+    }.map_err(|e| format!("Can't read directory {:?}\n  {:?}", opt.source, e)).or_fail(Failure::MissingSource)?;
+    // Worms are scored, and written, by prefix and then id, whichever thread
+    // finishes first, so that reruns give identical files
     dats.sort();
 
     let mut counts: BTreeMap<String, u32> = BTreeMap::new();
@@ -108,80 +1429,312 @@ fn main() {
             n = *v;
         }
     }
+    if let Some(e) = &earlier { key = e.prefix.clone(); }
 
-    let mut rows: Vec<Scores> = Vec::new();
+    let mut manifest = Manifest::new(opt.source.clone(), key.clone(), config.clone());
+    manifest.quick = opt.quick;
+    if let Some(p) = previous {
+        manifest.sample = p.manifest.sample.clone();
+        manifest.rescored_from = Some(p.target.clone());
+    }
+    if let Some(e) = &earlier {
+        manifest.files = e.files.clone();
+        manifest.outputs = e.outputs.clone();
+        manifest.time_offsets = e.time_offsets.clone();
+        // Files are matched by name, as the source may be given another way this time
+        let scored: Vec<&std::ffi::OsStr> = e.files.iter().filter_map(|f| f.file_name()).collect();
+        let before = dats.len();
+        dats.retain(|d| !d.files().all(|f| f.file_name().map(|n| scored.contains(&n)).unwrap_or(false)));
+        say!(out, "{} of {} files were scored already by the run in {:?}", before - dats.len(), before, opt.target);
+    }
 
-    for d in dats {
-        if opt.verbose { println!("Found {:?}", d); }
-        if key == d.prefix {
-            let mut f = std::fs::File::open(d.path.clone()).unwrap();
-            let mut v: Vec<u8> = Vec::new();
-            match f.read_to_end(&mut v) {
-                Err(e) => { println!("Error reading {:?}\n  {:?}", d.path, e); std::process::exit(1); }
-                _      => ()
-            }
-            let data = match get_data_lines(v.as_slice()) {
-                Ok(y)  => y.1,
-                Err(e) => { println!("Error parsing {:?}\n  {:?}", d.path, e); std::process::exit(1) },
-            };
-            let area = the_area(&data);
-            let midline = the_midline(&data);
-            let speed1 = the_speed_in(0.0, 4.0, &data);
-            let speed2 = the_speed_in(1.5, 3.5, &data);
-            let xs = the_coord(|d| d.x, &data);
-            let ys = the_coord(|d| d.y, &data);
-            if opt.verbose {
-                println!("  a  {}+-{} (n={})", area.mean(), area.error(), area.len());
-                println!("  m  {}+-{} (n={})", midline.mean(), midline.error(), midline.len());
-                println!("  s  {:?}", speed1);
-                println!("  s' {:?}", speed2);
-                println!("  x  {} -> {};  [{}, {}];  {:?}", xs.first, xs.last, xs.bound0, xs.bound1, xs.stats);
-                println!("  y  {} -> {};  [{}, {}];  {:?}", ys.first, ys.last, ys.bound0, ys.bound1, ys.stats);
-                println!();
-            }
+    let verbosity = opt.verbosity();
+    if verbosity.shows(LogArea::Io, 2) { for d in dats.iter() { say!(out, "Found {:?}", d); } }
+    let mut skipped: Vec<SkippedFile> = dats.iter()
+        .filter(|d| d.prefix != key)
+        .map(|d| SkippedFile{ path: d.path.clone(), reason: format!("prefix {} is not the most common one, {}", d.prefix, key) })
+        .collect();
+    dats.retain(|d| d.prefix == key);
+    let unstitched = skipped.len();
+    let mut dats = stitched(dats, config.fragments.stitch, &mut skipped);
+    if skipped.len() > unstitched {
+        say!(out, "Warning: skipped {} files that are fragments of tracks; set fragments.stitch to join them", skipped.len() - unstitched);
+    }
+    if let Some(k) = opt.inspect.filter(|k| !dats.iter().any(|d| d.id == *k)) { say!(out, "Warning: there is no worm {} to inspect", k); }
+    if let Some(k) = opt.sample {
+        let seed = opt.seed.unwrap_or_else(|| Rng::from_time().next_u64());
+        let chosen = Rng::new(seed).choose_indices(dats.len(), k);
+        let available = dats.len();
+        for (i, d) in dats.iter().enumerate() {
+            if chosen.binary_search(&i).is_err() { skipped.push(SkippedFile{ path: d.path.clone(), reason: "not sampled".to_string() }); }
+        }
+        let mut i = 0;
+        dats.retain(|_| { i += 1; chosen.binary_search(&(i-1)).is_ok() });
+        say!(out, "Sampled {} of {} worms with seed {}", dats.len(), available, seed);
+        manifest.sample = Some(Subsample{ seed, requested: k, available, selected: dats.iter().map(|d| d.id).collect() });
+    }
 
-            rows.push(the_everything(d.id, &data));
+    let mut rows: Vec<Scores> = Vec::new();
+    let mut histograms: Vec<SpeedHistogram> = Vec::new();
+    let mut raw: Vec<(&Dat, Vec<DataLine>, std::ops::Range<usize>)> = Vec::new();
+    let mut timings: Vec<FileTiming> = Vec::new();
+    let started = std::time::Instant::now();
+    if opt.cache {
+        let dir = atomic_target.join("cache");
+        std::fs::create_dir_all(&dir).map_err(|e| format!("Error creating {:?}\n{:?}", dir, e)).or_fail(Failure::Write)?;
+    }
+    // A rescore uses the earlier run's cache if it has one
+    let cache_from = opt.from_cache.clone().or_else(|| previous.map(|p| p.target.clone()).filter(|t| t.join("cache").is_dir()));
+    let inputs = RunInputs{ offsets, previous, cache_from, cache_to: if opt.cache { Some(atomic_target.clone()) } else { None } };
+    let results = score_files(&dats, opt, config, &inputs, threads)?;
+    let elapsed = started.elapsed();
+    for (d, mut result) in dats.iter().zip(results.into_iter()) {
+        sort_scores(&mut result.scores);
+        for line in result.log.iter() { say!(out, "{}", line); }
+        timings.push(result.timing);
+        manifest.files.extend(d.files().cloned());
+        if let Some(x) = result.offset { manifest.time_offsets.insert(d.path.clone(), x); }
+        if opt.cache { manifest.outputs.extend(d.files().map(|p| cache_name(p))); }
+        if let Some(h) = result.histogram { histograms.push(h); }
+        if keeps_data(opt, config) { raw.push((d, result.data, rows.len() .. rows.len() + result.scores.len())); }
+        rows.extend(result.scores);
+    }
+    if config.alignment.enabled {
+        let prepared: Vec<Vec<DataLine>> = raw.iter().map(|(_, data, _)| the_prepared(data, config).lines).collect();
+        let worms: Vec<&[DataLine]> = prepared.iter().map(|p| p.as_slice()).collect();
+        let offsets = the_alignment_offsets(&config.alignment, config.metrics.precision(), &worms);
+        for ((d, data, r), x) in raw.iter_mut().zip(offsets.into_iter()) {
+            let x = match x { Some(x) if x != 0.0 => x, _ => continue };
+            shift_times(x, data);
+            let mut rescored = the_tracks(d.id, data, config);
+            if rescored.len() != r.len() {
+                shift_times(-x, data);
+                say!(out, "Warning: worm {} gives {} tracks instead of {} once shifted by {} s, so it was left unaligned", d.id, rescored.len(), r.len(), x);
+                continue;
+            }
+            // Scratch scores lack what was set per file when it was first scored
+            for (s, old) in rescored.iter_mut().zip(rows[r.clone()].iter()) { s.decimation = old.decimation; }
+            rows.splice(r.clone(), rescored);
+            manifest.alignment_offsets.insert(d.path.clone(), x);
+        }
+        say!(out, "Aligned {} of {} worms by cross-correlation", manifest.alignment_offsets.len(), raw.len());
+    }
+    if let Some(q) = &config.windows.quietest {
+        let worms: Vec<Dataset> = raw.iter().map(|(_, data, _)| Dataset::new(the_prepared(data, config).lines)).collect();
+        manifest.calm_window = the_quietest_window(q, &worms);
+        match manifest.calm_window {
+            Some(w) => {
+                say!(out, "Quietest calm window is {} to {}", w.start, w.end);
+                let mut chosen = config.clone();
+                chosen.windows.set("calm", w);
+                let changed = ["windows".to_string()];
+                for (d, data, r) in raw.iter() {
+                    let rescored = the_rescored(d.id, &rows[r.clone()], &changed, data, &chosen);
+                    if rescored.len() == r.len() { rows.splice(r.clone(), rescored); }
+                }
+            },
+            None => say!(out, "Warning: no speeds in any quiet window candidate, so the configured calm window was used"),
         }
     }
 
-    println!("Analyzed {} files from {:?}", rows.len(), opt.source);
+    if config.exploration.is_adaptive() {
+        manifest.estimated_arena = estimate_arena(&rows, config.exploration.arena_trim, config.metrics.precision());
+        match manifest.estimated_arena {
+            Some(a) => {
+                say!(out, "Estimated arena x {} to {}, y {} to {}", a.x0, a.x1, a.y0, a.y1);
+                let mut spatial = config.clone();
+                spatial.exploration.arena = Some(a);
+                let changed = ["exploration".to_string()];
+                for (d, data, r) in raw.iter() {
+                    let rescored = the_rescored(d.id, &rows[r.clone()], &changed, data, &spatial);
+                    if rescored.len() == r.len() { rows.splice(r.clone(), rescored); }
+                }
+            },
+            None => say!(out, "Warning: could not estimate the arena, so each worm's own bounds were used"),
+        }
+    }
+    flag_lost_at_edge(&config.qc, config.exploration.arena.or(manifest.estimated_arena), &mut rows);
+    // Rows rescored with a chosen window or arena still belong to this configuration
+    let metadata = config.metadata.metadata_of(key.as_str());
+    for r in rows.iter_mut() {
+        r.metadata = metadata.clone();
+        r.config_hash = Some(config_hash.clone());
+        r.environment = manifest.environment.clone();
+    }
 
-    let mut jsonname = key.clone();
-    jsonname.push_str(".scores");
-    let scores_file = atomic_target.join(Path::new(&jsonname));
-    match std::fs::write(scores_file.clone(), serde_json::to_string(&rows).unwrap().as_str()) {
-        Err(e) => { println!("Error writing {:?}\n  {:?}", jsonname, e); std::process::exit(1); },
-        _      => { println!("  Wrote {:?}", scores_file); }
+    say!(out, "Analyzed {} files from {:?}", manifest.files.len(), opt.source);
+    let warned = rows.iter().filter(|r| r.warnings.len() > 0).count();
+    if warned > 0 { say!(out, "  {} worms have windows that could not be scored; see warnings in scores", warned); }
+    let flagged = rows.iter().filter(|r| r.qc.flags.len() > 0).count();
+    if flagged > 0 { say!(out, "  {} tracks failed QC limits; see qc in scores", flagged); }
+    let ranged = rows.iter().filter(|r| r.out_of_range.as_ref().map(|o| o.total() > 0).unwrap_or(false)).count();
+    if ranged > 0 { say!(out, "  {} tracks had values outside validity ranges; see out_of_range in scores", ranged); }
+    let repeated: u64 = rows.iter().filter_map(|r| r.duplicates).sum();
+    if repeated > 0 { say!(out, "  Removed {} repeated frames; see duplicates in scores", repeated); }
+    let capped = rows.iter().filter(|r| r.ceiling.map(|n| n > 0).unwrap_or(false)).count();
+    if capped > 0 { say!(out, "  {} tracks had speeds over the ceiling; see ceiling in scores", capped); }
+    let lost = rows.iter().filter(|r| r.qc.lost_at_edge).count();
+    if lost > 0 { 
+        say!(out, "  {} tracks were lost at the arena edge{}", lost, if config.qc.exclude_lost_at_edge { " and are left out of the table" } else { "" });
     }
+    if opt.profile {
+        let bytes: usize = timings.iter().map(|t| t.bytes).sum();
+        say!(out, "  Processed {:.1} MB in {:.2} s: {:.1} MB/s (threads: {})",
+            (bytes as f64) / 1e6, elapsed.as_secs_f64(), (bytes as f64) / 1e6 / elapsed.as_secs_f64(), threads.size
+        );
+        let slowest = dats.iter().zip(timings.iter()).max_by(|a, b| (a.1.read + a.1.parse + a.1.score).cmp(&(b.1.read + b.1.parse + b.1.score)));
+        if let Some((d, t)) = slowest {
+            say!(out, "  Slowest file {:?} took {:.3} s", d.path, (t.read + t.parse + t.score).as_secs_f64());
+        }
+    }
+    let swapped = rows.iter().filter(|r| r.swaps.len() > 0).count();
+    if swapped > 0 { say!(out, "  {} tracks have suspected identity swaps; see swaps in scores", swapped); }
+
+    let mut stem = key.clone();
+    if let Some(q) = opt.quick { stem.push_str(format!(".quick{}", q).as_str()); }
 
-    if rows.len() > 0 {
-        let mut csvname = key.clone();
-        csvname.push_str(".csv");
-        let csv_file = atomic_target.join(Path::new(&csvname));
-        let mut csv = String::new();
-        let mut first = true;
-        for score in rows { 
-            if first {
-                csv.push_str(score.title().as_str());
-                csv.push('\n');
-                first = false;
+    let scoresname = output_name(stem.as_str(), opt.format.ext())?;
+    let csvname = output_name(stem.as_str(), "csv")?;
+    if let Some(e) = &earlier {
+        for name in [&scoresname, &csvname].iter() {
+            if !e.outputs.iter().any(|o| o == Path::new(name)) {
+                return Err(format!("{:?} has no {}; append with the output format and template it was written with", opt.target, name)).or_fail(Failure::BadArgs);
             }
-            csv.push_str(score.to_string().as_str());
-            csv.push('\n');
         }
-        match std::fs::write(csv_file.clone(), csv.as_str()) {
-            Err(e) => { println!("Error writing {:?}\n  {:?}", csvname, e); std::process::exit(1); },
-            _      => { println!("  Wrote {:?}", csv_file); }
+    }
+    let bytes = match &earlier {
+        None    => opt.format.encode(&rows),
+        Some(_) => {
+            let file = opt.target.join(&scoresname);
+            let mut all = read_scores_file(&file).map_err(|e| format!("Error reading {:?}\n  {}", file, e)).or_fail(Failure::BadInput)?;
+            say!(out, "  Appending {} worms to the {} in {:?}", rows.len(), all.len(), file);
+            all.extend(rows.iter().cloned());
+            sort_scores(&mut all);
+            opt.format.encode(&all)
+        },
+    }.map_err(|e| format!("Error encoding {:?}\n  {}", scoresname, e)).or_fail(Failure::Other)?;
+    write_output(&atomic_target, &scoresname, bytes, &mut manifest, out)?;
+
+    let tabled: Vec<Scores> = rows.iter().filter(|r| !(config.qc.exclude_lost_at_edge && r.qc.lost_at_edge)).cloned().collect();
+    let mut csv = table_with(titles.as_str(), &tabled).map_err(|e| format!("Error formatting {:?}\n  {}", csvname, e)).or_fail(Failure::Other)?;
+    if opt.columns.len() > 0 { csv = select_columns(csv.as_str(), &opt.columns).map_err(|e| e.to_string()).or_fail(Failure::Other)?; }
+    let mut csv = with_config_comment(with_missing(csv.as_str(), opt.missing.as_str()).as_str(), config_hash.as_str());
+    if earlier.is_some() {
+        let file = atomic_target.join(&csvname);
+        let existing = std::fs::read_to_string(&file).map_err(|e| format!("Error reading {:?}\n  {:?}", file, e)).or_fail(Failure::BadInput)?;
+        csv = append_table(existing.as_str(), csv.as_str(), opt.missing.as_str()).map_err(|e| format!("Can't append to {:?}\n  {}", file, e)).or_fail(Failure::BadInput)?;
+    }
+    write_output(&atomic_target, &csvname, csv, &mut manifest, out)?;
+
+    if histograms.len() > 0 {
+        let histname = output_name(stem.as_str(), "histograms")?;
+        write_output(&atomic_target, &histname, serde_json::to_string(&histograms).unwrap().as_str(), &mut manifest, out)?;
+
+        let mut long = SpeedHistogram::long_title();
+        long.push('\n');
+        for h in histograms.iter() { h.push_long_rows(&mut long); }
+        let longname = output_name(stem.as_str(), "histograms.csv")?;
+        write_output(&atomic_target, &longname, with_config_comment(with_missing(long.as_str(), opt.missing.as_str()).as_str(), config_hash.as_str()), &mut manifest, out)?;
+    }
+
+    if config.intervals.report && config.intervals.csv {
+        let mut long = intervals_long_title();
+        long.push('\n');
+        for r in rows.iter() { push_interval_rows(r, &mut long); }
+        let longname = output_name(stem.as_str(), "intervals.csv")?;
+        write_output(&atomic_target, &longname, with_config_comment(with_missing(long.as_str(), opt.missing.as_str()).as_str(), config_hash.as_str()), &mut manifest, out)?;
+    }
+
+    #[cfg(feature = "hdf5")]
+    {
+        if opt.hdf5 {
+            let h5name = output_name(stem.as_str(), "h5")?;
+            let worms: Vec<WormRecord> = raw.iter().map(|(d, data, r)| WormRecord{ id: d.id, data, scores: &rows[r.clone()] }).collect();
+            write_hdf5(&atomic_target.join(&h5name), &worms).map_err(|e| format!("Error writing {:?}\n  {}", h5name, e)).or_fail(Failure::Write)?;
+            say!(out, "  Wrote {:?}", atomic_target.join(&h5name));
+            manifest.outputs.push(PathBuf::from(h5name));
+        }
+    }
+
+    if opt.wcon {
+        let dir = atomic_target.join("wcon");
+        std::fs::create_dir_all(&dir).map_err(|e| format!("Error creating {:?}\n{:?}", dir, e)).or_fail(Failure::Write)?;
+        for (d, data, r) in raw.iter() {
+            let stem = d.path.file_stem().map(|f| f.to_string_lossy().to_string()).unwrap_or_default();
+            let wcon = the_wcon(d.id, data, &rows[r.clone()], config_hash.as_str());
+            write_output(&atomic_target, format!("wcon/{}.wcon", stem).as_str(), wcon.to_string(), &mut manifest, out)?;
+        }
+    }
+
+    if opt.export_speed_series {
+        let dir = atomic_target.join("speed");
+        std::fs::create_dir_all(&dir).map_err(|e| format!("Error creating {:?}\n{:?}", dir, e)).or_fail(Failure::Write)?;
+        for (d, data, _) in raw.iter() {
+            let stem = d.path.file_stem().map(|f| f.to_string_lossy().to_string()).unwrap_or_default();
+            let series = speed_series_table(&the_prepared(data, config).lines);
+            let text = with_config_comment(with_missing(series.as_str(), opt.missing.as_str()).as_str(), config_hash.as_str());
+            write_output(&atomic_target, format!("speed/{}.csv", stem).as_str(), text, &mut manifest, out)?;
         }
     }
 
-    match std::fs::rename(atomic_target.clone(), opt.target.clone()) {
-        Err(e) => { 
-            println!("Could not move temp {:?}", atomic_target);
-            println!("                 to {:?}", opt.target);
-            println!("             error: {:?}", e);
-            std::process::exit(1);
+    if opt.panel {
+        let mut panel = Vec::with_capacity(rows.len());
+        for (_, data, r) in raw.iter() {
+            let lines = the_prepared(data, config).lines;
+            let kept = rows[r.clone()].iter().filter(|s| !(config.qc.exclude_lost_at_edge && s.qc.lost_at_edge));
+            panel.extend(kept.map(|s| the_panel_row(s, &lines, config)));
         }
-        _      => ()
+        let name = output_name(stem.as_str(), "panel.csv")?;
+        let table = panel_table(&panel).map_err(|e| format!("Error formatting {:?}\n  {}", name, e)).or_fail(Failure::Other)?;
+        write_output(&atomic_target, &name, with_config_comment(with_missing(table.as_str(), opt.missing.as_str()).as_str(), config_hash.as_str()), &mut manifest, out)?;
     }
+
+    if opt.missingness {
+        let name = output_name(stem.as_str(), "missingness.csv")?;
+        write_output(&atomic_target, &name, with_config_comment(missingness_table(&rows).as_str(), config_hash.as_str()), &mut manifest, out)?;
+    }
+
+    if opt.report {
+        let info = ReportInfo{
+            version: VERSION.to_string(), source: opt.source.clone(), prefix: key.clone(), config_hash: config_hash.clone(),
+            files: manifest.files.len(), skipped, precision: config.metrics.precision()
+        };
+        let reportname = output_name(stem.as_str(), "html")?;
+        write_output(&atomic_target, &reportname, html_report(&info, &rows), &mut manifest, out)?;
+    }
+
+    if opt.profile { write_output(&atomic_target, "profile.csv", with_missing(profile_table(&dats, &timings).as_str(), opt.missing.as_str()), &mut manifest, out)?; }
+    if earlier.is_some() {
+        let mut seen = std::collections::BTreeSet::new();
+        manifest.outputs.retain(|o| seen.insert(o.clone()));
+    }
+
+    let manifest_file = atomic_target.join("manifest.json");
+    std::fs::write(manifest_file.clone(), serde_json::to_string_pretty(&manifest).unwrap().as_str())
+        .map_err(|e| format!("Error writing {:?}\n  {:?}", manifest_file, e)).or_fail(Failure::Write)?;
+    say!(out, "  Wrote {:?}", manifest_file);
+
+    // An existing target is moved aside only now, so a failed run leaves it as it was
+    let aside = match opt.target.exists() {
+        true  => Some(
+            move_aside(&opt.target, if opt.backup { utc_timestamp(std::time::SystemTime::now()) } else { "replaced".to_string() }.as_str())
+                .map_err(|e| format!("Could not move existing target {:?} aside\n  {}", opt.target, e)).or_fail(Failure::Write)?
+        ),
+        false => None,
+    };
+    publish_atomic(&atomic_target, &opt.target).map_err(|e| {
+        if let Some(a) = &aside { let _ = std::fs::rename(a, &opt.target); }
+        format!("Could not move temp {:?}\n                 to {:?}\n             error: {}", atomic_target, opt.target, e)
+    }).or_fail(Failure::Write)?;
+    if let Some(a) = aside {
+        if opt.backup { say!(out, "  Previous target kept as {:?}", a); }
+        else {
+            std::fs::remove_dir_all(&a).map_err(|e| format!("Error removing replaced target {:?}\n{:?}", a, e)).or_fail(Failure::Write)?;
+            say!(out, "  Replaced previous target {:?}", opt.target);
+        }
+    }
+
+    let mut outputs: Vec<PathBuf> = manifest.outputs.iter().map(|o| opt.target.join(o)).collect();
+    outputs.push(opt.target.join("manifest.json"));
+    Ok(Report{ files: manifest.files.len(), rows: rows.len(), warned, flagged, outputs })
 }
@@ -0,0 +1,89 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use serde::{Serialize, Deserialize};
+
+use crate::*;
+
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Subsample {
+    pub seed: u64,
+    pub requested: usize,
+    pub available: usize,
+    pub selected: Vec<WormId>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    pub version: String,
+    pub source: PathBuf,
+    pub prefix: String,
+    pub files: Vec<PathBuf>,
+    pub outputs: Vec<PathBuf>,
+
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub quick: Option<u32>,
+
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub sample: Option<Subsample>,
+
+    #[serde(default)]
+    pub full_precision: bool,
+
+    // The earlier run these scores were brought up to date from
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub rescored_from: Option<PathBuf>,
+
+    // Offsets subtracted from each file's times
+    #[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
+    pub time_offsets: BTreeMap<PathBuf, f64>,
+
+    // Offsets found by cross-correlating speeds, subtracted after `time_offsets`
+    #[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
+    pub alignment_offsets: BTreeMap<PathBuf, f64>,
+
+    // Arena estimated from the plate, when the configuration asks for one
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub estimated_arena: Option<Arena>,
+
+    // Calm window chosen from the data, when the configuration asks for one
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub calm_window: Option<Window>,
+
+    // `Config::hash` of `config`
+    #[serde(default)]
+    pub config_hash: String,
+
+    // The build and machine of the run; absent from manifests written before it was recorded
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub environment: Option<Environment>,
+
+    pub config: Config,
+}
+
+impl Manifest {
+    pub fn new(source: PathBuf, prefix: String, config: Config) -> Manifest {
+        Manifest{ 
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            source, prefix,
+            files: Vec::new(),
+            outputs: Vec::new(),
+            quick: None,
+            sample: None,
+            full_precision: config.metrics.full_precision,
+            rescored_from: None,
+            time_offsets: BTreeMap::new(),
+            alignment_offsets: BTreeMap::new(),
+            estimated_arena: None,
+            calm_window: None,
+            config_hash: config.hash(),
+            environment: Some(Environment::capture()),
+            config
+        }
+    }
+}
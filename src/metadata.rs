@@ -0,0 +1,59 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use std::collections::BTreeMap;
+
+use regex::Regex;
+use serde::{Serialize, Deserialize};
+
+
+// Recording details encoded in file names.  Each named capture of `pattern`,
+// matched against the prefix (such as `20220131_rig3_plateA`), becomes a
+// column; a capture that doesn't match is missing.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MetadataSettings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+}
+
+impl MetadataSettings {
+    fn regex(&self) -> Option<Regex> { self.pattern.as_ref().and_then(|p| Regex::new(p).ok()) }
+
+    pub fn errors(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if let Some(p) = &self.pattern {
+            match Regex::new(p) {
+                Err(e) => errors.push(format!("metadata pattern {:?} is not a regular expression: {}", p, e)),
+                Ok(r) if r.capture_names().flatten().next().is_none() => errors.push(format!("metadata pattern {:?} has no named captures like (?P<plate>...)", p)),
+                _ => {},
+            }
+        }
+        errors
+    }
+
+    // Column names, in the order they are written
+    pub fn fields(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.regex().iter().flat_map(|r| r.capture_names().flatten().map(|s| s.to_string()).collect::<Vec<_>>()).collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    pub fn metadata_of(&self, prefix: &str) -> BTreeMap<String, Option<String>> {
+        let mut values: BTreeMap<String, Option<String>> = self.fields().into_iter().map(|k| (k, None)).collect();
+        if let Some(c) = self.regex().and_then(|r| r.captures(prefix)) {
+            for (k, v) in values.iter_mut() { *v = c.name(k).map(|m| m.as_str().to_string()).filter(|s| !s.is_empty()); }
+        }
+        values
+    }
+}
+
+// Values go in a space-separated table, so may not contain whitespace
+pub fn metadata_cell(value: &Option<String>) -> String {
+    match value {
+        Some(v) => v.split_whitespace().collect::<Vec<_>>().join("_"),
+        None    => "NaN".to_string(),
+    }
+}
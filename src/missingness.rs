@@ -0,0 +1,91 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use crate::*;
+
+
+// Whether each metric could be computed for a worm: `ok`, or a one-word reason
+fn cells(s: &Scores) -> Vec<(String, Option<String>)> {
+    let finite = |x: f64| if x.is_finite() { None } else { Some("no-data".to_string()) };
+    let optional = |x: Option<f64>| x.map(finite);
+    let mut cells: Vec<(String, Option<Option<String>>)> = vec![
+        ("area".to_string(), Some(finite(s.area.stats().mean))),
+        ("midline".to_string(), Some(finite(s.midline.stats().mean))),
+        ("area-rate".to_string(), optional(s.shape_rates.as_ref().map(|r| r.area.mean))),
+        ("midline-rate".to_string(), optional(s.shape_rates.as_ref().map(|r| r.midline.mean))),
+        ("shape".to_string(), optional(s.shape_index.as_ref().map(|x| x.mean))),
+    ];
+    for (name, speed) in s.speeds.iter() {
+        let why = s.warnings.iter().find(|w| w.window == *name).map(|w| match w.problem {
+            WindowProblem::Empty              => "empty",
+            WindowProblem::BeforeRecording{..} => "starts-before-recording",
+            WindowProblem::AfterRecording{..}  => "ends-after-recording",
+            WindowProblem::TooFewSamples{..}   => "too-few-speeds",
+        });
+        let cell = match (speed, why) {
+            (Some(sp), _)  => finite(sp.stats.mean),
+            (None, Some(w)) => Some(w.to_string()),
+            (None, None)    => Some("no-data".to_string()),
+        };
+        cells.push((name.to_string(), Some(cell)));
+    }
+    let arousal = match &s.arousal {
+        Some(a) => if a.t.is_finite() { None } else { Some("too-few-speeds".to_string()) },
+        None    => Some("needs-calm-and-aroused".to_string()),
+    };
+    cells.push(("arousal".to_string(), Some(arousal)));
+    cells.push(("x".to_string(), Some(finite(s.x.stats.mean))));
+    cells.push(("y".to_string(), Some(finite(s.y.stats.mean))));
+    cells.push(("spread".to_string(), optional(s.spread.as_ref().map(|x| x.gyration))));
+    cells.push(("exploration".to_string(), optional(s.exploration.as_ref().map(|e| e.fraction))));
+    cells.push(("composite".to_string(), optional(s.composite)));
+    cells.push(("edge-fraction".to_string(), optional(s.edge_fraction)));
+    // A window's metrics are those it was configured with, whether or not they have values
+    for w in s.windowed.iter() {
+        let sub = |m: &str| format!("{}-{}", w.name, m);
+        let value = |x: Option<f64>| Some(finite(x.unwrap_or(std::f64::NAN)));
+        for m in w.metrics.iter() {
+            match m {
+                WindowMetric::Area     => cells.push((sub("area"), value(w.area.as_ref().map(|a| a.mean)))),
+                WindowMetric::Midline  => cells.push((sub("midline"), value(w.midline.as_ref().map(|a| a.mean)))),
+                WindowMetric::Coord    => {
+                    cells.push((sub("x"), value(w.x.as_ref().map(|c| c.stats.mean))));
+                    cells.push((sub("y"), value(w.y.as_ref().map(|c| c.stats.mean))));
+                },
+                WindowMetric::Speed    => cells.push((sub("speed"), value(w.speed.as_ref().map(|sp| sp.stats.mean)))),
+                WindowMetric::Activity => cells.push((sub("activity"), value(w.activity))),
+            }
+        }
+    }
+    for (name, x) in s.extras.iter() { cells.push((name.clone(), Some(finite(*x)))); }
+    cells.into_iter().filter_map(|(name, c)| c.map(|c| (name, c))).collect()
+}
+
+// One row per worm and one column per metric that any worm has.  A metric a
+// worm lacks entirely (an optional one that wasn't computed) is `no-data`.
+pub fn missingness_table(rows: &[Scores]) -> String {
+    let all: Vec<Vec<(String, Option<String>)>> = rows.iter().map(cells).collect();
+    let mut names: Vec<String> = Vec::new();
+    for c in all.iter() {
+        for (name, _) in c.iter() { if !names.contains(name) { names.push(name.clone()); } }
+    }
+    let mut table = format!("id {}\n", names.join(" "));
+    for (s, c) in rows.iter().zip(all.iter()) {
+        match s.track {
+            Some(k) => table.push_str(format!("{}.{}", s.id, k).as_str()),
+            None    => table.push_str(s.id.to_string().as_str()),
+        }
+        for name in names.iter() {
+            let cell = match c.iter().find(|(n, _)| n == name) {
+                Some((_, None))    => "ok",
+                Some((_, Some(r))) => r.as_str(),
+                None               => "no-data",
+            };
+            table.push(' ');
+            table.push_str(cell);
+        }
+        table.push('\n');
+    }
+    table
+}
@@ -0,0 +1,53 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::MetrologyError;
+
+
+pub const DEFAULT_OUTPUT_TEMPLATE: &str = "{prefix}.{ext}";
+
+// Civil date (UTC) as YYYYMMDD, after Howard Hinnant's days-to-civil algorithm
+pub fn utc_date(t: SystemTime) -> String {
+    let days = t.duration_since(UNIX_EPOCH).map(|d| d.as_secs() / 86400).unwrap_or(0) as i64;
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe/1460 + doe/36524 - doe/146096) / 365;
+    let doy = doe - (365*yoe + yoe/4 - yoe/100);
+    let mp = (5*doy + 2)/153;
+    let d = doy - (153*mp + 2)/5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + if m <= 2 { 1 } else { 0 };
+    format!("{:04}{:02}{:02}", y, m, d)
+}
+
+// UTC date and time as YYYYMMDD-HHMMSS
+pub fn utc_timestamp(t: SystemTime) -> String {
+    let s = t.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0) % 86400;
+    format!("{}-{:02}{:02}{:02}", utc_date(t), s / 3600, (s / 60) % 60, s % 60)
+}
+
+// Replaces each `{name}` in the template with its value.  Unknown names and
+// unbalanced braces are errors so that typos don't silently produce odd files.
+pub fn fill_template(template: &str, values: &[(&str, &str)]) -> Result<String, MetrologyError> {
+    let bad = |msg: String| MetrologyError::BadConfig(format!("output template {:?}: {}", template, msg));
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(i) = rest.find(|c| c == '{' || c == '}') {
+        if rest[i..].starts_with('}') { return Err(bad("unmatched }".into())); }
+        out.push_str(&rest[..i]);
+        let j = rest[i..].find('}').ok_or_else(|| bad("unmatched {".into()))? + i;
+        let name = &rest[i+1..j];
+        match values.iter().find(|(k, _)| *k == name) {
+            Some((_, v)) => out.push_str(v),
+            None         => return Err(bad(format!("unknown placeholder {{{}}}", name))),
+        }
+        rest = &rest[j+1..];
+    }
+    out.push_str(rest);
+    if out.contains('/') || out.contains('\\') { return Err(bad("file names may not contain path separators".into())); }
+    Ok(out)
+}
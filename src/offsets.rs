@@ -0,0 +1,76 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::*;
+
+
+// Rigs whose clocks start at different points can share one set of window
+// definitions by giving each file an offset: the recorded time that counts
+// as time zero.  It is subtracted from every time in the file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TimeOffsets {
+    by_name: BTreeMap<String, f64>,
+}
+
+impl TimeOffsets {
+    // Tab-separated lines of name and offset in seconds, where the name is a
+    // .dat file name or a prefix covering a whole plate.  Blank lines and
+    // lines starting with # are skipped.
+    pub fn from_tsv(text: &str) -> Result<TimeOffsets, MetrologyError> {
+        let mut by_name = BTreeMap::new();
+        for (i, line) in text.lines().enumerate() {
+            if line.trim().len() == 0 || line.starts_with('#') { continue; }
+            let fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() != 2 {
+                return Err(MetrologyError::BadConfig(format!("time offsets line {} should be name<TAB>offset", i+1)));
+            }
+            let offset = parse_offset(fields[1])
+                .ok_or_else(|| MetrologyError::BadConfig(format!("time offset {:?} on line {} is not a number", fields[1], i+1)))?;
+            if by_name.insert(fields[0].trim().to_string(), offset).is_some() {
+                return Err(MetrologyError::BadConfig(format!("time offset for {:?} is given twice", fields[0].trim())));
+            }
+        }
+        Ok(TimeOffsets{ by_name })
+    }
+
+    pub fn load(path: &Path) -> Result<TimeOffsets, MetrologyError> {
+        TimeOffsets::from_tsv(std::fs::read_to_string(path)?.as_str())
+    }
+
+    pub fn is_empty(&self) -> bool { self.by_name.is_empty() }
+
+    pub fn insert(&mut self, name: &str, offset: f64) { self.by_name.insert(name.to_string(), offset); }
+
+    // An entry for the file itself wins over one for its prefix
+    pub fn lookup(&self, file_name: &str, prefix: &str) -> Option<f64> {
+        self.by_name.get(file_name).or_else(|| self.by_name.get(prefix)).cloned()
+    }
+}
+
+fn parse_offset(text: &str) -> Option<f64> {
+    text.trim().parse::<f64>().ok().filter(|x| x.is_finite())
+}
+
+pub fn sidecar_path(dat: &Path) -> PathBuf {
+    let mut name = dat.as_os_str().to_os_string();
+    name.push(".offset");
+    PathBuf::from(name)
+}
+
+// The offset in `<file>.offset` beside a .dat file, if there is one
+pub fn read_sidecar_offset(dat: &Path) -> Result<Option<f64>, MetrologyError> {
+    let path = sidecar_path(dat);
+    if !path.exists() { return Ok(None); }
+    let text = std::fs::read_to_string(&path)?;
+    parse_offset(text.as_str())
+        .map(Some)
+        .ok_or_else(|| MetrologyError::BadConfig(format!("{:?} should hold one number, not {:?}", path, text.trim())))
+}
+
+pub fn shift_times(offset: f64, lines: &mut [DataLine]) {
+    for d in lines.iter_mut() { d.time -= offset; }
+}
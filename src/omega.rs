@@ -0,0 +1,95 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use std::fmt;
+use std::fmt::Display;
+
+use serde::{Serialize, Deserialize};
+
+use crate::*;
+
+
+// A worm curling into an omega turn looks shorter and goes nowhere, so a
+// likely turn is a spell with midline below `shortening` times the worm's
+// median midline, lasting `min_duration` to `max_duration` seconds, over
+// which the centroid moves no more than `max_displacement` mm.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OmegaSettings {
+    pub report: bool,
+    pub shortening: f64,
+    pub max_displacement: f64,
+    pub min_duration: f64,
+    pub max_duration: f64,
+}
+
+impl Default for OmegaSettings {
+    fn default() -> Self { OmegaSettings{ report: false, shortening: 0.75, max_displacement: 0.2, min_duration: 0.3, max_duration: 5.0 } }
+}
+
+impl OmegaSettings {
+    pub fn errors(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if !(self.shortening > 0.0 && self.shortening < 1.0) { errors.push(format!("omega shortening must be between 0 and 1, not {}", self.shortening)); }
+        if !(self.max_displacement >= 0.0) { errors.push(format!("omega max_displacement must not be negative, not {}", self.max_displacement)); }
+        if !(self.min_duration >= 0.0) { errors.push(format!("omega min_duration must not be negative, not {}", self.min_duration)); }
+        if !(self.max_duration >= self.min_duration) { errors.push(format!("omega max_duration {} must not be less than min_duration {}", self.max_duration, self.min_duration)); }
+        errors
+    }
+}
+
+// Likely omega turns, and turns per minute of recording
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OmegaTurns {
+    pub count: u64,
+
+    #[serde(deserialize_with = "nan_if_null")]
+    pub rate: f64,
+}
+
+impl OmegaTurns {
+    pub fn zero() -> OmegaTurns { OmegaTurns{ count: 0, rate: std::f64::NAN } }
+}
+
+impl Display for OmegaTurns {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { write!(f, "{} {}", self.count, self.rate) }
+}
+
+impl StaticEntitled for OmegaTurns {
+    fn titles(specifier: &str) -> Vec<String> { prefixed(specifier, &["count", "rate"]) }
+}
+
+// Start and end times of each likely turn
+pub fn find_omega_turns(settings: &OmegaSettings, input: &[DataLine]) -> Vec<(f64, f64)> {
+    let usable: Vec<&DataLine> = input.iter().filter(|d| d.time.is_finite() && d.midline.is_finite() && d.x.is_finite() && d.y.is_finite()).collect();
+    let mut midlines: Vec<f64> = usable.iter().map(|d| d.midline).collect();
+    let threshold = settings.shortening * percentile_of(&mut midlines, 50.0);
+    if !(threshold > 0.0) { return vec![]; }
+
+    let mut found = Vec::new();
+    let mut i = 0;
+    while i < usable.len() {
+        if usable[i].midline >= threshold { i += 1; continue; }
+        let mut j = i;
+        while j + 1 < usable.len() && usable[j+1].midline < threshold { j += 1; }
+        let (a, b) = (usable[i], usable[j]);
+        let duration = b.time - a.time;
+        let moved = ((b.x - a.x).powi(2) + (b.y - a.y).powi(2)).sqrt();
+        if duration >= settings.min_duration && duration <= settings.max_duration && moved <= settings.max_displacement {
+            found.push((a.time, b.time));
+        }
+        i = j + 1;
+    }
+    found
+}
+
+pub fn the_omega_turns(settings: &OmegaSettings, precision: Precision, input: &[DataLine]) -> OmegaTurns {
+    let found = find_omega_turns(settings, input);
+    let times: Vec<f64> = input.iter().map(|d| d.time).filter(|t| t.is_finite()).collect();
+    let minutes = match (times.first(), times.last()) {
+        (Some(t0), Some(t1)) if t1 > t0 => (t1 - t0) / 60.0,
+        _ => std::f64::NAN,
+    };
+    OmegaTurns{ count: found.len() as u64, rate: precision.r6(found.len() as f64 / minutes) }
+}
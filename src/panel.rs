@@ -0,0 +1,76 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use std::fmt;
+use std::fmt::Display;
+
+use crate::*;
+
+
+// The panel is a short table of headline metrics whose columns stay the same
+// from version to version, unlike the full table.  Add columns only at the end.
+pub const PANEL_TITLE: &str = "id area-mean midline-mean initial-mean calm-mean aroused-mean path-length activity";
+
+#[derive(Debug, Clone)]
+pub struct PanelRow {
+    pub id: WormId,
+    pub track: Option<u32>,
+    pub area_mean: f64,
+    pub midline_mean: f64,
+    pub initial_mean: f64,
+    pub calm_mean: f64,
+    pub aroused_mean: f64,
+
+    // Distance in mm between successive positions, summed
+    pub path_length: f64,
+
+    // Fraction of frames faster than `composite.activity_threshold`
+    pub activity: f64,
+}
+
+impl Display for PanelRow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.track {
+            Some(k) => write!(f, "{}.{} ", self.id, k)?,
+            None    => write!(f, "{} ", self.id)?,
+        }
+        write!(f, "{} {} {} {} {} {} {}",
+            self.area_mean, self.midline_mean, self.initial_mean, self.calm_mean, self.aroused_mean, self.path_length, self.activity
+        )
+    }
+}
+
+pub fn the_path_length(precision: Precision, input: &[DataLine]) -> Option<f64> {
+    let mut path = 0.0;
+    let mut previous: Option<&DataLine> = None;
+    for d in input.iter().filter(|d| d.x.is_finite() && d.y.is_finite()) {
+        if let Some(p) = previous { path += (d.x - p.x).hypot(d.y - p.y); }
+        previous = Some(d);
+    }
+    previous.map(|_| precision.r6(path))
+}
+
+// One row per scores entry; `input` is the worm's data as scored, of which a
+// split track uses only its own times
+pub fn the_panel_row(scores: &Scores, input: &[DataLine], config: &Config) -> PanelRow {
+    let own: Vec<DataLine> =
+        if scores.track.is_some() { input.iter().filter(|d| d.time >= scores.t0 && d.time <= scores.t1).cloned().collect() }
+        else { input.to_vec() };
+    let h = scores.headline();
+    let precision = config.metrics.precision();
+    let value = |name: &str| h.iter().find(|(k, _)| *k == name).map(|(_, v)| *v).unwrap_or(std::f64::NAN);
+    PanelRow{
+        id: scores.id,
+        track: scores.track,
+        area_mean: value("area-mean"),
+        midline_mean: value("midline-mean"),
+        initial_mean: value("initial-mean"),
+        calm_mean: value("calm-mean"),
+        aroused_mean: value("aroused-mean"),
+        path_length: the_path_length(precision, &own).unwrap_or(std::f64::NAN),
+        activity: the_activity(config.composite.activity_threshold, &own).map(|a| precision.r6(a)).unwrap_or(std::f64::NAN),
+    }
+}
+
+pub fn panel_table(rows: &[PanelRow]) -> Result<String, MetrologyError> { table_with(PANEL_TITLE, rows) }
@@ -2,28 +2,74 @@
 // Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
 
 
+use std::borrow::Cow;
+
 use nom::*;
+use serde::{Serialize, Deserialize};
+
+use crate::error::MetrologyError;
 
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DataLine {
     pub time: f64,
     pub area: f64,
     pub speed: f64,
     pub midline: f64,
     pub x: f64,
-    pub y: f64
+    pub y: f64,
+
+    // Position among the file's data lines, from 0, and line in the file, from 1 (0 if unknown)
+    pub frame: u64,
+    pub source_line: u64,
 }
 
 named!(double_at_end<f64>,
     map_res!(map_res!(rest, std::str::from_utf8), |s: &str| s.parse::<f64>())
 );
 
+// Hexadecimal floating point as written by Java's `Double.toHexString` or C's
+// `%a`, like `-0x1.8p3`:  hex digits, maybe with a point, and a binary exponent
+fn hex_double(input: &[u8]) -> IResult<&[u8], f64> {
+    let fail = || Err(nom::Err::Error(error_position!(input, ErrorKind::HexDigit)));
+    let mut i = 0;
+    let negative = match input.first() { Some(b'-') => { i += 1; true }, Some(b'+') => { i += 1; false }, _ => false };
+    if !(input[i..].starts_with(b"0x") || input[i..].starts_with(b"0X")) { return fail(); }
+    i += 2;
+    let mut mantissa = 0f64;
+    let mut scale = 0i32;
+    let mut digits = 0;
+    let mut point = false;
+    while i < input.len() {
+        let c = input[i];
+        if c == b'.' && !point { point = true; }
+        else if let Some(d) = (c as char).to_digit(16) {
+            mantissa = mantissa * 16.0 + d as f64;
+            if point { scale -= 4; }
+            digits += 1;
+        }
+        else { break; }
+        i += 1;
+    }
+    if digits == 0 || i >= input.len() || !(input[i] == b'p' || input[i] == b'P') { return fail(); }
+    i += 1;
+    let exponent_start = i;
+    if i < input.len() && (input[i] == b'+' || input[i] == b'-') { i += 1; }
+    let digits_start = i;
+    while i < input.len() && input[i].is_ascii_digit() { i += 1; }
+    if i == digits_start { return fail(); }
+    let exponent = std::str::from_utf8(&input[exponent_start..i]).ok().and_then(|s| s.parse::<i64>().ok()).unwrap_or(0).clamp(-4096, 4096) as i32;
+    let value = mantissa * 2f64.powi(exponent + scale);
+    Ok((&input[i..], if negative { -value } else { value }))
+}
+
 named!(java_double<f64>,
     alt_complete!(
-        double
+        hex_double
+        | double
         | map!(tag_s!("NaN"), |_| std::f64::NAN)
         | map!(tag_s!("-Infinity"), |_| std::f64::NEG_INFINITY)
+        | map!(tag_s!("+Infinity"), |_| std::f64::INFINITY)
         | map!(tag_s!("Infinity"), |_| std::f64::INFINITY)
         | double_at_end
     )
@@ -59,10 +105,155 @@ named!(get_data_line<DataLine>,
         multispace >>
         y: java_double >>
         token_end >>
-        (DataLine{ time, area, speed, midline, x, y })
+        (DataLine{ time, area, speed, midline, x, y, frame: 0, source_line: 0 })
     )
 );
 
-named!(pub get_data_lines< Vec<DataLine> >,
-    many1!(get_data_line)
-);
+// Like `many1!(get_data_line)`, but numbering each line, with the first
+// line of the input being `first_line` of the file
+fn get_numbered_lines(input: &[u8], first_line: u64) -> IResult<&[u8], Vec<DataLine>> {
+    let mut lines = Vec::new();
+    let mut rest = input;
+    let mut line = first_line;
+    loop {
+        match get_data_line(rest) {
+            Ok((next, mut d)) => {
+                d.frame = lines.len() as u64;
+                d.source_line = line;
+                line += rest[..rest.len() - next.len()].iter().filter(|&&c| c == b'\n').count() as u64;
+                lines.push(d);
+                if next.len() == rest.len() { break; }
+                rest = next;
+            },
+            Err(nom::Err::Error(_)) if lines.len() > 0 => break,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok((rest, lines))
+}
+
+pub fn get_data_lines(input: &[u8]) -> IResult<&[u8], Vec<DataLine>> { get_numbered_lines(input, 1) }
+
+pub fn strip_bom(input: &[u8]) -> &[u8] {
+    if input.starts_with(&[0xEF, 0xBB, 0xBF]) { &input[3..] } else { input }
+}
+
+// Whether the input begins with a complete data line
+pub fn starts_with_data_line(input: &[u8]) -> bool { get_data_line(input).is_ok() }
+
+
+fn parse_error(input: &[u8], e: nom::Err<&[u8]>) -> MetrologyError {
+    let consumed = match e {
+        nom::Err::Error(Context::Code(rest, _)) | nom::Err::Failure(Context::Code(rest, _)) => input.len() - rest.len(),
+        _ => input.len(),
+    };
+    MetrologyError::Parse{ line: 1 + input[..consumed].iter().filter(|&&c| c == b'\n').count() }
+}
+
+pub fn parse_data_lines(input: &[u8]) -> Result<Vec<DataLine>, MetrologyError> {
+    if input.iter().all(|c| c.is_ascii_whitespace()) { return Err(MetrologyError::EmptyData); }
+    get_data_lines(input).map(|x| x.1).map_err(|e| parse_error(input, e))
+}
+
+fn split_on_lines(input: &[u8], pieces: usize) -> Vec<&[u8]> {
+    let mut chunks = Vec::with_capacity(pieces);
+    let mut i0 = 0;
+    for k in 1..pieces {
+        let mut i = std::cmp::max(i0, (input.len() * k) / pieces);
+        while i < input.len() && input[i] != b'\n' { i += 1; }
+        if i < input.len() { i += 1; }
+        if i > i0 { chunks.push(&input[i0..i]); i0 = i; }
+    }
+    if i0 < input.len() || chunks.len() == 0 { chunks.push(&input[i0..]); }
+    chunks
+}
+
+// How numbers are written.  Some rigs export "3,14" for 3.14, or group
+// thousands; both are rewritten to plain numbers before parsing.  Only ASCII
+// separators are supported, and a space can't be one since it separates values.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InputFormat {
+    pub decimal: char,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thousands: Option<char>,
+}
+
+impl Default for InputFormat {
+    fn default() -> Self { InputFormat{ decimal: '.', thousands: None } }
+}
+
+impl InputFormat {
+    pub fn is_plain(&self) -> bool { self.decimal == '.' && self.thousands.is_none() }
+
+    pub fn errors(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        let seps = std::iter::once(("decimal", self.decimal)).chain(self.thousands.map(|c| ("thousands", c)));
+        for (name, c) in seps {
+            if !c.is_ascii_punctuation() {
+                errors.push(format!("{} separator must be ASCII punctuation, not {:?}", name, c));
+            }
+        }
+        if self.thousands == Some(self.decimal) { errors.push(format!("decimal and thousands separators are both {:?}", self.decimal)); }
+        errors
+    }
+
+    // Line breaks are untouched, so parse errors still report the right line.
+    // Files saved from spreadsheets may also start with a byte order mark,
+    // which is dropped, or have non-breaking spaces, which become plain
+    // spaces.  A lone 0xA0 is only a non-breaking space in a file that isn't
+    // UTF-8 (so Latin-1); in UTF-8 it is part of characters such as `à`.
+    pub fn normalize<'a>(&self, input: &'a [u8]) -> Cow<'a, [u8]> {
+        let input = strip_bom(input);
+        let latin1 = std::str::from_utf8(input).is_err();
+        let nbsp = if latin1 { input.contains(&0xA0) } else { input.windows(2).any(|w| w == [0xC2, 0xA0]) };
+        if self.is_plain() && !nbsp { return Cow::Borrowed(input); }
+        let decimal = self.decimal as u8;
+        let thousands = self.thousands.map(|c| c as u8);
+        let mut out = Vec::with_capacity(input.len());
+        for (i, &c) in input.iter().enumerate() {
+            if c == 0xC2 && !latin1 && input.get(i+1) == Some(&0xA0) { continue; }
+            else if c == 0xA0 && (latin1 || (i > 0 && input[i-1] == 0xC2)) { out.push(b' '); }
+            else if Some(c) == thousands { continue; }
+            else if c == decimal { out.push(b'.'); }
+            else { out.push(c); }
+        }
+        Cow::Owned(out)
+    }
+}
+
+// Gives the same lines as `get_data_lines` would:  if a chunk stops parsing
+// early, everything after it is dropped just as the sequential parser would
+// never have reached it.
+pub fn parse_data_lines_parallel(input: &[u8], threads: usize) -> Result<Vec<DataLine>, MetrologyError> {
+    const MIN_CHUNK: usize = 1 << 20;
+
+    let pieces = std::cmp::max(1, std::cmp::min(threads, input.len() / MIN_CHUNK));
+    if pieces == 1 { return parse_data_lines(input); }
+    if input.iter().all(|c| c.is_ascii_whitespace()) { return Err(MetrologyError::EmptyData); }
+
+    let chunks = split_on_lines(input, pieces);
+    let mut first_lines = vec![1u64];
+    for c in chunks.iter() { first_lines.push(first_lines[first_lines.len() - 1] + c.iter().filter(|&&b| b == b'\n').count() as u64); }
+    let results: Vec<_> = std::thread::scope(|scope| {
+        let handles: Vec<_> = chunks.iter().zip(first_lines.iter()).map(|(c, &n)| scope.spawn(move || get_numbered_lines(c, n))).collect();
+        handles.into_iter().map(|h| h.join().expect("Parsing thread panicked")).collect()
+    });
+
+    let mut lines = Vec::new();
+    for (k, r) in results.into_iter().enumerate() {
+        match r {
+            Ok((rest, mut v)) => {
+                for d in v.iter_mut() { d.frame += lines.len() as u64; }
+                lines.append(&mut v);
+                if rest.len() > 0 { break; }
+            },
+            Err(e) => {
+                if k == 0 { return Err(parse_error(chunks[0], e)); }
+                break;
+            }
+        }
+    }
+    Ok(lines)
+}
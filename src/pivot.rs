@@ -0,0 +1,80 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use crate::*;
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PivotShape {
+    // One row per worm, one column per value
+    Wide,
+
+    // One row per worm and value, with columns `metric value`
+    Long,
+}
+
+impl std::str::FromStr for PivotShape {
+    type Err = String;
+    fn from_str(s: &str) -> Result<PivotShape, String> {
+        match s {
+            "wide" => Ok(PivotShape::Wide),
+            "long" => Ok(PivotShape::Long),
+            _      => Err(format!("unknown shape {:?}; use wide or long", s)),
+        }
+    }
+}
+
+// Columns of the scores table, by name, picked from each worm's row.  With no
+// `values`, every column not in `index` is used.  A column some worms lack is
+// NaN for those; a column no worm has is an error.
+pub fn pivot_table(scores: &[Scores], index: &[String], values: &[String], shape: PivotShape) -> Result<String, MetrologyError> {
+    let rows: Vec<Vec<(String, String)>> = scores.iter().map(|s| {
+        let title = s.title();
+        let row = s.to_string();
+        title.split(' ').map(|t| t.to_string()).zip(row.split(' ').map(|v| v.to_string())).collect()
+    }).collect();
+    let values: Vec<String> =
+        if values.len() > 0 { values.to_vec() }
+        else {
+            let mut all: Vec<String> = Vec::new();
+            for (name, _) in rows.iter().flatten() {
+                if !index.contains(name) && !all.contains(name) { all.push(name.clone()); }
+            }
+            all
+        };
+    let unknown: Vec<&str> = index.iter().chain(values.iter())
+        .filter(|w| rows.len() > 0 && !rows.iter().any(|r| r.iter().any(|(name, _)| name == *w)))
+        .map(|w| w.as_str())
+        .collect();
+    if unknown.len() > 0 { return Err(MetrologyError::BadConfig(format!("no columns named {}", unknown.join(", ")))); }
+
+    let value_of = |row: &[(String, String)], name: &str| row.iter().find(|(n, _)| n == name).map(|(_, v)| v.clone()).unwrap_or_else(|| "NaN".to_string());
+    let mut table = String::new();
+    match shape {
+        PivotShape::Wide => {
+            table.push_str(index.iter().chain(values.iter()).cloned().collect::<Vec<String>>().join(" ").as_str());
+            table.push('\n');
+            for row in rows.iter() {
+                let picked: Vec<String> = index.iter().chain(values.iter()).map(|w| value_of(row, w)).collect();
+                table.push_str(picked.join(" ").as_str());
+                table.push('\n');
+            }
+        },
+        PivotShape::Long => {
+            table.push_str(index.iter().cloned().chain(vec!["metric".to_string(), "value".to_string()]).collect::<Vec<String>>().join(" ").as_str());
+            table.push('\n');
+            for row in rows.iter() {
+                let keys: Vec<String> = index.iter().map(|w| value_of(row, w)).collect();
+                for v in values.iter() {
+                    let mut line = keys.clone();
+                    line.push(v.clone());
+                    line.push(value_of(row, v));
+                    table.push_str(line.join(" ").as_str());
+                    table.push('\n');
+                }
+            }
+        },
+    }
+    Ok(table)
+}
@@ -0,0 +1,205 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::error::MetrologyError;
+
+
+// Copies the files of `from`, and its directories' files, into `to`
+pub fn copy_tree(from: &Path, to: &Path) -> io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() { copy_tree(&entry.path(), &target)?; }
+        else { fs::copy(entry.path(), &target)?; }
+    }
+    Ok(())
+}
+
+fn same_tree(a: &Path, b: &Path) -> io::Result<bool> {
+    let mut na: Vec<_> = fs::read_dir(a)?.map(|e| e.map(|e| e.file_name())).collect::<io::Result<_>>()?;
+    let mut nb: Vec<_> = fs::read_dir(b)?.map(|e| e.map(|e| e.file_name())).collect::<io::Result<_>>()?;
+    na.sort();
+    nb.sort();
+    if na != nb { return Ok(false); }
+    for name in na {
+        let pa = a.join(&name);
+        let pb = b.join(&name);
+        if pa.is_dir() {
+            if !pb.is_dir() || !same_tree(&pa, &pb)? { return Ok(false); }
+        }
+        else if pb.is_dir() || fs::read(&pa)? != fs::read(&pb)? { return Ok(false); }
+    }
+    Ok(true)
+}
+
+// Renames `temp` to `target`.  If the rename is refused (e.g. across devices,
+// or on some network shares), copies into a sibling of `target`, checks that
+// the copy is identical, renames that into place, and only then removes `temp`.
+pub fn publish_atomic(temp: &Path, target: &Path) -> Result<(), MetrologyError> {
+    let rename_error = match fs::rename(temp, target) {
+        Ok(()) => return Ok(()),
+        Err(e) => e,
+    };
+    if !temp.is_dir() || target.exists() { return Err(rename_error.into()); }
+
+    let mut copy_name = target.file_name().map(|f| f.to_os_string()).ok_or(rename_error)?;
+    copy_name.push(".copying");
+    let copy: PathBuf = target.with_file_name(copy_name);
+    if copy.exists() {
+        return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{:?} exists already", copy)).into());
+    }
+
+    let copied = copy_tree(temp, &copy).and_then(|_| same_tree(temp, &copy));
+    match copied {
+        Ok(true) => (),
+        Ok(false) => {
+            let _ = fs::remove_dir_all(&copy);
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("copy of {:?} does not match original", temp)).into());
+        },
+        Err(e) => { let _ = fs::remove_dir_all(&copy); return Err(e.into()); }
+    }
+    fs::rename(&copy, target)?;
+    fs::remove_dir_all(temp)?;
+    Ok(())
+}
+
+// Renames `target` to the sibling `<target>.<suffix>`, or `<target>.<suffix>-2`
+// and so on if that is taken, and returns the new path
+pub fn move_aside(target: &Path, suffix: &str) -> Result<PathBuf, MetrologyError> {
+    let name = target.file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("cannot rename {:?}", target)))?
+        .to_string_lossy().to_string();
+    let mut aside = target.with_file_name(format!("{}.{}", name, suffix));
+    let mut k = 1;
+    while aside.exists() {
+        k += 1;
+        aside = target.with_file_name(format!("{}.{}-{}", name, suffix, k));
+    }
+    fs::rename(target, &aside)?;
+    Ok(aside)
+}
+
+// Held while a run writes a target, as the file `<target>.lock` giving the
+// host, process id, and start time (seconds since 1970) of the run.  A lock
+// is stale, and taken over, if its process is gone from this host or it is
+// older than `stale_after`; `recovered` then says the earlier run died.  A
+// lock that cannot be read is stale once it has gone UNWRITTEN_GRACE unchanged.
+#[derive(Debug)]
+pub struct RunLock {
+    path: PathBuf,
+    pub recovered: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct LockHolder {
+    host: String,
+    pid: u32,
+    started: u64,
+}
+
+impl LockHolder {
+    fn this_process() -> LockHolder {
+        LockHolder{ host: host_name(), pid: std::process::id(), started: unix_now() }
+    }
+
+    fn parse(text: &str) -> Option<LockHolder> {
+        let mut fields = text.split_whitespace();
+        let host = fields.next()?.to_string();
+        let pid = fields.next()?.parse().ok()?;
+        let started = fields.next()?.parse().ok()?;
+        Some(LockHolder{ host, pid, started })
+    }
+
+    fn is_stale(&self, stale_after: Duration) -> bool {
+        let gone = self.host == host_name() && Path::new("/proc").is_dir() && !Path::new("/proc").join(self.pid.to_string()).exists();
+        gone || unix_now().saturating_sub(self.started) > stale_after.as_secs()
+    }
+}
+
+// How long a lock file may stay unreadable before it is taken as abandoned
+pub const UNWRITTEN_GRACE: Duration = Duration::from_secs(10);
+
+fn unchanged_for(path: &Path, grace: Duration) -> bool {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+        .and_then(|t| t.elapsed().ok())
+        .map(|age| age > grace)
+        .unwrap_or(false)
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+pub(crate) fn host_name() -> String {
+    fs::read_to_string("/proc/sys/kernel/hostname").ok()
+        .or_else(|| std::env::var("HOSTNAME").ok())
+        .or_else(|| std::env::var("COMPUTERNAME").ok())
+        .map(|h| h.trim().to_string())
+        .filter(|h| h.len() > 0 && !h.contains(char::is_whitespace))
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+impl RunLock {
+    pub fn path_for(target: &Path) -> Option<PathBuf> {
+        let mut name = target.file_name()?.to_os_string();
+        name.push(".lock");
+        Some(target.with_file_name(name))
+    }
+
+    pub fn acquire(target: &Path, stale_after: Duration) -> Result<RunLock, MetrologyError> {
+        let path = RunLock::path_for(target)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("no lock file for {:?}", target)))?;
+        if let Some(parent) = path.parent().filter(|p| p.as_os_str().len() > 0) { fs::create_dir_all(parent)?; }
+        let mut recovered = false;
+        loop {
+            match fs::OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut f) => {
+                    let me = LockHolder::this_process();
+                    writeln!(f, "{} {} {}", me.host, me.pid, me.started)?;
+                    return Ok(RunLock{ path, recovered });
+                },
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => (),
+                Err(e) => return Err(e.into()),
+            }
+            // A lock being written right now may not parse yet; it counts as held until
+            // it has gone long enough unchanged that its writer must have died
+            let text = fs::read_to_string(&path).unwrap_or_default();
+            let unreadable = match LockHolder::parse(text.as_str()) {
+                Some(ref h) if h.is_stale(stale_after) => false,
+                Some(h) => return Err(MetrologyError::Locked{ path, host: h.host, pid: h.pid, started: h.started }),
+                None if unchanged_for(&path, UNWRITTEN_GRACE) => true,
+                None    => return Err(MetrologyError::Locked{ path, host: "unknown".into(), pid: 0, started: 0 }),
+            };
+            // Move the stale lock aside before removing it, so that if another
+            // run took it over first, its fresh lock is put back, not deleted;
+            // it is put back by linking, which fails rather than overwrite a
+            // lock made by yet another run in the meantime
+            let mut aside = path.as_os_str().to_os_string();
+            aside.push(format!(".stale.{}", std::process::id()));
+            let aside = PathBuf::from(aside);
+            if fs::rename(&path, &aside).is_err() { continue; }
+            let moved = fs::read_to_string(&aside).ok();
+            if moved.as_ref() != Some(&text) || (unreadable && !unchanged_for(&aside, UNWRITTEN_GRACE)) {
+                if let Err(e) = fs::hard_link(&aside, &path) {
+                    return Err(io::Error::new(e.kind(), format!("lock {:?} was taken over during recovery and is left at {:?}: {}", path, aside, e)).into());
+                }
+                fs::remove_file(&aside)?;
+                continue;
+            }
+            fs::remove_file(&aside)?;
+            recovered = true;
+        }
+    }
+}
+
+impl Drop for RunLock {
+    fn drop(&mut self) { let _ = fs::remove_file(&self.path); }
+}
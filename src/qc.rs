@@ -0,0 +1,299 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use std::fmt;
+use std::fmt::Display;
+
+use serde::{Serialize, Deserialize};
+
+use crate::*;
+
+
+// Any limit that is set and exceeded adds a flag to the QC block
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QcLimits {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub area_rate: Option<f64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub midline_rate: Option<f64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub x_rate: Option<f64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub y_rate: Option<f64>,
+
+    // Speed integrated over time should match the path length from x and y;
+    // a ratio off by more than this factor in either direction suggests the
+    // wrong units or column order
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub speed_path_ratio: Option<f64>,
+
+    // Windows with fewer than this fraction of the frames expected from the frame rate are flagged
+    pub min_window_fraction: f64,
+
+    // Tracks that end early within this fraction of the arena's width or height of its edge are lost at the edge
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub edge_margin: Option<f64>,
+
+    // Leave tracks lost at the edge out of the summary table (they stay in the scores)
+    pub exclude_lost_at_edge: bool,
+}
+
+impl Default for QcLimits {
+    fn default() -> Self {
+        QcLimits{ 
+            area_rate: None, midline_rate: None, x_rate: None, y_rate: None, speed_path_ratio: Some(3.0), min_window_fraction: 0.5,
+            edge_margin: None, exclude_lost_at_edge: false
+        }
+    }
+}
+
+impl QcLimits {
+    pub fn errors(&self) -> Vec<String> {
+        match self.speed_path_ratio {
+            Some(r) if !(r >= 1.0) => vec![format!("qc speed_path_ratio must be at least 1, not {}", r)],
+            _ => Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FrameRate {
+    #[serde(deserialize_with = "nan_if_null")]
+    pub median_dt: f64,
+
+    #[serde(deserialize_with = "nan_if_null")]
+    pub jitter: f64,
+
+    pub dropped_frames: u64,
+}
+
+impl FrameRate {
+    pub fn zero() -> FrameRate { FrameRate{ median_dt: std::f64::NAN, jitter: std::f64::NAN, dropped_frames: 0 } }
+
+    pub fn expected_frames(&self, duration: f64) -> f64 { duration / self.median_dt }
+}
+
+impl Display for FrameRate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} {}", self.median_dt, self.jitter, self.dropped_frames)
+    }
+}
+
+impl StaticEntitled for FrameRate {
+    fn titles(specifier: &str) -> Vec<String> { prefixed(specifier, &["median-dt", "jitter", "dropped-frames"]) }
+}
+
+fn median_of(values: &mut Vec<f64>) -> f64 {
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let n = values.len();
+    if n == 0 { std::f64::NAN }
+    else if n % 2 == 1 { values[n/2] }
+    else { 0.5 * (values[n/2 - 1] + values[n/2]) }
+}
+
+// Jitter is the median absolute deviation of the frame intervals.  Gaps of
+// more than 1.5 typical intervals count as the nearest whole number of
+// missing frames.
+pub fn estimate_frame_rate(precision: Precision, input: &[DataLine]) -> Option<FrameRate> {
+    let times: Vec<f64> = input.iter().map(|d| d.time).filter(|t| t.is_finite()).collect();
+    let mut dts: Vec<f64> = times.windows(2).map(|w| w[1] - w[0]).filter(|dt| *dt > 0.0).collect();
+    if dts.len() == 0 { return None; }
+
+    let dropped_frames = {
+        let median_dt = median_of(&mut dts.clone());
+        dts.iter().filter(|dt| **dt > 1.5 * median_dt).map(|dt| ((dt / median_dt).round() as u64).saturating_sub(1)).sum()
+    };
+    let median_dt = median_of(&mut dts);
+    let mut deviations: Vec<f64> = dts.iter().map(|dt| (dt - median_dt).abs()).collect();
+    let jitter = median_of(&mut deviations);
+    Some(FrameRate{ median_dt: precision.r6(median_dt), jitter: precision.r6(jitter), dropped_frames })
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Qc {
+    #[serde(deserialize_with = "nan_if_null")]
+    pub area_rate: f64,
+
+    #[serde(deserialize_with = "nan_if_null")]
+    pub midline_rate: f64,
+
+    #[serde(deserialize_with = "nan_if_null")]
+    pub x_rate: f64,
+
+    #[serde(deserialize_with = "nan_if_null")]
+    pub y_rate: f64,
+
+    // Integrated speed over path length
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub speed_path_ratio: Option<f64>,
+
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub frame_rate: Option<FrameRate>,
+
+    #[serde(skip_serializing_if = "std::ops::Not::not", default)]
+    pub lost_at_edge: bool,
+
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub flags: Vec<String>,
+}
+
+impl Qc {
+    pub fn zero() -> Qc {
+        Qc{ 
+            area_rate: std::f64::NAN, midline_rate: std::f64::NAN, x_rate: std::f64::NAN, y_rate: std::f64::NAN,
+            speed_path_ratio: None, frame_rate: None, lost_at_edge: false, flags: Vec::new()
+        }
+    }
+
+    // Flags a window whose sample count is well short of what the frame rate implies
+    pub fn check_window_count(&mut self, name: &str, window: &Window, n: u64, min_fraction: f64) {
+        if let Some(fr) = &self.frame_rate {
+            let expected = fr.expected_frames(window.end - window.start);
+            if expected.is_finite() && (n as f64) < min_fraction * expected {
+                self.flags.push(format!("{} window has {} frames but about {:.0} were expected", name, n, expected));
+            }
+        }
+    }
+
+    // Undoes `flag_lost_at_edge`, whose flag is always the last one added
+    pub fn clear_lost_at_edge(&mut self) {
+        if self.lost_at_edge {
+            self.lost_at_edge = false;
+            self.flags.pop();
+        }
+    }
+}
+
+impl Display for Qc {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} {} {} {} {}", 
+            self.area_rate, self.midline_rate, self.x_rate, self.y_rate, self.speed_path_ratio.unwrap_or(std::f64::NAN),
+            self.frame_rate.clone().unwrap_or(FrameRate::zero())
+        )
+    }
+}
+
+impl StaticEntitled for Qc {
+    fn titles(specifier: &str) -> Vec<String> {
+        let mut titles = prefixed(specifier, &["max-area-rate", "max-midline-rate", "max-x-rate", "max-y-rate", "speed-path-ratio"]);
+        titles.extend(FrameRate::titles(specifier));
+        titles
+    }
+}
+
+// Largest |dv/dt| between successive frames where the value is finite
+pub fn the_max_rate<F>(f: F, precision: Precision, input: &[DataLine]) -> f64
+where F: Fn(&DataLine) -> f64 {
+    the_max_rate_at(f, precision, input).0
+}
+
+// As `the_max_rate`, also giving the source line where the largest change ends
+pub fn the_max_rate_at<F>(f: F, precision: Precision, input: &[DataLine]) -> (f64, u64)
+where F: Fn(&DataLine) -> f64 {
+    let mut previous: Option<(f64, f64)> = None;
+    let mut biggest = std::f64::NAN;
+    let mut line = 0;
+    for d in input.iter() {
+        let v = f(d);
+        if !(v.is_finite() && d.time.is_finite()) { continue; }
+        if let Some((t, u)) = previous {
+            let dt = d.time - t;
+            if dt > 0.0 {
+                let rate = (v - u).abs() / dt;
+                if !(rate <= biggest) { biggest = rate; line = d.source_line; }
+            }
+        }
+        previous = Some((d.time, v));
+    }
+    (precision.r6(biggest), line)
+}
+
+// Trapezoidal integral of speed over time, divided by the distance between
+// successive positions, over frames where all are finite
+pub fn the_speed_path_ratio(precision: Precision, input: &[DataLine]) -> Option<f64> {
+    let mut travelled = 0.0;
+    let mut path = 0.0;
+    let mut previous: Option<&DataLine> = None;
+    for d in input.iter() {
+        if !(d.time.is_finite() && d.speed.is_finite() && d.x.is_finite() && d.y.is_finite()) { continue; }
+        if let Some(p) = previous {
+            let dt = d.time - p.time;
+            if dt > 0.0 {
+                travelled += 0.5 * (p.speed + d.speed) * dt;
+                path += (d.x - p.x).hypot(d.y - p.y);
+            }
+        }
+        previous = Some(d);
+    }
+    if path > 0.0 { Some(precision.r6(travelled / path)) } else { None }
+}
+
+pub fn the_qc(limits: &QcLimits, precision: Precision, input: &[DataLine]) -> Qc {
+    let (area_rate, area_line) = the_max_rate_at(|d| d.area, precision, input);
+    let (midline_rate, midline_line) = the_max_rate_at(|d| d.midline, precision, input);
+    let (x_rate, x_line) = the_max_rate_at(|d| d.x, precision, input);
+    let (y_rate, y_line) = the_max_rate_at(|d| d.y, precision, input);
+    let mut qc = Qc{
+        area_rate,
+        midline_rate,
+        x_rate,
+        y_rate,
+        speed_path_ratio: the_speed_path_ratio(precision, input),
+        frame_rate: estimate_frame_rate(precision, input),
+        lost_at_edge: false,
+        flags: Vec::new(),
+    };
+    let checks = [
+        ("area-rate", qc.area_rate, area_line, limits.area_rate),
+        ("midline-rate", qc.midline_rate, midline_line, limits.midline_rate),
+        ("x-rate", qc.x_rate, x_line, limits.x_rate),
+        ("y-rate", qc.y_rate, y_line, limits.y_rate),
+    ];
+    for (name, value, line, limit) in checks.iter() {
+        if let Some(l) = limit {
+            if *value > *l { 
+                if *line > 0 { qc.flags.push(format!("{} {} exceeds {} at line {}", name, value, l, line)); }
+                else { qc.flags.push(format!("{} {} exceeds {}", name, value, l)); }
+            }
+        }
+    }
+    if let (Some(ratio), Some(l)) = (qc.speed_path_ratio, limits.speed_path_ratio) {
+        if ratio > l || ratio * l < 1.0 {
+            qc.flags.push(format!("speed integrates to {} times the path length from x and y; check units and column order", ratio));
+        }
+    }
+    qc
+}
+
+// The arena is the configured one, or else the box around every track on the
+// plate.  A track is lost at the edge if its last position is within the
+// margin of that box and it ends at least two frames before the plate's last
+// recorded time, since worms that leave the field of view stop being tracked.
+pub fn flag_lost_at_edge(limits: &QcLimits, arena: Option<Arena>, rows: &mut [Scores]) {
+    let margin = match limits.edge_margin { Some(m) => m, None => return };
+    let finite = |v: f64| if v.is_finite() { Some(v) } else { None };
+    let arena = arena.unwrap_or_else(|| Arena{
+        x0: rows.iter().filter_map(|r| finite(r.x.bound0)).fold(std::f64::INFINITY, f64::min),
+        x1: rows.iter().filter_map(|r| finite(r.x.bound1)).fold(std::f64::NEG_INFINITY, f64::max),
+        y0: rows.iter().filter_map(|r| finite(r.y.bound0)).fold(std::f64::INFINITY, f64::min),
+        y1: rows.iter().filter_map(|r| finite(r.y.bound1)).fold(std::f64::NEG_INFINITY, f64::max),
+    });
+    let end = rows.iter().filter_map(|r| finite(r.t1)).fold(std::f64::NEG_INFINITY, f64::max);
+    let mx = margin * (arena.x1 - arena.x0);
+    let my = margin * (arena.y1 - arena.y0);
+    if !(mx.is_finite() && my.is_finite() && end.is_finite()) { return; }
+    for r in rows.iter_mut() {
+        let (x, y) = (r.x.last, r.y.last);
+        let near = x - arena.x0 <= mx || arena.x1 - x <= mx || y - arena.y0 <= my || arena.y1 - y <= my;
+        let dt = r.qc.frame_rate.as_ref().map(|fr| fr.median_dt).filter(|dt| dt.is_finite()).unwrap_or(0.0);
+        if near && r.t1 + 2.0 * dt < end {
+            r.qc.lost_at_edge = true;
+            r.qc.flags.push(format!("track ends at ({}, {}) near the arena edge at {} s, before recording ends at {} s", x, y, r.t1, end));
+        }
+    }
+}
@@ -0,0 +1,72 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use std::fmt;
+use std::fmt::Display;
+
+use serde::{Serialize, Deserialize};
+
+use average::Estimate;
+
+use crate::*;
+
+
+// How quickly body shape changes: mean |d/dt| of area (mm^2/s) and midline
+// (mm/s).  Egg laying and contractions show up here but not in the means.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShapeRates {
+    pub area: Sampled,
+    pub midline: Sampled,
+}
+
+impl ShapeRates {
+    pub fn zero() -> ShapeRates { ShapeRates{ area: Sampled::zero(), midline: Sampled::zero() } }
+
+    pub fn titles(specifier: &str, intervals: bool) -> Vec<String> {
+        let mut titles = Sampled::titles(format!("{}area-rate-", specifier).as_str(), intervals);
+        titles.extend(Sampled::titles(format!("{}midline-rate-", specifier).as_str(), intervals));
+        titles
+    }
+}
+
+impl Display for ShapeRates {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.area, self.midline)
+    }
+}
+
+impl Entitled for ShapeRates {
+    fn push_subtitle(&self, specifier: &str, to: &mut String) { to.push_str(ShapeRates::titles(specifier, self.area.has_intervals()).join(" ").as_str()); }
+}
+
+// |dv/dt| between successive frames where the value and time are finite
+pub fn the_abs_rate<F>(f: F, precision: Precision, input: &[DataLine]) -> Sampled
+where F: Fn(&DataLine) -> f64 {
+    let mut stats = average::Variance::new();
+    let mut previous: Option<(f64, f64)> = None;
+    for d in input.iter() {
+        let v = f(d);
+        if !(v.is_finite() && d.time.is_finite()) { continue; }
+        if let Some((t, u)) = previous {
+            let dt = d.time - t;
+            if dt > 0.0 { stats.add((v - u).abs() / dt); }
+        }
+        previous = Some((d.time, v));
+    }
+    Sampled::of_variance(&stats, precision)
+}
+
+pub fn the_shape_rates(precision: Precision, input: &[DataLine]) -> ShapeRates {
+    ShapeRates{ area: the_abs_rate(|d| d.area, precision, input), midline: the_abs_rate(|d| d.midline, precision, input) }
+}
+
+// Midline squared over area in each frame with both, a unitless index of how
+// thin a worm is: starved and dauer-like worms are thinner, so higher.
+pub fn the_shape_index(precision: Precision, input: &[DataLine]) -> Sampled {
+    let v: average::Variance = input.iter()
+        .filter(|d| d.midline.is_finite() && d.area.is_finite() && d.area > 0.0)
+        .map(|d| d.midline * d.midline / d.area)
+        .collect();
+    Sampled::of_variance(&v, precision)
+}
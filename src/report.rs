@@ -0,0 +1,132 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::PathBuf;
+
+use crate::*;
+
+
+#[derive(Debug, Clone)]
+pub struct SkippedFile {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+// What the run report needs beyond the scores themselves
+#[derive(Debug, Clone)]
+pub struct ReportInfo {
+    pub version: String,
+    pub source: PathBuf,
+    pub prefix: String,
+    pub config_hash: String,
+    pub files: usize,
+    pub skipped: Vec<SkippedFile>,
+    pub precision: Precision,
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn quantile(sorted: &[f64], q: f64) -> f64 {
+    if sorted.len() == 0 { return std::f64::NAN; }
+    let x = q * (sorted.len() - 1) as f64;
+    let (i, f) = (x.floor() as usize, x - x.floor());
+    if i + 1 < sorted.len() { sorted[i] + f * (sorted[i+1] - sorted[i]) } else { sorted[i] }
+}
+
+// A flag with its numbers taken out, so flags of the same kind count together
+fn flag_kind(flag: &str) -> String {
+    flag.split(' ').take_while(|w| !w.chars().any(|c| c.is_ascii_digit())).collect::<Vec<_>>().join(" ")
+}
+
+// Bars for 20 equal bins between the smallest and largest values
+fn histogram_svg(values: &[f64]) -> String {
+    const BINS: usize = 20;
+    let (w, h) = (400.0, 120.0);
+    let (lo, hi) = (values[0], values[values.len() - 1]);
+    let mut counts = [0usize; BINS];
+    for v in values.iter() {
+        let k = if hi > lo { (((v - lo) / (hi - lo)) * BINS as f64) as usize } else { 0 };
+        counts[std::cmp::min(k, BINS - 1)] += 1;
+    }
+    let most = *counts.iter().max().unwrap_or(&1) as f64;
+    let mut svg = format!("<svg width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">", w, h + 16.0, w, h + 16.0);
+    let bar = w / BINS as f64;
+    for (k, c) in counts.iter().enumerate() {
+        let bh = h * (*c as f64) / most;
+        let _ = write!(svg, "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"#4a7ab0\"><title>{}</title></rect>",
+            k as f64 * bar + 1.0, h - bh, bar - 2.0, bh, c);
+    }
+    let _ = write!(svg, "<text x=\"0\" y=\"{}\" font-size=\"11\">{}</text><text x=\"{}\" y=\"{}\" font-size=\"11\" text-anchor=\"end\">{}</text></svg>",
+        h + 13.0, lo, w, h + 13.0, hi);
+    svg
+}
+
+// One self-contained page: run details, summary statistics and distributions
+// of the headline metrics across worms, QC flags and window warnings by
+// kind, and files that were left out or gave no data.
+pub fn html_report(info: &ReportInfo, rows: &[Scores]) -> String {
+    let mut page = String::new();
+    let title = format!("Metrology run: {}", escape(info.prefix.as_str()));
+    let _ = write!(page, "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>{}</title>\n", title);
+    page.push_str("<style>body{font-family:sans-serif;margin:2em;max-width:60em}table{border-collapse:collapse;margin-bottom:1em}\
+        td,th{border:1px solid #ccc;padding:0.2em 0.6em;text-align:right}th{background:#eee}td.l{text-align:left}</style>\n</head><body>\n");
+    let _ = write!(page, "<h1>{}</h1>\n<table>\n", title);
+    let flagged = rows.iter().filter(|r| r.qc.flags.len() > 0).count();
+    let warned = rows.iter().filter(|r| r.warnings.len() > 0).count();
+    let details = [
+        ("Source", escape(info.source.to_string_lossy().as_ref())), ("Version", escape(info.version.as_str())),
+        ("Configuration", info.config_hash.clone()), ("Files scored", info.files.to_string()),
+        ("Tracks", rows.len().to_string()), ("Tracks flagged by QC", flagged.to_string()),
+        ("Tracks with unscored windows", warned.to_string()), ("Files left out", info.skipped.len().to_string()),
+    ];
+    for (k, v) in details.iter() { let _ = writeln!(page, "<tr><th>{}</th><td class=\"l\">{}</td></tr>", k, v); }
+    page.push_str("</table>\n");
+
+    page.push_str("<h2>Metrics across worms</h2>\n<table>\n<tr><th>metric</th><th>n</th><th>mean</th><th>sd</th><th>min</th><th>median</th><th>max</th></tr>\n");
+    let mut plots = String::new();
+    let names: Vec<&'static str> = Scores::zero().headline().iter().map(|x| x.0).collect();
+    for (i, name) in names.iter().enumerate() {
+        let mut values: Vec<f64> = rows.iter().map(|r| r.headline()[i].1).filter(|v| v.is_finite()).collect();
+        values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let n = values.len();
+        let mean = values.iter().sum::<f64>() / n as f64;
+        let sd = (values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n as f64 - 1.0)).sqrt();
+        let _ = writeln!(page, "<tr><td class=\"l\">{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            name, n, info.precision.r6(mean), info.precision.r6(sd), quantile(&values, 0.0), info.precision.r6(quantile(&values, 0.5)), quantile(&values, 1.0));
+        if n > 0 { let _ = writeln!(plots, "<h3>{}</h3>\n{}", name, histogram_svg(&values)); }
+    }
+    page.push_str("</table>\n");
+    page.push_str(plots.as_str());
+
+    let mut flags: BTreeMap<String, usize> = BTreeMap::new();
+    for r in rows.iter() {
+        for f in r.qc.flags.iter() { *flags.entry(flag_kind(f.as_str())).or_insert(0) += 1; }
+        for w in r.warnings.iter() { *flags.entry(format!("{} window not scored", w.window)).or_insert(0) += 1; }
+    }
+    page.push_str("<h2>QC flags and warnings</h2>\n");
+    if flags.len() == 0 { page.push_str("<p>None.</p>\n"); }
+    else {
+        page.push_str("<table>\n<tr><th>kind</th><th>tracks</th></tr>\n");
+        for (k, n) in flags.iter() { let _ = writeln!(page, "<tr><td class=\"l\">{}</td><td>{}</td></tr>", escape(k.as_str()), n); }
+        page.push_str("</table>\n");
+    }
+
+    page.push_str("<h2>Files left out or without data</h2>\n");
+    let empty: Vec<String> = rows.iter().filter(|r| !r.t0.is_finite()).map(|r| r.id.to_string()).collect();
+    if info.skipped.len() == 0 && empty.len() == 0 { page.push_str("<p>None.</p>\n"); }
+    else {
+        page.push_str("<table>\n<tr><th>file or worm</th><th>reason</th></tr>\n");
+        for s in info.skipped.iter() {
+            let _ = writeln!(page, "<tr><td class=\"l\">{}</td><td class=\"l\">{}</td></tr>", escape(s.path.to_string_lossy().as_ref()), escape(s.reason.as_str()));
+        }
+        for id in empty.iter() { let _ = writeln!(page, "<tr><td class=\"l\">worm {}</td><td class=\"l\">no usable data</td></tr>", id); }
+        page.push_str("</table>\n");
+    }
+    page.push_str("</body></html>\n");
+    page
+}
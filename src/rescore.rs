@@ -0,0 +1,68 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use crate::*;
+
+
+// Top-level sections of the configuration (`windows`, `qc`, ...) that differ
+pub fn changed_sections(old: &Config, new: &Config) -> Vec<String> {
+    match (serde_json::to_value(old), serde_json::to_value(new)) {
+        (Ok(serde_json::Value::Object(a)), Ok(serde_json::Value::Object(b))) => {
+            let mut names: Vec<String> = a.keys().chain(b.keys()).filter(|k| a.get(*k) != b.get(*k)).cloned().collect();
+            names.sort();
+            names.dedup();
+            names
+        },
+        _ => vec!["everything".to_string()],
+    }
+}
+
+// Sections whose metrics can be recomputed alone; any other change (to how
+// the data are read, cleaned, or split, or to which metrics are computed, say)
+// means scoring from scratch
+const SELECTIVE_SECTIONS: [&str; 11] = ["windows", "qc", "windowed", "composite", "exploration", "edge", "histogram", "derived", "bursts", "omega", "metadata"];
+
+// Whether old scores need the data to be brought up to date; histograms are
+// not part of the scores, and metadata comes from the file name.
+pub fn rescore_needs_data(changed: &[String]) -> bool {
+    changed.iter().any(|c| c != "histogram" && c != "metadata")
+}
+
+// Brings one worm's scores up to date with a new configuration, given the
+// sections that changed, recomputing only the metrics those sections affect.
+// A worm split into tracks is scored from scratch.
+pub fn the_rescored(id: WormId, old: &[Scores], changed: &[String], input: &[DataLine], config: &Config) -> Vec<Scores> {
+    let selective = changed.iter().all(|c| SELECTIVE_SECTIONS.contains(&c.as_str()));
+    if !selective || !config.selection().is_all() || old.len() != 1 || old[0].track.is_some() { return the_tracks(id, input, config); }
+
+    let precision = config.metrics.precision();
+    let mut s = old[0].clone();
+    // Old scores can read back with default windows the configuration leaves out
+    let mut speeds = WindowSpeeds::of(&config.windows);
+    for name in config.windows.names() { speeds.set(name, s.speeds.get(name).cloned()); }
+    s.speeds = speeds;
+    s.qc.clear_lost_at_edge();
+    s.config_hash = Some(config.hash());
+    s.set_intervals(config.metrics.ci95, precision);
+    if !rescore_needs_data(changed) { return vec![s]; }
+
+    let data = Dataset::new(the_prepared(input, config).lines);
+    if data.first_time().is_none() { return the_tracks(id, input, config); }
+    let has = |name: &str| changed.iter().any(|c| c == name);
+    if has("windows") {
+        s.speeds = data.window_speeds(config);
+        s.arousal = the_arousal(s.speeds.get("calm"), s.speeds.get("aroused"), precision);
+        s.warnings = data.window_warnings(config, &s.speeds);
+    }
+    if has("windows") || has("qc") { s.qc = data.checked_qc(config, &s.speeds); }
+    if has("windowed") || has("composite") { s.windowed = data.configured_windows(config); }
+    if has("bursts") { s.bursts = if config.bursts.report { Some(the_bursts(&config.bursts, precision, data.lines())) } else { None }; }
+    if has("omega") { s.omega = if config.omega.report { Some(the_omega_turns(&config.omega, precision, data.lines())) } else { None }; }
+    if has("derived") { s.extras = the_extras(&config.derived, precision, data.lines()); }
+    if has("composite") { s.composite = data.composite(&config.composite, precision); }
+    if has("exploration") { s.exploration = data.exploration(&config.exploration, precision); }
+    if has("exploration") || has("edge") { s.edge_fraction = the_edge_fraction(&config.edge, config.exploration.arena, precision, data.lines()); }
+    s.set_intervals(config.metrics.ci95, precision);
+    vec![s]
+}
@@ -0,0 +1,108 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use average::Estimate;
+
+use crate::*;
+
+
+// Estimators that take one frame at a time, so that software acquiring data
+// can follow a worm's metrics live.  `finish` can be called at any point and
+// gives what the whole-file functions would for the frames added so far.
+
+// Mean and standard error of one column, such as `RunningSampled::area()`
+#[derive(Debug, Clone)]
+pub struct RunningSampled {
+    of: fn(&DataLine) -> f64,
+    stats: average::Variance,
+}
+
+impl RunningSampled {
+    pub fn new(of: fn(&DataLine) -> f64) -> RunningSampled { RunningSampled{ of, stats: average::Variance::new() } }
+
+    pub fn area() -> RunningSampled { RunningSampled::new(|d| d.area) }
+
+    pub fn midline() -> RunningSampled { RunningSampled::new(|d| d.midline) }
+
+    pub fn speed() -> RunningSampled { RunningSampled::new(|d| d.speed) }
+
+    pub fn add(&mut self, data: &DataLine) { self.add_value((self.of)(data)); }
+
+    pub fn add_value(&mut self, x: f64) { if x.is_finite() { self.stats.add(x); } }
+
+    pub fn finish(&self, precision: Precision) -> Sampled { Sampled::of_variance(&self.stats, precision) }
+}
+
+// As `the_speed_of`: the mean speed, and the largest running median of five
+#[derive(Debug, Clone)]
+pub struct RunningSpeed {
+    stats: average::Variance,
+    five: [f64; 5],
+    max: f64,
+    n: u64,
+}
+
+impl Default for RunningSpeed {
+    fn default() -> Self { RunningSpeed::new() }
+}
+
+impl RunningSpeed {
+    pub fn new() -> RunningSpeed { RunningSpeed{ stats: average::Variance::new(), five: [0.0; 5], max: 0.0, n: 0 } }
+
+    pub fn add(&mut self, data: &DataLine) {
+        if !data.speed.is_finite() { return; }
+        self.stats.add(data.speed);
+        self.five[(self.n % 5) as usize] = data.speed;
+        self.n += 1;
+        if self.n >= 5 { self.max = self.max.max(median5(&self.five)); }
+    }
+
+    // Needs at least five valid speeds
+    pub fn finish(&self, precision: Precision) -> Option<Speed> {
+        if self.n >= 5 { Some(Speed::of(&self.stats, self.max, precision)) } else { None }
+    }
+}
+
+// As `the_coord`, for one column such as `RunningCoord::x()`
+#[derive(Debug, Clone)]
+pub struct RunningCoord {
+    of: fn(&DataLine) -> f64,
+    first: f64,
+    last: f64,
+    bound0: f64,
+    bound1: f64,
+    stats: average::Variance,
+}
+
+impl RunningCoord {
+    pub fn new(of: fn(&DataLine) -> f64) -> RunningCoord {
+        RunningCoord{ of, first: std::f64::NAN, last: std::f64::NAN, bound0: std::f64::NAN, bound1: std::f64::NAN, stats: average::Variance::new() }
+    }
+
+    pub fn x() -> RunningCoord { RunningCoord::new(|d| d.x) }
+
+    pub fn y() -> RunningCoord { RunningCoord::new(|d| d.y) }
+
+    pub fn add(&mut self, data: &DataLine) { self.add_value((self.of)(data)); }
+
+    pub fn add_value(&mut self, a: f64) {
+        if !a.is_finite() { return; }
+        if self.stats.is_empty() {
+            self.first = a;
+            self.bound0 = a;
+            self.bound1 = a;
+        }
+        else {
+            if a < self.bound0 { self.bound0 = a; }
+            if a > self.bound1 { self.bound1 = a; }
+        }
+        self.last = a;
+        self.stats.add(a);
+    }
+
+    pub fn finish(&self, precision: Precision) -> Coord {
+        if self.stats.is_empty() { return Coord::zero(); }
+        Coord{ first: self.first, last: self.last, bound0: self.bound0, bound1: self.bound1, stats: Sampled::of_variance(&self.stats, precision) }
+    }
+}
@@ -0,0 +1,78 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use std::collections::BTreeSet;
+
+
+// Metrics that can be computed on their own, besides the speed in each speed
+// window, named `speed:<window>` and computed after `shape`.  Those not
+// selected are left missing, so the table keeps the same columns.
+pub const METRIC_NAMES: [&str; 17] = [
+    "area", "midline", "rates", "shape", "arousal", "x", "y", "spread",
+    "windowed", "derived", "bursts", "omega", "exploration", "composite", "edge", "qc", "intervals",
+];
+
+// Names that stand for several metrics; `speed` also stands for every speed window
+const GROUPS: [(&str, &[&str]); 1] = [
+    ("coord", &["x", "y"]),
+];
+
+// What each metric is computed from, besides the prepared data every metric uses
+const DEPENDS_ON: [(&str, &[&str]); 3] = [
+    ("arousal", &["speed:calm", "speed:aroused"]),
+    ("spread", &["x", "y"]),
+    ("intervals", &["qc"]),
+];
+
+// The metrics to compute: those named and everything they depend on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetricSelection {
+    known: Vec<String>,
+    selected: BTreeSet<String>,
+}
+
+impl MetricSelection {
+    // Every metric there is with these speed windows, in order
+    fn known(windows: &[&str]) -> Vec<String> {
+        let (before, after) = METRIC_NAMES.split_at(4);
+        before.iter().map(|m| m.to_string())
+            .chain(windows.iter().map(|w| format!("speed:{}", w)))
+            .chain(after.iter().map(|m| m.to_string()))
+            .collect()
+    }
+
+    pub fn all(windows: &[&str]) -> MetricSelection {
+        let known = MetricSelection::known(windows);
+        MetricSelection{ selected: known.iter().cloned().collect(), known }
+    }
+
+    // No names means every metric
+    pub fn of(names: &[String], windows: &[&str]) -> Result<MetricSelection, String> {
+        if names.is_empty() { return Ok(MetricSelection::all(windows)); }
+        let known = MetricSelection::known(windows);
+        let mut pending: Vec<String> = Vec::new();
+        for name in names.iter().map(|n| n.trim()) {
+            if name == "speed" { pending.extend(windows.iter().map(|w| format!("speed:{}", w))); }
+            else if let Some((_, members)) = GROUPS.iter().find(|(g, _)| *g == name) { pending.extend(members.iter().map(|m| m.to_string())); }
+            else if known.iter().any(|m| m == name) { pending.push(name.to_string()); }
+            else {
+                let groups: Vec<&str> = GROUPS.iter().map(|(g, _)| *g).collect();
+                return Err(format!("unknown metric {:?}; use {}, speed, or {}", name, known.join(", "), groups.join(", ")));
+            }
+        }
+        let mut selected = BTreeSet::new();
+        while let Some(m) = pending.pop() {
+            if selected.contains(&m) { continue; }
+            if let Some((_, needs)) = DEPENDS_ON.iter().find(|(k, _)| *k == m) { pending.extend(needs.iter().map(|n| n.to_string())); }
+            selected.insert(m);
+        }
+        Ok(MetricSelection{ known, selected })
+    }
+
+    pub fn has(&self, name: &str) -> bool { self.selected.contains(name) }
+
+    pub fn is_all(&self) -> bool { self.known.iter().all(|m| self.has(m)) }
+
+    pub fn names(&self) -> Vec<&str> { self.known.iter().map(|m| m.as_str()).filter(|m| self.has(m)).collect() }
+}
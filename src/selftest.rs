@@ -0,0 +1,48 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use crate::*;
+
+
+// Worms for `metrology selftest`: the standard assay with noise, a faster
+// worm that loses frames, and a slowly speeding worm with no taps and a gap
+pub fn selftest_worms() -> Vec<(WormId, SynthSettings)> {
+    vec![
+        (1, SynthSettings{ noise: 0.01, seed: 11, ..SynthSettings::assay() }),
+        (2, SynthSettings{ speed: SpeedProfile::Constant(0.15), noise: 0.02, missing: 0.02, seed: 12, ..SynthSettings::assay() }),
+        (3, SynthSettings{ speed: SpeedProfile::Linear{ start: 0.05, slope: 1e-4 }, gaps: vec![Window::new(100.0, 120.0)], seed: 13, ..SynthSettings::default() }),
+    ]
+}
+
+// A track as choreography writes it, one frame per line
+pub fn dat_text(lines: &[DataLine]) -> String {
+    let mut text = String::new();
+    for d in lines.iter() { text.push_str(format!("{} {} {} {} {} {}\n", d.time, d.area, d.speed, d.midline, d.x, d.y).as_str()); }
+    text
+}
+
+// Columns of the scores table the self-test worms must have, with the default
+// configuration.  They change only when the generator or the metrics do.
+pub const SELFTEST_GOLDEN: [(WormId, &str, f64); 21] = [
+    (1, "calm-n", 501.0), (1, "initial-mean", 0.099854), (1, "calm-mean", 0.099178), (1, "aroused-mean", 0.100426),
+    (1, "area-n", 11501.0), (1, "x-mean", 10.788493), (1, "y-mean", 9.100917),
+    (2, "calm-n", 483.0), (2, "initial-mean", 0.151723), (2, "calm-mean", 0.151274), (2, "aroused-mean", 0.150016),
+    (2, "area-n", 11257.0), (2, "x-mean", 3.902194), (2, "y-mean", 11.694824),
+    (3, "calm-n", 501.0), (3, "initial-mean", 0.0515), (3, "calm-mean", 0.078), (3, "aroused-mean", 0.0945),
+    (3, "area-n", 11000.0), (3, "x-mean", 9.426466), (3, "y-mean", 9.984028),
+];
+
+// Each way the scores differ from the golden values; none if they match to
+// about five digits, which leaves room for platforms' math libraries
+pub fn selftest_failures(scores: &[Scores]) -> Vec<String> {
+    let mut failures = Vec::new();
+    let ids: Vec<WormId> = scores.iter().map(|s| s.id).collect();
+    let expected: Vec<WormId> = selftest_worms().iter().map(|(id, _)| *id).collect();
+    if ids != expected { failures.push(format!("expected worms {:?} but found {:?}", expected, ids)); }
+    for (id, metric, value) in SELFTEST_GOLDEN.iter() {
+        let found = scores.iter().find(|s| s.id == *id).and_then(|s| metric_of(s, metric)).unwrap_or(std::f64::NAN);
+        if !((found - value).abs() <= 1e-5 * value.abs().max(1e-3)) { failures.push(format!("worm {} {} is {}, not {}", id, metric, found, value)); }
+    }
+    failures
+}
@@ -0,0 +1,113 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use serde::{Serialize, Deserialize};
+
+use crate::*;
+
+
+// Where per-frame speeds come from.  The tracker's speed column is a finite
+// difference of position, which amplifies positional noise; a Savitzky-Golay
+// fit of x(t) and y(t) gives the velocity as the slope of a local polynomial.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum SpeedEstimator {
+    Tracker,
+    SavitzkyGolay {
+        #[serde(default = "SpeedEstimator::default_half_width")]
+        half_width: usize,
+        #[serde(default = "SpeedEstimator::default_order")]
+        order: usize,
+    },
+}
+
+impl SpeedEstimator {
+    fn default_half_width() -> usize { 5 }
+
+    fn default_order() -> usize { 2 }
+
+    pub fn errors(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if let SpeedEstimator::SavitzkyGolay{ half_width, order } = self {
+            if *order < 1 || *order > 4 { errors.push(format!("Savitzky-Golay order must be from 1 to 4, not {}", order)); }
+            if 2 * half_width < *order {
+                errors.push(format!("Savitzky-Golay window of {} frames is too short for order {}", 2 * half_width + 1, order));
+            }
+        }
+        errors
+    }
+
+    // Replaces the speed column with the estimated speed; frames without a
+    // finite time and position get NaN.
+    pub fn apply(&self, precision: Precision, lines: &mut [DataLine]) {
+        if let SpeedEstimator::SavitzkyGolay{ half_width, order } = self {
+            let speeds = savitzky_golay_speeds(*half_width, *order, precision, lines);
+            for (d, s) in lines.iter_mut().zip(speeds.into_iter()) { d.speed = s; }
+        }
+    }
+}
+
+impl Default for SpeedEstimator {
+    fn default() -> Self { SpeedEstimator::Tracker }
+}
+
+// Solves a small linear system by Gaussian elimination with partial pivoting
+pub(crate) fn solve(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Option<Vec<f64>> {
+    let n = b.len();
+    for k in 0..n {
+        let p = (k..n).max_by(|i, j| a[*i][k].abs().partial_cmp(&a[*j][k].abs()).unwrap())?;
+        if !(a[p][k].abs() > 1e-300) { return None; }
+        a.swap(k, p);
+        b.swap(k, p);
+        for i in (k+1)..n {
+            let f = a[i][k] / a[k][k];
+            for j in k..n { a[i][j] -= f * a[k][j]; }
+            b[i] -= f * b[k];
+        }
+    }
+    let mut x = vec![0.0; n];
+    for k in (0..n).rev() {
+        let s: f64 = ((k+1)..n).map(|j| a[k][j] * x[j]).sum();
+        x[k] = (b[k] - s) / a[k][k];
+    }
+    Some(x)
+}
+
+// Slope at tau = 0 of the least-squares polynomial through (tau, v)
+fn slope_at_zero(order: usize, taus: &[f64], values: &[f64]) -> Option<f64> {
+    let m = order + 1;
+    let mut a = vec![vec![0.0; m]; m];
+    let mut b = vec![0.0; m];
+    for (tau, v) in taus.iter().zip(values.iter()) {
+        let powers: Vec<f64> = (0..(2*m)).map(|k| tau.powi(k as i32)).collect();
+        for i in 0..m {
+            for j in 0..m { a[i][j] += powers[i + j]; }
+            b[i] += powers[i] * v;
+        }
+    }
+    solve(a, b).map(|c| c[1])
+}
+
+// Speed at each frame from local polynomial fits over 2*half_width + 1 valid
+// frames, shifted inward at the ends of the track.  Times are used as given,
+// so uneven frame intervals are handled correctly.
+pub fn savitzky_golay_speeds(half_width: usize, order: usize, precision: Precision, input: &[DataLine]) -> Vec<f64> {
+    let mut speeds = vec![std::f64::NAN; input.len()];
+    let valid: Vec<usize> = (0..input.len())
+        .filter(|&i| input[i].time.is_finite() && input[i].x.is_finite() && input[i].y.is_finite())
+        .collect();
+    let width = 2 * half_width + 1;
+    if valid.len() < width || width <= order { return speeds; }
+    for (k, &i) in valid.iter().enumerate() {
+        let start = std::cmp::min(k.saturating_sub(half_width), valid.len() - width);
+        let near = &valid[start .. start + width];
+        let taus: Vec<f64> = near.iter().map(|&j| input[j].time - input[i].time).collect();
+        let xs: Vec<f64> = near.iter().map(|&j| input[j].x).collect();
+        let ys: Vec<f64> = near.iter().map(|&j| input[j].y).collect();
+        if let (Some(vx), Some(vy)) = (slope_at_zero(order, &taus, &xs), slope_at_zero(order, &taus, &ys)) {
+            speeds[i] = precision.r6(vx.hypot(vy));
+        }
+    }
+    speeds
+}
@@ -0,0 +1,31 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use crate::*;
+
+
+// The first and last `n` lines of a track file, which is kept whole if it is
+// no longer than that.  Enough to test that a format parses, and small enough
+// to commit.
+pub fn snapshot_bytes(bytes: &[u8], n: usize) -> Vec<u8> {
+    let lines: Vec<&[u8]> = bytes.split_inclusive(|&c| c == b'\n').collect();
+    if lines.len() <= 2 * n { return bytes.to_vec(); }
+    let mut kept: Vec<u8> = lines[..n].concat();
+    kept.extend(lines[lines.len() - n..].concat());
+    if !kept.ends_with(b"\n") { kept.push(b'\n'); }
+    kept
+}
+
+// A fixture's expected scores and table, by extension, as the regression
+// tests compare them: worm 1 with the default configuration, without the
+// configuration hash, which changes with every new setting
+pub fn golden_outputs(lines: &[DataLine]) -> Result<Vec<(&'static str, String)>, MetrologyError> {
+    let mut scores = the_everything(1, lines, &Config::default());
+    scores.config_hash = None;
+    let scores = vec![scores];
+    Ok(vec![
+        ("scores", serde_json::to_string_pretty(&scores)? + "\n"),
+        ("csv", table_of(&scores)?),
+    ])
+}
@@ -0,0 +1,47 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use std::fmt;
+use std::fmt::Display;
+
+use serde::{Serialize, Deserialize};
+
+use crate::*;
+
+
+// How much of the plate a worm covered: the area of the bounding box of its
+// positions (mm^2), and its radius of gyration (mm), the root mean square
+// distance from the centroid.  Both come from the x and y summaries.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Spread {
+    #[serde(deserialize_with = "nan_if_null")]
+    pub bbox_area: f64,
+    #[serde(deserialize_with = "nan_if_null")]
+    pub gyration: f64,
+}
+
+impl Spread {
+    pub fn zero() -> Spread { Spread{ bbox_area: std::f64::NAN, gyration: std::f64::NAN } }
+}
+
+impl Display for Spread {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.bbox_area, self.gyration)
+    }
+}
+
+impl StaticEntitled for Spread {
+    fn titles(specifier: &str) -> Vec<String> { prefixed(specifier, &["bbox-area", "gyration"]) }
+}
+
+// The variance about the mean, dividing by n, is sem^2 (n - 1)
+fn spread_variance(s: &Sampled) -> f64 {
+    if s.n > 0 { s.sem * s.sem * (s.n - 1) as f64 } else { std::f64::NAN }
+}
+
+pub fn the_spread(x: &Coord, y: &Coord, precision: Precision) -> Spread {
+    let bbox_area = (x.bound1 - x.bound0) * (y.bound1 - y.bound0);
+    let gyration = if x.stats.n == 1 && y.stats.n == 1 { 0.0 } else { (spread_variance(&x.stats) + spread_variance(&y.stats)).sqrt() };
+    Spread{ bbox_area: precision.r6(bbox_area), gyration: precision.r6(gyration) }
+}
@@ -0,0 +1,70 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use serde::{Serialize, Deserialize};
+
+use crate::*;
+
+
+// A jump of more than `threshold` body lengths between consecutive frames is
+// taken to be the tracker swapping one worm's identity for another's.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SwapPolicy {
+    pub threshold: f64,
+    pub split: bool,
+}
+
+impl Default for SwapPolicy {
+    fn default() -> Self { SwapPolicy{ threshold: 1.0, split: false } }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Swap {
+    pub time: f64,
+    pub jump: f64,
+}
+
+// Returns the index of the first frame after each suspicious jump
+pub fn find_swaps(policy: &SwapPolicy, precision: Precision, input: &[DataLine]) -> Vec<(usize, Swap)> {
+    let body = the_midline(input);
+    if body.len() == 0 || !(body.mean() > 0.0) { return Vec::new(); }
+    let limit = policy.threshold * body.mean();
+
+    let mut swaps = Vec::new();
+    let mut previous: Option<&DataLine> = None;
+    for (i, data) in input.iter().enumerate() {
+        if !(data.x.is_finite() && data.y.is_finite()) { continue; }
+        if let Some(p) = previous {
+            let jump = ((data.x - p.x).powi(2) + (data.y - p.y).powi(2)).sqrt();
+            if jump > limit { swaps.push((i, Swap{ time: data.time, jump: precision.r6(jump) })); }
+        }
+        previous = Some(data);
+    }
+    swaps
+}
+
+// Scores the record as a whole, or, if the policy says to split, scores each
+// piece between suspected swaps as its own track, numbered from 1.
+pub fn the_tracks(id: WormId, input: &[DataLine], config: &Config) -> Vec<Scores> {
+    let swaps = find_swaps(&config.swaps, config.metrics.precision(), input);
+    if !config.swaps.split || swaps.len() == 0 {
+        let mut scores = the_everything(id, input, config);
+        scores.swaps = swaps.into_iter().map(|s| s.1).collect();
+        return vec![scores];
+    }
+
+    let mut tracks = Vec::with_capacity(swaps.len() + 1);
+    let mut i0 = 0;
+    let mut cause: Option<Swap> = None;
+    for (i, swap) in swaps.into_iter().chain(std::iter::once((input.len(), Swap{ time: std::f64::NAN, jump: std::f64::NAN }))) {
+        let mut scores = the_everything(id, &input[i0..i], config);
+        scores.track = Some(tracks.len() as u32 + 1);
+        scores.swaps = cause.into_iter().collect();
+        tracks.push(scores);
+        i0 = i;
+        cause = Some(swap);
+    }
+    tracks
+}
@@ -0,0 +1,98 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use crate::*;
+
+
+// Tracks with known properties, for power analyses and for testing the
+// metrics.  The same settings and seed always give the same track.
+
+// Speed in mm/s before noise and stimulus responses
+#[derive(Debug, Clone, PartialEq)]
+pub enum SpeedProfile {
+    Constant(f64),
+    Linear{ start: f64, slope: f64 },
+
+    // (time, speed) pairs in increasing time; each speed holds until the next time
+    Steps(Vec<(f64, f64)>),
+}
+
+impl SpeedProfile {
+    pub fn at(&self, t: f64) -> f64 {
+        match self {
+            SpeedProfile::Constant(v)          => *v,
+            SpeedProfile::Linear{ start, slope } => start + slope * t,
+            SpeedProfile::Steps(steps)         => steps.iter().take_while(|(t0, _)| *t0 <= t).last().map(|s| s.1).unwrap_or(0.0),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SynthSettings {
+    pub duration: f64,
+    pub rate: f64,
+    pub speed: SpeedProfile,
+
+    // Standard deviation of noise added to each frame's speed, in mm/s
+    pub noise: f64,
+    pub area: f64,
+    pub midline: f64,
+
+    // Stimulus times; each adds `response` mm/s to the speed, decaying with time constant `decay` s
+    pub stimuli: Vec<f64>,
+    pub response: f64,
+    pub decay: f64,
+
+    // Frames whose values are all NaN: those in `gaps`, and each other with chance `missing`
+    pub gaps: Vec<Window>,
+    pub missing: f64,
+
+    // Radians per square root second that the heading wanders
+    pub turning: f64,
+    pub seed: u64,
+}
+
+impl Default for SynthSettings {
+    fn default() -> Self {
+        SynthSettings{
+            duration: 460.0, rate: 25.0, speed: SpeedProfile::Constant(0.1), noise: 0.0, area: 0.08, midline: 1.0,
+            stimuli: Vec::new(), response: 0.0, decay: 5.0, gaps: Vec::new(), missing: 0.0, turning: 0.5, seed: 0
+        }
+    }
+}
+
+impl SynthSettings {
+    // The standard assay: twelve taps ten seconds apart after five minutes
+    pub fn assay() -> SynthSettings {
+        SynthSettings{ stimuli: (0..12).map(|k| 300.0 + 10.0 * k as f64).collect(), response: 0.1, ..SynthSettings::default() }
+    }
+
+    // Speed at `t` before noise
+    pub fn expected_speed(&self, t: f64) -> f64 {
+        let aroused: f64 = self.stimuli.iter().filter(|&&s| s <= t).map(|s| self.response * (-(t - s) / self.decay).exp()).sum();
+        (self.speed.at(t) + aroused).max(0.0)
+    }
+}
+
+// Positions follow the noisy speed along a wandering heading, from (10, 10)
+pub fn synth_track(settings: &SynthSettings) -> Vec<DataLine> {
+    let mut rng = Rng::new(settings.seed);
+    let n = (settings.duration * settings.rate).floor() as usize + 1;
+    let dt = 1.0 / settings.rate;
+    let (mut x, mut y, mut heading) = (10.0, 10.0, 2.0 * std::f64::consts::PI * rng.next_f64());
+    let mut lines = Vec::with_capacity(n);
+    for i in 0..n {
+        let time = i as f64 * dt;
+        let speed = (settings.expected_speed(time) + settings.noise * rng.next_gaussian()).max(0.0);
+        if i > 0 {
+            heading += settings.turning * dt.sqrt() * rng.next_gaussian();
+            x += speed * dt * heading.cos();
+            y += speed * dt * heading.sin();
+        }
+        let lost = rng.next_f64() < settings.missing || settings.gaps.iter().any(|g| g.start <= time && time <= g.end);
+        let d = DataLine{ time, area: settings.area, speed, midline: settings.midline, x, y, frame: i as u64, source_line: 0 };
+        lines.push(if lost { DataLine{ area: std::f64::NAN, speed: std::f64::NAN, midline: std::f64::NAN, x: std::f64::NAN, y: std::f64::NAN, ..d } } else { d });
+    }
+    lines
+}
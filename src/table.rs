@@ -0,0 +1,147 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use std::fmt::Display;
+
+use crate::*;
+
+
+// Every row must have exactly as many space-separated values as the title has
+// column names; anything else would silently shift columns in the output.
+pub fn check_widths(title: &str, rows: &[String]) -> Result<(), MetrologyError> {
+    let expected = title.split_whitespace().count();
+    for (i, row) in rows.iter().enumerate() {
+        let found = row.split_whitespace().count();
+        if found != expected { return Err(MetrologyError::Schema{ row: i + 1, expected, found }); }
+    }
+    Ok(())
+}
+
+// Title line followed by one line per item, checked for consistent width
+pub fn table_of<T: Entitled + Display>(items: &[T]) -> Result<String, MetrologyError> {
+    match items.first() {
+        Some(x) => table_with(x.title().as_str(), items),
+        None    => Ok(String::new()),
+    }
+}
+
+// As `table_of` but with the title given, so a table with no rows still has a header
+pub fn table_with<T: Display>(title: &str, items: &[T]) -> Result<String, MetrologyError> {
+    let rows: Vec<String> = items.iter().map(|x| x.to_string()).collect();
+    check_widths(title, &rows)?;
+
+    let mut table = String::new();
+    table.push_str(title);
+    table.push('\n');
+    for row in rows.iter() {
+        table.push_str(row.as_str());
+        table.push('\n');
+    }
+    Ok(table)
+}
+
+// Positions of the wanted columns in the title, in the order wanted
+pub fn column_indices(title: &str, wanted: &[String]) -> Result<Vec<usize>, MetrologyError> {
+    let names: Vec<&str> = title.split_whitespace().collect();
+    let mut indices = Vec::with_capacity(wanted.len());
+    let mut unknown = Vec::new();
+    for w in wanted.iter() {
+        match names.iter().position(|n| n == w) {
+            Some(i) => indices.push(i),
+            None    => unknown.push(w.as_str()),
+        }
+    }
+    if unknown.len() > 0 { return Err(MetrologyError::BadConfig(format!("no columns named {}", unknown.join(", ")))); }
+    Ok(indices)
+}
+
+// Keeps only the wanted columns of a table made by `table_of` or `table_with`,
+// header and rows alike
+pub fn select_columns(table: &str, wanted: &[String]) -> Result<String, MetrologyError> {
+    let mut lines = table.lines();
+    let title = match lines.next() {
+        Some(t) => t,
+        None    => return Ok(String::new()),
+    };
+    let indices = column_indices(title, wanted)?;
+    let mut selected = String::new();
+    for line in std::iter::once(title).chain(lines) {
+        let values: Vec<&str> = line.split_whitespace().collect();
+        let picked: Vec<&str> = indices.iter().map(|&i| values[i]).collect();
+        selected.push_str(picked.join(" ").as_str());
+        selected.push('\n');
+    }
+    Ok(selected)
+}
+
+// Time and speed of each frame, as scored (after removing repeated frames and
+// out-of-range values, and re-estimating speed if configured)
+pub fn speed_series_table(lines: &[DataLine]) -> String {
+    let mut out = String::with_capacity(24 * (lines.len() + 1));
+    out.push_str("time speed\n");
+    for d in lines { out.push_str(format!("{} {}\n", d.time, d.speed).as_str()); }
+    out
+}
+
+// A first line naming the configuration, so tables scored differently are not
+// pooled by mistake; most readers skip it as a comment (pandas with
+// `comment="#"`).
+pub fn with_config_comment(table: &str, config_hash: &str) -> String {
+    format!("# config {}\n{}", config_hash, table)
+}
+
+// `table`'s rows added below those of `existing`, a table written earlier
+// with the same missing-value token.  Comment lines, which name the
+// configuration, must be the same in both.  A column only one of them has is
+// kept, filled with `missing` in the other's rows, so columns new to `table`
+// go at the end.
+pub fn append_table(existing: &str, table: &str, missing: &str) -> Result<String, MetrologyError> {
+    let split = |text: &str| -> (Vec<String>, Vec<String>) {
+        let (comments, rest): (Vec<&str>, Vec<&str>) = text.lines().filter(|l| l.len() > 0).partition(|l| l.starts_with('#'));
+        (comments.into_iter().map(|l| l.to_string()).collect(), rest.into_iter().map(|l| l.to_string()).collect())
+    };
+    let (old_comments, old) = split(existing);
+    let (new_comments, new) = split(table);
+    if old.is_empty() { return Ok(table.to_string()); }
+    if new.is_empty() { return Ok(existing.to_string()); }
+    if old_comments != new_comments {
+        return Err(MetrologyError::BadConfig(format!("the table to append to begins {:?}, not {:?}", old_comments.join(" "), new_comments.join(" "))));
+    }
+    let cells = |line: &str| -> Vec<String> { line.split(' ').map(|c| c.to_string()).collect() };
+    let (old_titles, new_titles) = (cells(old[0].as_str()), cells(new[0].as_str()));
+    for (lines, titles) in [(&old, &old_titles), (&new, &new_titles)].iter() {
+        if let Some((i, row)) = lines[1..].iter().map(|l| cells(l.as_str())).enumerate().find(|(_, r)| r.len() != titles.len()) {
+            return Err(MetrologyError::Schema{ row: i + 1, expected: titles.len(), found: row.len() });
+        }
+    }
+
+    let mut titles = old_titles.clone();
+    titles.extend(new_titles.iter().filter(|t| !old_titles.contains(t)).cloned());
+    let mut out = String::new();
+    for c in old_comments.iter() { out.push_str(c.as_str()); out.push('\n'); }
+    out.push_str(titles.join(" ").as_str());
+    out.push('\n');
+    for (lines, had) in [(&old, &old_titles), (&new, &new_titles)].iter() {
+        for row in lines[1..].iter().map(|l| cells(l.as_str())) {
+            let picked: Vec<&str> = titles.iter().map(|t| had.iter().position(|h| h == t).map(|i| row[i].as_str()).unwrap_or(missing)).collect();
+            out.push_str(picked.join(" ").as_str());
+            out.push('\n');
+        }
+    }
+    Ok(out)
+}
+
+// Writes missing values (NaN) as the given token instead, which may be empty.
+// Rows must be checked and columns selected first, as an empty token leaves
+// adjacent spaces.
+pub fn with_missing(table: &str, token: &str) -> String {
+    if token == "NaN" { return table.to_string(); }
+    let mut out = String::with_capacity(table.len());
+    for line in table.lines() {
+        let values: Vec<&str> = line.split(' ').map(|v| if v == "NaN" { token } else { v }).collect();
+        out.push_str(values.join(" ").as_str());
+        out.push('\n');
+    }
+    out
+}
@@ -0,0 +1,72 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use serde::{Serialize, Deserialize};
+
+use crate::*;
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Well {
+    pub x: f64,
+    pub y: f64,
+    pub radius: f64,
+}
+
+// Edge preference needs the arena's shape: a circular well if one is given,
+// otherwise the rectangular `[exploration.arena]`.  Distance in mm.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EdgeSettings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub distance: Option<f64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub well: Option<Well>,
+}
+
+impl EdgeSettings {
+    pub fn errors(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if let Some(d) = self.distance {
+            if !(d > 0.0) { errors.push(format!("edge distance must be positive, not {}", d)); }
+        }
+        if let Some(w) = self.well {
+            if !(w.radius > 0.0) { errors.push(format!("well radius must be positive, not {}", w.radius)); }
+        }
+        errors
+    }
+}
+
+// Distance from a point to the nearest edge; negative outside
+fn edge_distance(well: Option<Well>, arena: Option<Arena>, x: f64, y: f64) -> Option<f64> {
+    match (well, arena) {
+        (Some(w), _)    => Some(w.radius - (x - w.x).hypot(y - w.y)),
+        (None, Some(a)) => Some((x - a.x0).min(a.x1 - x).min(y - a.y0).min(a.y1 - y)),
+        (None, None)    => None,
+    }
+}
+
+// Fraction of the time spent within the edge distance of the wall.  Each
+// frame counts for the time until the next one, so dropped frames don't
+// bias the answer.
+pub fn the_edge_fraction(settings: &EdgeSettings, arena: Option<Arena>, precision: Precision, input: &[DataLine]) -> Option<f64> {
+    let near = settings.distance?;
+    if settings.well.is_none() && arena.is_none() { return None; }
+
+    let mut edge = 0.0;
+    let mut total = 0.0;
+    let mut previous: Option<&DataLine> = None;
+    for d in input.iter().filter(|d| d.time.is_finite() && d.x.is_finite() && d.y.is_finite()) {
+        if let Some(p) = previous {
+            let dt = d.time - p.time;
+            if dt > 0.0 {
+                total += dt;
+                if edge_distance(settings.well, arena, p.x, p.y).map(|e| e <= near).unwrap_or(false) { edge += dt; }
+            }
+        }
+        previous = Some(d);
+    }
+    if total > 0.0 { Some(precision.r6(edge / total)) } else { None }
+}
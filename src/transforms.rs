@@ -0,0 +1,64 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use serde::{Serialize, Deserialize};
+
+use crate::*;
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Transform {
+    None,
+    Log,
+    Sqrt,
+}
+
+impl Default for Transform {
+    fn default() -> Self { Transform::None }
+}
+
+impl Transform {
+    // Values outside the domain (zero or less for log, negative for sqrt) become NaN
+    pub fn of(self, v: f64) -> f64 {
+        match self {
+            Transform::None => v,
+            Transform::Log  => if v > 0.0 { v.ln() } else { std::f64::NAN },
+            Transform::Sqrt => if v >= 0.0 { v.sqrt() } else { std::f64::NAN },
+        }
+    }
+
+    // Put before the column's name in titles, so `area-mean` becomes `log-area-mean`
+    pub fn prefix(self) -> &'static str {
+        match self {
+            Transform::None => "",
+            Transform::Log  => "log-",
+            Transform::Sqrt => "sqrt-",
+        }
+    }
+}
+
+// Transforms of whole columns, applied after cleaning and before scoring, for
+// analyses that need the variance stabilized.  Every metric computed from a
+// column sees the transformed values, thresholds included.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ColumnTransforms {
+    pub area: Transform,
+    pub midline: Transform,
+    pub speed: Transform,
+}
+
+impl ColumnTransforms {
+    pub fn is_identity(&self) -> bool { *self == ColumnTransforms::default() }
+
+    pub fn apply(&self, lines: &mut [DataLine]) {
+        if self.is_identity() { return; }
+        for d in lines.iter_mut() {
+            d.area = self.area.of(d.area);
+            d.midline = self.midline.of(d.midline);
+            d.speed = self.speed.of(d.speed);
+        }
+    }
+}
@@ -0,0 +1,80 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use std::fmt;
+use std::fmt::Display;
+
+use serde::{Serialize, Deserialize};
+
+use crate::*;
+
+
+// Inclusive [lower, upper] ranges outside of which a value is treated as
+// missing before any statistics are computed.  Area in mm^2, speed in mm/s.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ValidityRanges {
+    pub area: Option<[f64; 2]>,
+    pub midline: Option<[f64; 2]>,
+    pub speed: Option<[f64; 2]>,
+    pub x: Option<[f64; 2]>,
+    pub y: Option<[f64; 2]>,
+}
+
+impl ValidityRanges {
+    fn named(&self) -> [(&'static str, &Option<[f64; 2]>); 5] {
+        [("area", &self.area), ("midline", &self.midline), ("speed", &self.speed), ("x", &self.x), ("y", &self.y)]
+    }
+
+    pub fn is_empty(&self) -> bool { self.named().iter().all(|(_, r)| r.is_none()) }
+
+    pub fn errors(&self) -> Vec<String> {
+        self.named().iter()
+            .filter_map(|(name, r)| r.and_then(|[lo, hi]|
+                if lo <= hi { None } else { Some(format!("validity range for {} is [{}, {}] but lower must not exceed upper", name, lo, hi)) }
+            ))
+            .collect()
+    }
+
+    // Replaces out-of-range values with NaN; None if no ranges are set
+    pub fn apply(&self, lines: &mut [DataLine]) -> Option<OutOfRange> {
+        if self.is_empty() { return None; }
+        fn check(range: &Option<[f64; 2]>, value: &mut f64, count: &mut u64) {
+            if let Some([lo, hi]) = range {
+                if value.is_finite() && (*value < *lo || *value > *hi) {
+                    *value = std::f64::NAN;
+                    *count += 1;
+                }
+            }
+        }
+        let mut out = OutOfRange::default();
+        for d in lines.iter_mut() {
+            check(&self.area,    &mut d.area,    &mut out.area);
+            check(&self.midline, &mut d.midline, &mut out.midline);
+            check(&self.speed,   &mut d.speed,   &mut out.speed);
+            check(&self.x,       &mut d.x,       &mut out.x);
+            check(&self.y,       &mut d.y,       &mut out.y);
+        }
+        Some(out)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OutOfRange {
+    pub area: u64,
+    pub midline: u64,
+    pub speed: u64,
+    pub x: u64,
+    pub y: u64,
+}
+
+impl OutOfRange {
+    pub fn total(&self) -> u64 { self.area + self.midline + self.speed + self.x + self.y }
+}
+
+impl Display for OutOfRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "area {}, midline {}, speed {}, x {}, y {}", self.area, self.midline, self.speed, self.x, self.y)
+    }
+}
@@ -0,0 +1,70 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use std::fmt;
+use std::fmt::Display;
+
+
+// What a line of verbose output is about, so a long run can show detail on
+// one thing without the rest
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogArea {
+    Parsing,
+    Scoring,
+    Io,
+}
+
+impl std::str::FromStr for LogArea {
+    type Err = String;
+    fn from_str(s: &str) -> Result<LogArea, String> {
+        match s {
+            "parsing" => Ok(LogArea::Parsing),
+            "scoring" => Ok(LogArea::Scoring),
+            "io"      => Ok(LogArea::Io),
+            _         => Err(format!("unknown area {:?}; use parsing, scoring, or io", s)),
+        }
+    }
+}
+
+impl Display for LogArea {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LogArea::Parsing => write!(f, "parsing"),
+            LogArea::Scoring => write!(f, "scoring"),
+            LogArea::Io      => write!(f, "io"),
+        }
+    }
+}
+
+// Level 1 is a line or two per file, 2 adds detail per worm, and 3 adds
+// every score.  Naming areas limits output to them, at level 1 if none is given.
+#[derive(Debug, Clone, Default)]
+pub struct Verbosity {
+    pub level: u8,
+    pub only: Vec<LogArea>,
+}
+
+impl Verbosity {
+    pub fn new(level: u64, only: &[LogArea]) -> Verbosity {
+        let level = std::cmp::min(level, 3) as u8;
+        Verbosity{ level: if only.len() > 0 { std::cmp::max(level, 1) } else { level }, only: only.to_vec() }
+    }
+
+    pub fn is_quiet(&self) -> bool { self.level == 0 }
+
+    pub fn shows(&self, area: LogArea, level: u8) -> bool {
+        self.level >= level && (self.only.len() == 0 || self.only.contains(&area))
+    }
+
+    // Command-line arguments giving the same verbosity
+    pub fn args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if self.level > 0 { args.push(format!("-{}", "v".repeat(self.level as usize))); }
+        if self.only.len() > 0 {
+            args.push("--verbose-only".to_string());
+            args.push(self.only.iter().map(|a| a.to_string()).collect::<Vec<_>>().join(","));
+        }
+        args
+    }
+}
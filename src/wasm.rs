@@ -0,0 +1,27 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use wasm_bindgen::prelude::*;
+
+use crate::*;
+
+
+fn to_js(json: Result<String, MetrologyError>) -> Result<JsValue, JsValue> {
+    let text = json.map_err(|e| JsValue::from_str(e.to_string().as_str()))?;
+    js_sys::JSON::parse(text.as_str())
+}
+
+// Scores the contents of one .dat file with the default configuration.  The
+// result is the array of scores (one per track), as it appears in the scores
+// file; errors are thrown as strings.
+#[wasm_bindgen]
+pub fn score_bytes(bytes: &[u8]) -> Result<JsValue, JsValue> {
+    to_js(scores_json(0, bytes, &Config::default()))
+}
+
+// As `score_bytes`, for a given worm id and a configuration in TOML
+#[wasm_bindgen]
+pub fn score_bytes_with(bytes: &[u8], id: u32, config: &str) -> Result<JsValue, JsValue> {
+    to_js(Config::from_toml(config).and_then(|c| scores_json(id.into(), bytes, &c)))
+}
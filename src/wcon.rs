@@ -0,0 +1,183 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use serde_json::Value;
+
+use crate::*;
+
+
+// WCON (Worm tracker Commons Object Notation) files are JSON with a `units`
+// block and `data` records of times and positions.  Position is the centroid
+// `cx`, `cy` if given, else the mean of the skeleton points in `x`, `y`, in
+// either case shifted by the origin `ox`, `oy` if there is one.  Midline is
+// the skeleton's length, speed is the distance moved since the previous
+// frame, and area is missing since WCON doesn't record it.  Files written by
+// `the_wcon` carry all three under `@metrology`, and those are used instead.
+#[derive(Debug, Clone, Default)]
+pub struct WconFormat {
+    pub precision: Precision,
+}
+
+impl TrackFormat for WconFormat {
+    fn name(&self) -> &'static str { "wcon" }
+
+    fn extension(&self) -> &'static str { "wcon" }
+
+    fn sniff(&self, bytes: &[u8]) -> bool {
+        let bytes = strip_bom(bytes);
+        bytes.iter().find(|c| !c.is_ascii_whitespace()) == Some(&b'{') &&
+            bytes.windows(7).any(|w| w == b"\"units\"")
+    }
+
+    fn parse(&self, bytes: &[u8]) -> Result<Vec<DataLine>, MetrologyError> {
+        let root: Value = serde_json::from_slice(strip_bom(bytes)).map_err(|e| bad_wcon(e.to_string()))?;
+        let units = root.get("units").ok_or_else(|| bad_wcon("no units".to_string()))?;
+        let scale = |name: &str| -> Result<f64, MetrologyError> {
+            let unit = units.get(name).and_then(|u| u.as_str()).ok_or_else(|| bad_wcon(format!("no unit for {}", name)))?;
+            unit_scale(unit).ok_or_else(|| bad_wcon(format!("unknown unit {:?} for {}", unit, name)))
+        };
+        let (st, sx, sy) = (scale("t")?, scale("x")?, scale("y")?);
+
+        let records = match root.get("data") {
+            Some(Value::Array(a)) => a.iter().collect(),
+            Some(d @ Value::Object(_)) => vec![d],
+            _ => return Err(bad_wcon("no data".to_string())),
+        };
+        let mut ids: Vec<String> = records.iter().filter_map(|r| r.get("id")).map(|v| v.to_string()).collect();
+        ids.sort();
+        ids.dedup();
+        if ids.len() > 1 { return Err(bad_wcon(format!("holds {} worms; only one per file can be scored", ids.len()))); }
+
+        let mut frames = Vec::new();
+        for r in records.iter() { frames.extend(record_frames(r, st, sx, sy, self.precision)?); }
+        let has_speed = records.iter().all(|r| r.get("@metrology").and_then(|m| m.get("speed")).is_some());
+        if frames.len() == 0 { return Err(MetrologyError::EmptyData); }
+        frames.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap_or(std::cmp::Ordering::Equal));
+
+        let mut previous: Option<(f64, f64, f64)> = None;
+        for (i, d) in frames.iter_mut().enumerate() {
+            d.frame = i as u64;
+            if let (Some((t, x, y)), false) = (previous, has_speed) { 
+                if d.time > t { d.speed = self.precision.r6((d.x - x).hypot(d.y - y) / (d.time - t)); }
+            }
+            if d.time.is_finite() && d.x.is_finite() && d.y.is_finite() { previous = Some((d.time, d.x, d.y)); }
+        }
+        Ok(frames)
+    }
+}
+
+fn bad_wcon(message: String) -> MetrologyError { MetrologyError::Decode(format!("WCON file {}", message)) }
+
+// Factor taking the unit to mm or s
+fn unit_scale(unit: &str) -> Option<f64> {
+    match unit.trim() {
+        "s" | "sec" | "second" | "seconds" => Some(1.0),
+        "ms" | "millisecond" | "milliseconds" => Some(1e-3),
+        "min" | "minute" | "minutes" => Some(60.0),
+        "h" | "hr" | "hour" | "hours" => Some(3600.0),
+        "mm" | "millimeter" | "millimeters" | "millimetre" | "millimetres" => Some(1.0),
+        "um" | "µm" | "micron" | "microns" | "micrometer" | "micrometers" | "micrometre" | "micrometres" => Some(1e-3),
+        "cm" | "centimeter" | "centimeters" | "centimetre" | "centimetres" => Some(10.0),
+        "m" | "meter" | "meters" | "metre" | "metres" => Some(1e3),
+        _ => None,
+    }
+}
+
+// Null is written for values that weren't measured
+fn number(v: &Value) -> f64 { v.as_f64().unwrap_or(std::f64::NAN) }
+
+// Skeleton points at one time: a single number or an array of them
+fn points(v: Option<&Value>) -> Vec<f64> {
+    match v {
+        Some(Value::Array(a)) => a.iter().map(number).collect(),
+        Some(x)               => vec![number(x)],
+        None                  => Vec::new(),
+    }
+}
+
+// A field's value at frame i of n, where a record with one time holds it directly
+fn at(record: &Value, name: &str, i: usize, n: usize) -> Option<Value> {
+    let v = record.get(name)?;
+    match v {
+        Value::Array(a) if record.get("t").map(|t| t.is_array()).unwrap_or(false) => 
+            if a.len() == n { a.get(i).cloned() } else { None },
+        _ => Some(v.clone()),
+    }
+}
+
+fn mean(values: &[f64]) -> f64 {
+    let finite: Vec<f64> = values.iter().cloned().filter(|v| v.is_finite()).collect();
+    if finite.len() == 0 { std::f64::NAN } else { finite.iter().sum::<f64>() / finite.len() as f64 }
+}
+
+fn record_frames(record: &Value, st: f64, sx: f64, sy: f64, precision: Precision) -> Result<Vec<DataLine>, MetrologyError> {
+    let times: Vec<f64> = match record.get("t") {
+        Some(Value::Array(a)) => a.iter().map(number).collect(),
+        Some(t)               => vec![number(t)],
+        None                  => return Err(bad_wcon("has a data record without t".to_string())),
+    };
+    let n = times.len();
+    let ours = |name: &str, i: usize| -> Option<f64> {
+        record.get("@metrology")?.get(name)?.as_array().filter(|a| a.len() == n).map(|a| number(&a[i]))
+    };
+    let mut frames = Vec::with_capacity(n);
+    for (i, t) in times.iter().enumerate() {
+        let xs = points(at(record, "x", i, n).as_ref());
+        let ys = points(at(record, "y", i, n).as_ref());
+        let ox = at(record, "ox", i, n).map(|v| number(&v)).unwrap_or(0.0);
+        let oy = at(record, "oy", i, n).map(|v| number(&v)).unwrap_or(0.0);
+        let cx = at(record, "cx", i, n).map(|v| number(&v)).unwrap_or_else(|| mean(&xs));
+        let cy = at(record, "cy", i, n).map(|v| number(&v)).unwrap_or_else(|| mean(&ys));
+        let midline = 
+            if xs.len() > 1 && xs.len() == ys.len() {
+                precision.r6(xs.windows(2).zip(ys.windows(2)).map(|(x, y)| (sx * (x[1] - x[0])).hypot(sy * (y[1] - y[0]))).sum())
+            }
+            else { std::f64::NAN };
+        frames.push(DataLine{
+            time: st * t,
+            area: ours("area", i).unwrap_or(std::f64::NAN),
+            speed: ours("speed", i).unwrap_or(std::f64::NAN),
+            midline: ours("midline", i).unwrap_or(midline),
+            x: sx * (cx + ox), y: sy * (cy + oy), frame: 0, source_line: 0
+        });
+    }
+    Ok(frames)
+}
+
+// Per-window metrics of one scored row, as attached to exported WCON
+fn window_metrics(s: &Scores) -> Value {
+    let mut m = serde_json::Map::new();
+    if let Some(k) = s.track { m.insert("track".to_string(), Value::from(k)); }
+    for (name, speed) in s.speeds.iter() {
+        if let Some(sp) = speed { m.insert(name.to_string(), serde_json::to_value(sp).unwrap_or(Value::Null)); }
+    }
+    if let Some(a) = &s.arousal { m.insert("arousal".to_string(), serde_json::to_value(a).unwrap_or(Value::Null)); }
+    if s.windowed.len() > 0 { m.insert("windowed".to_string(), serde_json::to_value(&s.windowed).unwrap_or(Value::Null)); }
+    Value::Object(m)
+}
+
+// One worm as WCON, with its centroid track as x and y, and the rest of its
+// data and its window metrics under `@metrology`.  Non-finite values are
+// written as null.
+pub fn the_wcon(id: WormId, data: &[DataLine], scores: &[Scores], config_hash: &str) -> Value {
+    let column = |f: fn(&DataLine) -> f64| Value::from(data.iter().map(|d| serde_json::to_value(f(d)).unwrap_or(Value::Null)).collect::<Vec<_>>());
+    serde_json::json!({
+        "units": { "t": "s", "x": "mm", "y": "mm", "@metrology": { "area": "mm^2", "speed": "mm/s", "midline": "mm" } },
+        "metadata": {
+            "software": { "name": "metrology", "version": env!("CARGO_PKG_VERSION"), "featureID": "@metrology", "settings": config_hash }
+        },
+        "data": [{
+            "id": id.to_string(),
+            "t": column(|d| d.time),
+            "x": column(|d| d.x),
+            "y": column(|d| d.y),
+            "@metrology": {
+                "area": column(|d| d.area),
+                "speed": column(|d| d.speed),
+                "midline": column(|d| d.midline),
+                "windows": scores.iter().map(window_metrics).collect::<Vec<_>>()
+            }
+        }]
+    })
+}
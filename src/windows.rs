@@ -0,0 +1,537 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use std::fmt;
+use std::fmt::Display;
+
+use serde::{Serialize, Deserialize};
+
+use crate::*;
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Window {
+    pub start: f64,
+    pub end: f64,
+}
+
+impl Window {
+    pub fn new(start: f64, end: f64) -> Window { Window{ start, end } }
+
+    pub fn overlaps(&self, that: &Window) -> bool { self.start < that.end && that.start < self.end }
+
+    pub fn speed(&self, max: &MaxEstimator, precision: Precision, input: &[DataLine]) -> Option<Speed> { the_speed_in(self.start, self.end, max, precision, input) }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Bound {
+    // The frame exactly at the bound is in the window
+    Closed,
+    Open,
+}
+
+// Which frames a speed window is scored from, and whether the recording must
+// be seen to extend past it: some frame before the window, and some after,
+// not counting frames that fall in it.  A frame exactly at an open bound
+// counts as outside.  The default is [start, end] with both required.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowBounds {
+    pub start: Bound,
+    pub end: Bound,
+    pub require_before: bool,
+    pub require_after: bool,
+}
+
+impl Default for WindowBounds {
+    fn default() -> Self { WindowBounds{ start: Bound::Closed, end: Bound::Closed, require_before: true, require_after: true } }
+}
+
+impl WindowBounds {
+    pub fn is_default(&self) -> bool { *self == WindowBounds::default() }
+
+    pub fn is_before(&self, w: &Window, t: f64) -> bool {
+        match self.start { Bound::Closed => t < w.start, Bound::Open => t <= w.start }
+    }
+
+    pub fn is_after(&self, w: &Window, t: f64) -> bool {
+        match self.end { Bound::Closed => t > w.end, Bound::Open => t >= w.end }
+    }
+
+    pub fn contains(&self, w: &Window, t: f64) -> bool { t.is_finite() && !self.is_before(w, t) && !self.is_after(w, t) }
+}
+
+// A window a speed is scored in, written as `<name>_speed` in the scores and
+// as `<name>-` columns in the table
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpeedWindow {
+    pub name: String,
+    pub start: f64,
+    pub end: f64,
+}
+
+impl SpeedWindow {
+    pub fn new(name: &str, start: f64, end: f64) -> SpeedWindow { SpeedWindow{ name: name.to_string(), start, end } }
+
+    pub fn window(&self) -> Window { Window::new(self.start, self.end) }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpeedWindows {
+    // Scored in this order; by default initial, calm, and aroused
+    pub speed: Vec<SpeedWindow>,
+
+    // If set, a window the recording only partly covers is still scored when
+    // at least this fraction of it has data
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_coverage: Option<f64>,
+
+    // If set, the calm window is chosen from the data of the whole run
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quietest: Option<QuietestWindow>,
+
+    // If set, the windows are fractions of each recording, from its first
+    // time (0) to its last (1), instead of seconds
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub fractional: bool,
+
+    #[serde(skip_serializing_if = "WindowBounds::is_default")]
+    pub bounds: WindowBounds,
+}
+
+impl Default for SpeedWindows {
+    fn default() -> Self {
+        SpeedWindows{ 
+            speed: vec![
+                SpeedWindow::new("initial",  10.0,  20.0),
+                SpeedWindow::new("calm",    270.0, 290.0),
+                SpeedWindow::new("aroused", 440.0, 450.0),
+            ],
+            min_coverage: None,
+            quietest: None,
+            fractional: false,
+            bounds: WindowBounds::default(),
+        }
+    }
+}
+
+impl SpeedWindows {
+    pub fn named(&self) -> Vec<(&str, Window)> { self.speed.iter().map(|w| (w.name.as_str(), w.window())).collect() }
+
+    pub fn names(&self) -> Vec<&str> { self.speed.iter().map(|w| w.name.as_str()).collect() }
+
+    pub fn get(&self, name: &str) -> Option<Window> { self.speed.iter().find(|w| w.name == name).map(|w| w.window()) }
+
+    // Replaces the times of a window, or adds the window at the end
+    pub fn set(&mut self, name: &str, window: Window) {
+        match self.speed.iter_mut().find(|w| w.name == name) {
+            Some(w) => { w.start = window.start; w.end = window.end; },
+            None    => self.speed.push(SpeedWindow::new(name, window.start, window.end)),
+        }
+    }
+
+    // Problems that make a window useless for every file
+    pub fn errors(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        for (i, w) in self.speed.iter().enumerate() {
+            if w.name.len() == 0 || w.name.contains(char::is_whitespace) {
+                errors.push(format!("speed window name {:?} must be nonempty without spaces", w.name));
+            }
+            if self.speed[..i].iter().any(|v| v.name == w.name) { errors.push(format!("speed window name {:?} is used twice", w.name)); }
+            if !(w.start < w.end) { errors.push(format!("{} window starts at {} but ends at {}", w.name, w.start, w.end)); }
+        }
+        if let Some(c) = self.min_coverage {
+            if !(c > 0.0 && c <= 1.0) { errors.push(format!("min_coverage must be in (0, 1], not {}", c)); }
+        }
+        if let Some(q) = &self.quietest {
+            errors.extend(q.errors());
+            if self.get("calm").is_none() { errors.push("a quietest calm window needs a speed window named calm".to_string()); }
+        }
+        if self.fractional {
+            for (name, w) in self.named().iter().filter(|(_, w)| !(w.start >= 0.0 && w.end <= 1.0)) {
+                errors.push(format!("fractional {} window [{}, {}] must be within [0, 1]", name, w.start, w.end));
+            }
+            if self.quietest.is_some() { errors.push("a quietest calm window is in seconds, so can't be used with fractional windows".to_string()); }
+        }
+        errors
+    }
+
+    // The windows in seconds for a recording from `first` to `last`
+    pub fn resolved(&self, first: f64, last: f64) -> SpeedWindows {
+        if !self.fractional { return self.clone(); }
+        let at = |w: &SpeedWindow| SpeedWindow{ start: first + w.start * (last - first), end: first + w.end * (last - first), ..w.clone() };
+        SpeedWindows{ speed: self.speed.iter().map(at).collect(), fractional: false, ..self.clone() }
+    }
+
+    // Problems that are probably mistakes but still give answers
+    pub fn warnings(&self) -> Vec<String> {
+        let named = self.named();
+        let mut ws = Vec::new();
+        for i in 0..named.len() {
+            for j in (i+1)..named.len() {
+                if named[i].1.overlaps(&named[j].1) {
+                    ws.push(format!("{} window [{}, {}] overlaps {} window [{}, {}]",
+                        named[i].0, named[i].1.start, named[i].1.end,
+                        named[j].0, named[j].1.start, named[j].1.end
+                    ));
+                }
+            }
+        }
+        ws
+    }
+}
+
+// The calm window is the `length`-second window within `search`, trying
+// starts `step` seconds apart, where the population is slowest: the mean over
+// worms of each worm's mean speed in the window is least.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QuietestWindow {
+    pub search: Window,
+    pub length: f64,
+
+    #[serde(default = "QuietestWindow::default_step")]
+    pub step: f64,
+}
+
+impl QuietestWindow {
+    fn default_step() -> f64 { 1.0 }
+
+    pub fn errors(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if !(self.search.start < self.search.end) { errors.push(format!("quietest window search starts at {} but ends at {}", self.search.start, self.search.end)); }
+        if !(self.length > 0.0 && self.length <= self.search.end - self.search.start) {
+            errors.push(format!("quietest window length {} must be positive and fit within the search interval", self.length));
+        }
+        if !(self.step > 0.0) { errors.push(format!("quietest window step must be positive, not {}", self.step)); }
+        errors
+    }
+
+    pub fn candidates(&self) -> Vec<Window> {
+        let n = ((self.search.end - self.search.start - self.length) / self.step + 1e-9).floor().max(0.0) as usize + 1;
+        (0..n).map(|k| {
+            let start = self.search.start + (k as f64) * self.step;
+            Window::new(start, start + self.length)
+        }).collect()
+    }
+}
+
+// The quietest candidate window, or None if no worm has speeds in any of them
+pub fn the_quietest_window(q: &QuietestWindow, worms: &[Dataset]) -> Option<Window> {
+    let mut best: Option<(Window, f64)> = None;
+    for w in q.candidates() {
+        let means: Vec<f64> = worms.iter().filter_map(|d| {
+            let speeds: average::Variance = d.slice_time(w.start, w.end).iter().map(|x| x.speed).filter(|s| s.is_finite()).collect();
+            if speeds.is_empty() { None } else { Some(speeds.mean()) }
+        }).collect();
+        if means.is_empty() { continue; }
+        let population = means.iter().sum::<f64>() / means.len() as f64;
+        if best.map(|(_, b)| population < b).unwrap_or(true) { best = Some((w, population)); }
+    }
+    best.map(|(w, _)| w)
+}
+
+// Speeds in the speed windows, in order, with None where a window couldn't be
+// scored.  In the scores each is a `<name>_speed` field, as when they were
+// fixed fields, so older files read the same.
+#[derive(Debug, Clone)]
+pub struct WindowSpeeds(Vec<(String, Option<Speed>)>);
+
+impl Default for WindowSpeeds {
+    fn default() -> Self { WindowSpeeds::of(&SpeedWindows::default()) }
+}
+
+impl WindowSpeeds {
+    // Windows with these names, in order, with no speeds yet
+    pub fn named<S: AsRef<str>>(names: &[S]) -> WindowSpeeds { WindowSpeeds(names.iter().map(|n| (n.as_ref().to_string(), None)).collect()) }
+
+    pub fn of(windows: &SpeedWindows) -> WindowSpeeds { WindowSpeeds::named(&windows.names()[..]) }
+
+    pub fn get(&self, name: &str) -> Option<&Speed> { self.0.iter().find(|(n, _)| n == name).and_then(|(_, s)| s.as_ref()) }
+
+    // Replaces the speed of a window, or adds the window at the end
+    pub fn set(&mut self, name: &str, speed: Option<Speed>) {
+        match self.0.iter_mut().find(|(n, _)| n == name) {
+            Some(entry) => entry.1 = speed,
+            None        => self.0.push((name.to_string(), speed)),
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, Option<&Speed>)> { self.0.iter().map(|(n, s)| (n.as_str(), s.as_ref())) }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> { self.0.iter().map(|(n, _)| n.as_str()) }
+}
+
+impl Serialize for WindowSpeeds {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeMap;
+        let mut map = serializer.serialize_map(None)?;
+        // Windows without a speed are null, so every window reads back
+        for (name, speed) in self.0.iter() { map.serialize_entry(format!("{}_speed", name).as_str(), speed)?; }
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for WindowSpeeds {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<WindowSpeeds, D::Error> {
+        struct Fields;
+        impl<'de> serde::de::Visitor<'de> for Fields {
+            type Value = WindowSpeeds;
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result { write!(f, "window speeds as <name>_speed fields") }
+            fn visit_map<A: serde::de::MapAccess<'de>>(self, mut map: A) -> Result<WindowSpeeds, A::Error> {
+                let mut speeds = WindowSpeeds(Vec::new());
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.strip_suffix("_speed") {
+                        Some(name) => { let s: Option<Speed> = map.next_value()?; speeds.set(name, s); },
+                        None       => { map.next_value::<serde::de::IgnoredAny>()?; },
+                    }
+                }
+                // Older files left out default windows without a speed; files
+                // with windows of their own list every window they were scored with
+                let defaults = WindowSpeeds::default();
+                if speeds.names().all(|n| defaults.names().any(|d| d == n)) {
+                    for (i, (name, _)) in defaults.0.into_iter().enumerate() {
+                        if speeds.0.iter().all(|(n, _)| *n != name) { speeds.0.insert(i.min(speeds.0.len()), (name, None)); }
+                    }
+                }
+                Ok(speeds)
+            }
+        }
+        deserializer.deserialize_map(Fields)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "reason")]
+pub enum WindowProblem {
+    Empty,
+    BeforeRecording{ #[serde(deserialize_with = "nan_if_null")] first: f64 },
+    AfterRecording{ #[serde(deserialize_with = "nan_if_null")] last: f64 },
+    TooFewSamples{ n: u64 },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WindowWarning {
+    pub window: String,
+    pub start: f64,
+    pub end: f64,
+
+    #[serde(flatten)]
+    pub problem: WindowProblem,
+}
+
+impl Display for WindowWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} window [{}, {}] ", self.window, self.start, self.end)?;
+        match self.problem {
+            WindowProblem::Empty                  => write!(f, "is empty"),
+            WindowProblem::BeforeRecording{first} => write!(f, "needs data before the start but recording starts at {}", first),
+            WindowProblem::AfterRecording{last}   => write!(f, "needs data after the end but recording ends at {}", last),
+            WindowProblem::TooFewSamples{n}       => write!(f, "has only {} valid speeds (5 needed)", n),
+        }
+    }
+}
+
+// Explains why `the_speed_within` could not produce a value for this window
+pub fn diagnose_window(window: &Window, bounds: &WindowBounds, input: &[DataLine]) -> Option<WindowProblem> {
+    if !(window.start < window.end) { return Some(WindowProblem::Empty); }
+
+    let mut first = std::f64::NAN;
+    let mut last = std::f64::NAN;
+    let mut before = false;
+    let mut after = false;
+    let mut n = 0u64;
+    for data in input.iter() {
+        if !data.time.is_finite() { continue; }
+        if !first.is_finite() { first = data.time; }
+        last = data.time;
+        if bounds.is_before(window, data.time) { before = true; }
+        else if bounds.is_after(window, data.time) { after = true; break; }
+        else if data.speed.is_finite() { n += 1; }
+    }
+    if !before && bounds.require_before    { Some(WindowProblem::BeforeRecording{ first }) }
+    else if !after && bounds.require_after { Some(WindowProblem::AfterRecording{ last }) }
+    else if n < 5   { Some(WindowProblem::TooFewSamples{ n }) }
+    else            { None }
+}
+
+pub fn the_window_warnings(windows: &SpeedWindows, input: &[DataLine]) -> Vec<WindowWarning> {
+    windows.named().iter()
+        .filter_map(|(name, w)| 
+            diagnose_window(w, &windows.bounds, input).map(|problem| WindowWarning{ window: name.to_string(), start: w.start, end: w.end, problem })
+        )
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WindowMetric {
+    Area,
+    Midline,
+    Coord,
+    Speed,
+    Activity,
+}
+
+// A window for the generic engine: every requested metric is computed from
+// the lines whose time falls within [start, end].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NamedWindow {
+    pub name: String,
+    pub start: f64,
+    pub end: f64,
+    pub metrics: Vec<WindowMetric>,
+}
+
+pub fn named_window_errors(windows: &[NamedWindow]) -> Vec<String> {
+    let mut errors = Vec::new();
+    for (i, w) in windows.iter().enumerate() {
+        if w.name.len() == 0 || w.name.contains(char::is_whitespace) {
+            errors.push(format!("window name {:?} must be nonempty without spaces", w.name));
+        }
+        if windows[..i].iter().any(|v| v.name == w.name) { errors.push(format!("window name {:?} is used twice", w.name)); }
+        if !(w.start < w.end) { errors.push(format!("{} window starts at {} but ends at {}", w.name, w.start, w.end)); }
+    }
+    errors
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WindowScores {
+    pub name: String,
+    pub start: f64,
+    pub end: f64,
+    pub metrics: Vec<WindowMetric>,
+
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub area: Option<Sampled>,
+
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub midline: Option<Sampled>,
+
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub x: Option<Coord>,
+
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub y: Option<Coord>,
+
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub speed: Option<Speed>,
+
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub activity: Option<f64>,
+}
+
+impl WindowScores {
+    // The row's cells for this window; a metric that couldn't be scored is
+    // written missing, with intervals if the row has them
+    pub fn write_cells(&self, f: &mut fmt::Formatter, intervals: bool) -> fmt::Result {
+        let mut first = true;
+        for m in self.metrics.iter() {
+            if !first { write!(f, " ")?; }
+            first = false;
+            match m {
+                WindowMetric::Area     => write!(f, "{}", self.area.clone().unwrap_or(Sampled::missing(intervals)))?,
+                WindowMetric::Midline  => write!(f, "{}", self.midline.clone().unwrap_or(Sampled::missing(intervals)))?,
+                WindowMetric::Coord    => write!(f, "{} {}", self.x.clone().unwrap_or(Coord::missing(intervals)), self.y.clone().unwrap_or(Coord::missing(intervals)))?,
+                WindowMetric::Speed    => write!(f, "{}", self.speed.clone().unwrap_or(Speed::missing(intervals)))?,
+                WindowMetric::Activity => write!(f, "{}", self.activity.unwrap_or(std::f64::NAN))?,
+            }
+        }
+        Ok(())
+    }
+
+    pub fn set_intervals(&mut self, on: bool, precision: Precision) {
+        if let Some(a) = self.area.as_mut() { a.set_intervals(on, precision); }
+        if let Some(m) = self.midline.as_mut() { m.set_intervals(on, precision); }
+        if let Some(x) = self.x.as_mut() { x.stats.set_intervals(on, precision); }
+        if let Some(y) = self.y.as_mut() { y.stats.set_intervals(on, precision); }
+        if let Some(s) = self.speed.as_mut() { s.set_intervals(on, precision); }
+    }
+
+    pub fn titles_of(name: &str, metrics: &[WindowMetric], specifier: &str, intervals: bool) -> Vec<String> {
+        let sub = |m: &str| format!("{}{}-{}", specifier, name, m);
+        let mut titles = Vec::new();
+        for m in metrics.iter() {
+            match m {
+                WindowMetric::Area     => titles.extend(Sampled::titles(sub("area-").as_str(), intervals)),
+                WindowMetric::Midline  => titles.extend(Sampled::titles(sub("midline-").as_str(), intervals)),
+                WindowMetric::Coord    => {
+                    titles.extend(Coord::titles(sub("x-").as_str(), intervals));
+                    titles.extend(Coord::titles(sub("y-").as_str(), intervals));
+                },
+                WindowMetric::Speed    => titles.extend(Speed::titles(sub("speed-").as_str(), intervals)),
+                WindowMetric::Activity => titles.push(sub("activity")),
+            }
+        }
+        titles
+    }
+}
+
+pub fn the_window_scores(window: &NamedWindow, activity_threshold: f64, max: &MaxEstimator, precision: Precision, input: &[DataLine]) -> WindowScores {
+    let inside: Vec<DataLine> = input.iter().filter(|d| d.time >= window.start && d.time <= window.end).cloned().collect();
+    window_scores_of(window, activity_threshold, max, precision, &inside)
+}
+
+// Scores for a window given only the lines inside it
+pub fn window_scores_of(window: &NamedWindow, activity_threshold: f64, max: &MaxEstimator, precision: Precision, inside: &[DataLine]) -> WindowScores {
+    let wants = |m: WindowMetric| window.metrics.contains(&m);
+    WindowScores{
+        name: window.name.clone(),
+        start: window.start,
+        end: window.end,
+        metrics: window.metrics.clone(),
+        area:     if wants(WindowMetric::Area)     { Some(Sampled::of_variance(&the_area(inside), precision)) } else { None },
+        midline:  if wants(WindowMetric::Midline)  { Some(Sampled::of_variance(&the_midline(inside), precision)) } else { None },
+        x:        if wants(WindowMetric::Coord)    { Some(the_coord(|d| d.x, precision, inside)) } else { None },
+        y:        if wants(WindowMetric::Coord)    { Some(the_coord(|d| d.y, precision, inside)) } else { None },
+        speed:    if wants(WindowMetric::Speed)    { the_speed_by(max, precision, inside.iter()) } else { None },
+        activity: if wants(WindowMetric::Activity) { the_activity(activity_threshold, inside) } else { None },
+    }
+}
+
+pub fn the_windowed(windows: &[NamedWindow], activity_threshold: f64, max: &MaxEstimator, precision: Precision, input: &[DataLine]) -> Vec<WindowScores> {
+    windows.iter().map(|w| the_window_scores(w, activity_threshold, max, precision, input)).collect()
+}
+
+// The frames a worm's speed windows were scored from, to check a suspicious
+// value: each finite speed within the window's bounds in the data as scored
+#[derive(Debug, Clone)]
+pub struct WindowFrames {
+    pub name: String,
+    pub window: Window,
+    pub scored: bool,
+    pub frames: Vec<(f64, f64)>,
+}
+
+impl Display for WindowFrames {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} window {} to {}: {} frames{}", self.name, self.window.start, self.window.end, self.frames.len(), if self.scored { "" } else { ", not scored" })
+    }
+}
+
+pub fn the_window_frames(input: &[DataLine], config: &Config) -> Vec<WindowFrames> {
+    let data = Dataset::new(the_prepared(input, config).lines);
+    let frames = |inside: &[DataLine]| inside.iter().filter(|d| d.speed.is_finite()).map(|d| (d.time, d.speed)).collect();
+    let bounds = &config.windows.bounds;
+    let precision = config.metrics.precision();
+    let mut all: Vec<WindowFrames> = data.speed_windows(&config.windows).named().iter().map(|(name, w)| {
+        let scored = data.speed_covering(w, bounds, &config.speed.max, config.windows.min_coverage, precision).is_some();
+        WindowFrames{ name: name.to_string(), window: *w, scored, frames: frames(&data.slice_within(w, bounds)) }
+    }).collect();
+    for nw in config.windowed.iter() {
+        let w = Window::new(nw.start, nw.end);
+        let scored = data.window_scores(nw, config.composite.activity_threshold, &config.speed.max, precision).speed.is_some();
+        all.push(WindowFrames{ name: nw.name.clone(), window: w, scored, frames: frames(&data.slice_time(w.start, w.end)) });
+    }
+    all
+}
+
+pub fn window_frames_table(windows: &[WindowFrames]) -> String {
+    let mut out = String::from("window time speed\n");
+    for w in windows {
+        for (t, s) in w.frames.iter() { out.push_str(format!("{} {} {}\n", w.name, t, s).as_str()); }
+    }
+    out
+}
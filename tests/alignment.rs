@@ -0,0 +1,45 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use metrology::*;
+
+
+// Four noisy worms responding to the same taps, one of them started `shift` seconds late
+fn plate(shift: f64) -> Vec<Vec<DataLine>> {
+    (0..4).map(|k| {
+        let mut lines = synth_track(&SynthSettings{ noise: 0.01, response: 0.2, seed: 11 + k, ..SynthSettings::assay() });
+        if k == 2 { for d in lines.iter_mut() { d.time += shift; } }
+        lines
+    }).collect()
+}
+
+fn offsets(worms: &[Vec<DataLine>]) -> Vec<Option<f64>> {
+    let settings = AlignmentSettings{ enabled: true, ..AlignmentSettings::default() };
+    the_alignment_offsets(&settings, Precision::default(), &worms.iter().map(|w| w.as_slice()).collect::<Vec<_>>())
+}
+
+#[test]
+fn late_starts_are_recovered() {
+    assert_eq!(offsets(&plate(2.0)), vec![Some(0.0), Some(0.0), Some(2.0), Some(0.0)]);
+}
+
+#[test]
+fn early_starts_are_recovered() {
+    assert_eq!(offsets(&plate(-1.5)), vec![Some(0.0), Some(0.0), Some(-1.5), Some(0.0)]);
+}
+
+#[test]
+fn aligned_worms_need_no_offset() {
+    assert_eq!(offsets(&plate(0.0)), vec![Some(0.0); 4]);
+}
+
+#[test]
+fn shifts_beyond_the_limit_are_not_found() {
+    assert_ne!(offsets(&plate(8.0))[2], Some(8.0));
+}
+
+#[test]
+fn a_worm_alone_cannot_be_aligned() {
+    assert_eq!(offsets(&plate(2.0)[..1]), vec![None]);
+}
@@ -0,0 +1,79 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use metrology::*;
+
+
+const TEXT: &str = "0.0 0.08 0.1 1.0 10.0 5.0\n0.5 0.09 0.2 1.1 10.1 5.1\n";
+
+// A source file and the cache written for it, in a fresh directory
+fn cached(name: &str) -> (PathBuf, PathBuf) {
+    let dir = std::env::temp_dir().join(format!("metrology-cache-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let source = dir.join("20220101_120000.dat");
+    fs::write(&source, TEXT).unwrap();
+    let cache = dir.join(cache_name(&source));
+    fs::create_dir_all(cache.parent().unwrap()).unwrap();
+    fs::write(&cache, ParsedCache::of(&source, &InputFormat::default(), lines()).unwrap().encode().unwrap()).unwrap();
+    (source, cache)
+}
+
+fn lines() -> Vec<DataLine> {
+    (0..2).map(|i| DataLine{
+        time: 0.5 * i as f64, area: 0.08, speed: 0.1, midline: 1.0, x: 10.0, y: 5.0, frame: 7 + i, source_line: 1 + i
+    }).collect()
+}
+
+fn fields(lines: &[DataLine]) -> Vec<(u64, u64, u64, u64)> {
+    lines.iter().map(|d| (d.time.to_bits(), d.speed.to_bits(), d.frame, d.source_line)).collect()
+}
+
+#[test]
+fn cached_lines_load_back() {
+    let (source, cache) = cached("load");
+    let loaded = ParsedCache::load(&cache, &source, &InputFormat::default()).unwrap();
+    assert_eq!(fields(&loaded), fields(&lines()));
+}
+
+#[test]
+fn rewritten_sources_are_parsed_again() {
+    let (source, cache) = cached("rewritten");
+    fs::write(&source, TEXT.replace("0.1 1.0", "0.15 1.0")).unwrap();
+    assert!(ParsedCache::load(&cache, &source, &InputFormat::default()).is_none());
+}
+
+#[test]
+fn touched_sources_of_the_same_size_are_parsed_again() {
+    let (source, cache) = cached("touched");
+    let modified = fs::metadata(&source).unwrap().modified().unwrap();
+    fs::OpenOptions::new().write(true).open(&source).unwrap().set_modified(modified + Duration::from_millis(1)).unwrap();
+    assert!(ParsedCache::load(&cache, &source, &InputFormat::default()).is_none());
+}
+
+#[test]
+fn other_input_settings_are_parsed_again() {
+    let (source, cache) = cached("settings");
+    assert!(ParsedCache::load(&cache, &source, &InputFormat{ decimal: ',', thousands: None }).is_none());
+}
+
+#[test]
+fn caches_of_another_format_are_parsed_again() {
+    let (source, cache) = cached("format");
+    let mut old = ParsedCache::of(&source, &InputFormat::default(), lines()).unwrap();
+    old.format = CACHE_FORMAT - 1;
+    fs::write(&cache, old.encode().unwrap()).unwrap();
+    assert!(ParsedCache::load(&cache, &source, &InputFormat::default()).is_none());
+}
+
+#[test]
+fn caches_that_do_not_decode_are_parsed_again() {
+    let (source, cache) = cached("garbage");
+    fs::write(&cache, b"not a cache").unwrap();
+    assert!(ParsedCache::load(&cache, &source, &InputFormat::default()).is_none());
+}
@@ -0,0 +1,143 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+#![cfg(feature = "cli")]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+
+// A fresh directory to run in
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("metrology-cli-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn metrology(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_metrology")).current_dir(dir).args(args).output().unwrap()
+}
+
+fn stdout(out: &Output) -> String { String::from_utf8_lossy(&out.stdout).to_string() }
+
+#[test]
+fn help_lists_the_subcommands() {
+    let out = metrology(&scratch("help"), &["--help"]);
+    assert!(out.status.success());
+    for name in ["analyze", "batch", "rescore", "serve", "longitudinal", "dose-response", "pivot", "snapshot", "selftest"].iter() {
+        assert!(stdout(&out).contains(name), "{} is not listed", name);
+    }
+}
+
+#[test]
+fn analyze_is_the_default() {
+    let out = metrology(&scratch("default"), &["tracks", "scores", "--print-config"]);
+    assert!(out.status.success());
+    assert!(stdout(&out).contains("[windows"));
+}
+
+#[test]
+fn analyze_can_be_named() {
+    let out = metrology(&scratch("named"), &["analyze", "tracks", "scores", "--print-config"]);
+    assert!(out.status.success());
+    assert!(stdout(&out).contains("[windows"));
+}
+
+#[test]
+fn subcommands_are_run_by_name() {
+    let out = metrology(&scratch("batch"), &["batch", "runs.tsv"]);
+    assert_eq!(out.status.code(), Some(2));
+    assert!(stdout(&out).contains("Error reading \"runs.tsv\""));
+}
+
+#[test]
+fn source_directories_named_like_subcommands_are_analyzed() {
+    let dir = scratch("source");
+    fs::create_dir_all(dir.join("batch")).unwrap();
+    let out = metrology(&dir, &["batch", "scores", "--print-config"]);
+    assert!(out.status.success());
+    assert!(stdout(&out).contains("[windows"));
+}
+
+// Three sources of two worms each, and a spec running each to its own target
+fn batch_of_three(dir: &Path) -> PathBuf {
+    let mut spec = String::new();
+    for k in 0..3u64 {
+        let source = dir.join(format!("tracks{}", k));
+        fs::create_dir_all(&source).unwrap();
+        for id in 1..3u64 {
+            let lines = metrology::synth_track(&metrology::SynthSettings{ duration: 120.0, seed: 10 * k + id, ..metrology::SynthSettings::assay() });
+            fs::write(source.join(format!("plate.{}.dat", id)), metrology::dat_text(&lines)).unwrap();
+        }
+        spec.push_str(format!("tracks{}\tscores{}\n", k, k).as_str());
+    }
+    fs::write(dir.join("runs.tsv"), spec).unwrap();
+    dir.join("runs.tsv")
+}
+
+#[test]
+fn batch_runs_print_their_messages_together_and_in_order() {
+    let dir = scratch("together");
+    batch_of_three(&dir);
+    let out = metrology(&dir, &["batch", "runs.tsv", "--threads", "3"]);
+    assert!(out.status.success(), "{}", stdout(&out));
+    let text = stdout(&out);
+    let starts: Vec<usize> = (1..4).map(|k| text.find(format!("Run {}:", k).as_str()).unwrap()).collect();
+    let report = text.find("Batch report").unwrap();
+    assert!(starts[0] < starts[1] && starts[1] < starts[2] && starts[2] < report);
+    for k in 0..3 {
+        let end = if k < 2 { starts[k+1] } else { report };
+        let written: Vec<usize> = text.match_indices(format!("scores{}", k).as_str()).map(|(i, _)| i).filter(|i| *i < report).collect();
+        assert!(written.len() > 1);
+        assert!(written.iter().all(|i| *i >= starts[k] && *i < end), "messages of run {} are out of its block", k+1);
+    }
+    assert!(text.contains("3 of 3 runs succeeded"));
+}
+
+#[test]
+fn batch_runs_score_as_single_runs_do() {
+    let dir = scratch("shared");
+    batch_of_three(&dir);
+    assert!(metrology(&dir, &["batch", "runs.tsv", "--threads", "2"]).status.success());
+    for k in 0..3 {
+        let alone = metrology(&dir, &[format!("tracks{}", k).as_str(), format!("alone{}", k).as_str()]);
+        assert!(alone.status.success());
+        assert_eq!(fs::read_to_string(dir.join(format!("scores{}/plate.csv", k))).unwrap(), fs::read_to_string(dir.join(format!("alone{}/plate.csv", k))).unwrap());
+    }
+}
+
+#[test]
+fn batch_runs_may_not_share_a_target() {
+    let dir = scratch("same-target");
+    fs::write(dir.join("runs.tsv"), "a\tscores\nb\tscores\n").unwrap();
+    let out = metrology(&dir, &["batch", "runs.tsv"]);
+    assert_eq!(out.status.code(), Some(2));
+    assert!(stdout(&out).contains("Line 2 of \"runs.tsv\" has the same target as line 1"));
+}
+
+// Four worms of a plate, the third started two seconds late
+fn late_plate(dir: &Path) {
+    let source = dir.join("tracks");
+    fs::create_dir_all(&source).unwrap();
+    for id in 1..5u64 {
+        let mut lines = metrology::synth_track(&metrology::SynthSettings{ noise: 0.01, response: 0.2, seed: 10 + id, ..metrology::SynthSettings::assay() });
+        if id == 3 { for d in lines.iter_mut() { d.time += 2.0; } }
+        fs::write(source.join(format!("plate.{}.dat", id)), metrology::dat_text(&lines)).unwrap();
+    }
+}
+
+#[test]
+fn aligned_worms_keep_their_decimation() {
+    let dir = scratch("aligned-quick");
+    late_plate(&dir);
+    let out = metrology(&dir, &["tracks", "scores", "--quick", "2", "--set", "alignment.enabled=true"]);
+    assert!(out.status.success(), "{}", stdout(&out));
+    assert!(stdout(&out).contains("Aligned "));
+    let file = fs::read_dir(dir.join("scores")).unwrap().map(|e| e.unwrap().path()).find(|p| p.extension().map(|e| e == "scores").unwrap_or(false)).unwrap();
+    let scores = metrology::read_scores(&file).unwrap();
+    assert_eq!(scores.len(), 4);
+    assert!(scores.iter().all(|s| s.decimation == Some(2)));
+}
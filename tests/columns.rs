@@ -0,0 +1,42 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use metrology::*;
+
+mod common;
+use common::track;
+
+
+// Time, area, speed, midline, x, and y
+fn columns(lines: &[DataLine]) -> Vec<Vec<f64>> {
+    let of: [fn(&DataLine) -> f64; 6] = [|d| d.time, |d| d.area, |d| d.speed, |d| d.midline, |d| d.x, |d| d.y];
+    of.iter().map(|f| lines.iter().map(f).collect()).collect()
+}
+
+fn slices(c: &[Vec<f64>]) -> ColumnSlices<'_> { ColumnSlices{ time: &c[0], area: &c[1], speed: &c[2], midline: &c[3], x: &c[4], y: &c[5] } }
+
+#[test]
+fn scores_from_columns_match_scores_from_lines() {
+    let config = Config::default();
+    let lines = track(500);
+    let c = columns(&lines);
+    assert_eq!(Scores::from_columns(1, slices(&c), &config).unwrap().to_string(), the_everything(1, &lines, &config).to_string());
+}
+
+#[test]
+fn columns_of_different_lengths_are_rejected() {
+    let c = columns(&track(500));
+    match Scores::from_columns(1, ColumnSlices{ y: &c[5][1..], ..slices(&c) }, &Config::default()) {
+        Err(MetrologyError::ColumnLength{ column, expected, found }) => assert_eq!((column, expected, found), ("y", 500, 499)),
+        x => panic!("Expected column length error, got {:?}", x.map(|s| s.id)),
+    }
+}
+
+#[test]
+fn long_recordings_are_summarized_from_their_columns() {
+    let lines = track(COLUMNAR_MIN_LINES);
+    let dataset = Dataset::new(lines.clone());
+    let expected = column_coord(&columns(&lines)[4], Precision::Full);
+    for _ in 0..2 { assert_eq!(dataset.coord(Column::X, Precision::Full).to_string(), expected.to_string()); }
+}
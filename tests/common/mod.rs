@@ -0,0 +1,14 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use metrology::DataLine;
+
+
+// A worm at a steady 0.1 mm/s for `n` seconds, one frame a second, drifting
+// slowly along x
+pub fn track(n: usize) -> Vec<DataLine> {
+    (0..n).map(|i| DataLine{
+        time: i as f64, area: 0.08, speed: 0.1, midline: 1.0, x: 10.0 + 0.01 * i as f64, y: 5.0, frame: i as u64, source_line: 0
+    }).collect()
+}
@@ -0,0 +1,75 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use metrology::*;
+
+
+fn variables() -> Vec<(String, String)> {
+    vec![
+        ("METROLOGY_CALM_WINDOW".to_string(), "200,220".to_string()),
+        ("METROLOGY_SET".to_string(), "speed.ceiling.limit=2.5; metrics.bounds=true".to_string()),
+        ("HOME".to_string(), "/root".to_string()),
+    ]
+}
+
+const WINDOWS: &str = "[windows]\nspeed = [\n  { name = \"initial\", start = 0.0, end = 5.0 },\n  { name = \"calm\", start = 100.0, end = 120.0 },\n]\n";
+
+// The file, then the environment, then a flag
+fn layered() -> Config {
+    let mut overrides = environment_overrides(variables().into_iter());
+    overrides.push("windows.speed.calm.end=230".to_string());
+    Config::layered(WINDOWS, &overrides).unwrap()
+}
+
+#[test]
+fn only_metrology_variables_are_overrides() {
+    assert_eq!(environment_overrides(variables().into_iter()).len(), 3);
+}
+
+#[test]
+fn later_layers_override_earlier_ones() {
+    let config = layered();
+    assert_eq!(config.windows.get("calm"), Some(Window::new(200.0, 230.0)));
+}
+
+#[test]
+fn layers_keep_what_they_do_not_override() {
+    assert_eq!(layered().windows.get("initial"), Some(Window::new(0.0, 5.0)));
+}
+
+#[test]
+fn overrides_of_one_window_keep_the_default_windows() {
+    let config = Config::layered("", &["windows.speed.calm.end=300".to_string()]).unwrap();
+    assert_eq!(config.windows.names(), vec!["initial", "calm", "aroused"]);
+    assert_eq!(config.windows.get("calm"), Some(Window::new(270.0, 300.0)));
+}
+
+#[test]
+fn overrides_can_add_a_window() {
+    let config = Config::layered("", &["windows.speed.late={ start = 460, end = 470 }".to_string()]).unwrap();
+    assert_eq!(config.windows.names(), vec!["initial", "calm", "aroused", "late"]);
+}
+
+#[test]
+fn one_variable_can_set_several_values() {
+    let config = layered();
+    assert_eq!(config.speed.ceiling.limit, Some(2.5));
+    assert!(config.metrics.bounds);
+}
+
+#[test]
+fn overrides_without_a_value_are_rejected() {
+    assert!(Config::layered("", &["windows.speed.calm.end".to_string()]).is_err());
+}
+
+#[test]
+fn overridden_values_are_checked() {
+    assert!(Config::layered("", &["speed.ceiling.limit=-1".to_string()]).is_err());
+}
+
+#[test]
+fn printed_configurations_read_back_the_same() {
+    let config = layered();
+    assert_eq!(Config::from_toml(config.to_toml().as_str()).unwrap().hash(), config.hash());
+}
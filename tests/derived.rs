@@ -0,0 +1,112 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use metrology::*;
+
+mod common;
+use common::track;
+
+
+fn value(text: &str) -> f64 { parse_expression(text).unwrap().eval(&track(500)) }
+
+fn range(text: &str) -> Option<(f64, f64)> {
+    match parse_expression(text).unwrap() {
+        Expr::Summary{ range, .. } => range,
+        e => panic!("Expected a summary, got {:?}", e),
+    }
+}
+
+#[test]
+fn multiplication_binds_tighter_than_addition() {
+    assert_eq!(value("1 + 2 * 3"), 7.0);
+    assert_eq!(value("(1 + 2) * 3"), 9.0);
+}
+
+#[test]
+fn operators_of_equal_precedence_apply_left_to_right() {
+    assert_eq!(value("10 - 4 - 3"), 3.0);
+    assert_eq!(value("8 / 4 / 2"), 1.0);
+}
+
+#[test]
+fn unary_minus_applies_to_the_next_factor() {
+    assert_eq!(value("-2 * 3"), -6.0);
+    assert_eq!(value("2 - -3"), 5.0);
+    assert_eq!(value("-(1 + 2)"), -3.0);
+}
+
+#[test]
+fn ranges_are_two_numbers() {
+    assert_eq!(range("mean(speed, 440..450)"), Some((440.0, 450.0)));
+}
+
+#[test]
+fn ranges_may_start_before_zero() {
+    assert_eq!(range("mean(speed, -5..10)"), Some((-5.0, 10.0)));
+}
+
+#[test]
+fn ranges_may_be_left_out() {
+    assert_eq!(range("mean(speed)"), None);
+}
+
+#[test]
+fn ranges_include_their_start_but_not_their_end() {
+    assert_eq!(value("count(time, 10..20)"), 10.0);
+    assert_eq!(value("first(time, 10..20)"), 10.0);
+    assert_eq!(value("last(time, 10..20)"), 19.0);
+}
+
+#[test]
+fn empty_ranges_are_rejected() {
+    assert!(parse_expression("mean(speed, 20..20)").is_err());
+    assert!(parse_expression("mean(speed, 20..10)").is_err());
+}
+
+#[test]
+fn unknown_functions_are_rejected() {
+    assert!(parse_expression("exp(1)").unwrap_err().contains("unknown function"));
+}
+
+#[test]
+fn unknown_columns_are_rejected() {
+    assert!(parse_expression("mean(velocity, 10..20)").is_err());
+}
+
+#[test]
+fn trailing_text_is_rejected() {
+    assert!(parse_expression("1 + 2 3").is_err());
+    assert!(parse_expression("mean(speed))").is_err());
+}
+
+#[test]
+fn count_of_nothing_is_zero() {
+    assert_eq!(value("count(speed, 1000..2000)"), 0.0);
+}
+
+#[test]
+fn other_summaries_of_nothing_are_nan() {
+    for f in ["mean", "median", "sd", "sem", "min", "max", "first", "last"].iter() {
+        assert!(value(format!("{}(speed, 1000..2000)", f).as_str()).is_nan(), "{} of nothing", f);
+    }
+}
+
+#[test]
+fn spread_of_one_value_is_nan() {
+    assert!(value("sd(speed, 0..1)").is_nan());
+}
+
+#[test]
+fn summaries_skip_values_that_are_not_finite() {
+    let mut lines = track(500);
+    lines[12].speed = std::f64::NAN;
+    lines[13].speed = std::f64::INFINITY;
+    assert_eq!(parse_expression("count(speed, 10..20)").unwrap().eval(&lines), 8.0);
+}
+
+#[test]
+fn functions_outside_their_domain_are_nan() {
+    assert!(value("ln(-1)").is_nan());
+    assert!(value("sqrt(0 - 1)").is_nan());
+}
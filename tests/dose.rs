@@ -0,0 +1,90 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use metrology::*;
+
+
+const LOW: [f64; 5] = [1.0, 2.0, 3.0, 4.0, 5.0];
+const HIGH: [f64; 5] = [6.0, 7.0, 8.0, 9.0, 10.0];
+
+// Six worms moving at nearly the given speed
+fn condition(label: &str, speed: f64) -> DoseCondition {
+    DoseCondition{
+        label: label.to_string(),
+        scores: (0..6).map(|i| {
+            let lines: Vec<DataLine> = (0..500).map(|t| DataLine{
+                time: t as f64, area: 0.08, speed: speed + 0.001 * i as f64, midline: 1.0, x: 10.0, y: 5.0, frame: t as u64, source_line: 0
+            }).collect();
+            the_everything(i, &lines, &Config::default())
+        }).collect(),
+    }
+}
+
+// The control is listed in the middle; pq-1mM matches it and pq-4mM is half as fast
+fn dose_response() -> DoseResponse {
+    let conditions = vec![condition("pq-4mM", 0.05), condition("pq-0mM", 0.1), condition("pq-1mM", 0.1)];
+    the_dose_response(&conditions, "calm-mean", 1000, 3, Precision::default()).unwrap()
+}
+
+#[test]
+fn mann_whitney_separates_groups_without_overlap() {
+    let (u, p) = mann_whitney(&LOW, &HIGH);
+    assert_eq!(u, 0.0);
+    assert!((p - 0.0122).abs() < 1e-3);
+}
+
+#[test]
+fn mann_whitney_finds_nothing_in_ties() {
+    assert_eq!(mann_whitney(&[1.0, 1.0], &[1.0, 1.0]), (2.0, 1.0));
+}
+
+#[test]
+fn permutation_tests_separate_groups_without_overlap() {
+    assert!(permutation_p(&LOW, &HIGH, 2000, &mut Rng::new(7)) < 0.03);
+}
+
+#[test]
+fn permutation_tests_repeat_with_the_same_seed() {
+    assert_eq!(permutation_p(&LOW, &HIGH, 2000, &mut Rng::new(7)), permutation_p(&LOW, &HIGH, 2000, &mut Rng::new(7)));
+}
+
+#[test]
+fn the_lowest_dose_is_the_control() {
+    let dr = dose_response();
+    let cs = dr.comparisons.as_ref().unwrap();
+    assert_eq!((cs.control.as_str(), cs.n_control, cs.seed), ("pq-0mM", 6, 3));
+}
+
+#[test]
+fn doses_are_compared_in_order() {
+    let dr = dose_response();
+    let labels: Vec<&str> = dr.comparisons.as_ref().unwrap().groups.iter().map(|c| c.label.as_str()).collect();
+    assert_eq!(labels, vec!["pq-1mM", "pq-4mM"]);
+}
+
+#[test]
+fn doses_like_the_control_show_no_effect() {
+    let dr = dose_response();
+    let same = &dr.comparisons.as_ref().unwrap().groups[0];
+    assert_eq!((same.rank_biserial, same.difference), (0.0, 0.0));
+    assert!(same.permutation_p > 0.5);
+}
+
+#[test]
+fn doses_unlike_the_control_show_an_effect() {
+    let dr = dose_response();
+    let slow = &dr.comparisons.as_ref().unwrap().groups[1];
+    assert_eq!(slow.rank_biserial, -1.0);
+    assert!(slow.mann_whitney_p < 0.01 && slow.permutation_p < 0.01);
+}
+
+#[test]
+fn dose_comparisons_repeat_with_the_same_seed() {
+    assert_eq!(serde_json::to_string(&dose_response()).unwrap(), serde_json::to_string(&dose_response()).unwrap());
+}
+
+#[test]
+fn dose_comparisons_are_rows_of_the_table() {
+    assert!(dose_response_table(&dose_response()).contains("\ncalm-mean mann-whitney pq-4mM 4 6 -1 NaN NaN NaN "));
+}
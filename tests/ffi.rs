@@ -0,0 +1,55 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+#![cfg(feature = "ffi")]
+
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+
+use metrology::*;
+
+
+fn taken(out: *mut c_char) -> String {
+    let text = unsafe { CStr::from_ptr(out) }.to_string_lossy().to_string();
+    unsafe { metrology_free_string(out) };
+    text
+}
+
+#[test]
+fn buffers_score_as_in_the_scores_file() {
+    let data: String = (0..500).map(|i| format!("{} 0.08 0.1 1.0 {} 5.0\n", i as f64 * 0.04, 10.0 + 0.001 * i as f64)).collect();
+    let mut out: *mut c_char = std::ptr::null_mut();
+    let code = unsafe { metrology_score_buffer(data.as_ptr() as *const c_char, data.len(), &mut out) };
+    assert_eq!(code, METROLOGY_OK);
+    assert_eq!(taken(out), scores_json(0, data.as_bytes(), &Config::default()).unwrap());
+
+    let config = CString::new("[metrics]\nbounds = \"yes\"\n").unwrap();
+    let code = unsafe { metrology_score_buffer_with(data.as_ptr() as *const c_char, data.len(), 7, config.as_ptr(), &mut out) };
+    assert_eq!(code, METROLOGY_BAD_CONFIG);
+    assert!(taken(out).contains("bounds"));
+
+    let bad = "0.0 0.08 x\n";
+    let code = unsafe { metrology_score_buffer(bad.as_ptr() as *const c_char, bad.len(), &mut out) };
+    assert_eq!(code, METROLOGY_BAD_DATA);
+    assert!(taken(out).contains("line 1"));
+}
+
+#[test]
+fn configurations_stay_with_their_calls_across_threads() {
+    let data: String = (0..500).map(|i| format!("{} 0.08 0.1 1.0 {} 5.0\n", i as f64 * 0.04, 10.0 + 0.001 * i as f64)).collect();
+    let handles: Vec<_> = (0..8).map(|i| {
+        let data = data.clone();
+        std::thread::spawn(move || {
+            let intervals = i % 2 == 1;
+            let config = CString::new(format!("[metrics]\nci95 = {}\n", intervals)).unwrap();
+            let mut out: *mut c_char = std::ptr::null_mut();
+            for _ in 0..20 {
+                let code = unsafe { metrology_score_buffer_with(data.as_ptr() as *const c_char, data.len(), i, config.as_ptr(), &mut out) };
+                assert_eq!(code, METROLOGY_OK);
+                assert_eq!(taken(out).contains("ci95_lo"), intervals);
+            }
+        })
+    }).collect();
+    for h in handles { h.join().unwrap(); }
+}
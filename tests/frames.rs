@@ -0,0 +1,70 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use metrology::*;
+
+
+// Frames at 25 per second, keeping those `keep` accepts
+fn at_25fps(n: u64, keep: impl Fn(u64) -> bool) -> Vec<DataLine> {
+    (0..n).filter(|i| keep(*i)).map(|i| DataLine{
+        time: i as f64 * 0.04, area: 0.08, speed: 0.1, midline: 1.0,
+        x: 10.0 + 0.01 * i as f64, y: 5.0, frame: i, source_line: 0
+    }).collect()
+}
+
+fn rate(lines: &[DataLine]) -> FrameRate { estimate_frame_rate(Precision::Rounded, lines).unwrap() }
+
+#[test]
+fn a_steady_track_drops_no_frames() {
+    let fr = rate(&at_25fps(1000, |_| true));
+    assert!((fr.median_dt - 0.04).abs() < 1e-9);
+    assert!(fr.jitter < 1e-9);
+    assert_eq!(fr.dropped_frames, 0);
+}
+
+#[test]
+fn every_tenth_frame_dropped_is_counted_without_changing_the_rate() {
+    let fr = rate(&at_25fps(1001, |i| i % 10 != 9));
+    assert!((fr.median_dt - 0.04).abs() < 1e-9);
+    assert!(fr.jitter < 1e-9);
+    assert_eq!(fr.dropped_frames, 100);
+}
+
+#[test]
+fn a_long_gap_counts_every_missing_frame() {
+    let fr = rate(&at_25fps(1000, |i| i <= 400 || i >= 450));
+    assert!((fr.median_dt - 0.04).abs() < 1e-9);
+    assert_eq!(fr.dropped_frames, 49);
+}
+
+#[test]
+fn short_and_long_drops_add_up() {
+    let fr = rate(&at_25fps(1000, |i| !(i % 50 == 25 || (600..603).contains(&i))));
+    assert_eq!(fr.dropped_frames, 20 + 3);
+}
+
+#[test]
+fn jittery_intervals_are_not_dropped_frames() {
+    let mut lines = at_25fps(1000, |_| true);
+    for (i, d) in lines.iter_mut().enumerate() { d.time += 0.004 * (i as f64 * 12.9898).sin(); }
+    let fr = rate(&lines);
+    assert!((fr.median_dt - 0.04).abs() < 0.002);
+    assert!(fr.jitter > 0.0 && fr.jitter < 0.008);
+    assert_eq!(fr.dropped_frames, 0);
+}
+
+#[test]
+fn missing_times_are_skipped() {
+    let mut lines = at_25fps(1000, |_| true);
+    lines[300].time = std::f64::NAN;
+    let fr = rate(&lines);
+    assert!((fr.median_dt - 0.04).abs() < 1e-9);
+    assert_eq!(fr.dropped_frames, 1);
+}
+
+#[test]
+fn a_single_frame_has_no_rate() {
+    assert!(estimate_frame_rate(Precision::Rounded, &at_25fps(1, |_| true)).is_none());
+    assert!(estimate_frame_rate(Precision::Rounded, &[]).is_none());
+}
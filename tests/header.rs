@@ -0,0 +1,19 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use std::path::PathBuf;
+
+
+// The header is generated, but checked in for C and C++ users who don't build
+// with cargo, so it must be regenerated whenever src/ffi.rs changes.
+#[test]
+fn header_matches_cbindgen_output() {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let config = cbindgen::Config::from_file(root.join("cbindgen.toml")).unwrap();
+    let mut generated = Vec::new();
+    cbindgen::Builder::new().with_config(config).with_src(root.join("src").join("ffi.rs")).generate().unwrap().write(&mut generated);
+    let checked_in = std::fs::read_to_string(root.join("include").join("metrology.h")).unwrap();
+    assert!(String::from_utf8(generated).unwrap() == checked_in,
+        "include/metrology.h is out of date; regenerate it with\n  cbindgen --config cbindgen.toml --output include/metrology.h");
+}
@@ -0,0 +1,56 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use metrology::*;
+
+mod common;
+use common::track;
+
+
+// A long and a short track with bounds, bursts, a derived value, a custom window and metadata
+fn rows() -> Vec<Scores> {
+    let mut config = Config::from_toml("[metrics]\nbounds = true\n[bursts]\nreport = true\n[derived]\nratio = \"mean(speed, 300..400) / mean(speed, 10..20)\"\n").unwrap();
+    config.windowed = vec![NamedWindow{ name: "late".to_string(), start: 300.0, end: 400.0, metrics: vec![WindowMetric::Speed, WindowMetric::Activity] }];
+    let mut rows = vec![the_everything(1, &track(500), &config), the_everything(2, &track(15), &config)];
+    rows[1].track = Some(3);
+    rows[0].metadata.insert("strain".to_string(), Some("N2".to_string()));
+    rows[1].metadata.insert("strain".to_string(), None);
+    rows
+}
+
+fn table() -> String { with_config_comment(with_missing(table_of(&rows()).unwrap().as_str(), "NA").as_str(), "abc") }
+
+fn read() -> Vec<Scores> { Scores::from_csv_text(table().as_str()).unwrap() }
+
+#[test]
+fn tables_read_back_to_the_same_table() {
+    assert_eq!(table_of(&read()).unwrap(), table_of(&rows()).unwrap());
+}
+
+#[test]
+fn tables_read_back_what_the_table_does_not_spell_out() {
+    let read = read();
+    assert_eq!(read[1].track, Some(3));
+    assert!(read[1].speeds.get("aroused").is_none());
+    assert_eq!(read[0].config_hash.as_ref().map(|h| h.as_str()), Some("abc"));
+    assert_eq!(read[0].windowed[0].metrics, vec![WindowMetric::Speed, WindowMetric::Activity]);
+    assert!(read[0].extras.contains_key("ratio"));
+}
+
+#[test]
+fn utf16_spreadsheet_exports_read_back() {
+    let spreadsheet: String = table().lines().map(|l| l.replace(' ', ";").replace('.', ",") + "\r\n").collect();
+    let mut bytes = vec![0xFF, 0xFE];
+    for u in spreadsheet.encode_utf16() { bytes.extend(u.to_le_bytes().iter()); }
+    let path = std::env::temp_dir().join(format!("metrology-from-csv-{}.csv", std::process::id()));
+    std::fs::write(&path, &bytes).unwrap();
+    let again = Scores::from_csv(&path);
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(table_of(&again.unwrap()).unwrap(), table_of(&rows()).unwrap());
+}
+
+#[test]
+fn tables_that_are_not_scores_are_rejected() {
+    assert!(Scores::from_csv_text("id t0 t1\n1 0 1\n").is_err());
+}
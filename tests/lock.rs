@@ -0,0 +1,88 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Barrier};
+use std::time::{Duration, SystemTime};
+
+use metrology::*;
+
+
+const DAY: Duration = Duration::from_secs(24 * 3600);
+
+// A target in a fresh directory of its own
+fn target(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("metrology-lock-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir.join("scores")
+}
+
+fn lock_file(target: &PathBuf, text: &str, age: Duration) {
+    let path = RunLock::path_for(target).unwrap();
+    fs::write(&path, text).unwrap();
+    fs::OpenOptions::new().write(true).open(&path).unwrap().set_modified(SystemTime::now() - age).unwrap();
+}
+
+#[test]
+fn held_locks_name_their_holder() {
+    let target = target("held");
+    let _lock = RunLock::acquire(&target, DAY).unwrap();
+    match RunLock::acquire(&target, DAY) {
+        Err(MetrologyError::Locked{ pid, .. }) => assert_eq!(pid, std::process::id()),
+        x => panic!("Expected the lock to be held, got {:?}", x),
+    }
+}
+
+#[test]
+fn released_locks_are_taken_without_recovery() {
+    let target = target("released");
+    drop(RunLock::acquire(&target, DAY).unwrap());
+    assert!(!RunLock::acquire(&target, DAY).unwrap().recovered);
+    assert!(!RunLock::path_for(&target).unwrap().exists());
+}
+
+#[test]
+fn only_one_of_many_runs_takes_a_lock() {
+    let target = target("contended");
+    let barrier = Arc::new(Barrier::new(8));
+    let handles: Vec<_> = (0..8).map(|_| {
+        let (target, barrier) = (target.clone(), barrier.clone());
+        std::thread::spawn(move || {
+            barrier.wait();
+            let lock = RunLock::acquire(&target, DAY);
+            // Hold on until every run has tried
+            barrier.wait();
+            lock.is_ok()
+        })
+    }).collect();
+    assert_eq!(handles.into_iter().map(|h| h.join().unwrap()).filter(|taken| *taken).count(), 1);
+}
+
+#[test]
+fn old_locks_are_recovered() {
+    let target = target("old");
+    lock_file(&target, "elsewhere 1 0\n", Duration::from_secs(0));
+    assert!(RunLock::acquire(&target, DAY).unwrap().recovered);
+}
+
+#[test]
+fn unreadable_locks_being_written_are_held() {
+    let target = target("writing");
+    lock_file(&target, "", Duration::from_secs(0));
+    match RunLock::acquire(&target, DAY) {
+        Err(MetrologyError::Locked{ host, .. }) => assert_eq!(host, "unknown"),
+        x => panic!("Expected the lock to be held, got {:?}", x),
+    }
+}
+
+#[test]
+fn unreadable_locks_left_unwritten_are_recovered() {
+    let target = target("abandoned");
+    lock_file(&target, "elsewhere 1", UNWRITTEN_GRACE * 2);
+    let lock = RunLock::acquire(&target, DAY).unwrap();
+    assert!(lock.recovered);
+    assert!(fs::read_to_string(RunLock::path_for(&target).unwrap()).unwrap().contains(&std::process::id().to_string()));
+}
@@ -0,0 +1,68 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use std::path::PathBuf;
+use std::process::Command;
+
+use metrology::*;
+
+mod common;
+use common::track;
+
+
+fn git(args: &[&str]) -> Option<String> {
+    let out = Command::new("git").arg("-C").arg(env!("CARGO_MANIFEST_DIR")).args(args).output().ok()?;
+    if out.status.success() { Some(String::from_utf8(out.stdout).unwrap().trim().to_string()) } else { None }
+}
+
+fn manifest() -> Manifest { Manifest::new("src".into(), "plate".into(), Config::default()) }
+
+fn scores() -> Scores { the_everything(1, &track(40), &Config::default()) }
+
+#[test]
+fn environment_records_the_build_and_machine() {
+    let env = manifest().environment.unwrap();
+    assert_eq!(env.version, env!("CARGO_PKG_VERSION"));
+    assert_eq!(env.os, std::env::consts::OS);
+    assert!(env.cpus >= 1 && env.host.len() > 0);
+}
+
+#[test]
+fn manifests_read_back_with_the_environment() {
+    let manifest = manifest();
+    let read: Manifest = serde_json::from_str(serde_json::to_string(&manifest).unwrap().as_str()).unwrap();
+    assert_eq!(read.environment, manifest.environment);
+}
+
+#[test]
+fn manifests_written_before_the_environment_still_read() {
+    let mut old = serde_json::to_value(&manifest()).unwrap();
+    old.as_object_mut().unwrap().remove("environment");
+    assert_eq!(serde_json::from_value::<Manifest>(old).unwrap().environment, None);
+}
+
+#[test]
+fn scores_leave_out_the_environment_by_default() {
+    assert!(!serde_json::to_string(&scores()).unwrap().contains("environment"));
+}
+
+#[test]
+fn scores_read_back_with_the_environment() {
+    let mut scores = scores();
+    scores.environment = manifest().environment;
+    let read: Scores = serde_json::from_str(serde_json::to_string(&scores).unwrap().as_str()).unwrap();
+    assert_eq!(read.environment, scores.environment);
+}
+
+#[test]
+fn environment_records_the_commit_of_this_checkout() {
+    let checkout = git(&["rev-parse", "--show-toplevel"]).map(PathBuf::from).and_then(|p| p.canonicalize().ok());
+    let commit = Environment::capture().git_commit;
+    if checkout == PathBuf::from(env!("CARGO_MANIFEST_DIR")).canonicalize().ok() {
+        assert_eq!(commit, git(&["rev-parse", "HEAD"]));
+    }
+    else {
+        assert_eq!(commit, option_env!("METROLOGY_GIT_COMMIT").map(|s| s.to_string()));
+    }
+}
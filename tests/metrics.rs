@@ -0,0 +1,98 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use metrology::*;
+
+mod common;
+use common::track;
+
+
+fn only_area_and_arousal() -> Config { Config::from_toml("[metrics]\nonly = [\"area\", \"arousal\"]\n").unwrap() }
+
+#[test]
+fn selected_metrics_and_what_they_need_are_scored_as_usual() {
+    let (some, all) = (the_everything(1, &track(500), &only_area_and_arousal()), the_everything(1, &track(500), &Config::default()));
+    assert_eq!(some.title(), all.title());
+    assert_eq!(some.area.to_string(), all.area.to_string());
+    assert!(some.speeds.get("calm").is_some() && some.speeds.get("aroused").is_some());
+}
+
+#[test]
+fn unselected_metrics_are_left_missing() {
+    let some = the_everything(1, &track(500), &only_area_and_arousal());
+    assert!(some.speeds.get("initial").is_none());
+    assert_eq!(some.midline.to_string(), the_everything(2, &Vec::new(), &Config::default()).midline.to_string());
+}
+
+#[test]
+fn unknown_metrics_are_rejected() {
+    assert!(Config::from_toml("[metrics]\nonly = [\"speed:sleepy\"]\n").is_err());
+}
+
+#[test]
+fn speed_selects_every_speed_window() {
+    assert_eq!(MetricSelection::of(&["speed".to_string()], &SpeedWindows::default().names()).unwrap().names(), vec!["speed:initial", "speed:calm", "speed:aroused"]);
+}
+
+// A track with one frame of zero area and one without a midline
+fn with_gaps() -> Vec<DataLine> {
+    let mut lines = track(500);
+    lines[7].area = 0.0;
+    lines[8].midline = std::f64::NAN;
+    lines
+}
+
+fn with_shape_index() -> Config { Config::from_toml("[metrics]\nshape_index = true\n").unwrap() }
+
+#[test]
+fn shape_index_is_midline_squared_over_area() {
+    let s = the_everything(1, &with_gaps(), &with_shape_index());
+    let index = s.shape_index.as_ref().unwrap();
+    assert_eq!((index.n, index.mean), (498, 12.5));
+}
+
+#[test]
+fn shape_index_has_columns_when_configured() {
+    assert!(the_everything(1, &with_gaps(), &with_shape_index()).title().contains(" shape-n shape-mean shape-sem "));
+}
+
+#[test]
+fn shape_index_is_off_by_default() {
+    assert!(the_everything(2, &with_gaps(), &Config::default()).shape_index.is_none());
+}
+
+// A 20 by 20 grid of positions 0.1 apart
+fn grid() -> Vec<DataLine> {
+    (0..400).map(|i| DataLine{
+        time: i as f64, area: 0.08, speed: 0.1, midline: 1.0, x: 0.1 * (i % 20) as f64, y: 0.1 * (i / 20) as f64, frame: i as u64, source_line: 0
+    }).collect()
+}
+
+fn with_spread() -> Config { Config::from_toml("[metrics]\nspread = true\n").unwrap() }
+
+#[test]
+fn spread_has_the_bounding_box_area_and_radius_of_gyration() {
+    let s = the_everything(1, &grid(), &with_spread());
+    assert!((s.spread.as_ref().unwrap().bbox_area - 3.61).abs() < 1e-4);
+    assert!((s.spread.as_ref().unwrap().gyration - 0.665f64.sqrt()).abs() < 1e-4);
+}
+
+#[test]
+fn spread_has_columns_after_position() {
+    let s = the_everything(1, &grid(), &with_spread());
+    assert_eq!(s.title(), Scores::titles_for(&with_spread(), "").join(" "));
+    assert!(s.title().contains(" y-sem spread-bbox-area spread-gyration exploration-visited "));
+}
+
+#[test]
+fn spread_reads_back_from_tables() {
+    let s = the_everything(1, &grid(), &with_spread());
+    let read = Scores::from_csv_text(table_of(&[s.clone()]).unwrap().as_str()).unwrap();
+    assert_eq!(read[0].spread.as_ref().map(|x| x.gyration), s.spread.as_ref().map(|x| x.gyration));
+}
+
+#[test]
+fn spread_is_off_by_default() {
+    assert!(the_everything(1, &grid(), &Config::default()).spread.is_none());
+}
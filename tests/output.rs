@@ -0,0 +1,70 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use metrology::*;
+
+mod common;
+use common::track;
+
+
+// The same metadata inserted in two different orders
+fn with_metadata(order: &[(&str, &str)]) -> Scores {
+    let mut s = the_everything(1, &track(50), &Config::default());
+    for (k, v) in order.iter() { s.metadata.insert(k.to_string(), Some(v.to_string())); }
+    s
+}
+
+fn shuffled() -> (Scores, Scores) {
+    (with_metadata(&[("strain", "N2"), ("plate", "A"), ("day", "3")]), with_metadata(&[("day", "3"), ("strain", "N2"), ("plate", "A")]))
+}
+
+#[test]
+fn scores_sort_by_id_then_track() {
+    let config = Config::default();
+    let mut rows: Vec<Scores> = [(3, None), (1, Some(2)), (2, None), (1, Some(1))].iter().map(|&(id, k)| {
+        let mut s = the_everything(id, &track(50), &config);
+        s.track = k;
+        s
+    }).collect();
+    sort_scores(&mut rows);
+    assert_eq!(rows.iter().map(|s| (s.id, s.track)).collect::<Vec<_>>(), vec![(1, Some(1)), (1, Some(2)), (2, None), (3, None)]);
+}
+
+#[test]
+fn metadata_order_does_not_change_the_output() {
+    let (a, b) = shuffled();
+    assert_eq!(serde_json::to_string(&a).unwrap(), serde_json::to_string(&b).unwrap());
+    assert_eq!(a.to_string(), b.to_string());
+}
+
+// A long and a short worm with most optional parts, and metadata
+fn varied() -> Vec<Scores> {
+    let config = Config::from_toml("[metrics]\nbounds = true\nspread = true\n[bursts]\nreport = true\n[derived]\nratio = \"mean(speed, 300..400) / mean(speed, 10..20)\"\n").unwrap();
+    let mut rows = vec![the_everything(1, &track(500), &config), the_everything(2, &track(15), &config)];
+    rows[1].track = Some(3);
+    rows[0].metadata.insert("strain".to_string(), Some("N2".to_string()));
+    rows[1].metadata.insert("strain".to_string(), None);
+    rows
+}
+
+fn msgpack_round_trip(name: &str, rows: &[Scores]) -> Vec<Scores> {
+    let path = std::env::temp_dir().join(format!("metrology-{}-{}.msgpack", name, std::process::id()));
+    std::fs::write(&path, ScoresFormat::Msgpack.encode(rows).unwrap()).unwrap();
+    let read = read_scores_msgpack(&path);
+    std::fs::remove_file(&path).unwrap();
+    read.unwrap()
+}
+
+#[test]
+fn msgpack_scores_read_back_as_written() {
+    let rows = varied();
+    assert_eq!(serde_json::to_string(&msgpack_round_trip("as-written", &rows)).unwrap(), serde_json::to_string(&rows).unwrap());
+}
+
+#[test]
+fn msgpack_scores_keep_missing_windows_missing() {
+    let read = msgpack_round_trip("missing", &varied());
+    assert!(read[1].speeds.get("aroused").is_none());
+    assert_eq!(table_of(&read).unwrap(), table_of(&varied()).unwrap());
+}
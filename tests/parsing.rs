@@ -0,0 +1,275 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use metrology::*;
+
+
+const PLAIN: &[u8] = b"0.0 0.08 0.1 1.0 11.0 5.0\n0.04 0.08 0.2 1.0 11.1 5.0\n";
+
+fn parsed(bytes: &[u8], input: &InputFormat) -> Vec<(f64, f64, f64)> {
+    parse_data_lines(&input.normalize(bytes)).unwrap().iter().map(|d| (d.time, d.speed, d.x)).collect()
+}
+
+#[test]
+fn byte_order_mark_is_ignored() {
+    let mut bytes = vec![0xEF, 0xBB, 0xBF];
+    bytes.extend_from_slice(PLAIN);
+    assert_eq!(parsed(&bytes, &InputFormat::default()), parsed(PLAIN, &InputFormat::default()));
+}
+
+#[test]
+fn non_breaking_spaces_separate_values() {
+    let utf8 = String::from_utf8(PLAIN.to_vec()).unwrap().replace(' ', "\u{a0}");
+    assert_eq!(parsed(utf8.as_bytes(), &InputFormat::default()), parsed(PLAIN, &InputFormat::default()));
+    let latin1: Vec<u8> = PLAIN.iter().map(|&c| if c == b' ' { 0xA0 } else { c }).collect();
+    assert_eq!(parsed(&latin1, &InputFormat::default()), parsed(PLAIN, &InputFormat::default()));
+}
+
+#[test]
+fn utf8_characters_with_an_a0_byte_are_kept() {
+    // `à` is C3 A0 in UTF-8
+    let text = "# souche à 20 °C\n".as_bytes();
+    let normalized = InputFormat::default().normalize(text);
+    assert_eq!(&*normalized, text);
+    assert!(matches!(normalized, std::borrow::Cow::Borrowed(_)));
+}
+
+#[test]
+fn byte_order_mark_and_separators_together() {
+    let comma = InputFormat{ decimal: ',', thousands: None };
+    let text = String::from_utf8(PLAIN.to_vec()).unwrap().replace('.', ",").replace(' ', "\u{a0} ");
+    let mut bytes = vec![0xEF, 0xBB, 0xBF];
+    bytes.extend_from_slice(text.as_bytes());
+    assert_eq!(parsed(&bytes, &comma), parsed(PLAIN, &InputFormat::default()));
+}
+
+#[test]
+fn errors_after_a_byte_order_mark_are_still_on_line_one() {
+    match parse_data_lines(&InputFormat::default().normalize(&[0xEF, 0xBB, 0xBF, b'x'])) {
+        Err(MetrologyError::Parse{ line }) => assert_eq!(line, 1),
+        x => panic!("Expected parse error, got {:?}", x),
+    }
+}
+
+#[test]
+fn files_with_a_byte_order_mark_are_recognized() {
+    let mut bytes = vec![0xEF, 0xBB, 0xBF];
+    bytes.extend_from_slice(PLAIN);
+    let formats = TrackFormats::new(&InputFormat::default(), Precision::default(), 1);
+    assert_eq!(formats.parse(std::path::Path::new("a.dat"), &bytes).unwrap().len(), 2);
+    assert!(DatFormat::default().sniff(&bytes));
+}
+
+// Each form is tried in the middle of a line, at the end of a line, and at
+// the very end of the input, where the parser has no following byte to see
+const NUMERIC_TOKENS: &[(&str, f64)] = &[
+    ("0.25", 0.25),
+    ("25", 25.0),
+    ("-25", -25.0),
+    ("+25", 25.0),
+    ("+0.5", 0.5),
+    ("-.5", -0.5),
+    (".5", 0.5),
+    ("5.", 5.0),
+    ("1e10", 1e10),
+    ("1E10", 1e10),
+    ("1.2E-3", 1.2e-3),
+    ("1.2e+3", 1.2e3),
+    ("-1.2E+03", -1.2e3),
+    ("+1e-2", 1e-2),
+    ("0x1.8p3", 12.0),
+    ("0X1.8P3", 12.0),
+    ("-0x1.0p-2", -0.25),
+    ("0x10p0", 16.0),
+    ("0x1p4", 16.0),
+    ("0x0.0p0", 0.0),
+    ("Infinity", std::f64::INFINITY),
+    ("-Infinity", std::f64::NEG_INFINITY),
+    ("+Infinity", std::f64::INFINITY),
+];
+
+fn same(a: f64, b: f64) -> bool { a == b || (a.is_nan() && b.is_nan()) }
+
+#[test]
+fn numeric_token_forms() {
+    for (token, value) in NUMERIC_TOKENS.iter() {
+        let middle = format!("0.0 0.08 {} 1.0 11.0 5.0\n", token);
+        let end = format!("0.0 0.08 0.1 1.0 11.0 {}\n", token);
+        let last = format!("0.0 0.08 0.1 1.0 11.0 {}", token);
+        let got = |text: &str| parse_data_lines(text.as_bytes()).map(|v| v.to_vec());
+        match got(middle.as_str()) {
+            Ok(v) => assert!(v.len() == 1 && same(v[0].speed, *value), "{} in the middle gave {:?}", token, v),
+            Err(e) => panic!("{} in the middle: {}", token, e),
+        }
+        for text in [end, last].iter() {
+            match got(text.as_str()) {
+                Ok(v) => assert!(v.len() == 1 && same(v[0].y, *value), "{} at the end gave {:?}", token, v),
+                Err(e) => panic!("{} at the end of {:?}: {}", token, text, e),
+            }
+        }
+    }
+}
+
+#[test]
+fn nan_token() {
+    let v = parse_data_lines(b"0.0 0.08 NaN 1.0 11.0 NaN").unwrap();
+    assert!(v[0].speed.is_nan() && v[0].y.is_nan());
+}
+
+#[test]
+fn malformed_numbers_are_rejected() {
+    for token in ["1e", "0x", "0x1.8", "1.2.3", "--1", "e5", "0xg"].iter() {
+        let text = format!("0.0 0.08 {} 1.0 11.0 5.0\n", token);
+        assert!(parse_data_lines(text.as_bytes()).is_err(), "{} was accepted", token);
+    }
+}
+
+fn hint_for(text: &[u8]) -> String {
+    match DatFormat::default().parse(text) {
+        Err(MetrologyError::Misformat{ hint, .. }) => hint,
+        other => panic!("expected a misformat hint for {:?}, got {:?}", String::from_utf8_lossy(text), other.map(|v| v.len())),
+    }
+}
+
+#[test]
+fn misformatted_files_get_hints() {
+    assert!(hint_for(b"% 1\n1 0.042 320.5 210.2 95 ... %% 1 2 3\n").contains(".blobs"));
+    assert!(hint_for(b"1 0.000 3 3 0.0 %% 0 1\n").contains("summary"));
+    assert!(hint_for(b"PK\x03\x04\x14\x00").contains("zip"));
+    assert!(hint_for(b"time area speed midline x y\n0.0 0.08 0.1 1.0 11.0 5.0\n").contains("header"));
+    assert!(hint_for(b"0.0,0.08,0.1,1.0,11.0,5.0\n").contains("commas"));
+    assert!(hint_for(b"0.0 0.08 0.1 1.0\n").contains("line 1 has 4"));
+}
+
+#[test]
+fn other_parse_errors_have_no_hint() {
+    match DatFormat::default().parse(b"0.0 0.08 x 1.0 11.0 5.0\n") {
+        Err(MetrologyError::Parse{ line }) => assert_eq!(line, 1),
+        other => panic!("expected a plain parse error, got {:?}", other.map(|v| v.len())),
+    }
+}
+
+#[test]
+fn worm_ids_past_u32_are_kept_whole() {
+    let big: WormId = 20_221_014_000_123;
+    assert_eq!(worm_fragment("20221014000123a"), Some((big, "a")));
+    let lines: Vec<DataLine> = (0..300).map(|i| DataLine{ time: i as f64, area: 0.08, speed: 0.1, midline: 1.0, x: 11.0, y: 5.0, frame: i, source_line: 0 }).collect();
+    let s = the_everything(big, &lines, &Config::default());
+    let json = serde_json::to_string(&vec![s.clone()]).unwrap();
+    assert!(json.starts_with("[{\"id\":20221014000123,"));
+    assert_eq!(serde_json::from_str::<Vec<Scores>>(json.as_str()).unwrap()[0].id, big);
+    assert_eq!(Scores::from_csv_text(table_of(&[s]).unwrap().as_str()).unwrap()[0].id, big);
+    let old = json.replace("20221014000123", "42");
+    assert_eq!(serde_json::from_str::<Vec<Scores>>(old.as_str()).unwrap()[0].id, 42);
+}
+
+#[test]
+fn fragments_are_named_and_stitched_in_time_order() {
+    assert_eq!(worm_fragment("042"), Some((42, "")));
+    assert_eq!(worm_fragment("042b"), Some((42, "b")));
+    assert_eq!(worm_fragment("04x2"), None);
+    assert_eq!(worm_fragment("a"), None);
+    let piece = |times: &[f64]| -> Vec<DataLine> {
+        times.iter().map(|&t| DataLine{ time: t, area: 0.08, speed: 0.1, midline: 1.0, x: 11.0, y: 5.0, frame: 0, source_line: 0 }).collect()
+    };
+    let joined = stitch_fragments(vec![piece(&[2.0, 3.0]), piece(&[0.0, 1.0, 2.0])]).unwrap();
+    assert_eq!(joined.iter().map(|d| d.time).collect::<Vec<f64>>(), vec![0.0, 1.0, 2.0, 2.0, 3.0]);
+    match stitch_fragments(vec![piece(&[0.0, 2.0]), piece(&[1.0, 3.0])]) {
+        Err(MetrologyError::FragmentOverlap{ end, start }) => assert_eq!((end, start), (2.0, 1.0)),
+        other => panic!("expected overlapping fragments, got {:?}", other.map(|v| v.len())),
+    }
+}
+
+#[test]
+fn integrity_problems_name_the_line() {
+    let data = DatFormat::default().parse(b"0.0 0.08 0.1 1.0 11.0 5.0\n0.5 0.08 NaN 1.0 11.0 5.0\n1.0 0.08 0.1 1.0 11.0 5.0\n").unwrap();
+    assert!(check_integrity(&data).is_ok());
+    let data = DatFormat::default().parse(b"0.0 0.08 0.1 1.0 11.0 5.0\n0.5 0.08 0.1 1.0 11.0 5.0\n0.5 0.0 -0.1 1.0 11.0 5.0\n").unwrap();
+    match check_integrity(&data) {
+        Err(MetrologyError::Integrity{ problem, others }) => {
+            assert_eq!(problem, "line 3: time 0.5 does not follow 0.5 on line 2");
+            assert_eq!(others, 2);
+        },
+        other => panic!("expected an integrity error, got {:?}", other),
+    }
+}
+
+#[test]
+fn snapshots_keep_both_ends_and_parse_again() {
+    let text: String = (0..10).map(|i| format!("{}.0 0.08 0.1 1.0 11.0 5.0\n", i)).collect();
+    let kept = snapshot_bytes(text.as_bytes(), 2);
+    let times: Vec<f64> = parse_data_lines(&kept).unwrap().iter().map(|d| d.time).collect();
+    assert_eq!(times, vec![0.0, 1.0, 8.0, 9.0]);
+    assert_eq!(snapshot_bytes(text.as_bytes(), 5), text.as_bytes());
+    assert_eq!(snapshot_bytes(&text.as_bytes()[..text.len() - 1], 1), b"0.0 0.08 0.1 1.0 11.0 5.0\n9.0 0.08 0.1 1.0 11.0 5.0\n".to_vec());
+}
+
+#[test]
+fn track_files_are_filtered_and_sorted_by_name() {
+    let dir = std::env::temp_dir().join(format!("metrology-track-files-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("b.10.dat")).unwrap();
+    for name in ["b.2.dat", "a.7.wcon", "b.10.DAT", "notes.txt", "b.1.dat", "a.7.dat"].iter() { std::fs::write(dir.join(name), PLAIN).unwrap(); }
+    let formats = TrackFormats::new(&InputFormat::default(), Precision::default(), 1);
+    let found = formats.track_files(&dir);
+    std::fs::remove_dir_all(&dir).unwrap();
+    let names: Vec<String> = found.unwrap().iter().map(|p| p.file_name().unwrap().to_string_lossy().to_string()).collect();
+    assert_eq!(names, vec!["a.7.dat", "a.7.wcon", "b.1.dat", "b.2.dat"]);
+}
+
+// About 5 MB of lines, so that several threads each get a chunk
+fn long_text() -> String {
+    (0..120_000).map(|i| format!("{} {} {} 1.0{} {} 5.0\n", i as f64 * 0.04, 0.08 + (i % 7) as f64 * 1e-3, (i % 13) as f64 * 0.01, i % 10, 10.0 + i as f64 * 1e-4)).collect()
+}
+
+fn parsed_fully(lines: &[DataLine]) -> Vec<(u64, u64, u64, u64, u64)> {
+    lines.iter().map(|d| (d.time.to_bits(), d.speed.to_bits(), d.midline.to_bits(), d.frame, d.source_line)).collect()
+}
+
+#[test]
+fn parallel_parsing_matches_serial_parsing() {
+    let text = long_text();
+    let serial = parsed_fully(&parse_data_lines(text.as_bytes()).unwrap());
+    assert_eq!(serial.len(), 120_000);
+    for threads in [1, 2, 3, 4, 7, 16].iter() {
+        assert_eq!(parsed_fully(&parse_data_lines_parallel(text.as_bytes(), *threads).unwrap()), serial, "{} threads", threads);
+    }
+}
+
+#[test]
+fn parallel_parsing_stops_where_serial_parsing_stops() {
+    let mut text = long_text();
+    let cut = text[text.len() * 7 / 10..].find('\n').unwrap() + text.len() * 7 / 10 + 1;
+    text.insert_str(cut, "not a data line\n");
+    let serial = parsed_fully(&parse_data_lines(text.as_bytes()).unwrap());
+    assert!(serial.len() < 120_000);
+    for threads in [2, 3, 4, 7, 16].iter() {
+        assert_eq!(parsed_fully(&parse_data_lines_parallel(text.as_bytes(), *threads).unwrap()), serial, "{} threads", threads);
+    }
+}
+
+#[test]
+fn parallel_parsing_fails_where_serial_parsing_fails() {
+    let text = long_text();
+    let headed = format!("time area speed midline x y\n{}", text);
+    let broken = format!("\n\n0.0 0.08 x 1.0 11.0 5.0\n{}", text);
+    let blank = " \n".repeat(1 << 20);
+    for bad in [headed, broken, blank].iter() {
+        let serial = format!("{:?}", parse_data_lines(bad.as_bytes()).map(|v| v.len()));
+        assert!(serial.starts_with("Err"), "{}", serial);
+        for threads in [2, 4, 7].iter() {
+            assert_eq!(format!("{:?}", parse_data_lines_parallel(bad.as_bytes(), *threads).map(|v| v.len())), serial, "{} threads", threads);
+        }
+    }
+}
+
+#[test]
+fn long_files_with_a_header_get_the_hint_on_any_number_of_threads() {
+    let headed = format!("time area speed midline x y\n{}", long_text());
+    for threads in [1, 4].iter() {
+        match (DatFormat{ threads: *threads, ..DatFormat::default() }).parse(headed.as_bytes()) {
+            Err(MetrologyError::Misformat{ line, hint }) => { assert_eq!(line, 1); assert!(hint.contains("header")); },
+            other => panic!("expected a header hint on {} threads, got {:?}", threads, other.map(|v| v.len())),
+        }
+    }
+}
@@ -0,0 +1,45 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use metrology::*;
+
+mod common;
+use common::track;
+
+
+// The second worm is too short to have an aroused window
+fn two_worms() -> Vec<Scores> {
+    vec![the_everything(1, &track(500), &Config::default()), the_everything(2, &track(15), &Config::default())]
+}
+
+fn names(names: &[&str]) -> Vec<String> { names.iter().map(|n| n.to_string()).collect() }
+
+fn pivoted(values: &[&str], shape: PivotShape) -> Result<String, MetrologyError> { pivot_table(&two_worms(), &names(&["id"]), &names(values), shape) }
+
+#[test]
+fn wide_pivots_have_a_column_per_value() {
+    let wide = pivoted(&["area-mean", "aroused-mean"], PivotShape::Wide).unwrap();
+    let lines: Vec<&str> = wide.lines().collect();
+    assert_eq!(lines[0], "id area-mean aroused-mean");
+    assert!(lines[1].starts_with("1 0.08 "));
+    assert_eq!(lines[2], "2 0.08 NaN");
+}
+
+#[test]
+fn long_pivots_have_a_row_per_value() {
+    let long = pivoted(&["area-mean", "aroused-mean"], PivotShape::Long).unwrap();
+    assert_eq!(long.lines().count(), 5);
+    assert_eq!(long.lines().nth(4), Some("2 aroused-mean NaN"));
+}
+
+#[test]
+fn unknown_columns_are_rejected() {
+    assert!(pivoted(&["no-such-column"], PivotShape::Wide).is_err());
+}
+
+#[test]
+fn no_values_pivots_every_column() {
+    let all = pivoted(&[], PivotShape::Wide).unwrap();
+    assert_eq!(all.lines().next().unwrap().split(' ').count(), two_worms()[0].title().split(' ').count());
+}
@@ -0,0 +1,54 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use metrology::*;
+
+
+// Values with more digits than rounding keeps
+fn wiggly(n: usize) -> Vec<DataLine> {
+    (0..n).map(|i| {
+        let t = i as f64 / 3.0;
+        DataLine{
+            time: t, area: 0.08 + (t / 7.0).sin() / 300.0, speed: 0.1 + (t / 5.0).cos() / 30.0, midline: 1.0 + t.sqrt() / 1e3,
+            x: 10.0 + t / 7.0, y: 5.0 + (t / 11.0).sin(), frame: i as u64, source_line: i as u64 + 1
+        }
+    }).collect()
+}
+
+fn full() -> Config { Config::from_toml("[metrics]\nfull_precision = true\nbounds = true\nspread = true\n").unwrap() }
+
+fn bits(s: &Scores) -> Vec<u64> {
+    let mut bits: Vec<u64> = s.headline().iter().map(|(_, v)| v.to_bits()).collect();
+    bits.extend([s.area.stats().sem, s.x.first, s.x.stats.mean, s.spread.as_ref().unwrap().gyration].iter().map(|v| v.to_bits()));
+    bits
+}
+
+#[test]
+fn full_precision_keeps_every_bit() {
+    let lines = wiggly(2000);
+    let exact = the_area(&lines).mean();
+    assert_eq!(the_everything(1, &lines, &full()).area.stats().mean.to_bits(), exact.to_bits());
+    assert_ne!(the_everything(1, &lines, &Config::default()).area.stats().mean.to_bits(), exact.to_bits());
+}
+
+#[test]
+fn full_precision_json_reads_back_bit_exactly() {
+    let s = the_everything(1, &wiggly(2000), &full());
+    let back: Vec<Scores> = serde_json::from_str(serde_json::to_string(&vec![s.clone()]).unwrap().as_str()).unwrap();
+    assert_eq!(bits(&back[0]), bits(&s));
+}
+
+#[test]
+fn full_precision_table_reads_back_bit_exactly() {
+    let s = the_everything(1, &wiggly(2000), &full());
+    let back = Scores::from_csv_text(table_of(&[s.clone()]).unwrap().as_str()).unwrap();
+    assert_eq!(bits(&back[0]), bits(&s));
+}
+
+#[test]
+fn full_precision_is_recorded_in_the_configuration_only_when_on() {
+    assert!(full().to_toml().contains("full_precision = true"));
+    assert!(!Config::default().to_toml().contains("full_precision"));
+    assert_ne!(full().hash(), Config::from_toml("[metrics]\nbounds = true\nspread = true\n").unwrap().hash());
+}
@@ -0,0 +1,104 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use proptest::prelude::*;
+
+use metrology::*;
+
+
+// Reported values are rounded to six decimal places, so agree only that far
+const ROUNDING: f64 = 1e-6;
+
+fn value() -> impl Strategy<Value = f64> {
+    prop_oneof![
+        8 => -1e3..1e3f64,
+        1 => Just(std::f64::NAN),
+        1 => prop_oneof![Just(std::f64::INFINITY), Just(std::f64::NEG_INFINITY)],
+    ]
+}
+
+fn values() -> impl Strategy<Value = Vec<f64>> { prop::collection::vec(value(), 0..200) }
+
+// Frames every half second with the given speeds
+fn track(speeds: &[f64]) -> Vec<DataLine> {
+    speeds.iter().enumerate().map(|(i, &s)| {
+        DataLine{ time: 0.5 * i as f64, area: 0.08, speed: s, midline: 1.0, x: 10.0, y: 5.0, frame: i as u64, source_line: 0 }
+    }).collect()
+}
+
+fn finite(xs: &[f64]) -> Vec<f64> { xs.iter().cloned().filter(|x| x.is_finite()).collect() }
+
+fn coord_of(xs: &[f64]) -> Coord { the_coord(|d| d.x, Precision::default(), &xs.iter().map(|&x| DataLine{ x, ..track(&[0.0])[0].clone() }).collect::<Vec<_>>()) }
+
+proptest! {
+    #[test]
+    fn coord_is_bounded_and_counts_finite_values(xs in values()) {
+        let c = coord_of(&xs);
+        let ok = finite(&xs);
+        prop_assert_eq!(c.stats.n, ok.len() as u64);
+        if let (Some(first), Some(last)) = (ok.first(), ok.last()) {
+            prop_assert!(c.bound0 - ROUNDING <= c.stats.mean && c.stats.mean <= c.bound1 + ROUNDING);
+            prop_assert_eq!(c.bound0, ok.iter().cloned().fold(std::f64::INFINITY, f64::min));
+            prop_assert_eq!(c.bound1, ok.iter().cloned().fold(std::f64::NEG_INFINITY, f64::max));
+            prop_assert_eq!((c.first, c.last), (*first, *last));
+        }
+        else { prop_assert!(c.stats.mean.is_nan()); }
+    }
+
+    #[test]
+    fn sampled_ignores_order((xs, shuffled) in values().prop_flat_map(|xs| (Just(xs.clone()), Just(xs).prop_shuffle()))) {
+        let a = coord_of(&xs).stats;
+        let b = coord_of(&shuffled).stats;
+        prop_assert_eq!(a.n, b.n);
+        if a.n > 0 {
+            prop_assert!((a.mean - b.mean).abs() <= ROUNDING, "{} vs {}", a.mean, b.mean);
+            prop_assert!((a.sem - b.sem).abs() <= ROUNDING, "{} vs {}", a.sem, b.sem);
+        }
+    }
+
+    #[test]
+    fn running_and_columnar_estimators_agree(xs in values()) {
+        let whole = coord_of(&xs);
+        let mut running = RunningCoord::new(|d| d.x);
+        for &x in xs.iter() { running.add_value(x); }
+        let running = running.finish(Precision::default());
+        let columnar = column_coord(&xs, Precision::default());
+        for c in [running, columnar].iter() {
+            prop_assert_eq!(c.stats.n, whole.stats.n);
+            if whole.stats.n > 0 {
+                prop_assert_eq!((c.first, c.last, c.bound0, c.bound1), (whole.first, whole.last, whole.bound0, whole.bound1));
+                prop_assert!((c.stats.mean - whole.stats.mean).abs() <= ROUNDING);
+            }
+        }
+    }
+
+    #[test]
+    fn speed_window_uses_exactly_its_frames(speeds in prop::collection::vec(prop_oneof![9 => 0.0..1.0f64, 1 => Just(std::f64::NAN)], 0..200), t0 in 0.0..60.0f64, length in 0.0..30.0f64) {
+        let t1 = t0 + length;
+        let lines = track(&speeds);
+        let inside: Vec<f64> = finite(&lines.iter().filter(|d| d.time >= t0 && d.time <= t1).map(|d| d.speed).collect::<Vec<_>>());
+        match the_speed_in(t0, t1, &MaxEstimator::default(), Precision::default(), &lines) {
+            Some(s) => {
+                prop_assert_eq!(s.stats.n, inside.len() as u64);
+                let (lo, hi) = inside.iter().fold((std::f64::INFINITY, std::f64::NEG_INFINITY), |(a, b), &v| (a.min(v), b.max(v)));
+                prop_assert!(lo - ROUNDING <= s.stats.mean && s.stats.mean <= hi + ROUNDING);
+                prop_assert!(s.max <= hi + ROUNDING);
+            },
+            None => prop_assert!(inside.len() < 5 || lines.iter().all(|d| d.time >= t0) || lines.iter().all(|d| d.time <= t1)),
+        }
+    }
+
+    #[test]
+    fn validity_ranges_only_remove_values(xs in values(), lo in -500.0..0.0f64, width in 0.0..1000.0f64) {
+        let mut lines: Vec<DataLine> = xs.iter().map(|&x| DataLine{ x, ..track(&[0.0])[0].clone() }).collect();
+        let ranges = ValidityRanges{ x: Some([lo, lo + width]), ..ValidityRanges::default() };
+        let removed = ranges.apply(&mut lines).map(|o| o.x).unwrap_or(0);
+        prop_assert_eq!(lines.len(), xs.len());
+        for (d, &x) in lines.iter().zip(xs.iter()) {
+            if d.x.is_finite() { prop_assert!(d.x == x && lo <= x && x <= lo + width); }
+        }
+        let kept = finite(&lines.iter().map(|d| d.x).collect::<Vec<_>>()).len() as u64;
+        prop_assert_eq!(kept + removed, finite(&xs).len() as u64);
+    }
+}
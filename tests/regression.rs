@@ -0,0 +1,90 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use std::path::PathBuf;
+
+use metrology::*;
+
+
+// Each fixture in testdata, in any track format, is scored with the default
+// configuration and compared with its golden scores and table in
+// testdata/golden.  `metrology snapshot` adds fixtures.  After a deliberate
+// change to the metrics, run with METROLOGY_BLESS=1 to rewrite the golden
+// files, and review the difference before committing it.
+fn testdata() -> PathBuf { PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("testdata") }
+
+fn fixtures() -> Vec<PathBuf> {
+    let mut found: Vec<PathBuf> = std::fs::read_dir(testdata()).unwrap()
+        .map(|e| e.unwrap().path())
+        .filter(|p| p.is_file())
+        .collect();
+    found.sort();
+    found
+}
+
+fn scored(name: &str) -> Scores {
+    let bytes = std::fs::read(testdata().join(format!("{}.dat", name))).unwrap();
+    let mut scores = the_everything(1, &parse_data_lines(&bytes).unwrap(), &Config::default());
+    // The hash follows every change to the configuration, not just to the metrics
+    scores.config_hash = None;
+    scores
+}
+
+fn speed_mean(s: Option<&Speed>) -> f64 { s.unwrap().stats.mean }
+
+fn close(a: f64, b: f64) -> bool { (a - b).abs() < 1e-6 }
+
+#[test]
+fn scores_match_golden_files() {
+    let bless = std::env::var_os("METROLOGY_BLESS").is_some();
+    let mut changed = Vec::new();
+    let formats = TrackFormats::new(&InputFormat::default(), Precision::default(), 1);
+    for path in fixtures().iter() {
+        let name = path.file_stem().unwrap().to_string_lossy();
+        let lines = formats.parse(path, &std::fs::read(path).unwrap()).unwrap();
+        for (ext, text) in golden_outputs(&lines).unwrap().iter() {
+            let golden = testdata().join("golden").join(format!("{}.{}", name, ext));
+            if bless {
+                std::fs::create_dir_all(golden.parent().unwrap()).unwrap();
+                std::fs::write(&golden, text).unwrap();
+            }
+            else if std::fs::read_to_string(&golden).ok().as_ref() != Some(text) { changed.push(golden); }
+        }
+    }
+    assert!(changed.is_empty(), "Scores differ from {:?}; if the change is intended, rerun with METROLOGY_BLESS=1", changed);
+}
+
+#[test]
+fn constant_speed_is_exact() {
+    let s = scored("constant_speed");
+    for (_, w) in s.speeds.iter() {
+        assert!(close(speed_mean(w), 0.1));
+        assert_eq!(w.unwrap().stats.sem, 0.0);
+    }
+    assert!(close(s.area.stats().mean, 0.08));
+    assert!(close(s.x.first, 10.0) && close(s.x.last, 56.0));
+    assert!(s.qc.flags.is_empty(), "{:?}", s.qc.flags);
+}
+
+#[test]
+fn linear_drift_averages_to_window_centers() {
+    let s = scored("linear_drift");
+    let speed = |t: f64| 0.05 + 0.0001 * t;
+    let windows = SpeedWindows::default();
+    for (name, window) in windows.named().iter() {
+        let w = s.speeds.get(name);
+        assert!(close(speed_mean(w), speed((window.start + window.end) / 2.0)), "{} in {:?}", speed_mean(w), window);
+    }
+    assert!(close(s.area.stats().mean, 0.08 + 0.00001 * 230.0));
+}
+
+#[test]
+fn injected_nan_is_skipped() {
+    let clean = scored("constant_speed");
+    let s = scored("injected_nan");
+    assert!(close(s.area.stats().mean, 0.08));
+    assert_eq!(s.area.stats().n, clean.area.stats().n - 92);
+    assert!(close(speed_mean(s.speeds.get("calm")), 0.1));
+    assert!(s.speeds.get("calm").unwrap().stats.n < clean.speeds.get("calm").unwrap().stats.n);
+}
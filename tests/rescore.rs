@@ -0,0 +1,106 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use metrology::*;
+
+
+fn track() -> Vec<DataLine> {
+    synth_track(&SynthSettings{ noise: 0.01, response: 0.2, turning: 0.5, seed: 7, ..SynthSettings::assay() })
+}
+
+fn json(s: &[Scores]) -> String { serde_json::to_string(s).unwrap() }
+
+// Rescoring the old scores for a configuration with `toml` applied must match scoring from scratch
+fn rescores_like_scratch(toml: &str, section: &str) {
+    let lines = track();
+    let old_config = Config::default();
+    let new_config = Config::from_toml(toml).unwrap();
+    let changed = changed_sections(&old_config, &new_config);
+    assert_eq!(changed, vec![section.to_string()]);
+    let old = the_tracks(3, &lines, &old_config);
+    let scratch = the_tracks(3, &lines, &new_config);
+    assert_ne!(json(&old), json(&scratch), "changing {} should change the scores", section);
+    assert_eq!(json(&the_rescored(3, &old, &changed, &lines, &new_config)), json(&scratch));
+}
+
+#[test]
+fn rescoring_windows_matches_scratch() {
+    rescores_like_scratch(concat!(
+        "[windows]\nspeed = [\n",
+        "  { name = \"initial\", start = 10.0, end = 20.0 },\n",
+        "  { name = \"calm\", start = 250.0, end = 280.0 },\n",
+        "  { name = \"aroused\", start = 440.0, end = 450.0 },\n",
+        "]\n",
+    ), "windows");
+}
+
+#[test]
+fn rescoring_qc_matches_scratch() {
+    rescores_like_scratch("[qc]\narea_rate = 0.001\nx_rate = 0.001\n", "qc");
+}
+
+#[test]
+fn rescoring_qc_keeps_only_the_configured_windows() {
+    let windows = concat!(
+        "[windows]\nspeed = [\n",
+        "  { name = \"calm\", start = 270.0, end = 290.0 },\n",
+        "  { name = \"aroused\", start = 440.0, end = 450.0 },\n",
+        "]\n",
+    );
+    let lines = track();
+    let old_config = Config::from_toml(windows).unwrap();
+    let new_config = Config::from_toml(format!("{}[qc]\narea_rate = 0.001\nx_rate = 0.001\n", windows).as_str()).unwrap();
+    let changed = changed_sections(&old_config, &new_config);
+    assert_eq!(changed, vec!["qc".to_string()]);
+    // Old scores are read back from their file, which brings back the default windows
+    let old: Vec<Scores> = serde_json::from_str(json(&the_tracks(3, &lines, &old_config)).as_str()).unwrap();
+    let rescored = the_rescored(3, &old, &changed, &lines, &new_config);
+    assert_eq!(rescored[0].speeds.names().collect::<Vec<&str>>(), new_config.windows.names());
+    assert_eq!(rescored[0].title(), Scores::titles_for(&new_config, "").join(" "));
+    assert_eq!(rescored[0].title().split(' ').count(), rescored[0].to_string().split(' ').count());
+}
+
+#[test]
+fn rescoring_windowed_matches_scratch() {
+    rescores_like_scratch("[[windowed]]\nname = \"early\"\nstart = 0.0\nend = 60.0\nmetrics = [\"area\", \"speed\", \"activity\"]\n", "windowed");
+}
+
+#[test]
+fn rescoring_composite_matches_scratch() {
+    rescores_like_scratch("[composite]\narea = 1.0\nspeed = 2.0\nactivity = 1.0\n", "composite");
+}
+
+#[test]
+fn rescoring_exploration_matches_scratch() {
+    rescores_like_scratch("[exploration]\ncell = 0.5\narena = { x0 = 0.0, x1 = 60.0, y0 = 0.0, y1 = 40.0 }\n", "exploration");
+}
+
+#[test]
+fn rescoring_edge_matches_scratch() {
+    rescores_like_scratch("[edge]\ndistance = 1.0\nwell = { x = 5.0, y = 5.0, radius = 4.5 }\n", "edge");
+}
+
+#[test]
+fn rescoring_derived_matches_scratch() {
+    rescores_like_scratch("[derived]\narousal-ratio = \"mean(speed, 440..450) / mean(speed, 270..290)\"\n", "derived");
+}
+
+#[test]
+fn rescoring_bursts_matches_scratch() {
+    rescores_like_scratch("[bursts]\nreport = true\nstart = 0.4\nend = 0.25\nmin_duration = 0.5\n", "bursts");
+}
+
+#[test]
+fn rescoring_omega_matches_scratch() {
+    rescores_like_scratch("[omega]\nreport = true\nshortening = 0.75\nmax_displacement = 0.2\nmin_duration = 0.3\n", "omega");
+}
+
+#[test]
+fn rescoring_a_data_change_scores_from_scratch() {
+    let lines = track();
+    let new_config = Config::from_toml("[metrics]\nspread = true\n").unwrap();
+    let changed = changed_sections(&Config::default(), &new_config);
+    let old = the_tracks(3, &lines, &Config::default());
+    assert_eq!(json(&the_rescored(3, &old, &changed, &lines, &new_config)), json(&the_tracks(3, &lines, &new_config)));
+}
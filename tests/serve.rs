@@ -0,0 +1,122 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+#![cfg(feature = "cli")]
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::process::{Child, Command, Stdio};
+
+use metrology::*;
+
+
+// A server on a free port of this machine, stopped when dropped
+struct Server {
+    child: Child,
+    address: String,
+}
+
+impl Server {
+    fn start(args: &[&str]) -> Server {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_metrology"))
+            .args(["serve", "--port", "0", "--threads", "2"].iter()).args(args)
+            .stdout(Stdio::piped()).spawn().unwrap();
+        let mut lines = BufReader::new(child.stdout.take().unwrap()).lines();
+        let address = lines.find_map(|l| l.unwrap().strip_prefix("Serving on ").and_then(|r| r.split(' ').next()).map(|a| a.to_string())).unwrap();
+        Server{ child, address }
+    }
+
+    // The status and body of the response to a request of `head` lines and `body`
+    fn request(&self, head: &str, body: &[u8]) -> (u16, String) {
+        let mut stream = TcpStream::connect(self.address.as_str()).unwrap();
+        stream.write_all(format!("{}\r\nHost: {}\r\nConnection: close\r\n\r\n", head, self.address).as_bytes()).unwrap();
+        // A refused body may be cut off partway
+        let _ = stream.write_all(body);
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let status = response.split(' ').nth(1).unwrap().parse().unwrap();
+        let body = response.splitn(2, "\r\n\r\n").nth(1).unwrap_or("").to_string();
+        (status, body)
+    }
+
+    fn post(&self, path: &str, body: &[u8]) -> (u16, String) {
+        self.request(format!("POST {} HTTP/1.1\r\nContent-Length: {}", path, body.len()).as_str(), body)
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn dat() -> String { dat_text(&synth_track(&SynthSettings::assay())) }
+
+#[test]
+fn servers_listen_only_on_this_machine_by_default() {
+    assert!(Server::start(&[]).address.starts_with("127.0.0.1:"));
+}
+
+#[test]
+fn version_gives_the_configuration_hash() {
+    let (status, body) = Server::start(&[]).request("GET /version HTTP/1.1", b"");
+    assert_eq!(status, 200);
+    assert!(body.contains(format!("\"config\":\"{}\"", Config::default().hash()).as_str()));
+}
+
+#[test]
+fn posted_tracks_are_scored() {
+    let (status, body) = Server::start(&[]).post("/score?id=7", dat().as_bytes());
+    assert_eq!(status, 200);
+    let scores: Vec<Scores> = serde_json::from_str(body.as_str()).unwrap();
+    assert_eq!(scores.iter().map(|s| s.id).collect::<Vec<_>>(), vec![7]);
+}
+
+#[test]
+fn posted_batches_are_scored() {
+    let batch = serde_json::to_string(&[(1, dat()), (2, dat())].iter().cloned().collect::<std::collections::BTreeMap<u32, String>>()).unwrap();
+    let (status, body) = Server::start(&[]).post("/batch", batch.as_bytes());
+    assert_eq!(status, 200);
+    let scores: Vec<Scores> = serde_json::from_str(body.as_str()).unwrap();
+    assert_eq!(scores.len(), 2);
+}
+
+#[test]
+fn bad_worm_ids_are_rejected() {
+    assert_eq!(Server::start(&[]).post("/score?id=seven", dat().as_bytes()).0, 400);
+}
+
+#[test]
+fn data_that_does_not_parse_is_rejected() {
+    assert_eq!(Server::start(&[]).post("/score?id=7", b"not a track\n").0, 400);
+}
+
+#[test]
+fn wrong_methods_are_rejected() {
+    assert_eq!(Server::start(&[]).request("GET /score?id=7 HTTP/1.1", b"").0, 405);
+}
+
+#[test]
+fn unknown_paths_are_not_found() {
+    assert_eq!(Server::start(&[]).request("GET /scores HTTP/1.1", b"").0, 404);
+}
+
+#[test]
+fn bodies_declared_too_large_are_refused() {
+    assert_eq!(Server::start(&["--max-body", "1K"]).post("/score?id=7", dat()[..2048].as_bytes()).0, 413);
+}
+
+#[test]
+fn chunked_bodies_that_grow_too_large_are_refused() {
+    let mut chunked = Vec::new();
+    for chunk in dat()[..2048].as_bytes().chunks(500) {
+        chunked.extend(format!("{:x}\r\n", chunk.len()).bytes());
+        chunked.extend(chunk);
+        chunked.extend(b"\r\n");
+    }
+    chunked.extend(b"0\r\n\r\n");
+    let server = Server::start(&["--max-body", "1K"]);
+    assert_eq!(server.request("POST /score?id=7 HTTP/1.1\r\nTransfer-Encoding: chunked", &chunked).0, 413);
+}
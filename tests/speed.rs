@@ -0,0 +1,91 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use metrology::*;
+
+mod common;
+use common::track;
+
+
+// Ten frames far faster than the rest
+fn with_spikes() -> Vec<DataLine> {
+    let mut lines = track(500);
+    for d in lines.iter_mut().filter(|d| d.frame % 50 == 7) { d.speed = 5.0; }
+    lines
+}
+
+fn ceiling(action: &str) -> Config { Config::from_toml(format!("[speed.ceiling]\nlimit = 2.0\naction = {:?}\n", action).as_str()).unwrap() }
+
+#[test]
+fn clipped_speeds_stay_at_the_ceiling() {
+    let capped = the_prepared(&with_spikes(), &ceiling("clip"));
+    assert_eq!(capped.ceiling, Some(10));
+    assert!(capped.lines.iter().all(|d| d.speed <= 2.0));
+}
+
+#[test]
+fn clipping_is_the_default() {
+    let config = Config::from_toml("[speed.ceiling]\nlimit = 2.0\n").unwrap();
+    assert!(the_prepared(&with_spikes(), &config).lines.iter().any(|d| d.speed == 2.0));
+}
+
+#[test]
+fn dropped_speeds_are_missing() {
+    assert_eq!(the_prepared(&with_spikes(), &ceiling("drop")).lines.iter().filter(|d| d.speed.is_nan()).count(), 10);
+}
+
+#[test]
+fn frames_over_the_ceiling_are_counted_in_the_scores() {
+    assert_eq!(the_everything(1, &with_spikes(), &ceiling("drop")).ceiling, Some(10));
+    assert_eq!(the_everything(1, &with_spikes(), &Config::default()).ceiling, None);
+}
+
+#[test]
+fn negative_ceilings_are_rejected() {
+    assert!(Config::from_toml("[speed.ceiling]\nlimit = -1.0\n").is_err());
+}
+
+// Every fourth frame paused
+fn with_pauses() -> Vec<DataLine> {
+    let mut lines = track(500);
+    for d in lines.iter_mut().filter(|d| d.frame % 4 == 0) { d.speed = 0.0; }
+    lines
+}
+
+fn moving() -> Config { Config::from_toml("[speed]\nmoving_threshold = 0.05\n").unwrap() }
+
+#[test]
+fn moving_speeds_leave_out_pauses() {
+    let s = the_everything(1, &with_pauses(), &moving());
+    let calm = s.speeds.get("calm").unwrap();
+    let moving = calm.moving.as_ref().unwrap();
+    assert_eq!(moving.mean, 0.1);
+    assert!(calm.stats.mean < 0.1 && moving.n < calm.stats.n && moving.n > 0);
+}
+
+#[test]
+fn moving_speeds_have_columns_after_the_window() {
+    let s = the_everything(1, &with_pauses(), &moving());
+    assert_eq!(s.title(), Scores::titles_for(&moving(), "").join(" "));
+    assert!(s.title().contains(" calm-max calm-moving-n calm-moving-mean calm-moving-sem "));
+}
+
+#[test]
+fn moving_speeds_read_back_from_tables() {
+    let s = the_everything(1, &with_pauses(), &moving());
+    let read = Scores::from_csv_text(table_of(&[s.clone()]).unwrap().as_str()).unwrap();
+    let n = |s: &Scores| s.speeds.get("calm").and_then(|c| c.moving.as_ref()).map(|m| m.n);
+    assert!(n(&s).is_some());
+    assert_eq!(n(&read[0]), n(&s));
+}
+
+#[test]
+fn moving_speeds_are_off_by_default() {
+    assert!(the_everything(1, &with_pauses(), &Config::default()).speeds.get("calm").unwrap().moving.is_none());
+}
+
+#[test]
+fn negative_moving_thresholds_are_rejected() {
+    assert!(Config::from_toml("[speed]\nmoving_threshold = -1.0\n").is_err());
+}
@@ -0,0 +1,69 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use metrology::*;
+
+
+fn speed_mean(s: Option<&Speed>) -> f64 { s.unwrap().stats.mean }
+
+#[test]
+fn same_seed_same_track() {
+    let settings = SynthSettings{ noise: 0.02, missing: 0.05, seed: 7, ..SynthSettings::assay() };
+    let a = synth_track(&settings);
+    let b = synth_track(&settings);
+    assert_eq!(format!("{:?}", a), format!("{:?}", b));
+    assert_ne!(format!("{:?}", a), format!("{:?}", synth_track(&SynthSettings{ seed: 8, ..settings })));
+}
+
+#[test]
+fn scores_recover_the_profile() {
+    let settings = SynthSettings{ noise: 0.01, seed: 3, ..SynthSettings::assay() };
+    let s = the_everything(1, &synth_track(&settings), &Config::default());
+    assert!((speed_mean(s.speeds.get("calm")) - 0.1).abs() < 0.005, "calm {}", speed_mean(s.speeds.get("calm")));
+    let windows = SpeedWindows::default();
+    let w = windows.get("aroused").unwrap();
+    let aroused = settings.expected_speed((w.start + w.end) / 2.0);
+    assert!(speed_mean(s.speeds.get("aroused")) > 0.1 && (speed_mean(s.speeds.get("aroused")) - aroused).abs() < 0.02, "aroused {}", speed_mean(s.speeds.get("aroused")));
+}
+
+#[test]
+fn gaps_are_missing() {
+    let settings = SynthSettings{ gaps: vec![Window::new(100.0, 200.0)], ..SynthSettings::default() };
+    let lines = synth_track(&settings);
+    assert!(lines.iter().filter(|d| d.time >= 100.0 && d.time <= 200.0).all(|d| d.speed.is_nan() && d.x.is_nan()));
+    assert!(lines.iter().filter(|d| d.time < 100.0 || d.time > 200.0).all(|d| d.speed.is_finite()));
+}
+
+#[test]
+fn fractional_windows_follow_the_recording() {
+    let config = Config::from_toml(concat!(
+        "[windows]\nfractional = true\nspeed = [\n",
+        "  { name = \"initial\", start = 0.02, end = 0.04 },\n",
+        "  { name = \"calm\", start = 0.6, end = 0.62 },\n",
+        "  { name = \"aroused\", start = 0.95, end = 0.97 },\n",
+        "]\n",
+    )).unwrap();
+    for duration in [230.0, 460.0].iter() {
+        let settings = SynthSettings{ duration: *duration, speed: SpeedProfile::Linear{ start: 0.05, slope: 0.1 / duration }, ..SynthSettings::default() };
+        let s = the_everything(1, &synth_track(&settings), &config);
+        assert!(s.warnings.is_empty(), "{:?}", s.warnings);
+        assert!((speed_mean(s.speeds.get("aroused")) - (0.05 + 0.1 * 0.96)).abs() < 1e-3, "aroused {} over {} s", speed_mean(s.speeds.get("aroused")), duration);
+    }
+    assert!(Config::from_toml("[windows]\nfractional = true\n").is_err());
+}
+
+#[test]
+fn selftest_worms_match_their_golden_values() {
+    let scores: Vec<Scores> = selftest_worms().iter().map(|(id, settings)| {
+        let lines = parse_data_lines(dat_text(&synth_track(settings)).as_bytes()).unwrap();
+        the_everything(*id, &lines, &Config::default())
+    }).collect();
+    assert_eq!(selftest_failures(&scores), Vec::<String>::new());
+    let mut slow = scores.clone();
+    let mut calm = slow[1].speeds.get("calm").cloned().unwrap();
+    calm.stats.mean *= 0.9;
+    slow[1].speeds.set("calm", Some(calm));
+    assert_eq!(selftest_failures(&slow).len(), 1);
+    assert!(selftest_failures(&scores[1..]).len() > 1);
+}
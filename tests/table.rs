@@ -0,0 +1,140 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use metrology::*;
+
+mod common;
+use common::track;
+
+
+#[test]
+fn widths_match() {
+    assert!(check_widths("a b c", &vec!["1 2 3".to_string(), "4 5 6".to_string()]).is_ok());
+}
+
+#[test]
+fn widths_mismatch_names_the_row() {
+    match check_widths("a b c", &vec!["1 2 3".to_string(), "4 5".to_string()]) {
+        Err(MetrologyError::Schema{ row, expected, found }) => assert_eq!((row, expected, found), (2, 3, 2)),
+        x => panic!("Expected schema error, got {:?}", x),
+    }
+}
+
+#[test]
+fn scores_table_is_consistent_with_missing_windows() {
+    let config = Config::default();
+    let full = the_everything(1, &track(500), &config);
+    let short = the_everything(2, &track(15), &config);
+    let empty = the_everything(3, &Vec::new(), &config);
+    assert!(short.speeds.get("aroused").is_none());
+    let table = table_of(&[full, short, empty]).unwrap();
+    assert_eq!(table.lines().count(), 4);
+}
+
+#[test]
+fn scores_table_is_consistent_with_bounds() {
+    let mut config = Config::default();
+    config.metrics.bounds = true;
+    let rows = vec![the_everything(1, &track(500), &config), the_everything(2, &Vec::new(), &config)];
+    assert!(table_of(&rows).is_ok());
+}
+
+#[test]
+fn static_titles_match_scored_titles() {
+    let mut config = Config::from_toml("[metrics]\nbounds = true\nrates = true\nshape_index = true\n\n[[windowed]]\nname = \"late\"\nstart = 100\nend = 200\nmetrics = [\"speed\", \"coord\", \"activity\"]\n").unwrap();
+    let titles = Scores::titles_for(&config, "").join(" ");
+    assert_eq!(the_everything(1, &track(500), &config).title(), titles);
+    assert_eq!(the_everything(2, &Vec::new(), &config).title(), titles);
+    config.metrics.bounds = false;
+    assert_eq!(the_everything(3, &track(500), &config).title(), Scores::titles_for(&config, "").join(" "));
+}
+
+fn with_intervals() -> Config {
+    Config::from_toml("[metrics]\nci95 = true\nbounds = true\nrates = true\n\n[[windowed]]\nname = \"late\"\nstart = 100\nend = 200\nmetrics = [\"speed\", \"area\"]\n").unwrap()
+}
+
+#[test]
+fn intervals_are_columns_only_when_configured() {
+    let config = with_intervals();
+    let titles = Scores::titles_for(&config, "");
+    assert!(titles.iter().any(|t| t == "calm-ci95-lo") && titles.iter().any(|t| t == "late-speed-ci95-hi"));
+    assert!(!Scores::titles_for(&Config::default(), "").iter().any(|t| t.contains("ci95")));
+    assert!(!the_everything(1, &track(500), &Config::default()).to_string().contains("ci95"));
+}
+
+#[test]
+fn intervals_surround_the_mean() {
+    let s = the_everything(1, &track(500), &with_intervals());
+    let calm = &s.speeds.get("calm").unwrap().stats;
+    assert!(calm.ci95_lo.unwrap() <= calm.mean && calm.mean <= calm.ci95_hi.unwrap());
+    assert!(s.x.stats.ci95_lo.unwrap() < s.x.stats.mean);
+    assert!(the_everything(1, &track(500), &Config::default()).x.stats.ci95_lo.is_none());
+}
+
+#[test]
+fn intervals_keep_rows_as_wide_as_titles_when_windows_are_missing() {
+    let config = with_intervals();
+    let titles = Scores::titles_for(&config, "").join(" ");
+    for s in [the_everything(1, &track(500), &config), the_everything(2, &track(15), &config), the_everything(3, &Vec::new(), &config)].iter() {
+        assert_eq!(s.title(), titles);
+        assert!(check_widths(titles.as_str(), &vec![s.to_string()]).is_ok(), "{}", s);
+    }
+}
+
+#[test]
+fn missing_intervals_read_back_from_json() {
+    let short = the_everything(2, &track(15), &with_intervals());
+    let read: Scores = serde_json::from_str(serde_json::to_string(&short).unwrap().as_str()).unwrap();
+    assert_eq!(read.to_string(), short.to_string());
+    assert_eq!(read.title(), short.title());
+}
+
+#[test]
+fn intervals_read_back_from_csv() {
+    let config = with_intervals();
+    let rows = vec![the_everything(1, &track(500), &config), the_everything(2, &track(15), &config)];
+    let read = Scores::from_csv_text(table_of(&rows).unwrap().as_str()).unwrap();
+    assert!(read.iter().all(|s| s.ci95));
+    assert_eq!(read[1].to_string(), rows[1].to_string());
+}
+
+#[test]
+fn selected_columns_keep_header_and_rows_aligned() {
+    let table = "a b c\n1 2 3\n4 5 6\n";
+    assert_eq!(select_columns(table, &["c".to_string(), "a".to_string()]).unwrap(), "c a\n3 1\n6 4\n");
+    assert!(select_columns(table, &["d".to_string()]).is_err());
+}
+
+const EXISTING: &str = "# config abc\nid a b\n1 2 3\n";
+
+#[test]
+fn appended_tables_with_the_same_header_are_concatenated() {
+    assert_eq!(append_table(EXISTING, "# config abc\nid a b\n4 5 6\n", "NA").unwrap(), "# config abc\nid a b\n1 2 3\n4 5 6\n");
+}
+
+#[test]
+fn appended_tables_with_new_columns_fill_in_missing_values() {
+    assert_eq!(append_table(EXISTING, "# config abc\nid b c\n4 5 6\n", "NA").unwrap(), "# config abc\nid a b c\n1 2 3 NA\n4 NA 5 6\n");
+}
+
+#[test]
+fn appending_to_nothing_gives_the_new_table() {
+    assert_eq!(append_table("", "id a\n1 2\n", "NA").unwrap(), "id a\n1 2\n");
+}
+
+#[test]
+fn appended_tables_with_another_configuration_are_rejected() {
+    match append_table(EXISTING, "# config def\nid a b\n4 5 6\n", "NA") {
+        Err(MetrologyError::BadConfig(_)) => (),
+        x => panic!("Expected configuration error, got {:?}", x),
+    }
+}
+
+#[test]
+fn appended_tables_with_short_rows_name_the_row() {
+    match append_table(EXISTING, "# config abc\nid a b\n4 5\n", "NA") {
+        Err(MetrologyError::Schema{ row, expected, found }) => assert_eq!((row, expected, found), (1, 3, 2)),
+        x => panic!("Expected schema error, got {:?}", x),
+    }
+}
@@ -0,0 +1,37 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use metrology::*;
+
+mod common;
+use common::track;
+
+
+fn log_area_sqrt_speed() -> Config { Config::from_toml("[transforms]\narea = \"log\"\nspeed = \"sqrt\"\n").unwrap() }
+
+#[test]
+fn transformed_columns_are_renamed() {
+    let titles = Scores::titles_for(&log_area_sqrt_speed(), "");
+    assert!(titles.contains(&"log-area-mean".to_string()) && titles.contains(&"sqrt-calm-mean".to_string()));
+    assert!(!titles.contains(&"area-mean".to_string()));
+}
+
+#[test]
+fn transformed_titles_match_the_scores() {
+    let config = log_area_sqrt_speed();
+    assert_eq!(the_everything(1, &track(500), &config).title(), Scores::titles_for(&config, "").join(" "));
+}
+
+#[test]
+fn transformed_columns_are_scored_transformed() {
+    let h = the_everything(1, &track(500), &log_area_sqrt_speed()).headline();
+    let value = |name: &str| h.iter().find(|(k, _)| *k == name).map(|(_, v)| *v).unwrap();
+    assert!((value("area-mean") - 0.08f64.ln()).abs() < 1e-5);
+    assert!((value("calm-mean") - 0.1f64.sqrt()).abs() < 1e-5);
+}
+
+#[test]
+fn unknown_transforms_are_rejected() {
+    assert!(Config::from_toml("[transforms]\narea = \"cube\"\n").is_err());
+}
@@ -0,0 +1,165 @@
+// This file is distributed under the BSD 3-clause license.  See file LICENSE.
+// Copyright (c) 2022 Rex Kerr and Calico Life Sciences LLC
+
+
+use metrology::*;
+
+mod common;
+use common::track;
+
+
+fn scored() -> Scores { the_everything(1, &track(500), &Config::default()) }
+
+// Scores with an extra window copied from the aroused one
+fn with_late() -> Scores {
+    let mut s = scored();
+    s.speeds.set("late", s.speeds.get("aroused").cloned());
+    serde_json::from_str(serde_json::to_string(&s).unwrap().as_str()).unwrap()
+}
+
+#[test]
+fn window_speeds_are_named_in_json() {
+    let json = serde_json::to_string(&scored()).unwrap();
+    assert!(json.contains("\"calm_speed\":") && json.contains("\"aroused_speed\":"));
+}
+
+#[test]
+fn window_speeds_read_back_from_json_in_order() {
+    let s = scored();
+    let read: Scores = serde_json::from_str(serde_json::to_string(&s).unwrap().as_str()).unwrap();
+    assert_eq!(read.speeds.names().collect::<Vec<&str>>(), vec!["initial", "calm", "aroused"]);
+    assert_eq!(read.speeds.get("calm").unwrap().stats.mean, s.speeds.get("calm").unwrap().stats.mean);
+}
+
+#[test]
+fn added_windows_read_back_last() {
+    assert_eq!(with_late().speeds.names().last(), Some("late"));
+}
+
+#[test]
+fn added_windows_are_named_columns() {
+    let s = with_late();
+    let (title, row) = (s.title(), s.to_string());
+    assert!(title.contains(" late-mean "));
+    assert_eq!(title.split(' ').count(), row.split(' ').count());
+}
+
+// The default windows and a fourth, listed first
+fn with_early() -> Config {
+    Config::layered("", &["windows.speed.early={ start = 30.0, end = 40.0 }".to_string()]).map(|mut c| {
+        c.windows.speed.rotate_right(1);
+        c
+    }).unwrap()
+}
+
+#[test]
+fn configured_windows_are_columns_in_order() {
+    let config = with_early();
+    let s = the_everything(1, &track(500), &config);
+    assert_eq!(s.speeds.names().collect::<Vec<&str>>(), vec!["early", "initial", "calm", "aroused"]);
+    assert_eq!(s.title(), Scores::titles_for(&config, "").join(" "));
+    assert_eq!(s.title().split(' ').count(), s.to_string().split(' ').count());
+}
+
+#[test]
+fn configured_windows_without_data_keep_their_columns() {
+    let config = with_early();
+    let s = the_everything(1, &track(20), &config);
+    assert!(s.speeds.get("early").is_none());
+    assert_eq!(s.title(), Scores::titles_for(&config, "").join(" "));
+    assert_eq!(s.title().split(' ').count(), s.to_string().split(' ').count());
+}
+
+#[test]
+fn configured_windows_read_back_in_order() {
+    let s = the_everything(1, &track(20), &with_early());
+    let read: Scores = serde_json::from_str(serde_json::to_string(&s).unwrap().as_str()).unwrap();
+    assert_eq!(read.speeds.names().collect::<Vec<&str>>(), vec!["early", "initial", "calm", "aroused"]);
+}
+
+// Windows without the default initial one, and with one of their own
+const WITHOUT_INITIAL: &str = concat!(
+    "[windows]\nspeed = [\n",
+    "  { name = \"calm\", start = 270.0, end = 290.0 },\n",
+    "  { name = \"aroused\", start = 440.0, end = 450.0 },\n",
+    "  { name = \"late\", start = 600.0, end = 610.0 },\n",
+    "]\n",
+);
+
+#[test]
+fn windows_left_out_of_the_config_do_not_read_back() {
+    let config = Config::from_toml(WITHOUT_INITIAL).unwrap();
+    let s = the_everything(1, &track(500), &config);
+    let read: Scores = serde_json::from_str(serde_json::to_string(&s).unwrap().as_str()).unwrap();
+    assert_eq!(read.speeds.names().collect::<Vec<&str>>(), config.windows.names());
+}
+
+fn calm_frames(toml: &str) -> Option<u64> {
+    the_everything(1, &track(500), &Config::from_toml(toml).unwrap()).speeds.get("calm").map(|s| s.stats.n)
+}
+
+const EARLY: &str = concat!(
+    "[windows]\nspeed = [\n",
+    "  { name = \"initial\", start = 0.0, end = 20.0 },\n",
+    "  { name = \"calm\", start = 270.0, end = 290.0 },\n",
+    "  { name = \"aroused\", start = 440.0, end = 450.0 },\n",
+    "]\n",
+);
+
+fn early_without_lead_in() -> Config {
+    Config::from_toml(format!("{}\n[windows.bounds]\nrequire_before = false\n", EARLY).as_str()).unwrap()
+}
+
+#[test]
+fn windows_include_the_frames_at_both_ends() {
+    assert_eq!(calm_frames(""), Some(21));
+}
+
+#[test]
+fn open_starts_leave_out_the_first_frame() {
+    assert_eq!(calm_frames("[windows.bounds]\nstart = \"open\"\n"), Some(20));
+}
+
+#[test]
+fn open_ends_leave_out_the_last_frame() {
+    assert_eq!(calm_frames("[windows.bounds]\nstart = \"open\"\nend = \"open\"\n"), Some(19));
+}
+
+#[test]
+fn windows_at_the_start_are_scored_when_no_lead_in_is_required() {
+    let config = early_without_lead_in();
+    let initial = the_everything(1, &track(500), &config).speeds.get("initial").cloned().unwrap();
+    assert_eq!((initial.stats.n, initial.bounds), (21, Some(config.windows.bounds)));
+}
+
+#[test]
+fn windows_at_the_start_are_not_warned_about_when_no_lead_in_is_required() {
+    assert!(the_everything(1, &track(500), &early_without_lead_in()).warnings.iter().all(|w| w.window != "initial"));
+}
+
+#[test]
+fn windows_at_the_start_are_missing_when_a_lead_in_is_required() {
+    assert!(the_everything(1, &track(500), &Config::from_toml(EARLY).unwrap()).speeds.get("initial").is_none());
+}
+
+#[test]
+fn default_bounds_are_not_recorded() {
+    assert!(the_everything(1, &track(500), &Config::from_toml(EARLY).unwrap()).speeds.get("calm").unwrap().bounds.is_none());
+}
+
+#[test]
+fn unordered_frames_are_bounded_like_ordered_ones() {
+    let open = WindowBounds{ start: Bound::Open, end: Bound::Open, ..WindowBounds::default() };
+    let w = Window::new(270.0, 290.0);
+    let lines = track(500);
+    let mut shuffled = lines.clone();
+    shuffled.swap(0, 1);
+    let unordered = the_speed_within(&w, &open, &MaxEstimator::default(), Precision::default(), &shuffled).unwrap();
+    let ordered = Dataset::new(lines).speed_in(&w, &open, &MaxEstimator::default(), Precision::default()).unwrap();
+    assert_eq!((unordered.stats.n, unordered.stats.mean), (ordered.stats.n, ordered.stats.mean));
+}
+
+#[test]
+fn unknown_bounds_are_rejected() {
+    assert!(Config::from_toml("[windows.bounds]\nstart = \"half\"\n").is_err());
+}